@@ -1,8 +1,15 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::Write;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use base64::Engine;
+use clap::ValueEnum;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 use termion::event::Key;
@@ -11,48 +18,346 @@ use termion::event::MouseEvent::Press;
 use termion::raw::RawTerminal;
 use termion::screen::{ToAlternateScreen, ToMainScreen};
 
+use crate::decompress;
+use crate::decompress::StreamDecompressor;
+use crate::diff;
+use crate::diff::DiffStatus;
+use crate::edit;
+use crate::edit::Edit;
+use crate::filter;
 use crate::flatjson;
-use crate::input::TuiEvent;
-use crate::input::TuiEvent::{KeyEvent, MouseEvent, WinChEvent};
-use crate::jsonstringunescaper::{safe_unescape_json_string, UnescapeError};
+use crate::flatjson::{FlatJson, Index};
+use crate::follow::{FileChange, FollowedFile};
+use crate::input::TuiEvent::{DataEof, DataEvent, KeyEvent, MouseEvent, TickEvent, WinChEvent};
+use crate::input::{TuiEvent, TuiInput};
+use crate::jsonparser::RecoveredError;
+use crate::jsonstream::JsonStream;
+use crate::jsonstringunescaper::{
+    safe_unescape_json_string, unsafe_unescape_json_string, UnescapeError,
+};
+use crate::jumplist::JumpList;
+use crate::keybindings::{key_sequence_name, parse_key_sequence, KeyAction, KeyBindings, Lookup};
+use crate::lazyjson::LazyInput;
 use crate::lineprinter::JS_IDENTIFIER;
-use crate::options::{DataFormat, Opt};
+use crate::options::{DataFormat, Opt, OutputFormat, ParseOptions};
+use crate::parseerror::ParseError;
 use crate::screenwriter::{MessageSeverity, ScreenWriter};
 use crate::search::{JumpDirection, SearchDirection, SearchState};
+use crate::tableview::TableAction;
 use crate::types::TTYDimensions;
-use crate::viewer::{Action, JsonViewer, Mode};
+use crate::viewer::{Action, JsonViewer, Mode, ViewState};
 
 pub struct App {
-    viewer: JsonViewer,
+    // The state of the current buffer.
+    buffer: Buffer,
     screen_writer: ScreenWriter,
     input_state: InputState,
     input_buffer: Vec<u8>,
-    input_filename: String,
-    search_state: SearchState,
     message: Option<(String, MessageSeverity)>,
+    // A path to focus once we know the size of the screen.
+    initial_path: Option<String>,
+    // Whether to reload the input file whenever it changes.
+    watching: bool,
+    // The options that input files were parsed with, which are needed to
+    // parse them again when reloading them.
+    parse_options: ParseOptions,
+    // Every open buffer, except for the current one, which is swapped
+    // into the App when we switch to it.
+    buffers: Vec<Option<Buffer>>,
+    current_buffer: usize,
+    // The actions that keys are bound to, and the keys that have been
+    // pressed so far that are the start of a bound sequence.
+    key_bindings: KeyBindings,
+    pending_keys: Vec<Key>,
+}
+
+// The state of an input file that's open in a buffer.
+struct Buffer {
+    viewer: JsonViewer,
+    search_state: SearchState,
+    input_filename: String,
+    // Present while we're still loading streamed input.
+    input_stream: Option<JsonStream>,
+    // Streamed input may be compressed, so it's decompressed as it's read.
+    input_decompressor: StreamDecompressor,
+    // The input file, along with any incomplete value at the end of it,
+    // while we're following it as it grows.
+    followed_file: Option<(FollowedFile, JsonStream)>,
+    // The input file and how much of it has been loaded, if it can be
+    // followed. It's only opened once we start following it.
+    followable_file: Option<(PathBuf, u64)>,
+    // Whether we're following the input, in which case we keep the
+    // last line focused as more input is loaded.
+    following: bool,
+    // The documents that were replaced by applying filters, most recent
+    // last, so that we can return to them.
+    filtered_views: Vec<FilteredView>,
+    // The format of the input, which edited documents are written in.
+    data_format: DataFormat,
+    // The file that ":w" writes to when no filename is given, which is
+    // only set for uncompressed JSON and YAML input files.
+    input_path: Option<PathBuf>,
+    // The input file (of any format), which is read again when reloading
+    // the document, along with when it was last modified when we last
+    // read it.
+    input_file: Option<(PathBuf, Option<SystemTime>)>,
+    // Whether the document has been edited since it was last written
+    // to the input file.
+    modified: bool,
+    // Changes that can be undone, most recent last, and changes that
+    // were undone, which can be redone.
+    undo_stack: Vec<Change>,
+    redo_stack: Vec<Change>,
+    // Marks set with 'M', stored as paths so that they still refer to the
+    // same nodes after the rows of the document change.
+    marks: BTreeMap<char, String>,
+    // Where we were before making large jumps, for Ctrl-o and Ctrl-i.
+    jump_list: JumpList,
+    // When viewing a diff, whether the rows that are hidden are the ones
+    // that didn't change, rather than ones that don't match a search.
+    only_showing_changes: bool,
+}
+
+impl Buffer {
+    fn new(viewer: JsonViewer, input_filename: String) -> Buffer {
+        Buffer {
+            viewer,
+            search_state: SearchState::empty(),
+            input_filename,
+            input_stream: None,
+            input_decompressor: StreamDecompressor::default(),
+            followed_file: None,
+            followable_file: None,
+            following: false,
+            filtered_views: vec![],
+            data_format: DataFormat::Json,
+            input_path: None,
+            input_file: None,
+            modified: false,
+            undo_stack: vec![],
+            redo_stack: vec![],
+            marks: BTreeMap::new(),
+            jump_list: JumpList::default(),
+            only_showing_changes: false,
+        }
+    }
+
+    fn values_to_write(&self, scope: WriteScope) -> Result<Vec<Index>, String> {
+        let flatjson = &self.viewer.flatjson;
+        match scope {
+            WriteScope::Document => Ok(flatjson.top_level_values()),
+            WriteScope::FocusedValue => Ok(vec![self.viewer.focused_row]),
+            WriteScope::Matches => {
+                if !self.search_state.ever_searched {
+                    return Err("Type / to search".to_string());
+                } else if !self.search_state.any_matches() {
+                    return Err(self.search_state.no_matches_message());
+                }
+
+                // Matches inside a value we're already writing (like a
+                // match in its key) don't need to be written separately.
+                let mut values: Vec<Index> = vec![];
+                for row in self.search_state.rows_containing_matches(flatjson) {
+                    let row = flatjson[row].pair_index().unwrap_or(row).min(row);
+                    let end_of_last_value = values
+                        .last()
+                        .map(|&last| flatjson[last].pair_index().unwrap_or(last));
+                    if end_of_last_value.map_or(true, |end| row > end) {
+                        values.push(row);
+                    }
+                }
+                Ok(values)
+            }
+        }
+    }
+
+    // Write the document (or part of it) to a file, returning the message
+    // to show, or an error and how severe it is.
+    fn write_to_file(
+        &mut self,
+        filename: Option<String>,
+        overwrite_existing: bool,
+        write_format: Option<WriteFormat>,
+        scope: WriteScope,
+    ) -> Result<String, (String, MessageSeverity)> {
+        let writing_input_file = filename.is_none();
+        let filename = match filename {
+            Some(filename) => filename,
+            None if scope != WriteScope::Document => {
+                return Err((
+                    "Can't write part of the document to the input file (use :w <file>)"
+                        .to_string(),
+                    MessageSeverity::Error,
+                ));
+            }
+            // The document is the output of a filter, which would replace
+            // the input.
+            None if !self.filtered_views.is_empty() => {
+                return Err((
+                    "Can't write the output of a filter to the input file (use :unfilter or :w <file>)"
+                        .to_string(),
+                    MessageSeverity::Error,
+                ));
+            }
+            None => match &self.input_path {
+                Some(path) => path.to_string_lossy().to_string(),
+                None => {
+                    return Err((
+                        "No filename (use :w <file>)".to_string(),
+                        MessageSeverity::Error,
+                    ));
+                }
+            },
+        };
+
+        if self.viewer.lazy_input.is_some()
+            && (scope != WriteScope::Document || write_format.is_some())
+        {
+            return Err((
+                "Only the whole input can be written in large file mode".to_string(),
+                MessageSeverity::Error,
+            ));
+        }
+
+        let values = self
+            .values_to_write(scope)
+            .map_err(|err| (err, MessageSeverity::Warn))?;
+
+        // Use the format of the file we're writing, falling back to the
+        // format of the input.
+        let write_format = write_format.unwrap_or_else(|| {
+            WriteFormat::Output(OutputFormat::from_filename(&filename).unwrap_or(
+                match self.data_format {
+                    DataFormat::Yaml => OutputFormat::Yaml,
+                    _ => OutputFormat::Json,
+                },
+            ))
+        });
+
+        // In large-file mode we haven't parsed all of the input, so we
+        // write it out as is.
+        let file_contents: Result<Cow<[u8]>, UnescapeError> = match write_format {
+            _ if self.viewer.lazy_input.is_some() => Ok(Cow::Borrowed(
+                self.viewer.lazy_input.as_ref().unwrap().input(),
+            )),
+            WriteFormat::Output(format) if scope == WriteScope::Document => Ok(Cow::Owned(
+                self.viewer.flatjson.formatted(format).into_bytes(),
+            )),
+            WriteFormat::Output(format) => Ok(Cow::Owned(
+                self.viewer
+                    .flatjson
+                    .format_values(&values, format)
+                    .into_bytes(),
+            )),
+            #[cfg(feature = "sexp")]
+            WriteFormat::Sexp => self
+                .viewer
+                .flatjson
+                .sexp_string()
+                .map(|sexp| Cow::Owned(sexp.into_bytes())),
+        };
+
+        let file_contents = file_contents.map_err(|err| {
+            (
+                format!("Error formatting file contents: {err}"),
+                MessageSeverity::Error,
+            )
+        })?;
+
+        let result = if overwrite_existing {
+            replace_file(Path::new(&filename), &file_contents)
+        } else {
+            File::options()
+                .write(true)
+                .create_new(true)
+                .open(&filename)
+                .and_then(|mut file| file.write_all(&file_contents))
+        };
+
+        if let Err(err) = result {
+            let message = match err.kind() {
+                io::ErrorKind::AlreadyExists => {
+                    format!("{filename} already exists (add ! to overwrite)")
+                }
+                _ => format!("Error writing file: {err}"),
+            };
+            return Err((message, MessageSeverity::Error));
+        }
+
+        if writing_input_file {
+            self.modified = false;
+            // Don't reload our own changes when watching the input file.
+            if let Some((path, last_modified)) = &mut self.input_file {
+                *last_modified = modified_time(path);
+            }
+            // Every other version of the document is now different from
+            // the input file.
+            for change in self.undo_stack.iter_mut().chain(&mut self.redo_stack) {
+                if let Change::Document { modified, .. } = change {
+                    *modified = true;
+                }
+            }
+        }
+
+        Ok(format!("{filename} written"))
+    }
+}
+
+// A document that was replaced by the result of a filter, along with
+// where we were in it.
+struct FilteredView {
+    filter: String,
+    flatjson: FlatJson,
+    top_row: Index,
+    focused_row: Index,
+    recovered_errors: Vec<RecoveredError>,
+}
+
+// A change that can be undone (or redone). Applying a change returns the
+// change that reverts it.
+enum Change {
+    // Return to an earlier view of the document.
+    View(ViewState),
+    // Replace the document, e.g., with the document from before it was
+    // edited.
+    Document {
+        flatjson: FlatJson,
+        view_state: ViewState,
+        modified: bool,
+    },
+    // Apply a filter again, without re-evaluating it.
+    ApplyFilter {
+        filter: String,
+        flatjson: FlatJson,
+        view_state: ViewState,
+    },
+    // Return to the document from before the last filter was applied.
+    RemoveFilter,
 }
 
 // State to determine how to process the next event input.
 //
-// The default state accepts most commands, and also buffers
-// number inputs to provide a count for movement commands.
+// The default state looks up keys in the key bindings, and also
+// buffers number inputs to provide a count for movement commands.
 //
-// Other commands require a combination of (non-numeric) key
+// Some commands are bound to a sequence of (non-numeric) key
 // presses. When one of these commands is partially inputted,
-// pressing a key not part of the combination will cancel
-// the combination command, and no action will be performed.
+// pressing a key not part of the sequence will cancel the
+// command, and no action will be performed.
+//
+// Setting a mark, or jumping to one, requires the name of the
+// mark to be entered next.
 #[derive(PartialEq)]
 enum InputState {
     Default,
-    PendingPCommand,
-    PendingYCommand,
-    PendingZCommand,
+    PendingMarkToSet,
+    PendingMarkToJumpTo,
     WaitingForAnyKeyPress,
 }
 
 // Various things that can be copied/printed.
-#[derive(Copy, Clone)]
-enum ContentTarget {
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ContentTarget {
     PrettyPrintedValue,
     OneLineValue,
     String,
@@ -62,23 +367,60 @@ enum ContentTarget {
     QueryPath,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum WriteFormat {
-    Json,
+    Output(OutputFormat),
     #[cfg(feature = "sexp")]
     Sexp,
 }
 
+// Which part of the document to write to a file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum WriteScope {
+    Document,
+    FocusedValue,
+    // The values containing matches of the current search.
+    Matches,
+}
+
+#[derive(Debug, PartialEq)]
 enum Command {
-    Quit,
+    Quit {
+        discard_changes: bool,
+    },
     Help,
     SetShowLineNumber(Option<bool>),
     SetShowRelativeLineNumber(Option<bool>),
+    Filter(String),
+    Unfilter,
+    Reload {
+        discard_changes: bool,
+    },
+    GoTo(String),
+    Marks,
+    NextBuffer,
+    PrevBuffer,
+    // 1-based, like the buffer numbers shown to the user.
+    Buffer(usize),
+    ListBuffers,
+    ToggleOnlyShowingMatches,
+    ToggleOnlyShowingChanges,
     WriteFile {
-        filename: String,
+        // Defaults to the input file.
+        filename: Option<String>,
         overwrite_existing: bool,
-        write_format: WriteFormat,
+        // Determined by the filename when not specified.
+        write_format: Option<WriteFormat>,
+        scope: WriteScope,
+    },
+    ListKeyBindings,
+    // The keys and the name of the action, which are parsed when the
+    // command is run so that errors can be reported.
+    Map {
+        keys: String,
+        action: String,
     },
+    Unmap(String),
     Unknown,
 }
 
@@ -86,8 +428,14 @@ enum Command {
 const HELP: &str = std::include_str!("./jless.help");
 
 pub const MAX_BUFFER_SIZE: usize = 9;
+const MAX_UNDO_HISTORY: usize = 100;
 const BELL: &str = "\x07";
 
+// How often to check a followed file for new data.
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+// How often to check whether a watched file has changed.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 // https://docs.rs/termion/2.0.1/src/termion/input.rs.html#176-180
 //
 // The termion MouseTerminal sends the following escape codes:
@@ -111,47 +459,291 @@ const ENABLE_MOUSE_BUTTON_TRACKING: &str = "\x1b[?1002h";
 impl App {
     pub fn new(
         opt: &Opt,
-        data: String,
+        data: Vec<u8>,
         data_format: DataFormat,
         input_filename: String,
         stdout: RawTerminal<Box<dyn Write>>,
     ) -> Result<App, String> {
-        let flatjson = match Self::parse_input(data, data_format) {
-            Ok(flatjson) => flatjson,
-            Err(err) => return Err(format!("Unable to parse input: {err:?}")),
-        };
+        let data_len = data.len() as u64;
+        let compressed = decompress::is_compressed(&data);
+        let (flatjson, recovered_errors) =
+            match Self::parse_input(data, data_format, opt.parse_options()) {
+                Ok(parsed) => parsed,
+                Err(err) => return Err(format!("Unable to parse input: {}", err.report())),
+            };
 
-        let mut viewer = JsonViewer::new(flatjson, opt.mode);
-        viewer.scrolloff_setting = opt.scrolloff;
+        let mut app = Self::from_flatjson(opt, flatjson, input_filename, stdout);
+        if let Some(error) = recovered_errors.first() {
+            app.set_warning_message(match recovered_errors.len() {
+                1 => format!("Recovered from error: {}", error.error),
+                n => format!("Recovered from {n} errors; first: {}", error.error),
+            });
+        }
+        app.buffer.viewer.recovered_errors = recovered_errors;
+        app.set_up_document(opt, opt.input.first(), data_format, data_len, compressed)?;
+        Ok(app)
+    }
+
+    // Open another input file in a new buffer, after the existing ones.
+    // The current buffer stays the same.
+    pub fn open_buffer(
+        &mut self,
+        opt: &Opt,
+        path: &PathBuf,
+        data: Vec<u8>,
+        data_format: DataFormat,
+        input_filename: String,
+    ) -> Result<(), String> {
+        let data_len = data.len() as u64;
+        let compressed = decompress::is_compressed(&data);
+        let (flatjson, recovered_errors) =
+            match Self::parse_input(data, data_format, opt.parse_options()) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    return Err(format!(
+                        "Unable to parse {input_filename}: {}",
+                        err.report()
+                    ))
+                }
+            };
+
+        let mut viewer = Self::new_viewer(opt, flatjson);
+        viewer.recovered_errors = recovered_errors;
+        let buffer = Buffer::new(viewer, input_filename);
+        let current = std::mem::replace(&mut self.buffer, buffer);
+        let result = self.set_up_document(opt, Some(path), data_format, data_len, compressed);
+        let buffer = std::mem::replace(&mut self.buffer, current);
+        self.buffers.push(Some(buffer));
+
+        result
+    }
+
+    // Finish setting up the document in the current buffer after parsing
+    // it, based on where it came from.
+    fn set_up_document(
+        &mut self,
+        opt: &Opt,
+        path: Option<&PathBuf>,
+        data_format: DataFormat,
+        data_len: u64,
+        compressed: bool,
+    ) -> Result<(), String> {
+        if let Some(filter) = &opt.filter {
+            self.apply_filter(filter.clone())?;
+        }
+
+        self.buffer.data_format = data_format;
+
+        let path = path.filter(|path| path.as_os_str() != "-");
+
+        if matches!(data_format, DataFormat::Json | DataFormat::Yaml) && !compressed {
+            self.buffer.input_path = path.cloned();
+        }
+
+        if let Some(path) = path {
+            self.buffer.input_file = Some((path.clone(), modified_time(path)));
+            self.set_followable_file(path, data_len, compressed);
+        }
+
+        Ok(())
+    }
+
+    // Remember the input file so that we can start following it later.
+    fn set_followable_file(&mut self, path: &Path, data_len: u64, compressed: bool) {
+        self.buffer.followed_file = None;
+        self.buffer.followable_file = None;
+        if self.buffer.data_format == DataFormat::Json && !compressed {
+            self.buffer.followable_file = Some((path.to_path_buf(), data_len));
+        }
+    }
+
+    // Create an App for viewing the diff of two documents.
+    pub fn new_diff(
+        opt: &Opt,
+        flatjson: FlatJson,
+        diff_statuses: Vec<DiffStatus>,
+        input_filename: String,
+        stdout: RawTerminal<Box<dyn Write>>,
+    ) -> App {
+        let mut app = Self::from_flatjson(opt, flatjson, input_filename, stdout);
+        app.buffer.viewer.diff_statuses = Some(diff_statuses);
+        app
+    }
+
+    // Create an App that starts out empty, and whose input will be provided
+    // incrementally via DataEvents as it is read.
+    pub fn new_streaming(
+        opt: &Opt,
+        input_filename: String,
+        stdout: RawTerminal<Box<dyn Write>>,
+    ) -> App {
+        let mut app = Self::from_flatjson(opt, FlatJson::empty(), input_filename, stdout);
+        app.buffer.input_stream = Some(JsonStream::default());
+        app.buffer.following = opt.follow;
+        app
+    }
+
+    // Create an App that starts out empty, and loads the given file's
+    // contents, and any data appended to it later, while following it.
+    pub fn new_following(
+        opt: &Opt,
+        path: PathBuf,
+        input_filename: String,
+        stdout: RawTerminal<Box<dyn Write>>,
+    ) -> Result<App, String> {
+        let followed_file =
+            FollowedFile::open(path, 0).map_err(|err| format!("Unable to get input: {err}"))?;
+
+        let mut app = Self::from_flatjson(opt, FlatJson::empty(), input_filename, stdout);
+        app.buffer.followed_file = Some((followed_file, JsonStream::default()));
+        app.buffer.following = true;
+        Ok(app)
+    }
+
+    // Create an App for viewing a large file, whose contents are only
+    // parsed as they are displayed.
+    pub fn new_large_file(
+        opt: &Opt,
+        path: &Path,
+        input_filename: String,
+        stdout: RawTerminal<Box<dyn Write>>,
+    ) -> Result<App, String> {
+        let (lazy_input, flatjson) = LazyInput::open(path)?;
+
+        let mut app = Self::from_flatjson(opt, flatjson, input_filename, stdout);
+        app.buffer.viewer.lazy_input = Some(lazy_input);
+        Ok(app)
+    }
+
+    fn from_flatjson(
+        opt: &Opt,
+        flatjson: FlatJson,
+        input_filename: String,
+        stdout: RawTerminal<Box<dyn Write>>,
+    ) -> App {
+        let viewer = Self::new_viewer(opt, flatjson);
 
         let screen_writer =
             ScreenWriter::init(opt, stdout, Editor::<()>::new(), TTYDimensions::default());
 
-        Ok(App {
-            viewer,
+        App {
+            buffer: Buffer::new(viewer, input_filename),
             screen_writer,
             input_state: InputState::Default,
             input_buffer: vec![],
-            input_filename,
-            search_state: SearchState::empty(),
             message: None,
-        })
+            initial_path: opt.path.clone(),
+            watching: opt.watch,
+            parse_options: opt.parse_options(),
+            buffers: vec![None],
+            current_buffer: 0,
+            key_bindings: Self::key_bindings(opt),
+            pending_keys: vec![],
+        }
+    }
+
+    // The default key bindings, with any changes from the config file.
+    fn key_bindings(opt: &Opt) -> KeyBindings {
+        let mut key_bindings = KeyBindings::default();
+        for (keys, action) in opt.key_bindings.iter() {
+            match action {
+                Some(action) => key_bindings.bind(keys.clone(), *action),
+                None => {
+                    key_bindings.unbind(keys);
+                }
+            }
+        }
+        key_bindings.remap(&opt.key_remaps);
+        key_bindings
+    }
+
+    fn new_viewer(opt: &Opt, flatjson: FlatJson) -> JsonViewer {
+        let mut viewer = JsonViewer::new(flatjson, opt.mode());
+        viewer.scrolloff_setting = opt.scrolloff;
+        viewer
     }
 
-    fn parse_input(data: String, data_format: DataFormat) -> Result<flatjson::FlatJson, String> {
-        match data_format {
+    // Parse the input, returning the errors that were recovered from if
+    // we're parsing JSON leniently.
+    pub fn parse_input(
+        data: Vec<u8>,
+        data_format: DataFormat,
+        parse_options: ParseOptions,
+    ) -> Result<(FlatJson, Vec<RecoveredError>), ParseError> {
+        let data = match decompress::decompress(data) {
+            Ok(data) => data,
+            Err(err) => return Err(format!("Unable to decompress input: {err}").into()),
+        };
+
+        // Binary formats are parsed directly from the input bytes; all
+        // other formats need to be valid UTF-8.
+        let flatjson = match data_format {
+            DataFormat::MsgPack => Some(flatjson::parse_top_level_msgpack(&data)?),
+            DataFormat::Cbor => Some(flatjson::parse_top_level_cbor(&data)?),
+            _ => None,
+        };
+        if let Some(flatjson) = flatjson {
+            return Ok((flatjson, vec![]));
+        }
+
+        let data = match String::from_utf8(data) {
+            Ok(data) => data,
+            Err(err) => return Err(format!("Input is not valid UTF-8: {err}").into()),
+        };
+
+        if parse_options.lenient && matches!(data_format, DataFormat::Json | DataFormat::Json5) {
+            return flatjson::parse_top_level_json_leniently(
+                data,
+                data_format == DataFormat::Json5,
+            );
+        }
+
+        let flatjson = match data_format {
             DataFormat::Json => flatjson::parse_top_level_json(data),
+            DataFormat::Json5 => flatjson::parse_top_level_json5(data),
             DataFormat::Yaml => flatjson::parse_top_level_yaml(data),
-        }
+            DataFormat::Toml => flatjson::parse_top_level_toml(data),
+            DataFormat::Csv => flatjson::parse_top_level_csv(data, parse_options.csv_options(b',')),
+            DataFormat::Tsv => {
+                flatjson::parse_top_level_csv(data, parse_options.csv_options(b'\t'))
+            }
+            DataFormat::MsgPack | DataFormat::Cbor => unreachable!(),
+        }?;
+        Ok((flatjson, vec![]))
     }
 
-    pub fn run(&mut self, input: Box<dyn Iterator<Item = io::Result<TuiEvent>>>) {
+    pub fn run(&mut self, mut input: TuiInput) {
         let dimensions = TTYDimensions::from_size(termion::terminal_size().unwrap());
-        self.viewer.dimensions = dimensions.without_status_bar();
+        self.buffer.viewer.dimensions = dimensions.without_status_bar();
         self.screen_writer.dimensions = dimensions;
+        if self.buffer.following {
+            self.check_followed_file();
+            self.focus_bottom_if_following();
+        }
+        if let Some(path) = self.initial_path.take() {
+            if let Some(action) = self.go_to_path(&path) {
+                self.buffer.viewer.perform_action(action);
+            }
+        }
         self.draw_screen();
+        self.message = None;
+
+        loop {
+            // We only need to wake up periodically when we're following or
+            // watching a file.
+            let tick_interval = if self.buffer.following && self.buffer.followed_file.is_some() {
+                Some(FOLLOW_POLL_INTERVAL)
+            } else if self.watching && self.buffer.input_file.is_some() {
+                Some(WATCH_POLL_INTERVAL)
+            } else {
+                None
+            };
+            input.set_tick_interval(tick_interval);
+
+            let Some(event) = input.next() else {
+                break;
+            };
 
-        for event in input {
             let event = match event {
                 Ok(event) => event,
                 Err(io_error) => {
@@ -161,6 +753,62 @@ impl App {
                 }
             };
 
+            // Load streamed input as it arrives, regardless of the current input state.
+            if let DataEvent(_) | DataEof = event {
+                if self.buffer.input_stream.is_none() {
+                    continue;
+                }
+
+                let num_rows_before = self.buffer.viewer.flatjson.0.len();
+
+                match event {
+                    DataEvent(bytes) => self.load_input_data(&bytes),
+                    _ => self.finish_loading_input(),
+                }
+
+                if self.buffer.input_stream.is_none() && self.buffer.followed_file.is_none() {
+                    self.buffer.following = false;
+                }
+
+                let rows_added = num_rows_before != self.buffer.viewer.flatjson.0.len();
+                if rows_added {
+                    self.focus_bottom_if_following();
+                }
+
+                if self.input_state != InputState::WaitingForAnyKeyPress {
+                    // Only redraw the status bar if the loading status changed, or
+                    // we went from having no data to having some (or are following
+                    // the input, which changes the focused line), so we don't
+                    // clobber any messages being displayed.
+                    if self.buffer.input_stream.is_none()
+                        || num_rows_before == 0
+                        || (self.buffer.following && rows_added)
+                    {
+                        self.draw_screen();
+                    } else if rows_added {
+                        self.screen_writer
+                            .print_viewer(&self.buffer.viewer, &self.buffer.search_state);
+                    }
+                }
+                continue;
+            }
+
+            if let TickEvent = event {
+                let changed = if self.buffer.following {
+                    self.check_followed_file()
+                } else {
+                    self.watching && self.check_watched_file()
+                };
+                if changed {
+                    self.focus_bottom_if_following();
+                    if self.input_state != InputState::WaitingForAnyKeyPress {
+                        self.draw_screen();
+                        self.message = None;
+                    }
+                }
+                continue;
+            }
+
             // This state trumps everything else. We won't do anything until the user
             // hits a key, then we will redraw the screen and return to the default input
             // state. (We ignore the actual value of the key they press.)
@@ -197,6 +845,59 @@ impl App {
                 continue;
             }
 
+            // There's nothing to navigate until we've loaded at least one value.
+            if self.buffer.viewer.flatjson.is_empty() {
+                match event {
+                    KeyEvent(key) if self.is_quit_key(key) && self.can_quit() => break,
+                    WinChEvent => {
+                        let dimensions =
+                            TTYDimensions::from_size(termion::terminal_size().unwrap());
+                        self.screen_writer.dimensions = dimensions;
+                        self.buffer.viewer.dimensions = dimensions.without_status_bar();
+                    }
+                    _ => {}
+                }
+                self.draw_screen();
+                self.message = None;
+                continue;
+            }
+
+            // While following the input, any key press (or click) just stops
+            // following, except for the ones that quit.
+            if self.buffer.following {
+                match event {
+                    KeyEvent(key) if self.is_quit_key(key) => {
+                        if self.can_quit() {
+                            break;
+                        }
+                        self.draw_screen();
+                        self.message = None;
+                        continue;
+                    }
+                    KeyEvent(_) | MouseEvent(Press(..)) => {
+                        self.buffer.following = false;
+                        self.input_buffer.clear();
+                        self.draw_screen();
+                        self.message = None;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            // While viewing a table, keys navigate the table instead of the document.
+            if self.buffer.viewer.table_view.is_some() {
+                match self.handle_table_view_event(event) {
+                    ControlFlow::Break(()) => break,
+                    ControlFlow::Continue(Some(action)) => self.perform_action(action),
+                    ControlFlow::Continue(None) => {}
+                }
+
+                self.draw_screen();
+                self.message = None;
+                continue;
+            }
+
             // When "actively" searching, we want to show highlighted search terms.
             // We consider someone "actively" searching immediately after the start
             // of a search, and while they navigate between matches using n/N.
@@ -210,9 +911,11 @@ impl App {
             // we'll also stop considering the search active if the collapsed state
             // of the focused row changes.
             let mut jumped_to_search_match = false;
-            let focused_row_before = self.viewer.focused_row;
+            // Whether the action is a large jump that we can return from with Ctrl-o.
+            let mut adds_to_jump_list = false;
+            let focused_row_before = self.buffer.viewer.focused_row;
             let previous_collapsed_state_of_focused_row =
-                self.viewer.flatjson[focused_row_before].is_collapsed();
+                self.buffer.viewer.flatjson[focused_row_before].is_collapsed();
 
             let action = match event {
                 // Put this first so the current input state doesn't get reset
@@ -225,209 +928,214 @@ impl App {
                     ))
                 }
                 // Handle special input states:
-                // p commands:
-                event if self.input_state == InputState::PendingPCommand => {
-                    let content_target = match event {
-                        KeyEvent(Key::Char('p')) => Some(ContentTarget::PrettyPrintedValue),
-                        KeyEvent(Key::Char('v')) => Some(ContentTarget::OneLineValue),
-                        KeyEvent(Key::Char('s')) => Some(ContentTarget::String),
-                        KeyEvent(Key::Char('k')) => Some(ContentTarget::Key),
-                        KeyEvent(Key::Char('P')) => Some(ContentTarget::DotPath),
-                        KeyEvent(Key::Char('b')) => Some(ContentTarget::BracketPath),
-                        KeyEvent(Key::Char('q')) => Some(ContentTarget::QueryPath),
-                        _ => None,
-                    };
-
+                // M and ' commands, which take the name of a mark:
+                event if self.input_state == InputState::PendingMarkToSet => {
+                    self.input_state = InputState::Default;
                     self.input_buffer.clear();
 
-                    if let Some(content_target) = content_target {
-                        if self.print_content(content_target) {
-                            self.input_state = InputState::WaitingForAnyKeyPress;
-                            continue;
-                        }
+                    if let KeyEvent(Key::Char(mark @ 'a'..='z')) = event {
+                        self.set_mark(mark);
                     }
 
-                    self.input_state = InputState::Default;
-
                     None
                 }
-                // y commands:
-                event if self.input_state == InputState::PendingYCommand => {
-                    let content_target = match event {
-                        KeyEvent(Key::Char('y')) => Some(ContentTarget::PrettyPrintedValue),
-                        KeyEvent(Key::Char('v')) => Some(ContentTarget::OneLineValue),
-                        KeyEvent(Key::Char('s')) => Some(ContentTarget::String),
-                        KeyEvent(Key::Char('k')) => Some(ContentTarget::Key),
-                        KeyEvent(Key::Char('p')) => Some(ContentTarget::DotPath),
-                        KeyEvent(Key::Char('b')) => Some(ContentTarget::BracketPath),
-                        KeyEvent(Key::Char('q')) => Some(ContentTarget::QueryPath),
-                        _ => None,
-                    };
-
-                    if let Some(content_target) = content_target {
-                        self.copy_content(content_target);
-                    }
-
+                event if self.input_state == InputState::PendingMarkToJumpTo => {
                     self.input_state = InputState::Default;
                     self.input_buffer.clear();
 
-                    None
-                }
-                // z commands:
-                event if self.input_state == InputState::PendingZCommand => {
-                    let z_action = match event {
-                        KeyEvent(Key::Char('t')) => Some(Action::MoveFocusedLineToTop),
-                        KeyEvent(Key::Char('z')) => Some(Action::MoveFocusedLineToCenter),
-                        KeyEvent(Key::Char('b')) => Some(Action::MoveFocusedLineToBottom),
+                    match event {
+                        KeyEvent(Key::Char(mark @ 'a'..='z')) => {
+                            adds_to_jump_list = true;
+                            self.jump_to_mark(mark)
+                        }
                         _ => None,
-                    };
-
-                    self.input_state = InputState::Default;
-                    self.input_buffer.clear();
-
-                    z_action
-                }
-                // These inputs quit.
-                KeyEvent(Key::Ctrl('c') | Key::Char('q')) => break,
-                // Show the help page
-                KeyEvent(Key::F(1)) => {
-                    self.show_help();
-                    None
-                }
-                KeyEvent(Key::Esc) => {
-                    self.input_buffer.clear();
-                    self.search_state.set_no_longer_actively_searching();
-                    None
-                }
-                // These inputs may be buffered.
-                KeyEvent(Key::Char(ch @ '0'..='9')) => {
-                    if ch == '0' && self.input_buffer.is_empty() {
-                        Some(Action::FocusFirstSibling)
-                    } else {
-                        self.buffer_input(ch as u8);
-                        None
                     }
                 }
-                KeyEvent(Key::Char('p')) => {
-                    self.input_state = InputState::PendingPCommand;
-                    self.input_buffer.clear();
-                    self.buffer_input(b'p');
-                    None
-                }
-                KeyEvent(Key::Char('y')) => {
-                    self.input_state = InputState::PendingYCommand;
-                    self.input_buffer.clear();
-                    self.buffer_input(b'y');
-                    None
-                }
-                KeyEvent(Key::Char('z')) => {
-                    self.input_state = InputState::PendingZCommand;
-                    self.input_buffer.clear();
-                    self.buffer_input(b'z');
+                // Ctrl-c always quits, regardless of the key bindings.
+                KeyEvent(Key::Ctrl('c')) => {
+                    if self.can_quit() {
+                        break;
+                    }
                     None
                 }
                 // These inputs always clear the input_buffer (but may use its current contents).
                 KeyEvent(key) => {
-                    let action = match key {
-                        // These interpret the input buffer as a number.
-                        Key::Up | Key::Char('k') | Key::Ctrl('p') | Key::Backspace => {
-                            let lines = self.parse_input_buffer_as_number();
-                            Some(Action::MoveUp(lines))
+                    let Some(key_action) = self.key_action(key) else {
+                        self.draw_screen();
+                        self.message = None;
+                        continue;
+                    };
+
+                    let action = match key_action {
+                        KeyAction::Quit => {
+                            if self.can_quit() {
+                                break;
+                            }
+                            None
                         }
-                        Key::Down | Key::Char('j') | Key::Ctrl('n') | Key::Char('\n') => {
-                            let lines = self.parse_input_buffer_as_number();
-                            Some(Action::MoveDown(lines))
+                        KeyAction::Help => {
+                            self.show_help();
+                            None
                         }
-                        Key::Ctrl('e') => {
-                            let lines = self.parse_input_buffer_as_number();
-                            Some(Action::ScrollDown(lines))
+                        KeyAction::Cancel => {
+                            self.buffer.search_state.set_no_longer_actively_searching();
+                            None
                         }
-                        Key::Ctrl('y') => {
-                            let lines = self.parse_input_buffer_as_number();
-                            Some(Action::ScrollUp(lines))
+                        KeyAction::Print(content_target) => {
+                            self.input_buffer.clear();
+                            if self.print_content(content_target) {
+                                self.input_state = InputState::WaitingForAnyKeyPress;
+                                continue;
+                            }
+                            None
                         }
-                        Key::Ctrl('d') => {
-                            let maybe_distance = self.maybe_parse_input_buffer_as_number();
-                            Some(Action::JumpDown(maybe_distance))
+                        KeyAction::Copy(content_target) => {
+                            self.copy_content(content_target);
+                            None
                         }
-                        Key::Ctrl('u') => {
-                            let maybe_distance = self.maybe_parse_input_buffer_as_number();
+                        KeyAction::Delete => {
+                            self.delete_focused_node();
+                            None
+                        }
+                        KeyAction::EditValue => {
+                            self.edit_focused_value();
+                            None
+                        }
+                        KeyAction::RenameKey => {
+                            self.rename_focused_key();
+                            None
+                        }
+                        KeyAction::SetMark | KeyAction::JumpToMark => {
+                            self.input_state = match key_action {
+                                KeyAction::SetMark => InputState::PendingMarkToSet,
+                                _ => InputState::PendingMarkToJumpTo,
+                            };
+                            // Show the key until the name of the mark is entered.
+                            self.input_buffer.clear();
+                            self.buffer_keys(&[key]);
+                            self.draw_screen();
+                            self.message = None;
+                            continue;
+                        }
+                        KeyAction::NextChange => {
+                            adds_to_jump_list = true;
+                            self.jump_to_change(JumpDirection::Next)
+                        }
+                        KeyAction::PrevChange => {
+                            adds_to_jump_list = true;
+                            self.jump_to_change(JumpDirection::Prev)
+                        }
+                        KeyAction::FocusedLineToTop => Some(Action::MoveFocusedLineToTop),
+                        KeyAction::FocusedLineToCenter => Some(Action::MoveFocusedLineToCenter),
+                        KeyAction::FocusedLineToBottom => Some(Action::MoveFocusedLineToBottom),
+                        KeyAction::Unfilter if !self.buffer.filtered_views.is_empty() => {
+                            self.unfilter();
+                            None
+                        }
+                        KeyAction::MoveUp | KeyAction::Unfilter => {
+                            let lines = self.parse_input_buffer_as_number();
+                            Some(Action::MoveUp(lines))
+                        }
+                        KeyAction::MoveDown => {
+                            let lines = self.parse_input_buffer_as_number();
+                            Some(Action::MoveDown(lines))
+                        }
+                        KeyAction::ScrollDown => {
+                            let lines = self.parse_input_buffer_as_number();
+                            Some(Action::ScrollDown(lines))
+                        }
+                        KeyAction::ScrollUp => {
+                            let lines = self.parse_input_buffer_as_number();
+                            Some(Action::ScrollUp(lines))
+                        }
+                        KeyAction::JumpDown => {
+                            let maybe_distance = self.maybe_parse_input_buffer_as_number();
+                            Some(Action::JumpDown(maybe_distance))
+                        }
+                        KeyAction::JumpUp => {
+                            let maybe_distance = self.maybe_parse_input_buffer_as_number();
                             Some(Action::JumpUp(maybe_distance))
                         }
-                        Key::Ctrl('b') | Key::PageUp => {
+                        KeyAction::PageUp => {
                             let count = self.parse_input_buffer_as_number();
                             Some(Action::PageUp(count))
                         }
-                        Key::Ctrl('f') | Key::PageDown => {
+                        KeyAction::PageDown => {
                             let count = self.parse_input_buffer_as_number();
                             Some(Action::PageDown(count))
                         }
-                        Key::Char('K') => {
+                        KeyAction::PrevSibling => {
                             let lines = self.parse_input_buffer_as_number();
                             Some(Action::FocusPrevSibling(lines))
                         }
-                        Key::Char('J') => {
+                        KeyAction::NextSibling => {
                             let lines = self.parse_input_buffer_as_number();
                             Some(Action::FocusNextSibling(lines))
                         }
-                        Key::Char('n') => {
+                        KeyAction::NextMatch => {
                             let count = self.parse_input_buffer_as_number();
                             jumped_to_search_match = true;
                             self.jump_to_search_match(JumpDirection::Next, count)
                         }
-                        Key::Char('N') => {
+                        KeyAction::PrevMatch => {
                             let count = self.parse_input_buffer_as_number();
                             jumped_to_search_match = true;
                             self.jump_to_search_match(JumpDirection::Prev, count)
                         }
-                        Key::Char('g') => match self.maybe_parse_input_buffer_as_number() {
-                            None => Some(Action::FocusTop),
-                            Some(n) => Some(Action::JumpTo {
-                                line: n - 1,
-                                make_visible: false,
-                            }),
-                        },
-                        Key::Char('G') => match self.maybe_parse_input_buffer_as_number() {
-                            None => Some(Action::FocusBottom),
-                            Some(n) => Some(Action::JumpTo {
-                                line: n - 1,
-                                make_visible: true,
-                            }),
-                        },
-                        Key::Char('.') => {
+                        KeyAction::FocusTop => {
+                            adds_to_jump_list = true;
+                            match self.maybe_parse_input_buffer_as_number() {
+                                None => Some(Action::FocusTop),
+                                Some(n) => Some(Action::JumpTo {
+                                    line: n - 1,
+                                    make_visible: false,
+                                }),
+                            }
+                        }
+                        KeyAction::FocusBottom => {
+                            adds_to_jump_list = true;
+                            match self.maybe_parse_input_buffer_as_number() {
+                                None => Some(Action::FocusBottom),
+                                Some(n) => Some(Action::JumpTo {
+                                    line: n - 1,
+                                    make_visible: true,
+                                }),
+                            }
+                        }
+                        KeyAction::ScrollRight => {
                             let count = self.parse_input_buffer_as_number();
                             self.screen_writer
-                                .scroll_focused_line_right(&self.viewer, count);
+                                .scroll_focused_line_right(&self.buffer.viewer, count);
                             None
                         }
-                        Key::Char(',') => {
+                        KeyAction::ScrollLeft => {
                             let count = self.parse_input_buffer_as_number();
                             self.screen_writer
-                                .scroll_focused_line_left(&self.viewer, count);
+                                .scroll_focused_line_left(&self.buffer.viewer, count);
                             None
                         }
-                        Key::Char('/') => {
+                        KeyAction::SearchForward => {
                             let count = self.parse_input_buffer_as_number();
                             let action = self
                                 .get_search_input_and_start_search(SearchDirection::Forward, count);
                             jumped_to_search_match = action.is_some();
                             action
                         }
-                        Key::Char('?') => {
+                        KeyAction::SearchBackward => {
                             let count = self.parse_input_buffer_as_number();
                             let action = self
                                 .get_search_input_and_start_search(SearchDirection::Reverse, count);
                             jumped_to_search_match = action.is_some();
                             action
                         }
-                        Key::Char('*') => {
+                        KeyAction::SearchKeyForward => {
                             let count = self.parse_input_buffer_as_number();
                             let action =
                                 self.start_object_key_search(SearchDirection::Forward, count);
                             jumped_to_search_match = action.is_some();
                             action
                         }
-                        Key::Char('#') => {
+                        KeyAction::SearchKeyBackward => {
                             let count = self.parse_input_buffer_as_number();
                             let action =
                                 self.start_object_key_search(SearchDirection::Reverse, count);
@@ -435,40 +1143,84 @@ impl App {
                             action
                         }
                         // These ignore the input buffer
-                        Key::Char('w') => Some(Action::MoveDownUntilDepthChange),
-                        Key::Char('b') => Some(Action::MoveUpUntilDepthChange),
-                        Key::Left | Key::Char('h') => Some(Action::MoveLeft),
-                        Key::Right | Key::Char('l') => Some(Action::MoveRight),
-                        Key::Char('H') => Some(Action::FocusParent),
-                        Key::Char('c') => Some(Action::CollapseNodeAndSiblings),
-                        Key::Char('C') => Some(Action::DeepCollapseNodeAndSiblings),
-                        Key::Char('e') => Some(Action::ExpandNodeAndSiblings),
-                        Key::Char('E') => Some(Action::DeepExpandNodeAndSiblings),
-                        Key::Char(' ') => Some(Action::ToggleCollapsed),
-                        Key::Char('^') => Some(Action::FocusFirstSibling),
-                        Key::Char('$') => Some(Action::FocusLastSibling),
-                        Key::Home => Some(Action::FocusTop),
-                        Key::End => Some(Action::FocusBottom),
-                        Key::Char('%') => Some(Action::FocusMatchingPair),
-                        Key::Char('m') => Some(Action::ToggleMode),
-                        Key::Char('<') => {
+                        KeyAction::NextDepthChange => Some(Action::MoveDownUntilDepthChange),
+                        KeyAction::PrevDepthChange => Some(Action::MoveUpUntilDepthChange),
+                        KeyAction::MoveLeft => Some(Action::MoveLeft),
+                        KeyAction::MoveRight => Some(Action::MoveRight),
+                        KeyAction::FocusParent => Some(Action::FocusParent),
+                        KeyAction::CollapseSiblings => Some(Action::CollapseNodeAndSiblings),
+                        KeyAction::DeepCollapseSiblings => {
+                            Some(Action::DeepCollapseNodeAndSiblings)
+                        }
+                        KeyAction::ExpandSiblings => Some(Action::ExpandNodeAndSiblings),
+                        KeyAction::DeepExpandSiblings => Some(Action::DeepExpandNodeAndSiblings),
+                        KeyAction::ToggleCollapsed => Some(Action::ToggleCollapsed),
+                        KeyAction::FirstSibling => Some(Action::FocusFirstSibling),
+                        KeyAction::LastSibling => Some(Action::FocusLastSibling),
+                        KeyAction::MatchingPair => {
+                            adds_to_jump_list = true;
+                            Some(Action::FocusMatchingPair)
+                        }
+                        KeyAction::ToggleMode => Some(Action::ToggleMode),
+                        KeyAction::JumpBack => self.jump_back(),
+                        KeyAction::JumpForward => self.jump_forward(),
+                        KeyAction::ToggleOnlyMatches => {
+                            self.toggle_only_showing_matches();
+                            None
+                        }
+                        KeyAction::Follow => self.start_following(),
+                        KeyAction::Reload => {
+                            self.reload(false);
+                            None
+                        }
+                        KeyAction::Undo => {
+                            self.undo();
+                            None
+                        }
+                        KeyAction::Redo => {
+                            self.redo();
+                            None
+                        }
+                        KeyAction::InsertSiblingAfter => {
+                            self.insert_sibling(true);
+                            None
+                        }
+                        KeyAction::InsertSiblingBefore => {
+                            self.insert_sibling(false);
+                            None
+                        }
+                        KeyAction::TableView => {
+                            if let Err(err) = self.buffer.viewer.open_table_view() {
+                                self.set_warning_message(err);
+                            }
+                            None
+                        }
+                        // These are only looked up while viewing a table.
+                        KeyAction::TableDrillIn | KeyAction::TableSort => None,
+                        KeyAction::DecreaseIndentation => {
                             self.screen_writer
-                                .decrease_indentation_level(self.viewer.flatjson.2 as u16);
+                                .decrease_indentation_level(self.buffer.viewer.flatjson.2 as u16);
                             None
                         }
-                        Key::Char('>') => {
+                        KeyAction::IncreaseIndentation => {
                             self.screen_writer.increase_indentation_level();
                             None
                         }
-                        Key::Char(';') => {
+                        KeyAction::ScrollToEnd => {
                             self.screen_writer
-                                .scroll_focused_line_to_an_end(&self.viewer);
+                                .scroll_focused_line_to_an_end(&self.buffer.viewer);
                             None
                         }
-                        Key::Char(':') => {
-                            if let Some(command) = self.readline(":", "command") {
+                        KeyAction::Command => {
+                            let mut action = None;
+
+                            if let Some(command) = self.readline(":", "", "command") {
                                 match Self::parse_command(&command) {
-                                    Command::Quit => break,
+                                    Command::Quit { discard_changes } => {
+                                        if discard_changes || self.can_quit() {
+                                            break;
+                                        }
+                                    }
                                     Command::Help => self.show_help(),
                                     Command::SetShowLineNumber(Some(new_val)) => {
                                         self.screen_writer.show_line_numbers = new_val
@@ -484,17 +1236,78 @@ impl App {
                                         self.screen_writer.show_relative_line_numbers =
                                             !self.screen_writer.show_relative_line_numbers
                                     }
+                                    Command::Filter(filter) => match self.apply_filter(filter) {
+                                        Ok(()) => self.record_change(Change::RemoveFilter),
+                                        Err(err) => self.set_error_message(err),
+                                    },
+                                    Command::Unfilter => self.unfilter(),
+                                    Command::Reload { discard_changes } => {
+                                        self.reload(discard_changes)
+                                    }
+                                    Command::GoTo(path) => {
+                                        adds_to_jump_list = true;
+                                        action = self.go_to_path(&path);
+                                    }
+                                    Command::Marks => {
+                                        if self.print_marks() {
+                                            self.input_state = InputState::WaitingForAnyKeyPress;
+                                            self.input_buffer.clear();
+                                            continue;
+                                        }
+                                    }
+                                    command @ (Command::NextBuffer
+                                    | Command::PrevBuffer
+                                    | Command::Buffer(_)) => {
+                                        match Self::buffer_to_switch_to(
+                                            &command,
+                                            self.current_buffer,
+                                            self.buffers.len(),
+                                        ) {
+                                            Ok(index) => self.switch_to_buffer(index),
+                                            Err(err) => self.set_error_message(err),
+                                        }
+                                    }
+                                    Command::ListBuffers => {
+                                        self.print_buffers();
+                                        self.input_state = InputState::WaitingForAnyKeyPress;
+                                        self.input_buffer.clear();
+                                        continue;
+                                    }
+                                    Command::ToggleOnlyShowingMatches => {
+                                        self.toggle_only_showing_matches()
+                                    }
+                                    Command::ToggleOnlyShowingChanges => {
+                                        self.toggle_only_showing_changes()
+                                    }
                                     Command::WriteFile {
                                         filename,
                                         overwrite_existing,
                                         write_format,
+                                        scope,
                                     } => {
                                         self.write_contents_to_file(
                                             filename,
                                             overwrite_existing,
                                             write_format,
+                                            scope,
                                         );
                                     }
+                                    Command::ListKeyBindings => {
+                                        self.print_key_bindings();
+                                        self.input_state = InputState::WaitingForAnyKeyPress;
+                                        self.input_buffer.clear();
+                                        continue;
+                                    }
+                                    Command::Map { keys, action } => {
+                                        if let Err(err) = self.map_keys(&keys, &action) {
+                                            self.set_error_message(err);
+                                        }
+                                    }
+                                    Command::Unmap(keys) => {
+                                        if let Err(err) = self.unmap_keys(&keys) {
+                                            self.set_error_message(err);
+                                        }
+                                    }
                                     Command::Unknown => {
                                         self.set_warning_message(format!(
                                             "Unknown command: {command}"
@@ -503,11 +1316,7 @@ impl App {
                                 }
                             }
 
-                            None
-                        }
-                        _ => {
-                            eprint!("{BELL}\r");
-                            None
+                            action
                         }
                     };
 
@@ -535,59 +1344,875 @@ impl App {
                         }
                     }
                 }
-                TuiEvent::Unknown(bytes) => {
-                    self.set_error_message(format!("Unknown byte sequence: {bytes:?}"));
-                    None
+                TuiEvent::Unknown(bytes) => {
+                    self.set_error_message(format!("Unknown byte sequence: {bytes:?}"));
+                    None
+                }
+                DataEvent(_) | DataEof | TickEvent => {
+                    unreachable!("Data and tick events are handled above")
+                }
+            };
+
+            if let Some(action) = action {
+                self.perform_action(action);
+            }
+
+            if (adds_to_jump_list || jumped_to_search_match)
+                && focused_row_before != self.buffer.viewer.focused_row
+            {
+                self.buffer.jump_list.record(focused_row_before);
+            }
+
+            if jumped_to_search_match {
+                self.screen_writer.scroll_line_to_search_match(
+                    &self.buffer.viewer,
+                    self.buffer.search_state.current_match_range(),
+                );
+            } else {
+                // Check whether we're still actively searching. If the cursor moves,
+                // we're no longer actively searching. If the focused row was expanded
+                // or collapsed, we're still searching, but there's no longer a current
+                // match.
+                if focused_row_before != self.buffer.viewer.focused_row {
+                    self.buffer.search_state.set_no_longer_actively_searching();
+                } else if previous_collapsed_state_of_focused_row
+                    != self.buffer.viewer.flatjson[focused_row_before].is_collapsed()
+                {
+                    self.buffer
+                        .search_state
+                        .set_matches_visible_if_actively_searching();
+                }
+            }
+
+            // Explain why a line is marked as having been recovered from an
+            // error when it's focused.
+            if focused_row_before != self.buffer.viewer.focused_row && self.message.is_none() {
+                if let Some(error) = self
+                    .buffer
+                    .viewer
+                    .recovered_error(self.buffer.viewer.focused_row)
+                {
+                    self.set_warning_message(error.error.to_string());
+                }
+            }
+
+            self.draw_screen();
+            self.message = None;
+        }
+    }
+
+    fn draw_screen(&mut self) {
+        self.load_visible_containers();
+
+        let filename = self.status_bar_filename();
+        self.screen_writer.print(
+            &self.buffer.viewer,
+            &self.input_buffer,
+            &filename,
+            &self.buffer.search_state,
+            &self.message,
+        );
+    }
+
+    // In large-file mode, load any containers that are about to be
+    // displayed, and update everything that refers to the rows or pretty
+    // printed string of the document.
+    fn load_visible_containers(&mut self) {
+        self.buffer.viewer.load_visible_containers();
+
+        let loaded_containers = std::mem::take(&mut self.buffer.viewer.loaded_containers);
+        if !loaded_containers.is_empty() {
+            self.screen_writer.forget_truncated_value_views();
+            self.clear_undo_history();
+        }
+        for loaded in loaded_containers.into_iter() {
+            let replacements: Vec<_> = loaded
+                .into_iter()
+                .map(|container| (container.placeholder_range, container.new_text_len))
+                .collect();
+            self.buffer
+                .search_state
+                .adjust_for_replaced_text(&replacements);
+        }
+
+        if let Some(err) = self.buffer.viewer.load_error.take() {
+            self.set_error_message(err);
+        }
+    }
+
+    fn draw_status_bar(&mut self) {
+        let filename = self.status_bar_filename();
+        self.screen_writer.print_status_bar(
+            &self.buffer.viewer,
+            &self.input_buffer,
+            &filename,
+            &self.buffer.search_state,
+            &self.message,
+        );
+    }
+
+    // The filename displayed in the status bar, which also indicates
+    // whether we're still loading the input.
+    fn status_bar_filename(&self) -> String {
+        let mut filename = if let Some(view) = self.buffer.filtered_views.last() {
+            format!("{} (filter: {})", self.buffer.input_filename, view.filter)
+        } else if self.buffer.following {
+            format!("{} (following)", self.buffer.input_filename)
+        } else if self.buffer.input_stream.is_some() {
+            format!("{} (loading…)", self.buffer.input_filename)
+        } else {
+            self.buffer.input_filename.clone()
+        };
+
+        if self.buffer.modified {
+            filename.push_str(" [+]");
+        }
+
+        if self.buffers.len() > 1 {
+            filename = format!(
+                "[{}/{}] {filename}",
+                self.current_buffer + 1,
+                self.buffers.len()
+            );
+        }
+
+        if self.buffer.viewer.sparse_view && self.buffer.only_showing_changes {
+            filename.push_str(" (changes only)");
+        } else if self.buffer.viewer.sparse_view {
+            filename.push_str(" (matches only)");
+        }
+
+        filename
+    }
+
+    fn load_input_data(&mut self, bytes: &[u8]) {
+        let Some(input_stream) = &mut self.buffer.input_stream else {
+            return;
+        };
+
+        let bytes = match self.buffer.input_decompressor.push(bytes) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                self.buffer.input_stream = None;
+                self.set_error_message(format!("Unable to decompress input: {err}"));
+                return;
+            }
+        };
+
+        if let Some(chunk) = input_stream.push(&bytes) {
+            if !self.append_input(chunk) {
+                self.buffer.input_stream = None;
+            }
+        }
+    }
+
+    fn finish_loading_input(&mut self) {
+        let Some(mut input_stream) = self.buffer.input_stream.take() else {
+            return;
+        };
+
+        let chunk = match self.buffer.input_decompressor.finish() {
+            Ok(bytes) => {
+                input_stream.push(&bytes);
+                input_stream.finish()
+            }
+            Err(err) => Some(Err(format!("Unable to decompress input: {err}"))),
+        };
+
+        if let Some(chunk) = chunk {
+            self.append_input(chunk);
+        }
+
+        if self.buffer.viewer.flatjson.is_empty() && self.message.is_none() {
+            self.set_error_message("Unable to parse input: Unexpected EOF".to_string());
+        }
+    }
+
+    // Parse a chunk of complete top-level values and add them to the
+    // document. Returns false if there was an error, in which case the
+    // caller should stop loading any more input.
+    fn append_input(&mut self, chunk: Result<String, String>) -> bool {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                self.set_error_message(format!("Unable to parse input: {err}"));
+                return false;
+            }
+        };
+
+        match flatjson::parse_top_level_json(chunk) {
+            Ok(flatjson) => {
+                let appended_start = self.buffer.viewer.flatjson.1.len();
+                self.buffer.viewer.append_top_level_values(flatjson);
+                // Any search should also find matches in the new values.
+                self.buffer
+                    .search_state
+                    .add_matches_in_appended_text(&self.buffer.viewer.flatjson.1, appended_start);
+                true
+            }
+            // The error's position is relative to the chunk rather than
+            // the whole input, so we only show the message.
+            Err(err) => {
+                self.set_error_message(format!("Unable to parse input: {}", err.message));
+                false
+            }
+        }
+    }
+
+    fn replace_document(&mut self, flatjson: FlatJson) -> FlatJson {
+        self.screen_writer.forget_truncated_value_views();
+        self.buffer.search_state = SearchState::empty();
+        self.buffer.jump_list.clear();
+        self.buffer.viewer.replace_flatjson(flatjson)
+    }
+
+    // Replace the document with the result of applying a filter to it,
+    // remembering the current document so that we can return to it.
+    fn apply_filter(&mut self, filter: String) -> Result<(), String> {
+        if self.buffer.viewer.lazy_input.is_some() {
+            return Err("Filters aren't supported in large-file mode".to_string());
+        }
+        if self.buffer.following || self.buffer.input_stream.is_some() {
+            return Err("Can't filter input that is still loading".to_string());
+        }
+        if self.buffer.viewer.diff_statuses.is_some() {
+            return Err("Filters aren't supported when viewing a diff".to_string());
+        }
+
+        let flatjson = filter::apply_filter(&self.buffer.viewer.flatjson, &filter)?;
+        if flatjson.is_empty() {
+            return Err(format!("Filter produced no output: {filter}"));
+        }
+
+        self.push_filtered_document(filter, flatjson);
+
+        Ok(())
+    }
+
+    // Replace the document with the output of a filter, remembering the
+    // current document so that we can return to it.
+    fn push_filtered_document(&mut self, filter: String, flatjson: FlatJson) {
+        // The search is reset when we return to the original document, so
+        // we don't keep any rows hidden.
+        self.buffer.viewer.show_all_rows();
+        let top_row = self.buffer.viewer.top_row;
+        let focused_row = self.buffer.viewer.focused_row;
+        let recovered_errors = std::mem::take(&mut self.buffer.viewer.recovered_errors);
+        let original = self.replace_document(flatjson);
+        self.buffer.filtered_views.push(FilteredView {
+            filter,
+            flatjson: original,
+            top_row,
+            focused_row,
+            recovered_errors,
+        });
+    }
+
+    // Return to the document we were viewing before the last filter was
+    // applied. Returns the change that applies the filter again, or None
+    // if no filter has been applied.
+    fn remove_filter(&mut self) -> Option<Change> {
+        let view = self.buffer.filtered_views.pop()?;
+
+        self.buffer.viewer.show_all_rows();
+        let view_state = self.buffer.viewer.view_state();
+        let flatjson = self.replace_document(view.flatjson);
+        self.buffer.viewer.top_row = view.top_row;
+        self.buffer.viewer.focused_row = view.focused_row;
+        self.buffer.viewer.recovered_errors = view.recovered_errors;
+
+        Some(Change::ApplyFilter {
+            filter: view.filter,
+            flatjson,
+            view_state,
+        })
+    }
+
+    fn unfilter(&mut self) {
+        match self.remove_filter() {
+            Some(change) => self.record_change(change),
+            None => self.set_warning_message("No filter has been applied".to_string()),
+        }
+    }
+
+    // Perform an action, letting it be undone if it changes the view
+    // significantly.
+    fn perform_action(&mut self, action: Action) {
+        if let Some(view_state) = self.buffer.viewer.perform_undoable_action(action) {
+            self.record_change(Change::View(view_state));
+        }
+    }
+
+    // Record a change that reverts the last thing that was done.
+    fn record_change(&mut self, change: Change) {
+        if self.buffer.undo_stack.len() == MAX_UNDO_HISTORY {
+            self.buffer.undo_stack.remove(0);
+        }
+        self.buffer.undo_stack.push(change);
+        self.buffer.redo_stack.clear();
+    }
+
+    // Forget all the changes (and jumps), because the rows they refer to
+    // have changed.
+    fn clear_undo_history(&mut self) {
+        self.buffer.undo_stack.clear();
+        self.buffer.redo_stack.clear();
+        self.buffer.jump_list.clear();
+    }
+
+    fn undo(&mut self) {
+        let Some(change) = self.buffer.undo_stack.pop() else {
+            self.set_warning_message("Already at oldest change".to_string());
+            return;
+        };
+
+        match self.apply_change(change) {
+            Ok(inverse) => self.buffer.redo_stack.push(inverse),
+            Err(err) => self.set_error_message(err),
+        }
+    }
+
+    fn redo(&mut self) {
+        let Some(change) = self.buffer.redo_stack.pop() else {
+            self.set_warning_message("Already at newest change".to_string());
+            return;
+        };
+
+        match self.apply_change(change) {
+            Ok(inverse) => self.buffer.undo_stack.push(inverse),
+            Err(err) => self.set_error_message(err),
+        }
+    }
+
+    // Apply a change, returning the change that reverts it.
+    fn apply_change(&mut self, change: Change) -> Result<Change, String> {
+        let inverse = match change {
+            Change::View(view_state) => {
+                Change::View(self.buffer.viewer.restore_view_state(view_state))
+            }
+            Change::Document {
+                flatjson,
+                view_state,
+                modified,
+            } => {
+                self.buffer.viewer.show_all_rows();
+                let current_view_state = self.buffer.viewer.view_state();
+                let current_flatjson = self.replace_document(flatjson);
+                self.buffer.viewer.restore_view_state(view_state);
+                Change::Document {
+                    flatjson: current_flatjson,
+                    view_state: current_view_state,
+                    modified: std::mem::replace(&mut self.buffer.modified, modified),
+                }
+            }
+            Change::ApplyFilter {
+                filter,
+                flatjson,
+                view_state,
+            } => {
+                self.push_filtered_document(filter, flatjson);
+                self.buffer.viewer.restore_view_state(view_state);
+                Change::RemoveFilter
+            }
+            Change::RemoveFilter => self
+                .remove_filter()
+                .ok_or_else(|| "No filter has been applied".to_string())?,
+        };
+
+        Ok(inverse)
+    }
+
+    // Editing is only supported once the whole input has been loaded
+    // and parsed, and we're not viewing the result of a filter.
+    fn check_can_edit(&mut self) -> bool {
+        let reason = if self.buffer.viewer.lazy_input.is_some() {
+            "Editing isn't supported in large-file mode"
+        } else if self.buffer.following || self.buffer.input_stream.is_some() {
+            "Can't edit input that is still loading"
+        } else if !self.buffer.filtered_views.is_empty() {
+            "Can't edit a filtered document (use :unfilter first)"
+        } else if self.buffer.viewer.diff_statuses.is_some() {
+            "Can't edit a diff"
+        } else {
+            return true;
+        };
+
+        self.set_warning_message(reason.to_string());
+        false
+    }
+
+    // The focused node, which is the opening of a container if we're
+    // focused on the closing of one.
+    fn focused_node(&self) -> Index {
+        let row = &self.buffer.viewer.flatjson[self.buffer.viewer.focused_row];
+        if row.is_closing_of_container() {
+            row.pair_index().unwrap()
+        } else {
+            self.buffer.viewer.focused_row
+        }
+    }
+
+    fn edit_focused_value(&mut self) {
+        if !self.check_can_edit() {
+            return;
+        }
+
+        let index = self.focused_node();
+        let row = &self.buffer.viewer.flatjson[index];
+        if !row.is_primitive() {
+            self.set_warning_message("Only primitive values can be edited".to_string());
+            return;
+        }
+
+        let current_value = self.buffer.viewer.flatjson.1[row.range.clone()].to_string();
+        if let Some(value) = self.readline("Value: ", &current_value, "value") {
+            self.edit_document(Edit::ReplaceValue { index, value });
+        }
+    }
+
+    fn rename_focused_key(&mut self) {
+        if !self.check_can_edit() {
+            return;
+        }
+
+        let index = self.focused_node();
+        let Some(key_range) = self.buffer.viewer.flatjson[index].key_range.clone() else {
+            self.set_warning_message("Focused node doesn't have a key".to_string());
+            return;
+        };
+
+        let current_key = &self.buffer.viewer.flatjson.1[key_range];
+        let current_key = match current_key
+            .strip_prefix('"')
+            .and_then(|key| key.strip_suffix('"'))
+        {
+            Some(key) => unsafe_unescape_json_string(key).unwrap_or_else(|_| key.to_string()),
+            None => current_key.to_string(),
+        };
+
+        if let Some(key) = self.readline("Key: ", &current_key, "key") {
+            self.edit_document(Edit::RenameKey { index, key });
+        }
+    }
+
+    fn delete_focused_node(&mut self) {
+        if !self.check_can_edit() {
+            return;
+        }
+
+        let index = self.focused_node();
+        self.edit_document(Edit::Delete { index });
+    }
+
+    // Insert a new sibling after (or before) the focused node, prompting
+    // for a key first if the focused node is in an object.
+    fn insert_sibling(&mut self, after: bool) {
+        if !self.check_can_edit() {
+            return;
+        }
+
+        let index = self.focused_node();
+        let key = if self.buffer.viewer.flatjson[index].key_range.is_some() {
+            match self.readline("Key: ", "", "key") {
+                Some(key) => Some(key),
+                None => return,
+            }
+        } else {
+            None
+        };
+
+        if let Some(value) = self.readline("Value: ", "", "value") {
+            self.edit_document(Edit::Insert {
+                index,
+                after,
+                key,
+                value,
+            });
+        }
+    }
+
+    fn edit_document(&mut self, edit: Edit) {
+        match edit::apply_edit(&self.buffer.viewer.flatjson, &edit) {
+            Ok((flatjson, edited_row)) => {
+                self.buffer.viewer.show_all_rows();
+                let view_state = self.buffer.viewer.view_state();
+                let previous = self
+                    .buffer
+                    .viewer
+                    .replace_edited_flatjson(flatjson, edited_row);
+                self.record_change(Change::Document {
+                    flatjson: previous,
+                    view_state,
+                    modified: self.buffer.modified,
+                });
+
+                self.screen_writer.forget_truncated_value_views();
+                self.buffer.search_state = SearchState::empty();
+                self.buffer.jump_list.clear();
+                self.buffer.modified = true;
+                // The document no longer matches the input file.
+                self.buffer.followed_file = None;
+                self.buffer.followable_file = None;
+            }
+            Err(err) => self.set_error_message(err),
+        }
+    }
+
+    // Focus the node at the given path, expanding its ancestors.
+    fn go_to_path(&mut self, path: &str) -> Option<Action> {
+        match self.buffer.viewer.find_node_by_path(path) {
+            Ok(index) => Some(Action::JumpTo {
+                line: index,
+                make_visible: true,
+            }),
+            Err(err) => {
+                self.set_error_message(err);
+                None
+            }
+        }
+    }
+
+    // Check that quitting won't lose any changes that haven't been written,
+    // showing an error message if it would.
+    fn can_quit(&mut self) -> bool {
+        if self.buffer.modified {
+            self.set_error_message("No write since last change (add ! to override)".to_string());
+            return false;
+        }
+
+        let unwritten_buffer = self.buffers.iter().flatten().find(|buffer| buffer.modified);
+        if let Some(buffer) = unwritten_buffer {
+            let message = format!(
+                "No write since last change for buffer \"{}\" (add ! to override)",
+                buffer.input_filename
+            );
+            self.set_error_message(message);
+            return false;
+        }
+
+        true
+    }
+
+    // The index of the buffer that a buffer command switches to, given the
+    // index of the current buffer and the number of buffers.
+    fn buffer_to_switch_to(
+        command: &Command,
+        current_buffer: usize,
+        num_buffers: usize,
+    ) -> Result<usize, String> {
+        match *command {
+            Command::NextBuffer => Ok((current_buffer + 1) % num_buffers),
+            Command::PrevBuffer => Ok((current_buffer + num_buffers - 1) % num_buffers),
+            Command::Buffer(n) if n >= 1 && n <= num_buffers => Ok(n - 1),
+            Command::Buffer(n) => Err(format!("Buffer {n} does not exist")),
+            _ => Ok(current_buffer),
+        }
+    }
+
+    fn switch_to_buffer(&mut self, index: usize) {
+        if index == self.current_buffer {
+            return;
+        }
+
+        let buffer = self.buffers[index].take().unwrap();
+        let previous = std::mem::replace(&mut self.buffer, buffer);
+        self.buffers[self.current_buffer] = Some(previous);
+        self.current_buffer = index;
+
+        // The terminal may have been resized while viewing another buffer.
+        self.buffer
+            .viewer
+            .perform_action(Action::ResizeViewerDimensions(
+                self.screen_writer.dimensions.without_status_bar(),
+            ));
+        self.screen_writer.forget_truncated_value_views();
+    }
+
+    fn set_mark(&mut self, mark: char) {
+        let path = self
+            .buffer
+            .viewer
+            .flatjson
+            .build_path_to_node(
+                flatjson::PathType::DotWithTopLevelIndex,
+                self.focused_node(),
+            )
+            .unwrap();
+        self.buffer.marks.insert(mark, path);
+    }
+
+    // Focus the node that a mark was set on, expanding its ancestors.
+    fn jump_to_mark(&mut self, mark: char) -> Option<Action> {
+        let Some(path) = self.buffer.marks.get(&mark).cloned() else {
+            self.set_error_message(format!("Mark not set: '{mark}"));
+            return None;
+        };
+
+        match self.buffer.viewer.find_node_by_path(&path) {
+            Ok(index) => Some(Action::JumpTo {
+                line: index,
+                make_visible: true,
+            }),
+            Err(err) => {
+                self.set_error_message(format!("Unable to jump to mark '{mark}: {err}"));
+                None
+            }
+        }
+    }
+
+    fn jump_back(&mut self) -> Option<Action> {
+        match self.buffer.jump_list.back(self.buffer.viewer.focused_row) {
+            Some(index) => Some(Action::JumpTo {
+                line: index,
+                make_visible: true,
+            }),
+            None => {
+                self.set_warning_message("Already at oldest jump".to_string());
+                None
+            }
+        }
+    }
+
+    fn jump_forward(&mut self) -> Option<Action> {
+        match self.buffer.jump_list.forward() {
+            Some(index) => Some(Action::JumpTo {
+                line: index,
+                make_visible: true,
+            }),
+            None => {
+                self.set_warning_message("Already at newest jump".to_string());
+                None
+            }
+        }
+    }
+
+    fn start_following(&mut self) -> Option<Action> {
+        if !self.buffer.filtered_views.is_empty() {
+            self.set_warning_message("Can't follow the input while it's filtered".to_string());
+            return None;
+        }
+
+        if self.buffer.modified {
+            self.set_warning_message("Can't follow the input after editing it".to_string());
+            return None;
+        }
+
+        if self.buffer.followed_file.is_none() && self.buffer.input_stream.is_none() {
+            let Some((path, data_len)) = self.buffer.followable_file.take() else {
+                self.set_warning_message(
+                    "Can only follow JSON files, or input that is still loading".to_string(),
+                );
+                return None;
+            };
+
+            match FollowedFile::open(path, data_len) {
+                Ok(followed_file) => {
+                    self.buffer.followed_file = Some((followed_file, JsonStream::default()));
+                }
+                Err(err) => {
+                    self.set_error_message(format!("Unable to follow input: {err}"));
+                    return None;
                 }
-            };
+            }
+        }
 
-            if let Some(action) = action {
-                self.viewer.perform_action(action);
+        self.buffer.following = true;
+        self.check_followed_file();
+        Some(Action::FocusBottom)
+    }
+
+    fn focus_bottom_if_following(&mut self) {
+        if self.buffer.following && !self.buffer.viewer.flatjson.is_empty() {
+            self.buffer.viewer.perform_action(Action::FocusBottom);
+        }
+    }
+
+    // Load any new data from the followed file. Returns whether anything
+    // changed (including whether a message was set).
+    fn check_followed_file(&mut self) -> bool {
+        let Some((followed_file, stream)) = &mut self.buffer.followed_file else {
+            return false;
+        };
+
+        let change = match followed_file.check_for_changes() {
+            Ok(Some(change)) => change,
+            Ok(None) => return false,
+            Err(err) => {
+                self.buffer.followed_file = None;
+                self.buffer.following = false;
+                self.set_error_message(format!("Unable to follow input: {err}"));
+                return true;
             }
+        };
 
-            if jumped_to_search_match {
-                self.screen_writer.scroll_line_to_search_match(
-                    &self.viewer,
-                    self.search_state.current_match_range(),
-                );
-            } else {
-                // Check whether we're still actively searching. If the cursor moves,
-                // we're no longer actively searching. If the focused row was expanded
-                // or collapsed, we're still searching, but there's no longer a current
-                // match.
-                if focused_row_before != self.viewer.focused_row {
-                    self.search_state.set_no_longer_actively_searching();
-                } else if previous_collapsed_state_of_focused_row
-                    != self.viewer.flatjson[focused_row_before].is_collapsed()
-                {
-                    self.search_state
-                        .set_matches_visible_if_actively_searching();
-                }
+        let (bytes, reload_reason) = match change {
+            FileChange::Appended(bytes) => (bytes, None),
+            FileChange::Truncated(bytes) => (bytes, Some("truncated")),
+            FileChange::Replaced(bytes) => (bytes, Some("replaced")),
+        };
+
+        if reload_reason.is_some() {
+            *stream = JsonStream::default();
+        }
+        let chunk = stream.push(&bytes);
+
+        if let Some(reason) = reload_reason {
+            self.replace_document(FlatJson::empty());
+            self.clear_undo_history();
+            self.set_info_message(format!(
+                "{} was {reason}; reloaded",
+                self.buffer.input_filename
+            ));
+        }
+
+        if let Some(chunk) = chunk {
+            if !self.append_input(chunk) {
+                self.buffer.followed_file = None;
+                self.buffer.following = false;
             }
+        }
 
-            self.draw_screen();
-            self.message = None;
+        true
+    }
+
+    // Reload the input file if it has been modified since we last read
+    // it. Returns whether anything changed (including whether a message
+    // was set).
+    fn check_watched_file(&mut self) -> bool {
+        let Some((path, last_modified)) = &self.buffer.input_file else {
+            return false;
+        };
+
+        // The file may be missing briefly while it's being replaced.
+        let modified = modified_time(path);
+        if modified.is_none() || modified == *last_modified {
+            return false;
         }
+
+        self.reload(false);
+        true
     }
 
-    fn draw_screen(&mut self) {
-        self.screen_writer.print(
-            &self.viewer,
-            &self.input_buffer,
-            &self.input_filename,
-            &self.search_state,
-            &self.message,
+    // Read and parse the input file again, and replace the document with
+    // the new version of it, keeping the same containers collapsed and
+    // the same node focused. Filters that were applied are applied again.
+    // If the input can't be parsed, we keep displaying the old version.
+    fn reload(&mut self, discard_changes: bool) {
+        let Some((path, last_modified)) = &mut self.buffer.input_file else {
+            self.set_warning_message("Only input files can be reloaded".to_string());
+            return;
+        };
+        let path = path.clone();
+
+        // Remember when the file was modified even if we can't reload it,
+        // so that we don't keep trying to reload it when watching it.
+        *last_modified = modified_time(&path);
+
+        if self.buffer.modified && !discard_changes {
+            self.set_warning_message(
+                "The document has been edited; use :e! to discard the changes".to_string(),
+            );
+            return;
+        }
+
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) => {
+                self.set_error_message(format!(
+                    "Unable to reload {}: {err}",
+                    self.buffer.input_filename
+                ));
+                return;
+            }
+        };
+        let data_len = data.len() as u64;
+        let compressed = decompress::is_compressed(&data);
+
+        let filters: Vec<&str> = self
+            .buffer
+            .filtered_views
+            .iter()
+            .map(|view| view.filter.as_str())
+            .collect();
+        let parsed = Self::parse_reloaded_input(
+            data,
+            self.buffer.data_format,
+            self.parse_options,
+            &filters,
+            &self.buffer.input_filename,
         );
+        let (flatjson, filtered_documents, mut recovered_errors) = match parsed {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                self.set_error_message(err);
+                return;
+            }
+        };
+
+        // The rows we were at in the documents that were filtered don't
+        // correspond to the rows in the new versions of them.
+        for (view, filtered_document) in self
+            .buffer
+            .filtered_views
+            .iter_mut()
+            .zip(filtered_documents)
+        {
+            view.flatjson = filtered_document;
+            view.top_row = 0;
+            view.focused_row = 0;
+            view.recovered_errors = std::mem::take(&mut recovered_errors);
+        }
+
+        self.buffer.viewer.show_all_rows();
+        self.screen_writer.forget_truncated_value_views();
+        self.buffer.search_state = SearchState::empty();
+        self.buffer.viewer.replace_reloaded_flatjson(flatjson);
+        self.buffer.viewer.recovered_errors = recovered_errors;
+        self.clear_undo_history();
+        self.buffer.modified = false;
+        self.set_followable_file(&path, data_len, compressed);
+        self.set_info_message(format!("Reloaded {}", self.buffer.input_filename));
+
+        // Keep following the new version of the file, if we can.
+        if self.buffer.following {
+            self.buffer.following = false;
+            if self.buffer.followable_file.is_some() {
+                self.start_following();
+            }
+        }
+        self.focus_bottom_if_following();
     }
 
-    fn draw_status_bar(&mut self) {
-        self.screen_writer.print_status_bar(
-            &self.viewer,
-            &self.input_buffer,
-            &self.input_filename,
-            &self.search_state,
-            &self.message,
-        );
+    // Parse a new version of the input file, and apply the filters that
+    // were applied to the old version to it. Returns the document to
+    // display, the documents that the filters were applied to, and the
+    // errors that were recovered from.
+    fn parse_reloaded_input(
+        data: Vec<u8>,
+        data_format: DataFormat,
+        parse_options: ParseOptions,
+        filters: &[&str],
+        input_filename: &str,
+    ) -> Result<(FlatJson, Vec<FlatJson>, Vec<RecoveredError>), String> {
+        let (mut flatjson, recovered_errors) =
+            match Self::parse_input(data, data_format, parse_options) {
+                Ok(parsed) => parsed,
+                Err(err) => return Err(format!("Unable to parse {input_filename}: {err}")),
+            };
+
+        let mut filtered_documents = vec![];
+        for filter in filters {
+            match filter::apply_filter(&flatjson, filter) {
+                Ok(filtered) => filtered_documents.push(std::mem::replace(&mut flatjson, filtered)),
+                Err(err) => return Err(format!("Unable to filter reloaded input: {err}")),
+            }
+        }
+
+        if flatjson.is_empty() {
+            return Err(format!("Nothing to display in {input_filename}"));
+        }
+
+        Ok((flatjson, filtered_documents, recovered_errors))
     }
 
     fn set_info_message(&mut self, s: String) {
@@ -602,11 +2227,12 @@ impl App {
         self.message = Some((s, MessageSeverity::Error));
     }
 
-    // Get user input via a readline prompt. May fail to return input if
+    // Get user input via a readline prompt, which starts out containing
+    // the given initial input. May fail to return input if
     // the user deliberately cancels the prompt via Ctrl-C or Ctrl-D, or
     // if an actual error occurs, in which case an error message is set.
-    fn readline(&mut self, prompt: &str, purpose: &str) -> Option<String> {
-        match self.screen_writer.get_command(prompt) {
+    fn readline(&mut self, prompt: &str, initial: &str, purpose: &str) -> Option<String> {
+        match self.screen_writer.get_command(prompt, initial) {
             Ok(s) => Some(s),
             // User hit Ctrl-C or Ctrl-D to cancel prompt
             Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => None,
@@ -617,6 +2243,89 @@ impl App {
         }
     }
 
+    // Handle an event while viewing a table, breaking if we should quit.
+    // Keys are looked up in the key bindings, and the actions for moving
+    // around the document move around the table instead.
+    fn handle_table_view_event(&mut self, event: TuiEvent) -> ControlFlow<(), Option<Action>> {
+        let key = match event {
+            WinChEvent => {
+                let dimensions = TTYDimensions::from_size(termion::terminal_size().unwrap());
+                self.screen_writer.dimensions = dimensions;
+                return ControlFlow::Continue(Some(Action::ResizeViewerDimensions(
+                    dimensions.without_status_bar(),
+                )));
+            }
+            KeyEvent(key) => key,
+            _ => return ControlFlow::Continue(None),
+        };
+
+        let key_action = match key {
+            Key::Ctrl('c') => Some(KeyAction::Quit),
+            key => self.key_action(key),
+        };
+        let Some(key_action) = key_action else {
+            return ControlFlow::Continue(None);
+        };
+
+        let table_action = match key_action {
+            KeyAction::MoveUp | KeyAction::Unfilter => {
+                Some(TableAction::MoveUp(self.parse_input_buffer_as_number()))
+            }
+            KeyAction::MoveDown => Some(TableAction::MoveDown(self.parse_input_buffer_as_number())),
+            KeyAction::PageUp => Some(TableAction::PageUp(self.parse_input_buffer_as_number())),
+            KeyAction::PageDown => Some(TableAction::PageDown(self.parse_input_buffer_as_number())),
+            KeyAction::MoveLeft => Some(TableAction::MoveLeft),
+            KeyAction::MoveRight => Some(TableAction::MoveRight),
+            KeyAction::FocusTop => Some(TableAction::FocusFirstRow),
+            KeyAction::FocusBottom => Some(TableAction::FocusLastRow),
+            KeyAction::FirstSibling => Some(TableAction::FocusFirstColumn),
+            KeyAction::LastSibling => Some(TableAction::FocusLastColumn),
+            KeyAction::TableSort => Some(TableAction::SortByFocusedColumn),
+            KeyAction::TableDrillIn => {
+                // Return to the document, focusing the focused cell.
+                self.input_buffer.clear();
+                let table_view = self.buffer.viewer.table_view.take().unwrap();
+                return ControlFlow::Continue(Some(Action::JumpTo {
+                    line: table_view.focused_node(),
+                    make_visible: true,
+                }));
+            }
+            KeyAction::TableView | KeyAction::Cancel => {
+                self.buffer.viewer.table_view = None;
+                None
+            }
+            KeyAction::Help => {
+                self.show_help();
+                None
+            }
+            KeyAction::Quit => {
+                if self.can_quit() {
+                    return ControlFlow::Break(());
+                }
+                None
+            }
+            _ => {
+                eprint!("{BELL}\r");
+                None
+            }
+        };
+
+        self.input_buffer.clear();
+
+        if let Some(table_action) = table_action {
+            self.buffer.viewer.perform_table_action(table_action);
+        }
+
+        ControlFlow::Continue(None)
+    }
+
+    // Whether a key quits on its own, before any input has been loaded,
+    // or while following the input, when sequences of keys aren't
+    // entered.
+    fn is_quit_key(&self, key: Key) -> bool {
+        key == Key::Ctrl('c') || self.key_bindings.lookup(&[key]) == Lookup::Action(KeyAction::Quit)
+    }
+
     fn buffer_input(&mut self, ch: u8) {
         // Don't buffer leading 0s.
         if self.input_buffer.is_empty() && ch == b'0' {
@@ -630,6 +2339,62 @@ impl App {
         self.input_buffer.push(ch);
     }
 
+    // Show keys that were pressed in the input buffer, where the count
+    // is shown.
+    fn buffer_keys(&mut self, keys: &[Key]) {
+        for ch in key_sequence_name(keys).chars() {
+            // The buffer is truncated by bytes, so only ASCII characters
+            // are shown.
+            self.buffer_input(if ch.is_ascii() { ch as u8 } else { b'?' });
+        }
+    }
+
+    // Handle a key pressed in the default input state, returning the
+    // action bound to it, or to the sequence of keys that it completes.
+    // Digits are buffered to provide a count, except for a leading 0.
+    fn key_action(&mut self, key: Key) -> Option<KeyAction> {
+        if let (true, Key::Char(ch @ '0'..='9')) = (self.pending_keys.is_empty(), key) {
+            if ch != '0' || !self.input_buffer.is_empty() {
+                self.buffer_input(ch as u8);
+                return None;
+            }
+        }
+
+        self.pending_keys.push(key);
+
+        let lookup = if self.buffer.viewer.table_view.is_some() {
+            self.key_bindings.lookup_in_table(&self.pending_keys)
+        } else {
+            self.key_bindings.lookup(&self.pending_keys)
+        };
+        match lookup {
+            Lookup::Action(action) => {
+                // The keys of a sequence replace the count in the buffer.
+                if self.pending_keys.len() > 1 {
+                    self.input_buffer.clear();
+                }
+                self.pending_keys.clear();
+                Some(action)
+            }
+            Lookup::Pending => {
+                self.input_buffer.clear();
+                let pending_keys = self.pending_keys.clone();
+                self.buffer_keys(&pending_keys);
+                None
+            }
+            Lookup::Unbound => {
+                // Only ring the bell when a single key isn't bound, not
+                // when a sequence is cancelled.
+                if self.pending_keys.len() == 1 {
+                    eprint!("{BELL}\r");
+                }
+                self.pending_keys.clear();
+                self.input_buffer.clear();
+                None
+            }
+        }
+    }
+
     fn maybe_parse_input_buffer_as_number(&mut self) -> Option<usize> {
         let n = str::parse::<usize>(std::str::from_utf8(&self.input_buffer).unwrap());
         self.input_buffer.clear();
@@ -650,17 +2415,17 @@ impl App {
             SearchDirection::Reverse => "?",
         };
 
-        let search_term = self.readline(prompt_str, "search input")?;
+        let search_term = self.readline(prompt_str, "", "search input")?;
 
         // In vim, /<CR> or ?<CR> is a longcut for repeating the previous search.
         if search_term.is_empty() {
             // This will actually set the direction of a search going forward.
-            self.search_state.direction = direction;
+            self.buffer.search_state.direction = direction;
             self.jump_to_search_match(JumpDirection::Next, jumps)
         } else {
             if self.initialize_search(direction, search_term) {
-                if !self.search_state.any_matches() {
-                    self.set_warning_message(self.search_state.no_matches_message());
+                if !self.buffer.search_state.any_matches() {
+                    self.set_warning_message(self.buffer.search_state.no_matches_message());
                     None
                 } else {
                     self.jump_to_search_match(JumpDirection::Next, jumps)
@@ -672,9 +2437,14 @@ impl App {
     }
 
     fn initialize_search(&mut self, direction: SearchDirection, search_term: String) -> bool {
-        match SearchState::initialize_search(search_term, &self.viewer.flatjson.1, direction) {
+        match SearchState::initialize_search(search_term, &self.buffer.viewer.flatjson.1, direction)
+        {
             Ok(ss) => {
-                self.search_state = ss;
+                self.buffer.search_state = ss;
+                // Keep only showing matches, but of the new search.
+                if self.buffer.viewer.sparse_view {
+                    self.show_only_matches();
+                }
                 true
             }
             Err(err_message) => {
@@ -684,6 +2454,80 @@ impl App {
         }
     }
 
+    // Toggle between showing the whole document and only showing the
+    // rows that match the current search, along with their ancestors.
+    fn toggle_only_showing_matches(&mut self) {
+        if self.buffer.viewer.sparse_view {
+            self.buffer.viewer.show_all_rows();
+        } else if !self.buffer.search_state.ever_searched {
+            self.set_info_message("Type / to search".to_string());
+        } else if !self.buffer.search_state.any_matches() {
+            self.set_warning_message(self.buffer.search_state.no_matches_message());
+        } else {
+            self.show_only_matches();
+        }
+    }
+
+    fn show_only_matches(&mut self) {
+        if self.buffer.search_state.any_matches() {
+            let rows = self
+                .buffer
+                .search_state
+                .rows_containing_matches(&self.buffer.viewer.flatjson);
+            self.buffer.viewer.show_only_rows(&rows);
+            self.buffer.only_showing_changes = false;
+        } else {
+            self.buffer.viewer.show_all_rows();
+        }
+    }
+
+    // Jump to the start of the next (or previous) change in a diff.
+    fn jump_to_change(&mut self, direction: JumpDirection) -> Option<Action> {
+        let Some(diff_statuses) = &self.buffer.viewer.diff_statuses else {
+            self.set_warning_message("Not viewing a diff (use --diff)".to_string());
+            return None;
+        };
+
+        let focused_row = self.buffer.viewer.focused_row;
+        match diff::find_change(
+            &self.buffer.viewer.flatjson,
+            diff_statuses,
+            focused_row,
+            direction,
+        ) {
+            Some(index) => Some(Action::JumpTo {
+                line: index,
+                make_visible: true,
+            }),
+            None => {
+                self.set_warning_message("No more changes".to_string());
+                None
+            }
+        }
+    }
+
+    // Only show the parts of a diff that changed, or go back to showing
+    // everything.
+    fn toggle_only_showing_changes(&mut self) {
+        let Some(diff_statuses) = &self.buffer.viewer.diff_statuses else {
+            self.set_warning_message("Not viewing a diff (use --diff)".to_string());
+            return;
+        };
+
+        if self.buffer.viewer.sparse_view {
+            self.buffer.viewer.show_all_rows();
+            return;
+        }
+
+        let rows = diff::changed_rows(diff_statuses);
+        if rows.is_empty() {
+            self.set_info_message("No changes".to_string());
+        } else {
+            self.buffer.viewer.show_only_rows(&rows);
+            self.buffer.only_showing_changes = true;
+        }
+    }
+
     fn start_object_key_search(
         &mut self,
         direction: SearchDirection,
@@ -702,9 +2546,11 @@ impl App {
     }
 
     fn initialize_object_key_search(&mut self, direction: SearchDirection) -> bool {
-        if let Some(key_range) = &self.viewer.flatjson[self.viewer.focused_row].key_range {
+        if let Some(key_range) =
+            &self.buffer.viewer.flatjson[self.buffer.viewer.focused_row].key_range
+        {
             // Note key_range already includes quotes around key.
-            let object_key = format!("{}: ", &self.viewer.flatjson.1[key_range.clone()]);
+            let object_key = format!("{}: ", &self.buffer.viewer.flatjson.1[key_range.clone()]);
             self.initialize_search(direction, object_key)
         } else {
             false
@@ -716,17 +2562,17 @@ impl App {
         jump_direction: JumpDirection,
         jumps: usize,
     ) -> Option<Action> {
-        if !self.search_state.ever_searched {
+        if !self.buffer.search_state.ever_searched {
             self.set_info_message("Type / to search".to_string());
             return None;
-        } else if !self.search_state.any_matches() {
-            self.set_warning_message(self.search_state.no_matches_message());
+        } else if !self.buffer.search_state.any_matches() {
+            self.set_warning_message(self.buffer.search_state.no_matches_message());
             return None;
         }
 
-        let destination = self.search_state.jump_to_match(
-            self.viewer.focused_row,
-            &self.viewer.flatjson,
+        let destination = self.buffer.search_state.jump_to_match(
+            self.buffer.viewer.focused_row,
+            &self.buffer.viewer.flatjson,
             jump_direction,
             jumps,
         );
@@ -737,11 +2583,41 @@ impl App {
     }
 
     fn parse_command(command: &str) -> Command {
+        // Filters and paths may contain spaces, so we don't split them
+        // into arguments.
+        match command.trim().split_once(' ') {
+            Some(("filter", filter)) => return Command::Filter(filter.trim().to_string()),
+            Some(("goto", path)) => return Command::GoTo(path.trim().to_string()),
+            _ => {}
+        }
+
         let args: Vec<&str> = command.split(" ").filter(|s| !s.is_empty()).collect();
 
         match args.as_slice() {
             ["h" | "help"] => Command::Help,
-            ["q" | "quit" | "quit()" | "exit" | "exit()"] => Command::Quit,
+            ["unfilter"] => Command::Unfilter,
+            ["e" | "edit"] => Command::Reload {
+                discard_changes: false,
+            },
+            ["e!" | "edit!"] => Command::Reload {
+                discard_changes: true,
+            },
+            ["only"] => Command::ToggleOnlyShowingMatches,
+            ["marks"] => Command::Marks,
+            ["bn" | "bnext"] => Command::NextBuffer,
+            ["bp" | "bprev" | "bprevious" | "bN" | "bNext"] => Command::PrevBuffer,
+            ["b" | "buffer", n] => match n.parse::<usize>() {
+                Ok(n) => Command::Buffer(n),
+                Err(_) => Command::Unknown,
+            },
+            ["ls" | "buffers" | "files"] => Command::ListBuffers,
+            ["changes"] => Command::ToggleOnlyShowingChanges,
+            ["q" | "quit" | "quit()" | "exit" | "exit()"] => Command::Quit {
+                discard_changes: false,
+            },
+            ["q!" | "quit!"] => Command::Quit {
+                discard_changes: true,
+            },
             ["set", arg] => match *arg {
                 "number" => Command::SetShowLineNumber(Some(true)),
                 "number!" => Command::SetShowLineNumber(None),
@@ -751,32 +2627,67 @@ impl App {
                 "norelativenumber" => Command::SetShowRelativeLineNumber(Some(false)),
                 _ => Command::Unknown,
             },
-            ["w" | "write", filename] => Command::WriteFile {
-                filename: filename.to_string(),
-                overwrite_existing: false,
-                write_format: WriteFormat::Json,
-            },
-            ["w!" | "write!", filename] => Command::WriteFile {
-                filename: filename.to_string(),
-                overwrite_existing: true,
-                write_format: WriteFormat::Json,
+            ["map"] => Command::ListKeyBindings,
+            // Key sequences may contain spaces, e.g., "ctrl-w j".
+            ["map", keys @ .., action] if !keys.is_empty() => Command::Map {
+                keys: keys.join(" "),
+                action: action.to_string(),
             },
+            ["unmap", keys @ ..] if !keys.is_empty() => Command::Unmap(keys.join(" ")),
+            ["w" | "write", args @ ..] => Self::parse_write_command(args, false),
+            ["w!" | "write!", args @ ..] => Self::parse_write_command(args, true),
             #[cfg(feature = "sexp")]
             ["ws" | "writesexp", filename] => Command::WriteFile {
-                filename: filename.to_string(),
+                filename: Some(filename.to_string()),
                 overwrite_existing: false,
-                write_format: WriteFormat::Sexp,
+                write_format: Some(WriteFormat::Sexp),
+                scope: WriteScope::Document,
             },
             #[cfg(feature = "sexp")]
             ["ws!" | "writesexp!", filename] => Command::WriteFile {
-                filename: filename.to_string(),
+                filename: Some(filename.to_string()),
                 overwrite_existing: true,
-                write_format: WriteFormat::Sexp,
+                write_format: Some(WriteFormat::Sexp),
+                scope: WriteScope::Document,
             },
             _ => Command::Unknown,
         }
     }
 
+    // Parse the arguments of :w, which are an optional filename and
+    // options like vim's: ++value, ++matches and ++format=<format>.
+    fn parse_write_command(args: &[&str], overwrite_existing: bool) -> Command {
+        let mut filename = None;
+        let mut write_format = None;
+        let mut scope = WriteScope::Document;
+
+        for arg in args {
+            match arg.strip_prefix("++") {
+                Some("value") => scope = WriteScope::FocusedValue,
+                Some("matches") => scope = WriteScope::Matches,
+                Some(option) => {
+                    let Some(format) = option.strip_prefix("format=") else {
+                        return Command::Unknown;
+                    };
+                    match OutputFormat::from_str(format, true) {
+                        Ok(format) => write_format = Some(WriteFormat::Output(format)),
+                        Err(_) => return Command::Unknown,
+                    }
+                }
+                None if filename.is_none() => filename = Some(arg.to_string()),
+                None => return Command::Unknown,
+            }
+        }
+
+        Command::WriteFile {
+            // Writing the input file always overwrites it.
+            overwrite_existing: overwrite_existing || filename.is_none(),
+            filename,
+            write_format,
+            scope,
+        }
+    }
+
     fn show_help(&mut self) {
         let _ = write!(self.screen_writer.stdout, "{ToMainScreen}");
         let child = std::process::Command::new("less")
@@ -802,12 +2713,21 @@ impl App {
     }
 
     fn get_content_target_data(&self, content_target: ContentTarget) -> Result<String, String> {
-        let json = &self.viewer.flatjson.1;
-        let focused_row_index = self.viewer.focused_row;
-        let focused_row = &self.viewer.flatjson[focused_row_index];
+        let json = &self.buffer.viewer.flatjson.1;
+        let focused_row_index = self.buffer.viewer.focused_row;
+        let focused_row = &self.buffer.viewer.flatjson[focused_row_index];
 
         let data = match content_target {
+            // In large-file mode, the contents of containers may not have been
+            // loaded yet, so we copy them directly from the input.
+            ContentTarget::PrettyPrintedValue | ContentTarget::OneLineValue
+                if focused_row.is_container() && self.buffer.viewer.lazy_input.is_some() =>
+            {
+                let lazy_input = self.buffer.viewer.lazy_input.as_ref().unwrap();
+                lazy_input.input_text(focused_row_index)
+            }
             ContentTarget::PrettyPrintedValue if focused_row.is_container() => self
+                .buffer
                 .viewer
                 .flatjson
                 .pretty_printed_value(focused_row_index)
@@ -840,7 +2760,7 @@ impl App {
                 let quoteless_range = (key_range.start + 1)..(key_range.end - 1);
 
                 // Don't copy quotes in Data mode.
-                if self.viewer.mode == Mode::Data
+                if self.buffer.viewer.mode == Mode::Data
                     && JS_IDENTIFIER.is_match(&json[quoteless_range.clone()])
                 {
                     json[quoteless_range].to_string()
@@ -858,14 +2778,10 @@ impl App {
                     _ => unreachable!(),
                 };
 
-                match self
+                self.buffer
                     .viewer
                     .flatjson
-                    .build_path_to_node(path_type, focused_row_index)
-                {
-                    Ok(path) => path,
-                    Err(err) => return Err(err),
-                }
+                    .build_path_to_node(path_type, focused_row_index)?
             }
         };
 
@@ -875,7 +2791,7 @@ impl App {
     fn copy_content(&mut self, content_target: ContentTarget) {
         match self.get_content_target_data(content_target) {
             Ok(content) => {
-                let focused_row = &self.viewer.flatjson[self.viewer.focused_row];
+                let focused_row = &self.buffer.viewer.flatjson[self.buffer.viewer.focused_row];
 
                 let content_type = match content_target {
                     ContentTarget::PrettyPrintedValue if focused_row.is_container() => {
@@ -905,23 +2821,7 @@ impl App {
     fn print_content(&mut self, content_target: ContentTarget) -> bool {
         match self.get_content_target_data(content_target) {
             Ok(content) => {
-                // Exit raw mode so that the terminal interprets newlines as usual.
-                let _ = self.screen_writer.stdout.suspend_raw_mode();
-                // Go to the main screen so that the text will persist after exiting.
-                let _ = write!(self.screen_writer.stdout, "{ToMainScreen}");
-                // Disable mouse button tracking so that the user can use their mouse
-                // to highlight the text.
-                let _ = write!(self.screen_writer.stdout, "{DISABLE_MOUSE_BUTTON_TRACKING}");
-                let _ = write!(
-                    self.screen_writer.stdout,
-                    "{}{}{}\n\nPress any key to continue.",
-                    termion::clear::All,
-                    termion::cursor::Goto(1, 1),
-                    content
-                );
-                let _ = self.screen_writer.stdout.flush();
-                // Go back to raw mode so we can immediately get key presses.
-                let _ = self.screen_writer.stdout.activate_raw_mode();
+                self.print_text(&content);
                 true
             }
             Err(err) => {
@@ -931,41 +2831,315 @@ impl App {
         }
     }
 
+    fn print_marks(&mut self) -> bool {
+        if self.buffer.marks.is_empty() {
+            self.set_warning_message("No marks set".to_string());
+            return false;
+        }
+
+        let mut text = "mark  path".to_string();
+        for (mark, path) in self.buffer.marks.iter() {
+            // The path to a lone top-level value is empty.
+            let path = if path.is_empty() { "." } else { path };
+            text.push_str(&format!("\n {mark}    {path}"));
+        }
+
+        self.print_text(&text);
+        true
+    }
+
+    fn print_key_bindings(&mut self) {
+        let mut text = "keys        action".to_string();
+        for (keys, action) in self.key_bindings.describe() {
+            text.push_str(&format!("\n{keys:<11} {action}"));
+        }
+
+        self.print_text(&text);
+    }
+
+    fn map_keys(&mut self, keys: &str, action_name: &str) -> Result<(), String> {
+        let keys = parse_key_sequence(keys)?;
+        let action = KeyAction::from_name(action_name)
+            .ok_or_else(|| format!("Unknown action: {action_name}"))?;
+        self.key_bindings.bind(keys, action);
+        Ok(())
+    }
+
+    fn unmap_keys(&mut self, keys: &str) -> Result<(), String> {
+        let keys = parse_key_sequence(keys)?;
+        if !self.key_bindings.unbind(&keys) {
+            return Err(format!("No mapping for {}", key_sequence_name(&keys)));
+        }
+        Ok(())
+    }
+
+    fn print_buffers(&mut self) {
+        let text = Self::format_buffer_list(&self.buffers, &self.buffer);
+        self.print_text(&text);
+    }
+
+    // A list of the buffers, with their numbers, marking the current buffer
+    // with '%' and modified buffers with [+].
+    fn format_buffer_list(buffers: &[Option<Buffer>], current: &Buffer) -> String {
+        let mut text = String::new();
+        for (index, buffer) in buffers.iter().enumerate() {
+            let (marker, buffer) = match buffer {
+                Some(buffer) => (' ', buffer),
+                None => ('%', current),
+            };
+            let modified = if buffer.modified { " [+]" } else { "" };
+            text.push_str(&format!(
+                "{:>3} {marker} {}{modified}\n",
+                index + 1,
+                buffer.input_filename
+            ));
+        }
+
+        text.trim_end().to_string()
+    }
+
+    // Print text to the main screen, where it will stay until a key is pressed.
+    fn print_text(&mut self, text: &str) {
+        // Exit raw mode so that the terminal interprets newlines as usual.
+        let _ = self.screen_writer.stdout.suspend_raw_mode();
+        // Go to the main screen so that the text will persist after exiting.
+        let _ = write!(self.screen_writer.stdout, "{ToMainScreen}");
+        // Disable mouse button tracking so that the user can use their mouse
+        // to highlight the text.
+        let _ = write!(self.screen_writer.stdout, "{DISABLE_MOUSE_BUTTON_TRACKING}");
+        let _ = write!(
+            self.screen_writer.stdout,
+            "{}{}{}\n\nPress any key to continue.",
+            termion::clear::All,
+            termion::cursor::Goto(1, 1),
+            text
+        );
+        let _ = self.screen_writer.stdout.flush();
+        // Go back to raw mode so we can immediately get key presses.
+        let _ = self.screen_writer.stdout.activate_raw_mode();
+    }
+
+    // The values in a part of the document.
+
     fn write_contents_to_file(
         &mut self,
-        filename: String,
+        filename: Option<String>,
         overwrite_existing: bool,
-        write_format: WriteFormat,
+        write_format: Option<WriteFormat>,
+        scope: WriteScope,
     ) {
-        let mut file_open_options = File::options();
-        file_open_options
-            .read(true)
-            .write(true)
-            .create_new(!overwrite_existing);
-
-        match file_open_options.open(&filename) {
-            Err(err) => match err.kind() {
-                io::ErrorKind::AlreadyExists => self
-                    .set_error_message(format!("{filename} already exists (add ! to overwrite)")),
-                _ => self.set_error_message(format!("Error opening file for writing: {err}")),
-            },
-            Ok(mut file) => {
-                let file_contents: Result<String, UnescapeError> = match write_format {
-                    WriteFormat::Json => Ok(self.viewer.flatjson.pretty_printed()),
-                    #[cfg(feature = "sexp")]
-                    WriteFormat::Sexp => self.viewer.flatjson.sexp_string(),
-                };
+        match self
+            .buffer
+            .write_to_file(filename, overwrite_existing, write_format, scope)
+        {
+            Ok(message) => self.set_info_message(message),
+            Err((message, severity)) => self.message = Some((message, severity)),
+        }
+    }
+}
 
-                match file_contents {
-                    Err(err) => {
-                        self.set_error_message(format!("Error formatting file contents: {err}"))
-                    }
-                    Ok(file_contents) => match file.write_all(file_contents.as_bytes()) {
-                        Ok(()) => self.set_info_message(format!("{filename} written")),
-                        Err(err) => self.set_error_message(format!("Error writing file: {err}")),
-                    },
-                }
+// Replace the contents of a file (or create it) by writing them to a
+// temporary file in the same directory and then renaming it over the
+// file. This means a failed write never leaves a partially written file,
+// and that we never truncate a file that's memory mapped, like the input
+// in large-file mode, which would lose the rest of its contents.
+fn replace_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    // Replace the file a symlink points to, rather than the symlink.
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not a file"))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.jless-{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = File::options()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            // Keep the permissions of the file we're replacing.
+            if let Ok(metadata) = fs::metadata(&path) {
+                file.set_permissions(metadata.permissions())?;
             }
-        }
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp_path, &path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+// When a file was last modified, if we can tell.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+
+    fn buffer(input_filename: &str) -> Buffer {
+        let flatjson = parse_top_level_json("{}".to_string()).unwrap();
+        Buffer::new(
+            JsonViewer::new(flatjson, Mode::Data),
+            input_filename.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_parse_buffer_commands() {
+        assert_eq!(App::parse_command("bn"), Command::NextBuffer);
+        assert_eq!(App::parse_command("bnext"), Command::NextBuffer);
+        assert_eq!(App::parse_command("bp"), Command::PrevBuffer);
+        assert_eq!(App::parse_command("bN"), Command::PrevBuffer);
+        assert_eq!(App::parse_command("b 2"), Command::Buffer(2));
+        assert_eq!(App::parse_command("buffer 10"), Command::Buffer(10));
+        assert_eq!(App::parse_command("b two"), Command::Unknown);
+        assert_eq!(App::parse_command("b"), Command::Unknown);
+        assert_eq!(App::parse_command("ls"), Command::ListBuffers);
+        assert_eq!(App::parse_command("buffers"), Command::ListBuffers);
+    }
+
+    #[test]
+    fn test_parse_quit_commands() {
+        assert_eq!(
+            App::parse_command("q"),
+            Command::Quit {
+                discard_changes: false
+            }
+        );
+        assert_eq!(
+            App::parse_command("quit!"),
+            Command::Quit {
+                discard_changes: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_buffer_to_switch_to() {
+        let switch = |command, current| App::buffer_to_switch_to(&command, current, 3);
+
+        assert_eq!(switch(Command::NextBuffer, 0), Ok(1));
+        assert_eq!(switch(Command::NextBuffer, 2), Ok(0));
+        assert_eq!(switch(Command::PrevBuffer, 1), Ok(0));
+        assert_eq!(switch(Command::PrevBuffer, 0), Ok(2));
+        assert_eq!(switch(Command::Buffer(3), 0), Ok(2));
+        assert_eq!(
+            switch(Command::Buffer(0), 1),
+            Err("Buffer 0 does not exist".to_string())
+        );
+        assert_eq!(
+            switch(Command::Buffer(4), 1),
+            Err("Buffer 4 does not exist".to_string())
+        );
+
+        // With a single buffer, we stay on it.
+        assert_eq!(App::buffer_to_switch_to(&Command::NextBuffer, 0, 1), Ok(0));
+        assert_eq!(App::buffer_to_switch_to(&Command::PrevBuffer, 0, 1), Ok(0));
+    }
+
+    #[test]
+    fn test_parse_reloaded_input() {
+        let parse_options = ParseOptions {
+            lenient: false,
+            csv_has_header: true,
+            csv_infer_types: false,
+        };
+        let parse = |data: &str, filters: &[&str]| {
+            App::parse_reloaded_input(
+                data.as_bytes().to_vec(),
+                DataFormat::Json,
+                parse_options,
+                filters,
+                "a.json",
+            )
+        };
+
+        let (flatjson, filtered_documents, _) = parse(r#"{"a": [1, 2]}"#, &[".a"]).unwrap();
+        assert_eq!(flatjson.1, "[1, 2]");
+        assert_eq!(filtered_documents.len(), 1);
+        assert_eq!(filtered_documents[0].1, r#"{ "a": [1, 2] }"#);
+
+        // The file is no longer valid.
+        let err = parse(r#"{"a": [1, 2"#, &[]).err().unwrap();
+        assert!(err.starts_with("Unable to parse a.json: "), "{}", err);
+
+        let err = parse(r#"{"a": 1}"#, &[".a.b"]).err().unwrap();
+        assert!(
+            err.starts_with("Unable to filter reloaded input: "),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_format_buffer_list() {
+        let mut modified = buffer("b.json");
+        modified.modified = true;
+        let buffers = vec![Some(buffer("a.json")), None, Some(modified)];
+
+        assert_eq!(
+            App::format_buffer_list(&buffers, &buffer("current.yaml")),
+            concat!("  1   a.json\n", "  2 % current.yaml\n", "  3   b.json [+]",),
+        );
+    }
+
+    #[test]
+    fn test_write_filtered_document_to_input_file() {
+        let dir = std::env::temp_dir().join(format!("jless-write-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("input.json");
+        let json = r#"{"items": [1, 2]}"#;
+        fs::write(&path, json).unwrap();
+
+        let original = parse_top_level_json(json.to_string()).unwrap();
+        let filtered = filter::apply_filter(&original, ".items | length").unwrap();
+        let mut buffer = buffer("input.json");
+        buffer.viewer = JsonViewer::new(filtered, Mode::Data);
+        buffer.filtered_views.push(FilteredView {
+            filter: ".items | length".to_string(),
+            flatjson: original,
+            top_row: 0,
+            focused_row: 0,
+            recovered_errors: vec![],
+        });
+        buffer.input_path = Some(path.clone());
+        buffer.modified = true;
+
+        let write = |buffer: &mut Buffer, filename: Option<String>| {
+            buffer.write_to_file(filename, true, None, WriteScope::Document)
+        };
+
+        // A bare :w would replace the input with the filter's output.
+        let (message, _) = write(&mut buffer, None).unwrap_err();
+        assert_eq!(
+            message,
+            "Can't write the output of a filter to the input file (use :unfilter or :w <file>)"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), json);
+        assert!(buffer.modified);
+
+        // Writing it to another file is fine.
+        let other_path = dir.join("length.json");
+        let other = other_path.to_string_lossy().to_string();
+        let Ok(message) = write(&mut buffer, Some(other.clone())) else {
+            panic!("Unable to write {}", other);
+        };
+        assert_eq!(message, format!("{other} written"));
+        assert_eq!(fs::read_to_string(&other_path).unwrap(), "2\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), json);
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,362 @@
+// Parsers for binary data formats (MessagePack and CBOR).
+//
+// Both formats are decoded into a common representation, `Item`, which
+// is then converted into rows. Values that have no JSON equivalent are
+// represented as follows:
+//
+// - Binary data is displayed as a base64 encoded string.
+// - MessagePack extension types are displayed as objects:
+//   { "ext": <type>, "data": <base64 encoded data> }
+// - CBOR tags are dropped, and tagged values are displayed as the value
+//   they tag.
+// - Infinite and NaN floats are displayed as the strings "Infinity",
+//   "-Infinity" and "NaN".
+// - Map keys that aren't strings are displayed in square brackets, like
+//   we do for YAML, e.g., { [1]: "one", [true]: "yes" }
+//
+// Like JSON, the input may contain multiple top-level values, which are
+// displayed one after another.
+
+use base64::Engine;
+
+use crate::flatjson::{ContainerType, FlatJson, Index, Row, Value};
+use crate::flatjsonbuilder::FlatJsonBuilder;
+use crate::jsonstringescaper::escape_json_string;
+use crate::parseerror::ParseError;
+
+enum Item {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Binary(Vec<u8>),
+    Array(Vec<Item>),
+    Map(Vec<(Item, Item)>),
+}
+
+pub fn parse_msgpack(input: &[u8]) -> Result<(Vec<Row>, String, usize), ParseError> {
+    let mut items = vec![];
+    let mut bytes = input;
+
+    while !bytes.is_empty() {
+        match rmpv::decode::read_value(&mut bytes) {
+            Ok(value) => items.push(Item::from_msgpack(value)),
+            Err(err) => return Err(error_at(input.len() - bytes.len(), err.to_string())),
+        }
+    }
+
+    parse_items(items)
+}
+
+pub fn parse_cbor(input: &[u8]) -> Result<(Vec<Row>, String, usize), ParseError> {
+    let mut items = vec![];
+    let mut bytes = input;
+
+    while !bytes.is_empty() {
+        let offset = input.len() - bytes.len();
+        match ciborium::de::from_reader::<ciborium::Value, _>(&mut bytes) {
+            Ok(value) => items.push(Item::from_cbor(value).map_err(|err| error_at(offset, err))?),
+            Err(err) => return Err(error_at(input.len() - bytes.len(), err.to_string())),
+        }
+    }
+
+    parse_items(items)
+}
+
+// Binary input doesn't have lines and columns, so errors just say how
+// far into the input they occurred.
+fn error_at(offset: usize, message: String) -> ParseError {
+    ParseError::from(format!("{message} at byte {offset}"))
+}
+
+impl Item {
+    fn from_msgpack(value: rmpv::Value) -> Item {
+        use rmpv::Value as MsgPack;
+
+        match value {
+            MsgPack::Nil => Item::Null,
+            MsgPack::Boolean(b) => Item::Bool(b),
+            MsgPack::Integer(i) => Item::Number(i.to_string()),
+            MsgPack::F32(f) => Item::from_float(f.into(), format!("{f:?}")),
+            MsgPack::F64(f) => Item::from_float(f, format!("{f:?}")),
+            // Strings aren't guaranteed to be valid UTF-8.
+            MsgPack::String(s) => Item::String(String::from_utf8_lossy(s.as_bytes()).into_owned()),
+            MsgPack::Binary(bytes) => Item::Binary(bytes),
+            MsgPack::Array(arr) => Item::Array(arr.into_iter().map(Item::from_msgpack).collect()),
+            MsgPack::Map(map) => Item::Map(
+                map.into_iter()
+                    .map(|(k, v)| (Item::from_msgpack(k), Item::from_msgpack(v)))
+                    .collect(),
+            ),
+            MsgPack::Ext(ext_type, data) => Item::Map(vec![
+                (
+                    Item::String("ext".to_string()),
+                    Item::Number(ext_type.to_string()),
+                ),
+                (Item::String("data".to_string()), Item::Binary(data)),
+            ]),
+        }
+    }
+
+    fn from_cbor(value: ciborium::Value) -> Result<Item, String> {
+        use ciborium::Value as Cbor;
+
+        let item = match value {
+            Cbor::Null => Item::Null,
+            Cbor::Bool(b) => Item::Bool(b),
+            Cbor::Integer(i) => Item::Number(i128::from(i).to_string()),
+            Cbor::Float(f) => Item::from_float(f, format!("{f:?}")),
+            Cbor::Text(s) => Item::String(s),
+            Cbor::Bytes(bytes) => Item::Binary(bytes),
+            Cbor::Array(arr) => Item::Array(
+                arr.into_iter()
+                    .map(Item::from_cbor)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Cbor::Map(map) => Item::Map(
+                map.into_iter()
+                    .map(|(k, v)| Ok((Item::from_cbor(k)?, Item::from_cbor(v)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            // Tags (e.g., marking a string as a date) don't have a JSON
+            // equivalent, so we just display the tagged value.
+            Cbor::Tag(_, value) => Item::from_cbor(*value)?,
+            // ciborium::Value is non-exhaustive.
+            value => return Err(format!("Unsupported CBOR value: {value:?}")),
+        };
+
+        Ok(item)
+    }
+
+    // Infinity and NaN don't have a JSON equivalent, so they're displayed
+    // as strings.
+    fn from_float(f: f64, debug_str: String) -> Item {
+        if f.is_nan() {
+            Item::String("NaN".to_string())
+        } else if f.is_infinite() {
+            Item::String(if f > 0.0 { "Infinity" } else { "-Infinity" }.to_string())
+        } else {
+            Item::Number(debug_str)
+        }
+    }
+}
+
+#[derive(Default)]
+struct BinaryParser {
+    doc: FlatJsonBuilder,
+}
+
+fn parse_items(items: Vec<Item>) -> Result<(Vec<Row>, String, usize), ParseError> {
+    if items.is_empty() {
+        return Err("Unexpected EOF".to_string().into());
+    }
+
+    let mut parser = BinaryParser::default();
+    let mut siblings = None;
+
+    for item in items {
+        let key_range = parser.doc.start_child(None, None, &siblings);
+        let index = parser.parse_item(item);
+        parser.doc.finish_child(index, key_range, &mut siblings);
+    }
+
+    let FlatJson(rows, pretty_printed, max_depth) = parser.doc.build();
+    Ok((rows, pretty_printed, max_depth))
+}
+
+impl BinaryParser {
+    fn parse_item(&mut self, item: Item) -> Index {
+        match item {
+            Item::Null => self.doc.push_primitive(Value::Null, "null"),
+            Item::Bool(b) => self.doc.push_primitive(Value::Boolean, &b.to_string()),
+            Item::Number(n) => self.doc.push_primitive(Value::Number, &n),
+            Item::String(s) => self.doc.push_primitive(Value::String, &quote_string(&s)),
+            Item::Binary(bytes) => self
+                .doc
+                .push_primitive(Value::String, &encode_binary(&bytes)),
+            Item::Array(arr) => self.parse_array(arr),
+            Item::Map(map) => self.parse_map(map),
+        }
+    }
+
+    fn parse_array(&mut self, arr: Vec<Item>) -> Index {
+        let open_index = self.doc.open_container(ContainerType::Array);
+        let mut siblings = None;
+
+        for child in arr {
+            let key_range = self
+                .doc
+                .start_child(None, Some(ContainerType::Array), &siblings);
+            let child_index = self.parse_item(child);
+            self.doc.finish_child(child_index, key_range, &mut siblings);
+        }
+
+        self.doc
+            .finish_container(open_index, ContainerType::Array, false, siblings);
+        open_index
+    }
+
+    fn parse_map(&mut self, map: Vec<(Item, Item)>) -> Index {
+        let open_index = self.doc.open_container(ContainerType::Object);
+        let mut siblings = None;
+
+        for (key, value) in map {
+            let mut key_text = String::new();
+            pretty_print_key_item(&mut key_text, &key, true);
+
+            let key_range =
+                self.doc
+                    .start_child(Some(&key_text), Some(ContainerType::Object), &siblings);
+            let child_index = self.parse_item(value);
+            self.doc.finish_child(child_index, key_range, &mut siblings);
+        }
+
+        self.doc
+            .finish_container(open_index, ContainerType::Object, false, siblings);
+        open_index
+    }
+}
+
+// Print a map key (or a value nested inside of a non-string map key)
+// on a single line. Non-string keys are wrapped in square brackets.
+fn pretty_print_key_item(buf: &mut String, item: &Item, is_key: bool) {
+    if let Item::String(s) = item {
+        buf.push_str(&quote_string(s));
+        return;
+    }
+
+    if is_key {
+        buf.push('[');
+    }
+
+    match item {
+        Item::Null => buf.push_str("null"),
+        Item::Bool(b) => buf.push_str(if *b { "true" } else { "false" }),
+        Item::Number(n) => buf.push_str(n),
+        Item::Binary(bytes) => buf.push_str(&encode_binary(bytes)),
+        Item::Array(arr) => {
+            buf.push('[');
+            for (i, elem) in arr.iter().enumerate() {
+                if i != 0 {
+                    buf.push_str(", ");
+                }
+                pretty_print_key_item(buf, elem, false);
+            }
+            buf.push(']');
+        }
+        Item::Map(map) => {
+            if map.is_empty() {
+                buf.push_str("{}");
+            } else {
+                buf.push_str("{ ");
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i != 0 {
+                        buf.push_str(", ");
+                    }
+                    pretty_print_key_item(buf, key, true);
+                    buf.push_str(": ");
+                    pretty_print_key_item(buf, value, false);
+                }
+                buf.push_str(" }");
+            }
+        }
+        Item::String(_) => unreachable!(),
+    }
+
+    if is_key {
+        buf.push(']');
+    }
+}
+
+fn quote_string(s: &str) -> String {
+    format!("\"{}\"", escape_json_string(s))
+}
+
+fn encode_binary(bytes: &[u8]) -> String {
+    // Base64 output never needs to be escaped.
+    format!(
+        "\"{}\"",
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::OptionIndex;
+
+    #[test]
+    fn test_msgpack() {
+        let value = rmpv::Value::Map(vec![
+            (rmpv::Value::from("a"), rmpv::Value::from(1)),
+            (rmpv::Value::from(2), rmpv::Value::Binary(vec![1, 2, 3])),
+            (
+                rmpv::Value::Array(vec![rmpv::Value::Nil]),
+                rmpv::Value::Ext(5, vec![255]),
+            ),
+        ]);
+
+        let mut bytes = vec![];
+        rmpv::encode::write_value(&mut bytes, &value).unwrap();
+        rmpv::encode::write_value(&mut bytes, &rmpv::Value::F64(1.0)).unwrap();
+        rmpv::encode::write_value(&mut bytes, &rmpv::Value::F32(f32::INFINITY)).unwrap();
+
+        let (rows, pretty, _) = parse_msgpack(&bytes).unwrap();
+
+        assert_eq!(
+            pretty,
+            concat!(
+                r#"{ "a": 1, [2]: "AQID", [[null]]: { "ext": 5, "data": "/w==" } }"#,
+                "\n1.0",
+                "\n\"Infinity\"",
+            )
+        );
+        assert_eq!(rows[2].key_range, Some(10..13)); // [2]
+        assert_eq!(rows[0].next_sibling, OptionIndex::Index(rows.len() - 2));
+    }
+
+    #[test]
+    fn test_cbor() {
+        let value = ciborium::Value::Array(vec![
+            ciborium::Value::Tag(1, Box::new(ciborium::Value::Integer(1_700_000_000.into()))),
+            ciborium::Value::Text("tab\t".to_string()),
+            ciborium::Value::Float(f64::NAN),
+            ciborium::Value::Float(f64::NEG_INFINITY),
+            ciborium::Value::Float(0.5),
+        ]);
+
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&value, &mut bytes).unwrap();
+
+        let (_, pretty, _) = parse_cbor(&bytes).unwrap();
+
+        assert_eq!(pretty, r#"[1700000000, "tab\t", "NaN", "-Infinity", 0.5]"#);
+    }
+
+    #[test]
+    fn test_empty_containers() {
+        let value = rmpv::Value::Array(vec![rmpv::Value::Map(vec![]), rmpv::Value::Array(vec![])]);
+
+        let mut bytes = vec![];
+        rmpv::encode::write_value(&mut bytes, &value).unwrap();
+
+        let (rows, pretty, depth) = parse_msgpack(&bytes).unwrap();
+
+        assert_eq!(pretty, "[{}, []]");
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[2].range, 5..7);
+        assert_eq!(rows[2].prev_sibling, OptionIndex::Index(1));
+        assert_eq!(depth, 1);
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        // Truncated array
+        assert_eq!(
+            parse_msgpack(&[0x01, 0x92, 0x01]).unwrap_err().to_string(),
+            "I/O error while reading marker byte: failed to fill whole buffer at byte 3",
+        );
+        assert!(parse_cbor(&[0xff]).is_err());
+        assert!(parse_cbor(&[]).is_err());
+    }
+}
@@ -0,0 +1,522 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, ValueEnum};
+use termion::event::Key;
+
+use crate::highlighting;
+use crate::highlighting::Colors;
+use crate::keybindings::{parse_key_sequence, KeyAction};
+use crate::options::{ColorWhen, DataFormat, Opt};
+use crate::parseerror::ParseError;
+use crate::terminal::Color;
+use crate::viewer::Mode;
+
+// This module reads the config file, which sets defaults for command line
+// options, and some things that can't be set on the command line. The
+// config file is TOML, e.g.:
+//
+//     mode = "line"
+//     line-numbers = false
+//     relative-line-numbers = true
+//     scrolloff = 5
+//     color = "always"
+//
+//     # Data formats of files with other extensions.
+//     [formats]
+//     jsonl = "json"
+//     conf = "yaml"
+//
+//     [colors]
+//     key = "cyan"
+//     string = 214
+//
+//     # Keys that should do whatever other keys are bound to, or sequences
+//     # of keys bound to actions, or unbound.
+//     [keys]
+//     J = "ctrl-d"
+//     K = "ctrl-u"
+//     "ctrl-x ctrl-c" = "quit"
+//     dd = ""
+//
+// Options from the config file are turned into command line arguments,
+// which come before the arguments in JLESS_OPTIONS and on the command
+// line, so that those take precedence. The mode is the exception: it's
+// only the default mode of the viewer, so that it doesn't change how
+// input is printed when STDOUT isn't a TTY.
+
+const OPTIONS: &[&str] = &[
+    "mode",
+    "line-numbers",
+    "relative-line-numbers",
+    "scrolloff",
+    "color",
+    "formats",
+    "colors",
+    "keys",
+];
+
+const COLORS: &[&str] = &["key", "string", "number", "boolean", "null"];
+
+const ENV_VAR: &str = "JLESS_OPTIONS";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    // Command line arguments for the options that were set.
+    pub args: Vec<String>,
+    pub mode: Option<Mode>,
+    pub extension_formats: HashMap<String, DataFormat>,
+    pub colors: Option<Colors>,
+    pub key_remaps: HashMap<Key, Key>,
+    pub key_bindings: Vec<(Vec<Key>, Option<KeyAction>)>,
+}
+
+// Parse the options from the config file, JLESS_OPTIONS, and the command
+// line, exiting if any of them are invalid.
+pub fn parse_options() -> Opt {
+    let mut cli_args: Vec<OsString> = std::env::args_os().collect();
+    let program = cli_args.remove(0);
+
+    let env_args: Vec<OsString> = match std::env::var(ENV_VAR) {
+        Ok(options) => split_env_options(&options).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }),
+        Err(_) => vec![],
+    };
+
+    // We need to parse the other options first to know where the config
+    // file is.
+    let opt = Opt::parse_from(std::iter::once(&program).chain(&env_args).chain(&cli_args));
+
+    let config = if opt.no_config {
+        Config::default()
+    } else {
+        let (path, required) = match &opt.config {
+            Some(path) => (Some(path.clone()), true),
+            None => (default_config_path(), false),
+        };
+        match path {
+            Some(path) => read_config(&path, required).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }),
+            None => Config::default(),
+        }
+    };
+
+    let mut opt = Opt::parse_from(
+        std::iter::once(&program)
+            .chain(&config.args.iter().map(OsString::from).collect::<Vec<_>>())
+            .chain(&env_args)
+            .chain(&cli_args),
+    );
+
+    opt.default_mode = config.mode;
+    opt.extension_formats = config.extension_formats;
+    opt.key_remaps = config.key_remaps;
+    opt.key_bindings = config.key_bindings;
+    if let Some(colors) = config.colors {
+        highlighting::set_colors(colors);
+    }
+
+    opt
+}
+
+// Split the options in JLESS_OPTIONS like a shell would, so that values
+// containing spaces can be quoted.
+fn split_env_options(options: &str) -> Result<Vec<OsString>, String> {
+    match shlex::split(options) {
+        Some(args) => Ok(args.into_iter().map(OsString::from).collect()),
+        None => Err(format!("Unable to parse {ENV_VAR}: unmatched quote")),
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("jless").join("config.toml"))
+}
+
+// Read a config file. It's fine for the default config file not to exist.
+fn read_config(path: &Path, required: bool) -> Result<Config, String> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && !required => {
+            return Ok(Config::default())
+        }
+        Err(err) => return Err(format!("Unable to read {}: {err}", path.display())),
+    };
+
+    parse_config(&contents).map_err(|err| format!("Error in {}: {err}", path.display()))
+}
+
+pub fn parse_config(contents: &str) -> Result<Config, String> {
+    let table = match contents.parse::<toml::Table>() {
+        Ok(table) => table,
+        Err(err) => {
+            let mut message = err.message().trim_end().replace('\n', "; ");
+            // Some errors, e.g., a missing value, don't have a message.
+            if message.is_empty() {
+                message = "invalid TOML".to_owned();
+            }
+            return Err(match err.span() {
+                Some(span) => ParseError::at(contents, span.start, message).to_string(),
+                None => message,
+            });
+        }
+    };
+
+    let mut config = Config::default();
+
+    for (key, value) in table.iter() {
+        match key.as_str() {
+            "mode" => config.mode = Some(parse_enum::<Mode>(key, value)?),
+            "line-numbers" | "relative-line-numbers" => {
+                let arg = match expect_bool(key, value)? {
+                    true => format!("--{key}"),
+                    false => format!("--no-{key}"),
+                };
+                config.args.push(arg);
+            }
+            "scrolloff" => match value.as_integer() {
+                Some(n) if (0..=u16::MAX as i64).contains(&n) => {
+                    config.args.push(format!("--scrolloff={n}"));
+                }
+                _ => return Err(format!("{key} must be a non-negative integer")),
+            },
+            "color" => {
+                let when = parse_enum::<ColorWhen>(key, value)?;
+                config.args.push(format!("--color={}", value_name(when)));
+            }
+            "formats" => {
+                for (extension, format) in expect_table(key, value)? {
+                    let format = parse_enum::<DataFormat>(&format!("formats.{extension}"), format)?;
+                    config.extension_formats.insert(extension.clone(), format);
+                }
+            }
+            "colors" => {
+                let mut colors = Colors::default();
+                for (name, color) in expect_table(key, value)? {
+                    let color = parse_color(color).ok_or_else(|| {
+                        format!(
+                            "colors.{name} must be a color name, like \"cyan\", or a number \
+                             from 0 to 255"
+                        )
+                    })?;
+                    match name.as_str() {
+                        "key" => {
+                            colors.key = color;
+                            colors.focused_key = color;
+                        }
+                        "string" => colors.string = color,
+                        "number" => colors.number = color,
+                        "boolean" => colors.boolean = color,
+                        "null" => colors.null = color,
+                        _ => return Err(unknown_key_error(&format!("colors.{name}"), COLORS)),
+                    }
+                }
+                config.colors = Some(colors);
+            }
+            "keys" => {
+                for (from, to) in expect_table(key, value)? {
+                    let Some(to) = to.as_str() else {
+                        return Err(format!("keys.{from} must be a string"));
+                    };
+                    if to.is_empty() {
+                        config.key_bindings.push((parse_key_sequence(from)?, None));
+                    } else if let Some(action) = KeyAction::from_name(to) {
+                        config
+                            .key_bindings
+                            .push((parse_key_sequence(from)?, Some(action)));
+                    } else {
+                        let to =
+                            parse_key(to).map_err(|_| format!("Unknown key or action: {to}"))?;
+                        let from = match parse_key_sequence(from)?[..] {
+                            [from] => from,
+                            _ => return Err(format!("Unknown key: {from}")),
+                        };
+                        config.key_remaps.insert(from, to);
+                    }
+                }
+            }
+            _ => return Err(unknown_key_error(key, OPTIONS)),
+        }
+    }
+
+    Ok(config)
+}
+
+fn unknown_key_error(key: &str, valid_keys: &[&str]) -> String {
+    format!(
+        "Unknown key {key} (expected one of: {})",
+        valid_keys.join(", ")
+    )
+}
+
+fn expect_bool(key: &str, value: &toml::Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("{key} must be true or false"))
+}
+
+fn expect_table<'a>(key: &str, value: &'a toml::Value) -> Result<&'a toml::Table, String> {
+    value
+        .as_table()
+        .ok_or_else(|| format!("{key} must be a table, e.g., [{key}]"))
+}
+
+fn parse_enum<T: ValueEnum>(key: &str, value: &toml::Value) -> Result<T, String> {
+    let possible_values = || {
+        T::value_variants()
+            .iter()
+            .map(|variant| format!("\"{}\"", value_name(variant.clone())))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    value
+        .as_str()
+        .and_then(|value| T::from_str(value, true).ok())
+        .ok_or_else(|| format!("{key} must be one of {}", possible_values()))
+}
+
+fn value_name<T: ValueEnum>(value: T) -> String {
+    value.to_possible_value().unwrap().get_name().to_string()
+}
+
+// Colors can be given by name, or as a number in the 256 color palette.
+fn parse_color(value: &toml::Value) -> Option<Color> {
+    if let Some(n) = value.as_integer() {
+        return u8::try_from(n).ok().map(Color::C16);
+    }
+
+    let name = value.as_str()?.to_lowercase();
+    if name == "default" {
+        return Some(Color::Default);
+    }
+
+    const NAMES: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    let (name, offset) = match name.strip_prefix("bright-") {
+        Some(name) => (name, 8),
+        None if name == "gray" || name == "grey" => ("black", 8),
+        None => (name.as_str(), 0),
+    };
+    let index = NAMES.iter().position(|&n| n == name)?;
+    Some(Color::C16(index as u8 + offset))
+}
+
+// Parse the name of a key: a single character, a special key like "esc",
+// "enter", "space", "pagedown" or "f1", or "ctrl-<char>" or "alt-<char>".
+pub fn parse_key(name: &str) -> Result<Key, String> {
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return Ok(Key::Char(ch));
+    }
+
+    let lowercase_name = name.to_lowercase();
+    let single_char = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Some(ch),
+            _ => None,
+        }
+    };
+
+    let key = match lowercase_name.as_str() {
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "backspace" => Key::Backspace,
+        "delete" => Key::Delete,
+        "insert" => Key::Insert,
+        "esc" | "escape" => Key::Esc,
+        "enter" | "return" => Key::Char('\n'),
+        "tab" => Key::Char('\t'),
+        "backtab" => Key::BackTab,
+        "space" => Key::Char(' '),
+        _ => {
+            let key = if let Some(ch) = lowercase_name.strip_prefix("ctrl-") {
+                single_char(ch).map(Key::Ctrl)
+            } else if let Some(ch) = name.get(4..).filter(|_| lowercase_name.starts_with("alt-")) {
+                single_char(ch).map(Key::Alt)
+            } else if let Some(n) = lowercase_name.strip_prefix('f') {
+                n.parse::<u8>()
+                    .ok()
+                    .filter(|n| (1..=12).contains(n))
+                    .map(Key::F)
+            } else {
+                None
+            };
+            key.ok_or_else(|| format!("Unknown key: {name}"))?
+        }
+    };
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal;
+
+    #[test]
+    fn test_parse_config() {
+        let config = parse_config(
+            r#"
+            mode = "line"
+            line-numbers = false
+            relative-line-numbers = true
+            scrolloff = 5
+            color = "always"
+
+            [formats]
+            jsonl = "json"
+            conf = "YAML"
+
+            [colors]
+            key = "cyan"
+            string = 214
+            null = "bright-black"
+
+            [keys]
+            J = "ctrl-d"
+            ctrl-n = "down"
+            "ctrl-x ctrl-c" = "quit"
+            dd = ""
+            gg = "focus-top"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.args,
+            vec![
+                "--no-line-numbers",
+                "--relative-line-numbers",
+                "--scrolloff=5",
+                "--color=always",
+            ]
+        );
+        assert_eq!(config.mode, Some(Mode::Line));
+        assert_eq!(
+            config.extension_formats,
+            HashMap::from([
+                ("jsonl".to_string(), DataFormat::Json),
+                ("conf".to_string(), DataFormat::Yaml),
+            ])
+        );
+        assert_eq!(
+            config.colors,
+            Some(Colors {
+                key: Color::C16(6),
+                focused_key: Color::C16(6),
+                string: Color::C16(214),
+                null: terminal::LIGHT_BLACK,
+                ..Colors::default()
+            })
+        );
+        assert_eq!(
+            config.key_remaps,
+            HashMap::from([
+                (Key::Char('J'), Key::Ctrl('d')),
+                (Key::Ctrl('n'), Key::Down),
+            ])
+        );
+        assert_eq!(
+            config.key_bindings,
+            vec![
+                (vec![Key::Ctrl('x'), Key::Ctrl('c')], Some(KeyAction::Quit)),
+                (vec![Key::Char('d'), Key::Char('d')], None),
+                (
+                    vec![Key::Char('g'), Key::Char('g')],
+                    Some(KeyAction::FocusTop)
+                ),
+            ]
+        );
+
+        // The options are valid command line arguments.
+        let args = std::iter::once("jless".to_string()).chain(config.args);
+        let opt = Opt::try_parse_from(args).unwrap();
+        assert_eq!(opt.scrolloff, 5);
+        assert_eq!(opt.mode, None);
+    }
+
+    #[test]
+    fn test_split_env_options() {
+        assert_eq!(
+            split_env_options(r#"--mode line  --filter '.a | .b' --path "x y""#).unwrap(),
+            ["--mode", "line", "--filter", ".a | .b", "--path", "x y"]
+                .map(OsString::from)
+                .to_vec(),
+        );
+        assert_eq!(split_env_options("").unwrap(), Vec::<OsString>::new());
+        assert_eq!(
+            split_env_options("--filter '.a").unwrap_err(),
+            "Unable to parse JLESS_OPTIONS: unmatched quote"
+        );
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let error = |contents: &str| parse_config(contents).unwrap_err();
+
+        assert_eq!(
+            error("lines = true"),
+            "Unknown key lines (expected one of: mode, line-numbers, relative-line-numbers, \
+             scrolloff, color, formats, colors, keys)",
+        );
+        assert_eq!(
+            error("[colors]\nkeys = \"red\""),
+            "Unknown key colors.keys (expected one of: key, string, number, boolean, null)",
+        );
+        assert_eq!(
+            error("mode = \"lines\""),
+            "mode must be one of \"line\", \"data\""
+        );
+        assert_eq!(
+            error("scrolloff = -1"),
+            "scrolloff must be a non-negative integer"
+        );
+        assert_eq!(
+            error("formats = \"json\""),
+            "formats must be a table, e.g., [formats]"
+        );
+        assert_eq!(
+            error("[colors]\nkey = \"teal\""),
+            "colors.key must be a color name, like \"cyan\", or a number from 0 to 255"
+        );
+        assert_eq!(
+            error("[keys]\nJ = \"ctrl-down\""),
+            "Unknown key or action: ctrl-down"
+        );
+        assert_eq!(error("[keys]\ngg = \"G\""), "Unknown key: gg");
+        assert_eq!(error("mode = "), "invalid TOML at line 1, column 8");
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("J"), Ok(Key::Char('J')));
+        assert_eq!(parse_key(":"), Ok(Key::Char(':')));
+        assert_eq!(parse_key("Ctrl-D"), Ok(Key::Ctrl('d')));
+        assert_eq!(parse_key("alt-X"), Ok(Key::Alt('X')));
+        assert_eq!(parse_key("space"), Ok(Key::Char(' ')));
+        assert_eq!(parse_key("PageDown"), Ok(Key::PageDown));
+        assert_eq!(parse_key("f12"), Ok(Key::F(12)));
+        assert!(parse_key("f13").is_err());
+        assert!(parse_key("ctrl-").is_err());
+        assert!(parse_key("").is_err());
+    }
+}
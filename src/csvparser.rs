@@ -0,0 +1,223 @@
+use csv::{ErrorKind, ReaderBuilder, StringRecord};
+use logos::Logos;
+
+use crate::flatjson::{ContainerType, FlatJson, Index, Row, Value};
+use crate::flatjsonbuilder::FlatJsonBuilder;
+use crate::jsonstringescaper::escape_json_string;
+use crate::jsontokenizer::JsonToken;
+use crate::parseerror::ParseError;
+
+pub struct CsvOptions {
+    pub delimiter: u8,
+    // If true, the first record is used as the keys for the rest of the
+    // records, which are displayed as objects. Otherwise each record is
+    // displayed as an array.
+    pub has_header: bool,
+    // If true, fields that look like numbers or booleans are displayed
+    // as such; otherwise every field is displayed as a string.
+    pub infer_types: bool,
+}
+
+struct CsvParser {
+    doc: FlatJsonBuilder,
+    infer_types: bool,
+}
+
+pub fn parse(csv: String, options: CsvOptions) -> Result<(Vec<Row>, String, usize), ParseError> {
+    let mut parser = CsvParser {
+        doc: FlatJsonBuilder::default(),
+        infer_types: options.infer_types,
+    };
+
+    // We handle the header ourselves, and allow records to have differing
+    // numbers of fields, since we don't need to fit them into a table.
+    let mut reader = ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(csv.as_bytes());
+
+    let mut records = vec![];
+    for record in reader.records() {
+        match record {
+            Ok(record) => records.push(record),
+            Err(err) => return Err(csv_error(&csv, err)),
+        }
+    }
+
+    let header = if options.has_header && !records.is_empty() {
+        Some(records.remove(0))
+    } else {
+        None
+    };
+
+    // The entire input is displayed as a single array of records.
+    parser.parse_records(records, header.as_ref());
+
+    let FlatJson(rows, pretty_printed, max_depth) = parser.doc.build();
+    Ok((rows, pretty_printed, max_depth))
+}
+
+impl CsvParser {
+    fn parse_records(&mut self, records: Vec<StringRecord>, header: Option<&StringRecord>) {
+        let open_index = self.doc.open_container(ContainerType::Array);
+        let mut siblings = None;
+
+        for record in records.iter() {
+            let key_range = self
+                .doc
+                .start_child(None, Some(ContainerType::Array), &siblings);
+            let record_index = match header {
+                Some(header) => self.parse_record_as_object(header, record),
+                None => self.parse_record_as_array(record),
+            };
+            self.doc
+                .finish_child(record_index, key_range, &mut siblings);
+        }
+
+        self.doc
+            .finish_container(open_index, ContainerType::Array, false, siblings);
+    }
+
+    fn parse_record_as_object(&mut self, header: &StringRecord, record: &StringRecord) -> Index {
+        let open_index = self.doc.open_container(ContainerType::Object);
+        let mut siblings = None;
+
+        for (i, field) in record.iter().enumerate() {
+            // Fields without a corresponding header use their (1-based)
+            // column number as their key.
+            let key = match header.get(i) {
+                Some(key) => format!("\"{}\"", escape_json_string(key)),
+                None => format!("\"{}\"", i + 1),
+            };
+
+            let key_range =
+                self.doc
+                    .start_child(Some(&key), Some(ContainerType::Object), &siblings);
+            let field_index = self.parse_field(field);
+            self.doc.finish_child(field_index, key_range, &mut siblings);
+        }
+
+        self.doc
+            .finish_container(open_index, ContainerType::Object, false, siblings);
+        open_index
+    }
+
+    fn parse_record_as_array(&mut self, record: &StringRecord) -> Index {
+        let open_index = self.doc.open_container(ContainerType::Array);
+        let mut siblings = None;
+
+        for field in record.iter() {
+            let key_range = self
+                .doc
+                .start_child(None, Some(ContainerType::Array), &siblings);
+            let field_index = self.parse_field(field);
+            self.doc.finish_child(field_index, key_range, &mut siblings);
+        }
+
+        self.doc
+            .finish_container(open_index, ContainerType::Array, false, siblings);
+        open_index
+    }
+
+    fn parse_field(&mut self, field: &str) -> Index {
+        if self.infer_types && (field == "true" || field == "false") {
+            self.doc.push_primitive(Value::Boolean, field)
+        } else if self.infer_types && is_json_number(field) {
+            self.doc.push_primitive(Value::Number, field)
+        } else {
+            let quoted = format!("\"{}\"", escape_json_string(field));
+            self.doc.push_primitive(Value::String, &quoted)
+        }
+    }
+}
+
+// Errors are reported at the start of the record they occurred in.
+fn csv_error(csv: &str, err: csv::Error) -> ParseError {
+    let message = match err.kind() {
+        ErrorKind::Utf8 { err, .. } => format!("Invalid UTF-8 in field {}", err.field() + 1),
+        _ => err.to_string(),
+    };
+    match err.position() {
+        Some(position) => ParseError::at(csv, position.byte() as usize, message),
+        None => ParseError::from(message),
+    }
+}
+
+fn is_json_number(s: &str) -> bool {
+    let mut lexer = JsonToken::lexer(s);
+    lexer.next() == Some(JsonToken::Number) && lexer.span().len() == s.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::flatjson::OptionIndex;
+
+    const CSV: &str = indoc! {r#"
+        name,age,"multi
+        line"
+        "Smith, J",42,true
+        "say ""hi""",4.5e3
+    "#};
+
+    fn options(has_header: bool, infer_types: bool) -> CsvOptions {
+        CsvOptions {
+            delimiter: b',',
+            has_header,
+            infer_types,
+        }
+    }
+
+    #[test]
+    fn test_header_and_type_inference() {
+        let (rows, pretty, _) = parse(CSV.to_owned(), options(true, true)).unwrap();
+
+        assert_eq!(
+            pretty,
+            concat!(
+                r#"[{ "name": "Smith, J", "age": 42, "multi\nline": true }, "#,
+                r#"{ "name": "say \"hi\"", "age": 4.5e3 }]"#,
+            )
+        );
+
+        assert_eq!(rows[0].range, 0..pretty.len()); // Array
+        assert_eq!(rows[1].next_sibling, OptionIndex::Index(6));
+        assert_eq!(rows[6].prev_sibling, OptionIndex::Index(1));
+        assert_eq!(rows[6].index_in_parent, 1);
+        assert_eq!(rows[3].key_range, Some(23..28)); // "age"
+        assert_eq!(rows[3].range, 30..32); // 42
+    }
+
+    #[test]
+    fn test_no_header_without_type_inference() {
+        let (_, pretty, _) = parse(CSV.to_owned(), options(false, false)).unwrap();
+
+        assert_eq!(
+            pretty,
+            concat!(
+                r#"[["name", "age", "multi\nline"], "#,
+                r#"["Smith, J", "42", "true"], "#,
+                r#"["say \"hi\"", "4.5e3"]]"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_fields_without_a_header() {
+        let (rows, pretty, depth) = parse("a\n1,2\n".to_owned(), options(true, false)).unwrap();
+        assert_eq!(pretty, r#"[{ "a": "1", "2": "2" }]"#);
+        assert_eq!(rows[3].key_range, Some(13..16)); // "2"
+        assert_eq!(rows[3].index_in_parent, 1);
+        assert_eq!(depth, 2);
+    }
+
+    #[test]
+    fn test_empty() {
+        let (rows, pretty, _) = parse(String::new(), options(true, false)).unwrap();
+        assert_eq!(pretty, "[]");
+        assert_eq!(rows.len(), 1);
+    }
+}
@@ -0,0 +1,369 @@
+// Transparent decompression of compressed input.
+//
+// We detect whether input is compressed by looking at the magic bytes
+// at the start of it, rather than trusting the file extension, so that
+// compressed input can also be provided via STDIN. Input that isn't
+// compressed is passed through unchanged.
+//
+// Input can either be decompressed all at once, or incrementally, as
+// it's read, via a StreamDecompressor, which is used when streaming
+// input from STDIN.
+
+use std::io;
+use std::io::Write;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+const MAGIC_BYTES: [(Compression, &[u8]); 4] = [
+    (Compression::Gzip, b"\x1f\x8b"),
+    (Compression::Zstd, b"\x28\xb5\x2f\xfd"),
+    (Compression::Bzip2, b"BZh"),
+    (Compression::Xz, b"\xfd7zXZ\x00"),
+];
+
+// "BZh" is likely to appear at the start of plain text too, so bzip2
+// input is only detected if the magic bytes are followed by a block size
+// ('1' to '9') and the magic number of the first block, or of the end of
+// the stream, if no data was compressed.
+const BZIP2_BLOCK_MAGIC: [&[u8]; 2] = [b"1AY&SY", b"\x17rE8P\x90"];
+
+// File extensions of compressed files; these are ignored when determining
+// the format of the input, e.g., "data.yaml.gz" is parsed as YAML.
+const COMPRESSION_EXTENSIONS: [&str; 5] = [".gz", ".zst", ".zstd", ".bz2", ".xz"];
+
+enum Detection {
+    Compressed(Compression),
+    NotCompressed,
+    // The input is a prefix of some magic bytes, so we can't tell yet.
+    NeedMoreData,
+}
+
+fn detect_compression(bytes: &[u8]) -> Detection {
+    let mut need_more_data = false;
+
+    for (compression, magic) in MAGIC_BYTES.iter() {
+        if bytes.starts_with(magic) {
+            if *compression == Compression::Bzip2 {
+                return detect_bzip2_block(&bytes[magic.len()..]);
+            }
+            return Detection::Compressed(*compression);
+        }
+        if magic.starts_with(bytes) {
+            need_more_data = true;
+        }
+    }
+
+    if need_more_data {
+        Detection::NeedMoreData
+    } else {
+        Detection::NotCompressed
+    }
+}
+
+fn detect_bzip2_block(bytes: &[u8]) -> Detection {
+    let Some((block_size, block)) = bytes.split_first() else {
+        return Detection::NeedMoreData;
+    };
+
+    if !(b'1'..=b'9').contains(block_size) {
+        return Detection::NotCompressed;
+    }
+
+    let mut need_more_data = false;
+
+    for magic in BZIP2_BLOCK_MAGIC.iter() {
+        if block.starts_with(magic) {
+            return Detection::Compressed(Compression::Bzip2);
+        }
+        if magic.starts_with(block) {
+            need_more_data = true;
+        }
+    }
+
+    if need_more_data {
+        Detection::NeedMoreData
+    } else {
+        Detection::NotCompressed
+    }
+}
+
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    matches!(detect_compression(bytes), Detection::Compressed(_))
+}
+
+pub fn strip_compression_extension(filename: &str) -> &str {
+    for extension in COMPRESSION_EXTENSIONS.iter() {
+        if let Some(stripped) = filename.strip_suffix(extension) {
+            return stripped;
+        }
+    }
+
+    filename
+}
+
+// Decompress the input if it is compressed, otherwise return it as is.
+pub fn decompress(bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !is_compressed(&bytes) {
+        return Ok(bytes);
+    }
+
+    let mut decompressor = StreamDecompressor::default();
+    let mut output = decompressor.push(&bytes)?;
+    output.extend(decompressor.finish()?);
+    Ok(output)
+}
+
+// All of these decoders write the decompressed data to a Vec, which we
+// drain after each write. For zstd, we use the lower-level Writer, rather
+// than zstd::stream::write::Decoder, because only it reports an error if
+// the input ends part way through a frame.
+enum Decoder {
+    Gzip(flate2::write::MultiGzDecoder<Vec<u8>>),
+    Zstd(zstd::stream::zio::Writer<Vec<u8>, zstd::stream::raw::Decoder<'static>>),
+    Bzip2(bzip2::write::BzDecoder<Vec<u8>>),
+    Xz(xz2::write::XzDecoder<Vec<u8>>),
+}
+
+impl Decoder {
+    fn new(compression: Compression) -> io::Result<Decoder> {
+        Ok(match compression {
+            Compression::Gzip => Decoder::Gzip(flate2::write::MultiGzDecoder::new(vec![])),
+            Compression::Zstd => Decoder::Zstd(zstd::stream::zio::Writer::new(
+                vec![],
+                zstd::stream::raw::Decoder::new()?,
+            )),
+            Compression::Bzip2 => Decoder::Bzip2(bzip2::write::BzDecoder::new(vec![])),
+            Compression::Xz => Decoder::Xz(xz2::write::XzDecoder::new_multi_decoder(vec![])),
+        })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        // Flushing makes sure all the data that can be decompressed so far
+        // is written to the output.
+        let output = match self {
+            Decoder::Gzip(decoder) => {
+                decoder.write_all(bytes)?;
+                decoder.flush()?;
+                decoder.get_mut()
+            }
+            Decoder::Zstd(decoder) => {
+                decoder.write_all(bytes)?;
+                decoder.flush()?;
+                decoder.writer_mut()
+            }
+            Decoder::Bzip2(decoder) => {
+                decoder.write_all(bytes)?;
+                decoder.flush()?;
+                decoder.get_mut()
+            }
+            Decoder::Xz(decoder) => {
+                decoder.write_all(bytes)?;
+                decoder.flush()?;
+                decoder.get_mut()
+            }
+        };
+
+        Ok(std::mem::take(output))
+    }
+
+    fn finish(&mut self) -> io::Result<Vec<u8>> {
+        let output = match self {
+            Decoder::Gzip(decoder) => {
+                decoder.try_finish()?;
+                decoder.get_mut()
+            }
+            Decoder::Zstd(decoder) => {
+                decoder.finish()?;
+                decoder.writer_mut()
+            }
+            Decoder::Bzip2(decoder) => {
+                decoder.try_finish()?;
+                decoder.get_mut()
+            }
+            // XzDecoder doesn't expose try_finish, so we take the output
+            // directly. The decoder can't be used after this.
+            Decoder::Xz(decoder) => return decoder.finish(),
+        };
+
+        Ok(std::mem::take(output))
+    }
+}
+
+enum StreamState {
+    // We haven't seen enough data to know whether the input is compressed;
+    // the data we've seen so far is buffered.
+    Detecting(Vec<u8>),
+    NotCompressed,
+    Compressed(Box<Decoder>),
+}
+
+pub struct StreamDecompressor {
+    state: StreamState,
+}
+
+impl Default for StreamDecompressor {
+    fn default() -> Self {
+        StreamDecompressor {
+            state: StreamState::Detecting(vec![]),
+        }
+    }
+}
+
+impl StreamDecompressor {
+    // Add more (possibly compressed) data to the stream, returning any
+    // decompressed data that is available.
+    pub fn push(&mut self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        match &mut self.state {
+            StreamState::NotCompressed => Ok(bytes.to_vec()),
+            StreamState::Compressed(decoder) => decoder.write(bytes),
+            StreamState::Detecting(buffer) => {
+                buffer.extend_from_slice(bytes);
+
+                match detect_compression(buffer) {
+                    Detection::NeedMoreData => Ok(vec![]),
+                    Detection::NotCompressed => {
+                        let buffer = std::mem::take(buffer);
+                        self.state = StreamState::NotCompressed;
+                        Ok(buffer)
+                    }
+                    Detection::Compressed(compression) => {
+                        let buffer = std::mem::take(buffer);
+                        let mut decoder = Box::new(Decoder::new(compression)?);
+                        let output = decoder.write(&buffer);
+                        self.state = StreamState::Compressed(decoder);
+                        output
+                    }
+                }
+            }
+        }
+    }
+
+    // Signal that there is no more data, returning any remaining
+    // decompressed data. Returns an error if the compressed data was
+    // truncated.
+    pub fn finish(&mut self) -> io::Result<Vec<u8>> {
+        match &mut self.state {
+            StreamState::NotCompressed => Ok(vec![]),
+            StreamState::Compressed(decoder) => decoder.finish(),
+            // If the input is shorter than any magic bytes, it can't be
+            // compressed.
+            StreamState::Detecting(buffer) => Ok(std::mem::take(buffer)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    const JSON: &[u8] = b"{\"a\": 1}\n{\"b\": [true, false, null]}\n";
+
+    fn compress(compression: Compression, bytes: &[u8]) -> Vec<u8> {
+        match compression {
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(bytes).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Zstd => zstd::stream::encode_all(bytes, 0).unwrap(),
+            Compression::Bzip2 => {
+                let mut compressed = vec![];
+                bzip2::read::BzEncoder::new(bytes, bzip2::Compression::default())
+                    .read_to_end(&mut compressed)
+                    .unwrap();
+                compressed
+            }
+            Compression::Xz => {
+                let mut compressed = vec![];
+                xz2::read::XzEncoder::new(bytes, 6)
+                    .read_to_end(&mut compressed)
+                    .unwrap();
+                compressed
+            }
+        }
+    }
+
+    #[test]
+    fn test_decompress() {
+        for (compression, _) in MAGIC_BYTES.iter() {
+            let compressed = compress(*compression, JSON);
+            assert!(is_compressed(&compressed), "{:?}", compression);
+            assert_eq!(decompress(compressed).unwrap(), JSON, "{compression:?}");
+        }
+
+        assert!(!is_compressed(JSON));
+        assert_eq!(decompress(JSON.to_vec()).unwrap(), JSON);
+    }
+
+    #[test]
+    fn test_stream_decompressor() {
+        for (compression, _) in MAGIC_BYTES.iter() {
+            let compressed = compress(*compression, JSON);
+
+            // Feed the data in one byte at a time, to make sure we handle
+            // magic bytes split across multiple pushes.
+            let mut decompressor = StreamDecompressor::default();
+            let mut output = vec![];
+            for byte in compressed.iter() {
+                output.extend(decompressor.push(&[*byte]).unwrap());
+            }
+            output.extend(decompressor.finish().unwrap());
+
+            assert_eq!(output, JSON, "{compression:?}");
+        }
+
+        // Input that is shorter than the magic bytes.
+        let mut decompressor = StreamDecompressor::default();
+        assert_eq!(decompressor.push(b"\x1f").unwrap(), b"");
+        assert_eq!(decompressor.finish().unwrap(), b"\x1f");
+
+        // Truncated input.
+        for (compression, _) in MAGIC_BYTES.iter() {
+            let compressed = compress(*compression, JSON);
+            let mut decompressor = StreamDecompressor::default();
+            decompressor
+                .push(&compressed[..compressed.len() / 2])
+                .unwrap();
+            assert!(decompressor.finish().is_err(), "{:?}", compression);
+        }
+    }
+
+    #[test]
+    fn test_bzip2_detection() {
+        // Text that happens to start with the bzip2 magic bytes.
+        assert!(!is_compressed(b"BZh"));
+        assert!(!is_compressed(b"BZh is not compressed"));
+        assert!(!is_compressed(b"BZh9 is not compressed"));
+        assert_eq!(
+            decompress(b"BZh is not compressed".to_vec()).unwrap(),
+            b"BZh is not compressed"
+        );
+
+        let mut decompressor = StreamDecompressor::default();
+        assert_eq!(decompressor.push(b"BZh9").unwrap(), b"");
+        assert_eq!(decompressor.push(b"1AY").unwrap(), b"");
+        assert_eq!(decompressor.push(b"?").unwrap(), b"BZh91AY?");
+
+        assert!(is_compressed(&compress(Compression::Bzip2, b"")));
+        assert_eq!(decompress(compress(Compression::Bzip2, b"")).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_strip_compression_extension() {
+        assert_eq!(strip_compression_extension("data.yaml.gz"), "data.yaml");
+        assert_eq!(
+            strip_compression_extension("data.ndjson.zst"),
+            "data.ndjson"
+        );
+        assert_eq!(strip_compression_extension("data.json"), "data.json");
+    }
+}
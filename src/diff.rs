@@ -0,0 +1,823 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::flatjson::{ContainerType, FlatJson, Index, OptionIndex, PathType, Value};
+use crate::flatjsonbuilder::{container_info, FlatJsonBuilder};
+use crate::jsonstringescaper::escape_json_string;
+use crate::search::JumpDirection;
+
+// How a row of a merged document differs between the two documents that
+// were compared.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiffStatus {
+    Unchanged,
+    // Only present in the second (right) document.
+    Added,
+    // Only present in the first (left) document.
+    Removed,
+    // A container that is present in both documents, but whose contents
+    // differ.
+    Changed,
+}
+
+// Compare two documents, returning a merged document that contains every
+// node from both of them, along with the status of each of its rows.
+//
+// Object entries are matched up by their keys, and array elements by their
+// indexes, or, if an id key is given, elements that are objects are matched
+// up by the value of that key. When matching nodes have different values,
+// containers of the same type are compared recursively, and otherwise the
+// node from the left document is shown as removed, immediately followed by
+// the node from the right document shown as added.
+//
+// Numbers are compared by their values, so 1.0 and 1 are equal. To avoid
+// comparing the same subtrees over and over again as we descend into the
+// containers that changed, we first hash every node, and only compare nodes
+// whose hashes are equal.
+//
+// Containers that are unchanged start out collapsed, so that the changes
+// stand out.
+pub fn diff(
+    left: &FlatJson,
+    right: &FlatJson,
+    id_key: Option<&str>,
+) -> (FlatJson, Vec<DiffStatus>) {
+    let mut differ = Differ {
+        left,
+        right,
+        id_key: id_key.map(|key| format!("\"{}\"", escape_json_string(key))),
+        doc: FlatJsonBuilder::default(),
+        statuses: vec![],
+        left_hashes: hash_values(left),
+        right_hashes: hash_values(right),
+    };
+
+    let left_values = top_level_values(left);
+    let right_values = top_level_values(right);
+    // Top-level values are always matched up by their indexes.
+    let matches = matches_by_index(&left_values, &right_values);
+    differ.diff_children(&left_values, &right_values, &matches, None);
+
+    let statuses = differ.statuses;
+    let mut flatjson = differ.doc.build();
+
+    for index in 0..flatjson.0.len() {
+        let row = &flatjson[index];
+        let parent_unchanged = match row.parent {
+            OptionIndex::Index(parent) => statuses[parent] == DiffStatus::Unchanged,
+            OptionIndex::Nil => false,
+        };
+        if row.is_opening_of_container()
+            && statuses[index] == DiffStatus::Unchanged
+            && !parent_unchanged
+        {
+            flatjson.collapse(index);
+        }
+    }
+
+    (flatjson, statuses)
+}
+
+// Whether a row is the start of a change, i.e., a node that was added or
+// removed, but not one of its descendants. A node that replaces a removed
+// node is considered part of the same change.
+pub fn is_start_of_change(flatjson: &FlatJson, statuses: &[DiffStatus], index: Index) -> bool {
+    let row = &flatjson[index];
+    let status = statuses[index];
+
+    if row.is_closing_of_container() || !matches!(status, DiffStatus::Added | DiffStatus::Removed) {
+        return false;
+    }
+
+    if let OptionIndex::Index(parent) = row.parent {
+        if statuses[parent] == status {
+            return false;
+        }
+    }
+
+    match row.prev_sibling {
+        OptionIndex::Index(prev) => {
+            !(status == DiffStatus::Added
+                && statuses[prev] == DiffStatus::Removed
+                && flatjson[prev]
+                    .key_range
+                    .as_ref()
+                    .map(|r| &flatjson.1[r.clone()])
+                    == row.key_range.as_ref().map(|r| &flatjson.1[r.clone()]))
+        }
+        OptionIndex::Nil => true,
+    }
+}
+
+// Find the start of the next (or previous) change after a row.
+pub fn find_change(
+    flatjson: &FlatJson,
+    statuses: &[DiffStatus],
+    index: Index,
+    direction: JumpDirection,
+) -> Option<Index> {
+    match direction {
+        JumpDirection::Next => {
+            (index + 1..flatjson.0.len()).find(|&i| is_start_of_change(flatjson, statuses, i))
+        }
+        JumpDirection::Prev => (0..index)
+            .rev()
+            .find(|&i| is_start_of_change(flatjson, statuses, i)),
+    }
+}
+
+// The rows that were added or removed, which, along with their ancestors,
+// are the subtrees that changed.
+pub fn changed_rows(statuses: &[DiffStatus]) -> Vec<Index> {
+    statuses
+        .iter()
+        .enumerate()
+        .filter(|(_, status)| matches!(status, DiffStatus::Added | DiffStatus::Removed))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+// A summary of the changes in a merged document, one per line, e.g.,
+//
+// - .items[2].status: "ok"
+// + .items[2].status: "error"
+pub fn format_changes(flatjson: &FlatJson, statuses: &[DiffStatus]) -> String {
+    let mut changes = String::new();
+
+    for index in 0..flatjson.0.len() {
+        if !is_start_of_change(flatjson, statuses, index) {
+            continue;
+        }
+
+        let mut write_change = |index: Index| {
+            let marker = match statuses[index] {
+                DiffStatus::Added => '+',
+                _ => '-',
+            };
+            let path = flatjson
+                .build_path_to_node(PathType::DotWithTopLevelIndex, index)
+                .unwrap();
+            let path = if path.is_empty() { "." } else { &path };
+            let value = &flatjson.1[flatjson[index].range.clone()];
+            changes.push_str(&format!("{marker} {path}: {value}\n"));
+        };
+
+        write_change(index);
+        // Include the added node that replaces a removed one.
+        if let OptionIndex::Index(next) = flatjson[index].next_sibling {
+            if !is_start_of_change(flatjson, statuses, next) && statuses[next] == DiffStatus::Added
+            {
+                write_change(next);
+            }
+        }
+    }
+
+    changes
+}
+
+fn top_level_values(flatjson: &FlatJson) -> Vec<Index> {
+    if flatjson.0.is_empty() {
+        return vec![];
+    }
+    siblings(flatjson, OptionIndex::Index(0))
+}
+
+fn siblings(flatjson: &FlatJson, first: OptionIndex) -> Vec<Index> {
+    let mut siblings = vec![];
+    let mut sibling = first;
+    while let OptionIndex::Index(index) = sibling {
+        siblings.push(index);
+        sibling = flatjson[index].next_sibling;
+    }
+    siblings
+}
+
+fn matches_by_index(left_children: &[Index], right_children: &[Index]) -> Vec<Option<usize>> {
+    (0..right_children.len())
+        .map(|position| (position < left_children.len()).then_some(position))
+        .collect()
+}
+
+fn key_text(flatjson: &FlatJson, index: Index) -> Option<&str> {
+    let key_range = flatjson[index].key_range.clone()?;
+    Some(&flatjson.1[key_range])
+}
+
+struct Differ<'a> {
+    left: &'a FlatJson,
+    right: &'a FlatJson,
+    // The quoted key to match array elements by.
+    id_key: Option<String>,
+
+    doc: FlatJsonBuilder,
+    statuses: Vec<DiffStatus>,
+
+    // The hashes of the values of the nodes in each document, which are
+    // equal for equal values.
+    left_hashes: Vec<u64>,
+    right_hashes: Vec<u64>,
+}
+
+#[derive(Copy, Clone)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl<'a> Differ<'a> {
+    fn source(&self, side: Side) -> &'a FlatJson {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+        }
+    }
+
+    // Copy the merged children of two containers (or the top-level values
+    // of two documents), given the index of the left child that each right
+    // child was matched up with. Unmatched left children are placed just
+    // before the next right child that was matched with a left child after
+    // them.
+    fn diff_children(
+        &mut self,
+        left_children: &[Index],
+        right_children: &[Index],
+        matches: &[Option<usize>],
+        container_type: Option<ContainerType>,
+    ) -> Option<(Index, Index)> {
+        let mut matched = vec![false; left_children.len()];
+        for &left_position in matches.iter().flatten() {
+            matched[left_position] = true;
+        }
+
+        let mut siblings = None;
+        let mut next_left = 0;
+
+        for (&right_child, left_match) in right_children.iter().zip(matches) {
+            let Some(left_position) = *left_match else {
+                self.add_node(
+                    Side::Right,
+                    right_child,
+                    DiffStatus::Added,
+                    container_type,
+                    &mut siblings,
+                );
+                continue;
+            };
+
+            for position in next_left..left_position {
+                if !matched[position] {
+                    let left_child = left_children[position];
+                    self.add_node(
+                        Side::Left,
+                        left_child,
+                        DiffStatus::Removed,
+                        container_type,
+                        &mut siblings,
+                    );
+                }
+            }
+            next_left = next_left.max(left_position + 1);
+
+            self.diff_node(
+                left_children[left_position],
+                right_child,
+                container_type,
+                &mut siblings,
+            );
+        }
+
+        for position in next_left..left_children.len() {
+            if !matched[position] {
+                let left_child = left_children[position];
+                self.add_node(
+                    Side::Left,
+                    left_child,
+                    DiffStatus::Removed,
+                    container_type,
+                    &mut siblings,
+                );
+            }
+        }
+
+        siblings
+    }
+
+    // Copy a pair of matching nodes.
+    fn diff_node(
+        &mut self,
+        left: Index,
+        right: Index,
+        container_type: Option<ContainerType>,
+        siblings: &mut Option<(Index, Index)>,
+    ) {
+        if self.values_equal(left, right) {
+            self.add_node(
+                Side::Right,
+                right,
+                DiffStatus::Unchanged,
+                container_type,
+                siblings,
+            );
+            return;
+        }
+
+        let child_container_type = match (&self.left[left].value, &self.right[right].value) {
+            (
+                Value::OpenContainer {
+                    container_type: ContainerType::Object,
+                    ..
+                },
+                Value::OpenContainer {
+                    container_type: ContainerType::Object,
+                    ..
+                },
+            ) => ContainerType::Object,
+            (
+                Value::OpenContainer {
+                    container_type: ContainerType::Array,
+                    ..
+                },
+                Value::OpenContainer {
+                    container_type: ContainerType::Array,
+                    ..
+                },
+            ) => ContainerType::Array,
+            _ => {
+                self.add_node(
+                    Side::Left,
+                    left,
+                    DiffStatus::Removed,
+                    container_type,
+                    siblings,
+                );
+                self.add_node(
+                    Side::Right,
+                    right,
+                    DiffStatus::Added,
+                    container_type,
+                    siblings,
+                );
+                return;
+            }
+        };
+
+        let right_flatjson = self.right;
+        let key_range =
+            self.doc
+                .start_child(key_text(right_flatjson, right), container_type, siblings);
+        let open_index = self.doc.copy_row(right_flatjson, right).unwrap();
+        self.statuses.push(DiffStatus::Changed);
+
+        let left_children = siblings_of_first_child(self.left, left);
+        let right_children = siblings_of_first_child(self.right, right);
+        let matches = match child_container_type {
+            ContainerType::Object => self.match_entries(&left_children, &right_children),
+            ContainerType::Array => self.match_elements(&left_children, &right_children),
+        };
+        let children = self.diff_children(
+            &left_children,
+            &right_children,
+            &matches,
+            Some(child_container_type),
+        );
+
+        let (_, collapsed) = container_info(&right_flatjson[right].value);
+        self.doc
+            .finish_container(open_index, child_container_type, collapsed, children);
+        self.statuses
+            .resize(self.doc.next_index(), DiffStatus::Changed);
+
+        self.doc.finish_child(open_index, key_range, siblings);
+    }
+
+    // Copy a node from one of the documents, with all of its descendants
+    // having the same status.
+    fn add_node(
+        &mut self,
+        side: Side,
+        index: Index,
+        status: DiffStatus,
+        container_type: Option<ContainerType>,
+        siblings: &mut Option<(Index, Index)>,
+    ) {
+        let source = self.source(side);
+        let key_range = self
+            .doc
+            .start_child(key_text(source, index), container_type, siblings);
+        let new_index = self.doc.next_index();
+        self.doc.copy_node(source, index);
+        self.statuses.resize(self.doc.next_index(), status);
+        self.doc.finish_child(new_index, key_range, siblings);
+    }
+
+    // Match object entries by their keys.
+    fn match_entries(
+        &self,
+        left_children: &[Index],
+        right_children: &[Index],
+    ) -> Vec<Option<usize>> {
+        let mut left_positions: HashMap<&str, usize> = HashMap::new();
+        for (position, &child) in left_children.iter().enumerate().rev() {
+            left_positions.insert(key_text(self.left, child).unwrap(), position);
+        }
+
+        right_children
+            .iter()
+            .map(|&child| left_positions.remove(key_text(self.right, child).unwrap()))
+            .collect()
+    }
+
+    // Match array elements (or top-level values) by their ids, if an id
+    // key was given, or else by their indexes.
+    fn match_elements(
+        &self,
+        left_children: &[Index],
+        right_children: &[Index],
+    ) -> Vec<Option<usize>> {
+        if self.id_key.is_none() {
+            return matches_by_index(left_children, right_children);
+        }
+
+        let mut left_positions: HashMap<&str, usize> = HashMap::new();
+        for (position, &child) in left_children.iter().enumerate().rev() {
+            if let Some(id) = self.id_of_element(self.left, child) {
+                left_positions.insert(id, position);
+            }
+        }
+
+        right_children
+            .iter()
+            .map(|&child| {
+                self.id_of_element(self.right, child)
+                    .and_then(|id| left_positions.remove(id))
+            })
+            .collect()
+    }
+
+    // The text of the value of an element's id key, if it's an object
+    // that has one.
+    fn id_of_element(&self, flatjson: &'a FlatJson, index: Index) -> Option<&'a str> {
+        let id_key = self.id_key.as_deref()?;
+        siblings_of_first_child(flatjson, index)
+            .into_iter()
+            .find(|&child| key_text(flatjson, child) == Some(id_key))
+            .map(|child| &flatjson.1[flatjson[child].range.clone()])
+    }
+
+    fn values_equal(&self, left: Index, right: Index) -> bool {
+        if self.left_hashes[left] != self.right_hashes[right] {
+            return false;
+        }
+
+        let left_row = &self.left[left];
+        let right_row = &self.right[right];
+
+        match (&left_row.value, &right_row.value) {
+            (
+                Value::OpenContainer {
+                    container_type: ContainerType::Object,
+                    ..
+                },
+                Value::OpenContainer {
+                    container_type: ContainerType::Object,
+                    ..
+                },
+            ) => {
+                let left_children = siblings_of_first_child(self.left, left);
+                let right_children = siblings_of_first_child(self.right, right);
+                left_children.len() == right_children.len()
+                    && self
+                        .match_entries(&left_children, &right_children)
+                        .iter()
+                        .zip(right_children.iter())
+                        .all(|(left_match, &right_child)| match left_match {
+                            Some(position) => {
+                                self.values_equal(left_children[*position], right_child)
+                            }
+                            None => false,
+                        })
+            }
+            (
+                Value::OpenContainer {
+                    container_type: ContainerType::Array,
+                    ..
+                },
+                Value::OpenContainer {
+                    container_type: ContainerType::Array,
+                    ..
+                },
+            ) => {
+                let left_children = siblings_of_first_child(self.left, left);
+                let right_children = siblings_of_first_child(self.right, right);
+                left_children.len() == right_children.len()
+                    && left_children.iter().zip(right_children.iter()).all(
+                        |(&left_child, &right_child)| self.values_equal(left_child, right_child),
+                    )
+            }
+            (Value::OpenContainer { .. }, _) | (_, Value::OpenContainer { .. }) => false,
+            (left_value, right_value) => {
+                std::mem::discriminant(left_value) == std::mem::discriminant(right_value)
+                    && primitive_text(self.left, left) == primitive_text(self.right, right)
+            }
+        }
+    }
+}
+
+fn siblings_of_first_child(flatjson: &FlatJson, index: Index) -> Vec<Index> {
+    siblings(flatjson, flatjson[index].first_child())
+}
+
+// Hash the value of every node in a document, such that equal values
+// have equal hashes. The hashes of object entries are combined without
+// regard to their order, since reordered keys aren't a change. The
+// closing rows of containers aren't hashed.
+fn hash_values(flatjson: &FlatJson) -> Vec<u64> {
+    let mut hashes = vec![0; flatjson.0.len()];
+
+    // Children come after their parents, so we hash them first.
+    for index in (0..flatjson.0.len()).rev() {
+        let row = &flatjson[index];
+        let mut hasher = DefaultHasher::new();
+
+        match &row.value {
+            Value::CloseContainer { .. } => continue,
+            Value::OpenContainer {
+                container_type: ContainerType::Object,
+                ..
+            } => {
+                let mut entries_hash: u64 = 0;
+                let mut len = 0;
+                let mut child = row.first_child();
+                while let OptionIndex::Index(i) = child {
+                    let mut entry_hasher = DefaultHasher::new();
+                    key_text(flatjson, i).hash(&mut entry_hasher);
+                    hashes[i].hash(&mut entry_hasher);
+                    entries_hash = entries_hash.wrapping_add(entry_hasher.finish());
+                    len += 1;
+                    child = flatjson[i].next_sibling;
+                }
+                ("object", len, entries_hash).hash(&mut hasher);
+            }
+            Value::OpenContainer {
+                container_type: ContainerType::Array,
+                ..
+            } => {
+                "array".hash(&mut hasher);
+                let mut child = row.first_child();
+                while let OptionIndex::Index(i) = child {
+                    hashes[i].hash(&mut hasher);
+                    child = flatjson[i].next_sibling;
+                }
+            }
+            value => {
+                std::mem::discriminant(value).hash(&mut hasher);
+                primitive_text(flatjson, index).hash(&mut hasher);
+            }
+        }
+
+        hashes[index] = hasher.finish();
+    }
+
+    hashes
+}
+
+// The text of a primitive value that is compared, which, for numbers, is
+// the same for numbers with the same value.
+fn primitive_text(flatjson: &FlatJson, index: Index) -> Cow<'_, str> {
+    let row = &flatjson[index];
+    let text = &flatjson.1[row.range.clone()];
+    match row.value {
+        Value::Number => normalize_number(text).map_or(Cow::Borrowed(text), Cow::Owned),
+        _ => Cow::Borrowed(text),
+    }
+}
+
+// Write a number in a canonical form, as its significant digits and an
+// exponent, e.g., 1.50 and 15e-1 are both "15e-1". Returns None if the text
+// isn't a decimal number, e.g., NaN.
+fn normalize_number(text: &str) -> Option<String> {
+    let (negative, unsigned) = match text.as_bytes().first()? {
+        b'-' => (true, &text[1..]),
+        b'+' => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(e) => (&unsigned[..e], unsigned[e + 1..].parse::<i64>().ok()?),
+        None => (unsigned, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    if integer.is_empty() && fraction.is_empty()
+        || !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let digits = format!("{integer}{fraction}");
+    let digits = digits.trim_start_matches('0');
+    let significant_digits = digits.trim_end_matches('0');
+    if significant_digits.is_empty() {
+        // -0 is equal to 0.
+        return Some("0".to_string());
+    }
+
+    let trailing_zeros = (digits.len() - significant_digits.len()) as i64;
+    let exponent = exponent
+        .checked_sub(fraction.len() as i64)?
+        .checked_add(trailing_zeros)?;
+    let sign = if negative { "-" } else { "" };
+    Some(format!("{sign}{significant_digits}e{exponent}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+
+    use DiffStatus::*;
+
+    fn check_diff(
+        left: &str,
+        right: &str,
+        id_key: Option<&str>,
+        expected_json: &str,
+        expected_statuses: &[DiffStatus],
+    ) {
+        let left = parse_top_level_json(left.to_string()).unwrap();
+        let right = parse_top_level_json(right.to_string()).unwrap();
+        let (merged, statuses) = diff(&left, &right, id_key);
+
+        assert_eq!(merged.1, expected_json);
+        assert_eq!(statuses, expected_statuses);
+    }
+
+    #[test]
+    fn test_diff_objects() {
+        check_diff(
+            r#"{"a": 1, "b": 2, "c": {"d": true}}"#,
+            r#"{"b": 3, "c": {"d": true}, "e": null}"#,
+            None,
+            r#"{ "a": 1, "b": 2, "b": 3, "c": { "d": true }, "e": null }"#,
+            &[
+                Changed, Removed, Removed, Added, Unchanged, Unchanged, Unchanged, Added, Changed,
+            ],
+        );
+
+        // Reordered keys aren't a change.
+        check_diff(
+            r#"{"a": 1, "b": [2]}"#,
+            r#"{"b": [2], "a": 1}"#,
+            None,
+            r#"{ "b": [2], "a": 1 }"#,
+            &[
+                Unchanged, Unchanged, Unchanged, Unchanged, Unchanged, Unchanged,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_diff_arrays() {
+        check_diff(
+            "[1, [2, 3], 4]",
+            "[1, [2, 5]]",
+            None,
+            "[1, [2, 3, 5], 4]",
+            &[
+                Changed, Unchanged, Changed, Unchanged, Removed, Added, Changed, Removed, Changed,
+            ],
+        );
+
+        // Type changes are shown as a removed value and an added value.
+        check_diff(
+            "[[1]]",
+            r#"[{"a": 1}]"#,
+            None,
+            r#"[[1], { "a": 1 }]"#,
+            &[
+                Changed, Removed, Removed, Removed, Added, Added, Added, Changed,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_diff_arrays_by_id() {
+        let left = r#"[{"id": 1, "x": "a"}, {"id": 2}, {"id": 3}]"#;
+        let right = r#"[{"id": 3}, {"id": 1, "x": "b"}, {"id": 4}]"#;
+
+        check_diff(
+            left,
+            right,
+            Some("id"),
+            r#"[{ "id": 2 }, { "id": 3 }, { "id": 1, "x": "a", "x": "b" }, { "id": 4 }]"#,
+            &[
+                Changed, Removed, Removed, Removed, Unchanged, Unchanged, Unchanged, Changed,
+                Unchanged, Removed, Added, Changed, Added, Added, Added, Changed,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_numbers_are_compared_by_value() {
+        check_diff(
+            r#"{"a": 1.0, "b": 1e2, "c": [-0, 0.5]}"#,
+            r#"{"a": 1, "b": 100, "c": [0, 5E-1]}"#,
+            None,
+            r#"{ "a": 1, "b": 100, "c": [0, 5E-1] }"#,
+            &[
+                Unchanged, Unchanged, Unchanged, Unchanged, Unchanged, Unchanged, Unchanged,
+                Unchanged,
+            ],
+        );
+
+        check_diff(
+            "[10, 1.5]",
+            "[1, 15]",
+            None,
+            "[10, 1, 1.5, 15]",
+            &[Changed, Removed, Added, Removed, Added, Changed],
+        );
+    }
+
+    #[test]
+    fn test_normalize_number() {
+        assert_eq!(normalize_number("100"), Some("1e2".to_string()));
+        assert_eq!(normalize_number("1e2"), Some("1e2".to_string()));
+        assert_eq!(normalize_number("-0.0150"), Some("-15e-3".to_string()));
+        assert_eq!(normalize_number("+15E-4"), Some("15e-4".to_string()));
+        assert_eq!(normalize_number("-0"), Some("0".to_string()));
+        assert_eq!(normalize_number("NaN"), None);
+        assert_eq!(normalize_number("-Infinity"), None);
+    }
+
+    #[test]
+    fn test_hashes_of_equal_values_are_equal() {
+        let left = parse_top_level_json(r#"{"a": [1, {}], "b": 2.0} [1, 2]"#.to_string()).unwrap();
+        let right = parse_top_level_json(r#"{"b": 2, "a": [1, {}]} [2, 1]"#.to_string()).unwrap();
+        let left_hashes = hash_values(&left);
+        let right_hashes = hash_values(&right);
+
+        // Reordered keys.
+        assert_eq!(left_hashes[0], right_hashes[0]);
+        assert_eq!(left_hashes[1], right_hashes[2]);
+        // Arrays with the same elements in a different order.
+        assert_ne!(left_hashes[7], right_hashes[7]);
+    }
+
+    #[test]
+    fn test_unchanged_containers_are_collapsed() {
+        let left = parse_top_level_json(r#"{"a": [1], "b": {"c": [2]}}"#.to_string()).unwrap();
+        let right = parse_top_level_json(r#"{"a": [1], "b": 2}"#.to_string()).unwrap();
+        let (merged, _) = diff(&left, &right, None);
+
+        assert!(!merged[0].is_collapsed());
+        assert!(merged[1].is_collapsed());
+        // Removed containers stay expanded.
+        assert!(!merged[4].is_collapsed());
+        assert!(!merged[5].is_collapsed());
+    }
+
+    #[test]
+    fn test_changes() {
+        let left = parse_top_level_json(r#"{"a": 1, "b": [true], "c": "x"}"#.to_string()).unwrap();
+        let right = parse_top_level_json(r#"{"a": 2, "b": [], "d": "x"}"#.to_string()).unwrap();
+        let (merged, statuses) = diff(&left, &right, None);
+
+        assert_eq!(
+            format_changes(&merged, &statuses),
+            concat!(
+                "- .a: 1\n",
+                "+ .a: 2\n",
+                "- .b: [true]\n",
+                "+ .b: []\n",
+                "+ .d: \"x\"\n",
+                "- .c: \"x\"\n",
+            ),
+        );
+
+        let starts = [1, 3, 7, 8];
+        for (i, start) in starts.iter().enumerate() {
+            let next = starts.get(i + 1).copied();
+            assert_eq!(
+                find_change(&merged, &statuses, *start, JumpDirection::Next),
+                next
+            );
+        }
+        assert_eq!(
+            find_change(&merged, &statuses, 0, JumpDirection::Next),
+            Some(1)
+        );
+        assert_eq!(
+            find_change(&merged, &statuses, 1, JumpDirection::Prev),
+            None
+        );
+        assert_eq!(
+            find_change(&merged, &statuses, 8, JumpDirection::Prev),
+            Some(7)
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt::Write;
+
+use crate::flatjson::{FlatJson, Row};
+use crate::highlighting;
+use crate::lineprinter::{LinePrinter, JS_IDENTIFIER};
+use crate::terminal::{AnsiTerminal, Style, Terminal};
+use crate::viewer::Mode;
+
+// This module prints an entire document at once, e.g., when STDOUT isn't
+// a TTY, optionally with the same syntax highlighting that the LinePrinter
+// uses.
+//
+// In line mode the output, apart from any colors, is the same as
+// FlatJson::pretty_printed, so it's valid JSON: non-string keys (from
+// YAML or MessagePack) and JSON5's Infinity and NaN are written as
+// strings. In data mode, like in the viewer, closing delimiters, commas, and quotes
+// around object keys that are valid JS identifiers are elided, and
+// array elements are labeled with their indexes. Instead of the previews
+// shown in the viewer, containers are printed like YAML:
+//
+//     a:
+//       [0]: 1
+//       [1]: 2
+//     b: "x"
+//
+// Each top-level value after the first is preceded by a blank line.
+pub fn print_document(flatjson: &FlatJson, mode: Mode, colored: bool) -> String {
+    let mut printer = DocumentPrinter {
+        flatjson,
+        colored,
+        terminal: AnsiTerminal::new(String::new()),
+    };
+
+    for row in flatjson.0.iter() {
+        // Writing to a String doesn't fail.
+        let _ = match mode {
+            Mode::Line => printer.print_line_mode_row(row),
+            Mode::Data => printer.print_data_mode_row(row),
+        };
+    }
+
+    printer.terminal.output
+}
+
+struct DocumentPrinter<'a> {
+    flatjson: &'a FlatJson,
+    colored: bool,
+    terminal: AnsiTerminal,
+}
+
+impl<'a> DocumentPrinter<'a> {
+    fn print_line_mode_row(&mut self, row: &Row) -> std::fmt::Result {
+        self.print_indentation(row.depth)?;
+
+        if let Some(key_range) = &row.key_range {
+            let mut key = String::new();
+            self.flatjson.write_json_key(&mut key, key_range.clone());
+            self.print_styled(&key, &highlighting::colors().key_style())?;
+            write!(self.terminal, ": ")?;
+        }
+
+        // Check the container opening to see if a closing delimiter has a
+        // next sibling.
+        let mut trailing_comma = row.parent.is_some() && row.next_sibling.is_some();
+        match row.value.container_type() {
+            Some(container_type) if row.is_opening_of_container() => {
+                write!(self.terminal, "{}", container_type.open_str())?;
+                trailing_comma = false;
+            }
+            Some(container_type) => {
+                write!(self.terminal, "{}", container_type.close_str())?;
+                trailing_comma = row.parent.is_some()
+                    && self.flatjson[row.pair_index().unwrap()]
+                        .next_sibling
+                        .is_some();
+            }
+            None => {
+                let mut value = String::new();
+                self.flatjson.write_json_primitive(&mut value, row);
+                self.print_primitive(row, &value)?;
+            }
+        }
+
+        if trailing_comma {
+            write!(self.terminal, ",")?;
+        }
+        writeln!(self.terminal)
+    }
+
+    fn print_data_mode_row(&mut self, row: &Row) -> std::fmt::Result {
+        if row.is_closing_of_container() {
+            return Ok(());
+        }
+
+        if row.parent.is_nil() {
+            if row.prev_sibling.is_some() {
+                writeln!(self.terminal)?;
+            }
+            // The contents of top-level containers aren't indented.
+            if row.is_opening_of_container() {
+                return Ok(());
+            }
+        } else {
+            self.print_indentation(row.depth - 1)?;
+            self.print_data_mode_label(row)?;
+        }
+
+        if row.is_primitive() {
+            if row.parent.is_some() {
+                write!(self.terminal, " ")?;
+            }
+            self.print_primitive(row, &self.flatjson.1[row.range.clone()])?;
+        }
+
+        writeln!(self.terminal)
+    }
+
+    fn print_data_mode_label(&mut self, row: &Row) -> std::fmt::Result {
+        match &row.key_range {
+            Some(key_range) => {
+                let key = &self.flatjson.1[key_range.clone()];
+                let unquoted_key = &key[1..key.len() - 1];
+                let key = if key.starts_with('"') && JS_IDENTIFIER.is_match(unquoted_key) {
+                    unquoted_key
+                } else {
+                    key
+                };
+                self.print_styled(key, &highlighting::colors().key_style())?;
+            }
+            None => {
+                let index = format!("[{}]", row.index_in_parent);
+                self.print_styled(&index, &highlighting::DIMMED_STYLE)?;
+            }
+        }
+
+        write!(self.terminal, ":")
+    }
+
+    fn print_primitive(&mut self, row: &Row, text: &str) -> std::fmt::Result {
+        let style = Style {
+            fg: LinePrinter::color_for_value_type(&row.value),
+            ..Style::default()
+        };
+        self.print_styled(text, &style)
+    }
+
+    fn print_styled(&mut self, text: &str, style: &Style) -> std::fmt::Result {
+        if !self.colored {
+            return write!(self.terminal, "{text}");
+        }
+
+        self.terminal.set_style(style)?;
+        write!(self.terminal, "{text}")?;
+        self.terminal.reset_style()
+    }
+
+    fn print_indentation(&mut self, depth: usize) -> std::fmt::Result {
+        for _ in 0..depth {
+            write!(self.terminal, "  ")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flatjson::{parse_top_level_json, parse_top_level_json5, parse_top_level_yaml};
+
+    use super::*;
+
+    const JSON: &str = r#"{"a": [1, "two"], "b c": {"d": null, "e": {}}} true"#;
+
+    #[test]
+    fn test_print_document() {
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        assert_eq!(print_document(&fj, Mode::Line, false), fj.pretty_printed());
+
+        const DATA_MODE: &str = r#"a:
+  [0]: 1
+  [1]: "two"
+"b c":
+  d: null
+  e: {}
+
+true
+"#;
+        assert_eq!(print_document(&fj, Mode::Data, false), DATA_MODE);
+    }
+
+    #[test]
+    fn test_print_colored_document() {
+        let fj = parse_top_level_json(r#"{"a": [1, null]}"#.to_owned()).unwrap();
+
+        let light_blue = "\x1b[38;5;12m";
+        let magenta = "\x1b[38;5;5m";
+        let gray = "\x1b[38;5;8m";
+        let reset = "\x1b[0m";
+        assert_eq!(
+            print_document(&fj, Mode::Line, true),
+            format!(
+                "{{\n  {light_blue}\"a\"{reset}: [\n    {magenta}1{reset},\n    {gray}null{reset}\n  ]\n}}\n"
+            ),
+        );
+
+        // Non-string keys and NaN are written as strings, so the output
+        // is still valid JSON.
+        let fj = parse_top_level_yaml("1: a\n".to_owned()).unwrap();
+        let green = "\x1b[38;5;2m";
+        assert_eq!(
+            print_document(&fj, Mode::Line, true),
+            format!("{{\n  {light_blue}\"1\"{reset}: {green}\"a\"{reset}\n}}\n"),
+        );
+        let fj = parse_top_level_json5("[NaN]".to_owned()).unwrap();
+        assert_eq!(
+            print_document(&fj, Mode::Line, true),
+            format!("[\n  {magenta}\"NaN\"{reset}\n]\n"),
+        );
+        assert_eq!(print_document(&fj, Mode::Line, false), fj.pretty_printed());
+    }
+}
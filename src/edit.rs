@@ -0,0 +1,459 @@
+use crate::flatjson::{ContainerType, FlatJson, Index, OptionIndex};
+use crate::flatjsonbuilder::{container_info, FlatJsonBuilder};
+use crate::jsonparser;
+use crate::jsonstringescaper::escape_json_string;
+
+// A change to a document. Values are provided as JSON, while keys are
+// the (unescaped) contents of a string.
+pub enum Edit {
+    ReplaceValue {
+        index: Index,
+        value: String,
+    },
+    RenameKey {
+        index: Index,
+        key: String,
+    },
+    Delete {
+        index: Index,
+    },
+    // Insert a new sibling before or after a node. A key must be provided
+    // if (and only if) the node is an entry of an object.
+    Insert {
+        index: Index,
+        after: bool,
+        key: Option<String>,
+        value: String,
+    },
+}
+
+impl Edit {
+    fn index(&self) -> Index {
+        match self {
+            Edit::ReplaceValue { index, .. } => *index,
+            Edit::RenameKey { index, .. } => *index,
+            Edit::Delete { index } => *index,
+            Edit::Insert { index, .. } => *index,
+        }
+    }
+
+    fn with_index(&self, index: Index) -> Edit {
+        match self {
+            Edit::ReplaceValue { value, .. } => Edit::ReplaceValue {
+                index,
+                value: value.clone(),
+            },
+            Edit::RenameKey { key, .. } => Edit::RenameKey {
+                index,
+                key: key.clone(),
+            },
+            Edit::Delete { .. } => Edit::Delete { index },
+            Edit::Insert {
+                after, key, value, ..
+            } => Edit::Insert {
+                index,
+                after: *after,
+                key: key.clone(),
+                value: value.clone(),
+            },
+        }
+    }
+}
+
+// Apply an edit to a document, returning the edited document along with
+// the index of the node that was edited (or inserted). When a node is
+// deleted, the index of the node that takes its place (its next sibling,
+// or else its previous sibling, or else its parent) is returned instead.
+//
+// Rather than re-parsing the document, the rows and the pretty printed
+// version of the document are rebuilt from the existing ones, so the
+// text of values that weren't edited is kept as is (even if it isn't
+// valid JSON, e.g., non-string YAML keys), and collapsed containers
+// stay collapsed.
+pub fn apply_edit(flatjson: &FlatJson, edit: &Edit) -> Result<(FlatJson, Index), String> {
+    let index = edit.index();
+    let row = &flatjson[index];
+
+    if row.is_closing_of_container() {
+        return apply_edit(flatjson, &edit.with_index(row.pair_index().unwrap()));
+    }
+
+    let new_value = match edit {
+        Edit::ReplaceValue { value, .. } | Edit::Insert { value, .. } => Some(parse_value(value)?),
+        _ => None,
+    };
+
+    match edit {
+        Edit::RenameKey { .. } if row.key_range.is_none() => {
+            return Err("Only object keys can be renamed".to_string());
+        }
+        Edit::Delete { .. }
+            if row.parent.is_nil() && row.prev_sibling.is_nil() && row.next_sibling.is_nil() =>
+        {
+            return Err("Can't delete the only top-level value".to_string());
+        }
+        Edit::Insert { key, .. } => {
+            if key.is_none() && row.key_range.is_some() {
+                return Err("Entries inserted into objects must have a key".to_string());
+            }
+            if key.is_some() && row.key_range.is_none() {
+                return Err("Only entries inserted into objects can have a key".to_string());
+            }
+        }
+        _ => {}
+    }
+
+    let mut builder = Builder {
+        flatjson,
+        edit,
+        new_value,
+        doc: FlatJsonBuilder::default(),
+        edited_row: None,
+        replacing_deleted_row: false,
+    };
+
+    builder.copy_children(OptionIndex::Index(0), None);
+
+    let edited_row = builder.edited_row.unwrap();
+    Ok((builder.doc.build(), edited_row))
+}
+
+// Parse a value entered by the user, which must be a single JSON value.
+fn parse_value(value: &str) -> Result<FlatJson, String> {
+    let (rows, pretty_printed, max_depth) =
+        jsonparser::parse(value.to_string()).map_err(|err| format!("Invalid JSON value: {err}"))?;
+
+    if rows.is_empty() || rows[0].next_sibling.is_some() {
+        return Err("Invalid JSON value: expected a single value".to_string());
+    }
+
+    Ok(FlatJson(rows, pretty_printed, max_depth))
+}
+
+fn quoted_key(key: &str) -> String {
+    format!("\"{}\"", escape_json_string(key))
+}
+
+// Builds a new document by copying the rows of an existing document,
+// applying an edit along the way.
+struct Builder<'a> {
+    flatjson: &'a FlatJson,
+    edit: &'a Edit,
+    // The parsed value being inserted, or replacing an existing value.
+    new_value: Option<FlatJson>,
+
+    doc: FlatJsonBuilder,
+
+    // The new index of the edited row, once it has been copied.
+    edited_row: Option<Index>,
+    // Set after skipping over a deleted row, so that the next copied
+    // sibling becomes the edited row.
+    replacing_deleted_row: bool,
+}
+
+impl<'a> Builder<'a> {
+    // Copy a list of siblings from the document, applying the edit to
+    // them, and linking them together. Returns the first and last copied
+    // children, if any were copied.
+    fn copy_children(
+        &mut self,
+        first_child: OptionIndex,
+        container_type: Option<ContainerType>,
+    ) -> Option<(Index, Index)> {
+        let mut siblings = None;
+
+        let mut child = first_child;
+        while let OptionIndex::Index(index) = child {
+            child = self.flatjson[index].next_sibling;
+
+            if index != self.edit.index() {
+                self.copy_child(index, None, container_type, &mut siblings);
+                continue;
+            }
+
+            match self.edit {
+                Edit::ReplaceValue { .. } => {
+                    let key = self.key_text(index);
+                    self.copy_new_value(key, container_type, &mut siblings);
+                }
+                Edit::RenameKey { key, .. } => {
+                    self.copy_child(index, Some(quoted_key(key)), container_type, &mut siblings);
+                    self.edited_row = siblings.map(|(_, last)| last);
+                }
+                Edit::Delete { .. } => {
+                    self.replacing_deleted_row = true;
+                }
+                Edit::Insert { after, key, .. } => {
+                    let key = key.as_deref().map(quoted_key);
+                    if *after {
+                        self.copy_child(index, None, container_type, &mut siblings);
+                    }
+                    self.copy_new_value(key, container_type, &mut siblings);
+                    if !*after {
+                        self.copy_child(index, None, container_type, &mut siblings);
+                    }
+                }
+            }
+        }
+
+        // The deleted row was the last child, so we focus its previous
+        // sibling, or its parent if it was the only child.
+        if self.replacing_deleted_row {
+            self.replacing_deleted_row = false;
+            self.edited_row = match siblings {
+                Some((_, last)) => Some(last),
+                None => self.doc.current_parent(),
+            };
+        }
+
+        siblings
+    }
+
+    // Copy a node from the document, optionally replacing its key.
+    fn copy_child(
+        &mut self,
+        index: Index,
+        key: Option<String>,
+        container_type: Option<ContainerType>,
+        siblings: &mut Option<(Index, Index)>,
+    ) {
+        // Clear this before copying the node's descendants, so that it
+        // doesn't apply to them.
+        let replacing_deleted_row = std::mem::take(&mut self.replacing_deleted_row);
+
+        let key = key.or_else(|| self.key_text(index));
+        let key_range = self
+            .doc
+            .start_child(key.as_deref(), container_type, siblings);
+        let new_index = self.doc.next_index();
+
+        let flatjson = self.flatjson;
+        if let Some(open_index) = self.doc.copy_row(flatjson, index) {
+            let (child_container_type, collapsed) = container_info(&flatjson[index].value);
+            let children =
+                self.copy_children(flatjson[index].first_child(), Some(child_container_type));
+            self.doc
+                .finish_container(open_index, child_container_type, collapsed, children);
+        }
+
+        self.doc.finish_child(new_index, key_range, siblings);
+
+        if replacing_deleted_row {
+            self.edited_row = Some(new_index);
+        }
+    }
+
+    // Copy the value being inserted, or replacing an existing value.
+    fn copy_new_value(
+        &mut self,
+        key: Option<String>,
+        container_type: Option<ContainerType>,
+        siblings: &mut Option<(Index, Index)>,
+    ) {
+        let key_range = self
+            .doc
+            .start_child(key.as_deref(), container_type, siblings);
+        let new_index = self.doc.next_index();
+
+        // The new value doesn't have the edit applied to it.
+        let new_value = self.new_value.take().unwrap();
+        self.doc.copy_node(&new_value, 0);
+
+        self.doc.finish_child(new_index, key_range, siblings);
+        self.edited_row = Some(new_index);
+    }
+
+    fn key_text(&self, index: Index) -> Option<String> {
+        let key_range = self.flatjson[index].key_range.clone()?;
+        Some(self.flatjson.1[key_range].to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+
+    // Check that applying an edit produces exactly the same rows as parsing
+    // the expected document.
+    fn check_edit(json: &str, edit: Edit, expected_json: &str, expected_edited_row: Index) {
+        let flatjson = parse_top_level_json(json.to_string()).unwrap();
+        let (edited, edited_row) = apply_edit(&flatjson, &edit).unwrap();
+        let expected = parse_top_level_json(expected_json.to_string()).unwrap();
+
+        assert_eq!(edited.1, expected.1);
+        assert_eq!(format!("{:?}", edited.0), format!("{:?}", expected.0));
+        assert_eq!(edited.2, expected.2);
+        assert_eq!(edited_row, expected_edited_row);
+    }
+
+    const DOC: &str = r#"{"a": 1, "b": [true, {"c": null}], "d": "x"}"#;
+
+    #[test]
+    fn test_replace_value() {
+        check_edit(
+            DOC,
+            Edit::ReplaceValue {
+                index: 1,
+                value: r#"{"z": [1, 2]}"#.to_string(),
+            },
+            r#"{"a": {"z": [1, 2]}, "b": [true, {"c": null}], "d": "x"}"#,
+            1,
+        );
+        check_edit(
+            DOC,
+            Edit::ReplaceValue {
+                index: 3,
+                value: "  false ".to_string(),
+            },
+            r#"{"a": 1, "b": [false, {"c": null}], "d": "x"}"#,
+            3,
+        );
+        check_edit(
+            "1\n2\n3",
+            Edit::ReplaceValue {
+                index: 1,
+                value: "[]".to_string(),
+            },
+            "1\n[]\n3",
+            1,
+        );
+    }
+
+    #[test]
+    fn test_rename_key() {
+        check_edit(
+            DOC,
+            Edit::RenameKey {
+                index: 8,
+                key: "new \"key\"".to_string(),
+            },
+            r#"{"a": 1, "b": [true, {"c": null}], "new \"key\"": "x"}"#,
+            8,
+        );
+        // Renaming the key of a container, focused on its closing row.
+        check_edit(
+            DOC,
+            Edit::RenameKey {
+                index: 7,
+                key: "e".to_string(),
+            },
+            r#"{"a": 1, "e": [true, {"c": null}], "d": "x"}"#,
+            2,
+        );
+    }
+
+    #[test]
+    fn test_delete() {
+        check_edit(DOC, Edit::Delete { index: 2 }, r#"{"a": 1, "d": "x"}"#, 2);
+        check_edit(
+            DOC,
+            Edit::Delete { index: 8 },
+            r#"{"a": 1, "b": [true, {"c": null}]}"#,
+            2,
+        );
+        // Deleting the only child of a container leaves it empty.
+        check_edit(
+            DOC,
+            Edit::Delete { index: 5 },
+            r#"{"a": 1, "b": [true, {}], "d": "x"}"#,
+            4,
+        );
+        check_edit("1\n2\n3", Edit::Delete { index: 0 }, "2\n3", 0);
+        check_edit("1\n2\n3", Edit::Delete { index: 2 }, "1\n2", 1);
+    }
+
+    #[test]
+    fn test_insert() {
+        check_edit(
+            DOC,
+            Edit::Insert {
+                index: 1,
+                after: true,
+                key: Some("new".to_string()),
+                value: "[1]".to_string(),
+            },
+            r#"{"a": 1, "new": [1], "b": [true, {"c": null}], "d": "x"}"#,
+            2,
+        );
+        check_edit(
+            DOC,
+            Edit::Insert {
+                index: 1,
+                after: false,
+                key: Some("new".to_string()),
+                value: "2".to_string(),
+            },
+            r#"{"new": 2, "a": 1, "b": [true, {"c": null}], "d": "x"}"#,
+            1,
+        );
+        check_edit(
+            DOC,
+            Edit::Insert {
+                index: 4,
+                after: true,
+                key: None,
+                value: "3".to_string(),
+            },
+            r#"{"a": 1, "b": [true, {"c": null}, 3], "d": "x"}"#,
+            7,
+        );
+        check_edit(
+            "1\n2",
+            Edit::Insert {
+                index: 1,
+                after: true,
+                key: None,
+                value: r#"{"a": 3}"#.to_string(),
+            },
+            "1\n2\n{\"a\": 3}",
+            2,
+        );
+    }
+
+    #[test]
+    fn test_collapsed_containers_stay_collapsed() {
+        let mut flatjson = parse_top_level_json(DOC.to_string()).unwrap();
+        flatjson.collapse(2);
+
+        let (edited, _) = apply_edit(&flatjson, &Edit::Delete { index: 1 }).unwrap();
+        assert!(edited[1].is_collapsed());
+        assert!(edited[6].is_collapsed());
+        assert!(!edited[3].is_collapsed());
+    }
+
+    #[test]
+    fn test_invalid_edits() {
+        let flatjson = parse_top_level_json(DOC.to_string()).unwrap();
+
+        let invalid_value = Edit::ReplaceValue {
+            index: 1,
+            value: "nope".to_string(),
+        };
+        assert!(apply_edit(&flatjson, &invalid_value)
+            .unwrap_err()
+            .starts_with("Invalid JSON value"));
+
+        let multiple_values = Edit::ReplaceValue {
+            index: 1,
+            value: "1 2".to_string(),
+        };
+        assert!(apply_edit(&flatjson, &multiple_values).is_err());
+
+        let rename_element = Edit::RenameKey {
+            index: 3,
+            key: "a".to_string(),
+        };
+        assert!(apply_edit(&flatjson, &rename_element).is_err());
+
+        let missing_key = Edit::Insert {
+            index: 1,
+            after: true,
+            key: None,
+            value: "1".to_string(),
+        };
+        assert!(apply_edit(&flatjson, &missing_key).is_err());
+
+        assert!(apply_edit(&flatjson, &Edit::Delete { index: 0 }).is_err());
+    }
+}
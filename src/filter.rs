@@ -0,0 +1,1495 @@
+// A filter language, modelled on a subset of jq, for transforming the
+// document being viewed.
+//
+// The following jq features are supported:
+//
+// - Paths: ., .foo, ."foo", .[0], .["foo"], .[1:3], .[], ..
+// - Optional paths: .foo?, .[]?
+// - Pipes (|) and multiple outputs (,)
+// - Comparisons (==, !=, <, <=, >, >=), and, or, the alternative
+//   operator (//), and arithmetic (+, -, *, /, %)
+// - Literals, and array and object construction: [.[] | .id],
+//   {name, id: .user.id, (.key): .value}
+// - Builtins: length, keys, keys_unsorted, select(f), map(f), has(k),
+//   not, type, empty, add, first, last, reverse, sort, to_entries,
+//   test(re), startswith(s), endswith(s)
+//
+// Filters are evaluated directly over the rows of a FlatJson. Values
+// taken from the input are represented by the index of their row, and
+// are only converted into an Item when we need to inspect them. The
+// output is then serialized as JSON and parsed into a new FlatJson.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+
+use regex::Regex;
+
+use crate::flatjson;
+use crate::flatjson::{ContainerType, FlatJson, Index, OptionIndex, Value};
+use crate::jsonstringescaper::escape_json_string;
+use crate::jsonstringunescaper::unsafe_unescape_json_string;
+
+lazy_static::lazy_static! {
+    static ref JSON_NUMBER: Regex =
+        Regex::new(r"^-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?$").unwrap();
+}
+
+// Apply a filter to every top-level value of a document, returning a new
+// document containing all of the outputs. The returned document is empty
+// if the filter didn't produce any output.
+pub fn apply_filter(flatjson: &FlatJson, filter: &str) -> Result<FlatJson, String> {
+    let output = evaluate_filter(flatjson, filter)?;
+
+    if output.is_empty() {
+        return Ok(FlatJson::empty());
+    }
+
+    flatjson::parse_top_level_json(output).map_err(|err| err.to_string())
+}
+
+// Evaluate a filter, returning its outputs as newline-separated compact
+// JSON values.
+fn evaluate_filter(flatjson: &FlatJson, filter: &str) -> Result<String, String> {
+    let expr = Parser::parse(filter)?;
+    let evaluator = Evaluator { flatjson };
+
+    let mut output = String::new();
+    let mut top_level_value = if flatjson.is_empty() {
+        OptionIndex::Nil
+    } else {
+        OptionIndex::Index(0)
+    };
+
+    while let OptionIndex::Index(index) = top_level_value {
+        for item in evaluator.eval(&expr, &Item::Row(index))? {
+            evaluator.write_item(&item, &mut output);
+            output.push('\n');
+        }
+        top_level_value = flatjson[index].next_sibling;
+    }
+
+    Ok(output)
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    // A value from the input document.
+    Row(Index),
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Item>),
+    Object(Vec<(String, Item)>),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Identity,
+    Recurse,
+    Literal(Item),
+    Index(Box<Expr>, Box<Expr>),
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    Iterate(Box<Expr>),
+    Try(Box<Expr>),
+    Pipe(Box<Expr>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+    Alternative(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Array(Option<Box<Expr>>),
+    Object(Vec<(Expr, Expr)>),
+    Call(Builtin, Vec<Expr>),
+}
+
+#[derive(Debug, Copy, Clone)]
+enum BinaryOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Builtin {
+    Length,
+    Keys,
+    KeysUnsorted,
+    Select,
+    Has,
+    Not,
+    Type,
+    Empty,
+    Add,
+    First,
+    Last,
+    Reverse,
+    Sort,
+    ToEntries,
+    Test,
+    StartsWith,
+    EndsWith,
+}
+
+impl Builtin {
+    fn from_name(name: &str, num_args: usize) -> Option<Builtin> {
+        let builtin = match (name, num_args) {
+            ("length", 0) => Builtin::Length,
+            ("keys", 0) => Builtin::Keys,
+            ("keys_unsorted", 0) => Builtin::KeysUnsorted,
+            ("select", 1) => Builtin::Select,
+            ("has", 1) => Builtin::Has,
+            ("not", 0) => Builtin::Not,
+            ("type", 0) => Builtin::Type,
+            ("empty", 0) => Builtin::Empty,
+            ("add", 0) => Builtin::Add,
+            ("first", 0) => Builtin::First,
+            ("last", 0) => Builtin::Last,
+            ("reverse", 0) => Builtin::Reverse,
+            ("sort", 0) => Builtin::Sort,
+            ("to_entries", 0) => Builtin::ToEntries,
+            ("test", 1) => Builtin::Test,
+            ("startswith", 1) => Builtin::StartsWith,
+            ("endswith", 1) => Builtin::EndsWith,
+            _ => return None,
+        };
+        Some(builtin)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Dot,
+    DotDot,
+    Field(String),
+    Ident(String),
+    Number(f64),
+    String(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Dot => write!(f, "."),
+            Token::DotDot => write!(f, ".."),
+            Token::Field(name) => write!(f, ".{name}"),
+            Token::Ident(name) => write!(f, "{name}"),
+            Token::Number(n) => write!(f, "{}", format_number(*n)),
+            Token::String(s) => write!(f, "\"{}\"", escape_json_string(s)),
+            Token::Symbol(symbol) => write!(f, "{symbol}"),
+        }
+    }
+}
+
+// Longer symbols need to come before their prefixes.
+const SYMBOLS: [&str; 23] = [
+    "==", "!=", "<=", ">=", "//", "|", ",", ":", ";", "?", "(", ")", "[", "]", "{", "}", "<", ">",
+    "+", "-", "*", "/", "%",
+];
+
+fn is_ident_start(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_'
+}
+
+fn is_ident_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_'
+}
+
+fn tokenize(filter: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = filter;
+
+    'outer: loop {
+        rest = rest.trim_start();
+        let Some(ch) = rest.chars().next() else {
+            break;
+        };
+
+        if ch == '.' {
+            let after_dot = &rest[1..];
+            if let Some(after_dots) = after_dot.strip_prefix('.') {
+                tokens.push(Token::DotDot);
+                rest = after_dots;
+            } else if after_dot.starts_with(is_ident_start) {
+                let len = after_dot
+                    .find(|ch| !is_ident_char(ch))
+                    .unwrap_or(after_dot.len());
+                tokens.push(Token::Field(after_dot[..len].to_string()));
+                rest = &after_dot[len..];
+            } else {
+                tokens.push(Token::Dot);
+                rest = after_dot;
+            }
+        } else if ch == '"' {
+            let (s, len) = tokenize_string(rest)?;
+            tokens.push(Token::String(s));
+            rest = &rest[len..];
+        } else if ch.is_ascii_digit() {
+            let mut len = rest
+                .find(|ch: char| !ch.is_ascii_digit() && ch != '.')
+                .unwrap_or(rest.len());
+            // An optional exponent, e.g., 1e-5
+            if rest[len..].starts_with(['e', 'E']) {
+                let exponent = rest[len + 1..].trim_start_matches(['+', '-']);
+                let sign_len = rest.len() - (len + 1) - exponent.len();
+                let digits_len = exponent
+                    .find(|ch: char| !ch.is_ascii_digit())
+                    .unwrap_or(exponent.len());
+                len += 1 + sign_len + digits_len;
+            }
+            match rest[..len].parse::<f64>() {
+                Ok(n) => tokens.push(Token::Number(n)),
+                Err(_) => return Err(format!("Invalid filter: invalid number {}", &rest[..len])),
+            }
+            rest = &rest[len..];
+        } else if is_ident_start(ch) {
+            let len = rest.find(|ch| !is_ident_char(ch)).unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else {
+            for symbol in SYMBOLS.iter() {
+                if let Some(after_symbol) = rest.strip_prefix(symbol) {
+                    tokens.push(Token::Symbol(symbol));
+                    rest = after_symbol;
+                    continue 'outer;
+                }
+            }
+            return Err(format!("Invalid filter: unexpected character '{ch}'"));
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Tokenize a string literal at the start of the input, returning the
+// unescaped string, and the length of the literal.
+fn tokenize_string(input: &str) -> Result<(String, usize), String> {
+    let mut escaped = false;
+
+    for (i, ch) in input.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == '"' {
+            let contents = &input[1..i];
+            if !has_valid_escapes(contents) {
+                return Err(format!(
+                    "Invalid filter: invalid escape in string {}",
+                    &input[..=i]
+                ));
+            }
+            return match unsafe_unescape_json_string(contents) {
+                Ok(s) => Ok((s, i + 1)),
+                Err(err) => Err(format!("Invalid filter: {err}")),
+            };
+        }
+    }
+
+    Err("Invalid filter: unterminated string".to_string())
+}
+
+// Check that a string only contains valid JSON escape sequences, since
+// the JSON string unescaper assumes that its input is valid.
+fn has_valid_escapes(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            continue;
+        }
+
+        match chars.next() {
+            Some('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't') => {}
+            Some('u') => {
+                for _ in 0..4 {
+                    if !matches!(chars.next(), Some(ch) if ch.is_ascii_hexdigit()) {
+                        return false;
+                    }
+                }
+            }
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn parse(filter: &str) -> Result<Expr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(filter)?,
+            position: 0,
+        };
+
+        let expr = parser.parse_pipe()?;
+
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("Invalid filter: unexpected {token}")),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_is(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn peek_is_ident(&self, ident: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(s)) if s == ident)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => Err("Invalid filter: unexpected end of filter".to_string()),
+        }
+    }
+
+    fn consume(&mut self, symbol: &str) -> bool {
+        if self.peek_is(symbol) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.next()? {
+            Token::Symbol(s) if s == symbol => Ok(()),
+            token => Err(format!("Invalid filter: expected {symbol}, found {token}")),
+        }
+    }
+
+    fn parse_pipe(&mut self) -> Result<Expr, String> {
+        let lhs = self.parse_comma()?;
+        if self.consume("|") {
+            let rhs = self.parse_pipe()?;
+            return Ok(Expr::Pipe(Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_comma(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_alternative()?;
+        while self.consume(",") {
+            let rhs = self.parse_alternative()?;
+            lhs = Expr::Comma(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_alternative(&mut self) -> Result<Expr, String> {
+        let lhs = self.parse_or()?;
+        if self.consume("//") {
+            let rhs = self.parse_alternative()?;
+            return Ok(Expr::Alternative(Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_and()?;
+        while self.peek_is_ident("or") {
+            self.position += 1;
+            let rhs = self.parse_and()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_comparison()?;
+        while self.peek_is_ident("and") {
+            self.position += 1;
+            let rhs = self.parse_comparison()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        let lhs = self.parse_additive()?;
+
+        let op = match self.peek() {
+            Some(Token::Symbol("==")) => BinaryOp::Eq,
+            Some(Token::Symbol("!=")) => BinaryOp::Ne,
+            Some(Token::Symbol("<")) => BinaryOp::Lt,
+            Some(Token::Symbol("<=")) => BinaryOp::Le,
+            Some(Token::Symbol(">")) => BinaryOp::Gt,
+            Some(Token::Symbol(">=")) => BinaryOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.position += 1;
+
+        let rhs = self.parse_additive()?;
+        Ok(Expr::Binary(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("+")) => BinaryOp::Add,
+                Some(Token::Symbol("-")) => BinaryOp::Sub,
+                _ => return Ok(lhs),
+            };
+            self.position += 1;
+            let rhs = self.parse_multiplicative()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.parse_postfix()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Symbol("*")) => BinaryOp::Mul,
+                Some(Token::Symbol("/")) => BinaryOp::Div,
+                Some(Token::Symbol("%")) => BinaryOp::Mod,
+                _ => return Ok(lhs),
+            };
+            self.position += 1;
+            let rhs = self.parse_postfix()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    // A term followed by any number of path components, e.g., .foo[0].bar?
+    fn parse_postfix(&mut self) -> Result<Expr, String> {
+        let mut term = self.parse_term()?;
+
+        loop {
+            term = match self.peek() {
+                Some(Token::Field(name)) => {
+                    let name = name.clone();
+                    self.position += 1;
+                    Expr::Index(Box::new(term), Box::new(Expr::Literal(Item::String(name))))
+                }
+                Some(Token::Dot) => match self.tokens.get(self.position + 1) {
+                    Some(Token::String(s)) => {
+                        let s = s.clone();
+                        self.position += 2;
+                        Expr::Index(Box::new(term), Box::new(Expr::Literal(Item::String(s))))
+                    }
+                    Some(Token::Symbol("[")) => {
+                        self.position += 1;
+                        self.parse_brackets(term)?
+                    }
+                    _ => return Ok(term),
+                },
+                Some(Token::Symbol("[")) => self.parse_brackets(term)?,
+                Some(Token::Symbol("?")) => {
+                    self.position += 1;
+                    Expr::Try(Box::new(term))
+                }
+                _ => return Ok(term),
+            };
+        }
+    }
+
+    // Parse [], [index], or [from:to] following a term.
+    fn parse_brackets(&mut self, term: Expr) -> Result<Expr, String> {
+        self.expect("[")?;
+
+        if self.consume("]") {
+            return Ok(Expr::Iterate(Box::new(term)));
+        }
+
+        let from = if self.peek_is(":") {
+            None
+        } else {
+            Some(Box::new(self.parse_pipe()?))
+        };
+
+        if self.consume(":") {
+            let to = if self.peek_is("]") {
+                None
+            } else {
+                Some(Box::new(self.parse_pipe()?))
+            };
+            self.expect("]")?;
+            return Ok(Expr::Slice(Box::new(term), from, to));
+        }
+
+        self.expect("]")?;
+        Ok(Expr::Index(Box::new(term), from.unwrap()))
+    }
+
+    fn parse_term(&mut self) -> Result<Expr, String> {
+        let expr = match self.next()? {
+            Token::Dot => match self.peek() {
+                Some(Token::String(s)) => {
+                    let s = s.clone();
+                    self.position += 1;
+                    Expr::Index(
+                        Box::new(Expr::Identity),
+                        Box::new(Expr::Literal(Item::String(s))),
+                    )
+                }
+                _ => Expr::Identity,
+            },
+            Token::DotDot => Expr::Recurse,
+            Token::Field(name) => Expr::Index(
+                Box::new(Expr::Identity),
+                Box::new(Expr::Literal(Item::String(name))),
+            ),
+            Token::Number(n) => Expr::Literal(Item::Number(n)),
+            Token::String(s) => Expr::Literal(Item::String(s)),
+            Token::Ident(name) => self.parse_ident(name)?,
+            Token::Symbol("(") => {
+                let expr = self.parse_pipe()?;
+                self.expect(")")?;
+                expr
+            }
+            Token::Symbol("[") => {
+                if self.consume("]") {
+                    Expr::Array(None)
+                } else {
+                    let expr = self.parse_pipe()?;
+                    self.expect("]")?;
+                    Expr::Array(Some(Box::new(expr)))
+                }
+            }
+            Token::Symbol("{") => self.parse_object()?,
+            Token::Symbol("-") => Expr::Negate(Box::new(self.parse_postfix()?)),
+            token => return Err(format!("Invalid filter: unexpected {token}")),
+        };
+
+        Ok(expr)
+    }
+
+    fn parse_ident(&mut self, name: String) -> Result<Expr, String> {
+        match name.as_str() {
+            "null" => return Ok(Expr::Literal(Item::Null)),
+            "true" => return Ok(Expr::Literal(Item::Bool(true))),
+            "false" => return Ok(Expr::Literal(Item::Bool(false))),
+            _ => {}
+        }
+
+        let mut args = vec![];
+        if self.consume("(") {
+            args.push(self.parse_pipe()?);
+            while self.consume(";") {
+                args.push(self.parse_pipe()?);
+            }
+            self.expect(")")?;
+        }
+
+        // map(f) is just [.[] | f].
+        if name == "map" && args.len() == 1 {
+            let f = args.pop().unwrap();
+            let iterate = Expr::Iterate(Box::new(Expr::Identity));
+            return Ok(Expr::Array(Some(Box::new(Expr::Pipe(
+                Box::new(iterate),
+                Box::new(f),
+            )))));
+        }
+
+        match Builtin::from_name(&name, args.len()) {
+            Some(builtin) => Ok(Expr::Call(builtin, args)),
+            None => Err(format!(
+                "Invalid filter: {name}/{} is not defined",
+                args.len()
+            )),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Expr, String> {
+        let mut entries = vec![];
+
+        if self.consume("}") {
+            return Ok(Expr::Object(entries));
+        }
+
+        loop {
+            let (key, shorthand) = match self.next()? {
+                Token::Ident(name) | Token::String(name) => {
+                    (Expr::Literal(Item::String(name.clone())), Some(name))
+                }
+                Token::Symbol("(") => {
+                    let key = self.parse_pipe()?;
+                    self.expect(")")?;
+                    (key, None)
+                }
+                token => return Err(format!("Invalid filter: unexpected {token} in object")),
+            };
+
+            let value = if self.consume(":") {
+                self.parse_alternative()?
+            } else if let Some(name) = shorthand {
+                // {foo} is short for {foo: .foo}
+                Expr::Index(
+                    Box::new(Expr::Identity),
+                    Box::new(Expr::Literal(Item::String(name))),
+                )
+            } else {
+                return Err("Invalid filter: expected : after object key".to_string());
+            };
+
+            entries.push((key, value));
+
+            if self.consume("}") {
+                return Ok(Expr::Object(entries));
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+struct Evaluator<'a> {
+    flatjson: &'a FlatJson,
+}
+
+impl<'a> Evaluator<'a> {
+    fn eval(&self, expr: &Expr, input: &Item) -> Result<Vec<Item>, String> {
+        let mut outputs = vec![];
+
+        match expr {
+            Expr::Identity => outputs.push(input.clone()),
+            Expr::Recurse => self.recurse(input, &mut outputs),
+            Expr::Literal(item) => outputs.push(item.clone()),
+            Expr::Index(target, index) => {
+                let indexes = self.eval(index, input)?;
+                for target in self.eval(target, input)? {
+                    for index in indexes.iter() {
+                        outputs.push(self.index(&target, index)?);
+                    }
+                }
+            }
+            Expr::Slice(target, from, to) => {
+                let from = match from {
+                    Some(from) => self.eval(from, input)?,
+                    None => vec![Item::Null],
+                };
+                let to = match to {
+                    Some(to) => self.eval(to, input)?,
+                    None => vec![Item::Null],
+                };
+                for target in self.eval(target, input)? {
+                    for from in from.iter() {
+                        for to in to.iter() {
+                            outputs.push(self.slice(&target, from, to)?);
+                        }
+                    }
+                }
+            }
+            Expr::Iterate(target) => {
+                for target in self.eval(target, input)? {
+                    outputs.extend(self.iterate(&target)?);
+                }
+            }
+            Expr::Try(expr) => outputs = self.eval(expr, input).unwrap_or_default(),
+            Expr::Pipe(lhs, rhs) => {
+                for item in self.eval(lhs, input)? {
+                    outputs.extend(self.eval(rhs, &item)?);
+                }
+            }
+            Expr::Comma(lhs, rhs) => {
+                outputs = self.eval(lhs, input)?;
+                outputs.extend(self.eval(rhs, input)?);
+            }
+            Expr::Alternative(lhs, rhs) => {
+                outputs = self.eval(lhs, input).unwrap_or_default();
+                outputs.retain(|item| self.is_truthy(item));
+                if outputs.is_empty() {
+                    outputs = self.eval(rhs, input)?;
+                }
+            }
+            Expr::And(lhs, rhs) => {
+                for item in self.eval(lhs, input)? {
+                    if !self.is_truthy(&item) {
+                        outputs.push(Item::Bool(false));
+                        continue;
+                    }
+                    for item in self.eval(rhs, input)? {
+                        outputs.push(Item::Bool(self.is_truthy(&item)));
+                    }
+                }
+            }
+            Expr::Or(lhs, rhs) => {
+                for item in self.eval(lhs, input)? {
+                    if self.is_truthy(&item) {
+                        outputs.push(Item::Bool(true));
+                        continue;
+                    }
+                    for item in self.eval(rhs, input)? {
+                        outputs.push(Item::Bool(self.is_truthy(&item)));
+                    }
+                }
+            }
+            Expr::Negate(expr) => {
+                for item in self.eval(expr, input)? {
+                    match self.resolve(&item).as_ref() {
+                        Item::Number(n) => outputs.push(Item::Number(-n)),
+                        item => return Err(format!("{} cannot be negated", type_name(item))),
+                    }
+                }
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.eval(lhs, input)?;
+                for rhs in self.eval(rhs, input)? {
+                    for lhs in lhs.iter() {
+                        outputs.push(self.binary(*op, lhs, &rhs)?);
+                    }
+                }
+            }
+            Expr::Array(None) => outputs.push(Item::Array(vec![])),
+            Expr::Array(Some(expr)) => outputs.push(Item::Array(self.eval(expr, input)?)),
+            Expr::Object(entries) => {
+                // Each key and value may have multiple outputs, in which
+                // case we output every combination of them.
+                let mut objects = vec![vec![]];
+                for (key, value) in entries.iter() {
+                    let keys = self.eval(key, input)?;
+                    let values = self.eval(value, input)?;
+
+                    let mut new_objects = vec![];
+                    for object in objects.iter() {
+                        for key in keys.iter() {
+                            let key = match self.resolve(key).into_owned() {
+                                Item::String(key) => key,
+                                key => {
+                                    return Err(format!(
+                                        "Object keys must be strings, not {}",
+                                        type_name(&key)
+                                    ))
+                                }
+                            };
+                            for value in values.iter() {
+                                let mut object: Vec<(String, Item)> = object.clone();
+                                insert_entry(&mut object, key.clone(), value.clone());
+                                new_objects.push(object);
+                            }
+                        }
+                    }
+                    objects = new_objects;
+                }
+                outputs.extend(objects.into_iter().map(Item::Object));
+            }
+            Expr::Call(builtin, args) => outputs = self.call(*builtin, args, input)?,
+        }
+
+        Ok(outputs)
+    }
+
+    fn call(&self, builtin: Builtin, args: &[Expr], input: &Item) -> Result<Vec<Item>, String> {
+        let output = match builtin {
+            Builtin::Select => {
+                let mut outputs = vec![];
+                for item in self.eval(&args[0], input)? {
+                    if self.is_truthy(&item) {
+                        outputs.push(input.clone());
+                    }
+                }
+                return Ok(outputs);
+            }
+            Builtin::Empty => return Ok(vec![]),
+            Builtin::Has => {
+                let mut outputs = vec![];
+                for key in self.eval(&args[0], input)? {
+                    outputs.push(self.has(input, &key)?);
+                }
+                return Ok(outputs);
+            }
+            Builtin::Test | Builtin::StartsWith | Builtin::EndsWith => {
+                let mut outputs = vec![];
+                for arg in self.eval(&args[0], input)? {
+                    outputs.push(self.string_test(builtin, input, &arg)?);
+                }
+                return Ok(outputs);
+            }
+            Builtin::Not => Item::Bool(!self.is_truthy(input)),
+            Builtin::Type => Item::String(type_name(&self.resolve(input)).to_string()),
+            Builtin::Length => match self.resolve(input).as_ref() {
+                Item::Null => Item::Number(0.0),
+                Item::Number(n) => Item::Number(n.abs()),
+                Item::String(s) => Item::Number(s.chars().count() as f64),
+                Item::Array(items) => Item::Number(items.len() as f64),
+                Item::Object(entries) => Item::Number(entries.len() as f64),
+                item => return Err(format!("{} has no length", type_name(item))),
+            },
+            Builtin::Keys | Builtin::KeysUnsorted => match self.resolve(input).as_ref() {
+                Item::Object(entries) => {
+                    let mut keys: Vec<String> = entries.iter().map(|(k, _)| k.clone()).collect();
+                    if let Builtin::Keys = builtin {
+                        keys.sort();
+                    }
+                    Item::Array(keys.into_iter().map(Item::String).collect())
+                }
+                Item::Array(items) => {
+                    Item::Array((0..items.len()).map(|i| Item::Number(i as f64)).collect())
+                }
+                item => return Err(format!("{} has no keys", type_name(item))),
+            },
+            Builtin::Add => {
+                let mut sum = Item::Null;
+                for item in self.iterate(input)? {
+                    sum = self.binary(BinaryOp::Add, &sum, &item)?;
+                }
+                sum
+            }
+            Builtin::First => self.index(input, &Item::Number(0.0))?,
+            Builtin::Last => self.index(input, &Item::Number(-1.0))?,
+            Builtin::Reverse => match self.resolve(input).into_owned() {
+                Item::Null => Item::Array(vec![]),
+                Item::String(s) => Item::String(s.chars().rev().collect()),
+                Item::Array(mut items) => {
+                    items.reverse();
+                    Item::Array(items)
+                }
+                item => return Err(format!("Cannot reverse {}", type_name(&item))),
+            },
+            Builtin::Sort => match self.resolve(input).into_owned() {
+                Item::Array(mut items) => {
+                    items.sort_by(|a, b| self.compare(a, b));
+                    Item::Array(items)
+                }
+                item => return Err(format!("{} cannot be sorted", type_name(&item))),
+            },
+            Builtin::ToEntries => match self.resolve(input).into_owned() {
+                Item::Object(entries) => Item::Array(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| {
+                            Item::Object(vec![
+                                ("key".to_string(), Item::String(key)),
+                                ("value".to_string(), value),
+                            ])
+                        })
+                        .collect(),
+                ),
+                item => return Err(format!("{} has no keys", type_name(&item))),
+            },
+        };
+
+        Ok(vec![output])
+    }
+
+    // Convert a value from the input document into an Item; the children
+    // of arrays and objects are left as references to rows.
+    fn resolve<'b>(&self, item: &'b Item) -> Cow<'b, Item> {
+        let Item::Row(index) = item else {
+            return Cow::Borrowed(item);
+        };
+
+        let row = &self.flatjson[*index];
+        let text = &self.flatjson.1[row.range.clone()];
+
+        let item = match &row.value {
+            Value::Null => Item::Null,
+            Value::Boolean => Item::Bool(text.trim() == "true"),
+            Value::Number => Item::Number(text.parse().unwrap_or(f64::NAN)),
+            Value::String => Item::String(unescape(&text[1..text.len() - 1])),
+            Value::EmptyObject => Item::Object(vec![]),
+            Value::EmptyArray => Item::Array(vec![]),
+            Value::OpenContainer { container_type, .. } => {
+                let children = self.children(*index);
+                match container_type {
+                    ContainerType::Array => {
+                        Item::Array(children.into_iter().map(Item::Row).collect())
+                    }
+                    ContainerType::Object => Item::Object(
+                        children
+                            .into_iter()
+                            .map(|child| (self.key(child), Item::Row(child)))
+                            .collect(),
+                    ),
+                }
+            }
+            Value::CloseContainer { open_index, .. } => {
+                return Cow::Owned(self.resolve(&Item::Row(*open_index)).into_owned())
+            }
+        };
+
+        Cow::Owned(item)
+    }
+
+    fn children(&self, index: Index) -> Vec<Index> {
+        let mut children = vec![];
+        let mut child = self.flatjson[index].first_child();
+        while let OptionIndex::Index(i) = child {
+            children.push(i);
+            child = self.flatjson[i].next_sibling;
+        }
+        children
+    }
+
+    fn key(&self, index: Index) -> String {
+        let key_range = self.flatjson[index].key_range.clone().unwrap();
+        let key = &self.flatjson.1[key_range];
+
+        // Keys that aren't strings (e.g., from YAML) aren't quoted.
+        if key.starts_with('"') {
+            unescape(&key[1..key.len() - 1])
+        } else {
+            key.to_string()
+        }
+    }
+
+    fn is_truthy(&self, item: &Item) -> bool {
+        match item {
+            Item::Row(index) => {
+                let row = &self.flatjson[*index];
+                match row.value {
+                    Value::Null => false,
+                    Value::Boolean => self.flatjson.1[row.range.clone()].trim() == "true",
+                    _ => true,
+                }
+            }
+            Item::Null | Item::Bool(false) => false,
+            _ => true,
+        }
+    }
+
+    fn recurse(&self, item: &Item, outputs: &mut Vec<Item>) {
+        outputs.push(item.clone());
+        match self.resolve(item).as_ref() {
+            Item::Array(items) => {
+                for item in items.iter() {
+                    self.recurse(item, outputs);
+                }
+            }
+            Item::Object(entries) => {
+                for (_, item) in entries.iter() {
+                    self.recurse(item, outputs);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn index(&self, target: &Item, index: &Item) -> Result<Item, String> {
+        let target = self.resolve(target);
+        let index = self.resolve(index);
+
+        match (target.as_ref(), index.as_ref()) {
+            (Item::Null, Item::String(_) | Item::Number(_)) => Ok(Item::Null),
+            (Item::Object(entries), Item::String(key)) => Ok(entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.clone())
+                .unwrap_or(Item::Null)),
+            (Item::Array(items), Item::Number(n)) => {
+                let mut i = n.floor() as i64;
+                if i < 0 {
+                    i += items.len() as i64;
+                }
+                if i < 0 {
+                    return Ok(Item::Null);
+                }
+                Ok(items.get(i as usize).cloned().unwrap_or(Item::Null))
+            }
+            (target, Item::String(key)) => Err(format!(
+                "Cannot index {} with \"{}\"",
+                type_name(target),
+                key
+            )),
+            (target, index) => Err(format!(
+                "Cannot index {} with {}",
+                type_name(target),
+                type_name(index)
+            )),
+        }
+    }
+
+    fn slice(&self, target: &Item, from: &Item, to: &Item) -> Result<Item, String> {
+        let target = self.resolve(target);
+
+        let len = match target.as_ref() {
+            Item::Null => return Ok(Item::Null),
+            Item::Array(items) => items.len(),
+            Item::String(s) => s.chars().count(),
+            target => return Err(format!("Cannot index {} with object", type_name(target))),
+        };
+
+        let bound = |item: &Item, default: usize| match self.resolve(item).as_ref() {
+            Item::Null => Ok(default),
+            Item::Number(n) => {
+                let mut i = n.floor() as i64;
+                if i < 0 {
+                    i += len as i64;
+                }
+                Ok(i.clamp(0, len as i64) as usize)
+            }
+            _ => Err("Start and end indices of a slice must be numbers".to_string()),
+        };
+        let from = bound(from, 0)?;
+        let to = bound(to, len)?.max(from);
+
+        Ok(match target.into_owned() {
+            Item::Array(items) => Item::Array(items[from..to].to_vec()),
+            Item::String(s) => Item::String(s.chars().skip(from).take(to - from).collect()),
+            _ => unreachable!(),
+        })
+    }
+
+    fn iterate(&self, target: &Item) -> Result<Vec<Item>, String> {
+        match self.resolve(target).into_owned() {
+            Item::Array(items) => Ok(items),
+            Item::Object(entries) => Ok(entries.into_iter().map(|(_, v)| v).collect()),
+            target => Err(format!("Cannot iterate over {}", type_name(&target))),
+        }
+    }
+
+    fn has(&self, target: &Item, key: &Item) -> Result<Item, String> {
+        let target = self.resolve(target);
+        let key = self.resolve(key);
+
+        match (target.as_ref(), key.as_ref()) {
+            (Item::Object(entries), Item::String(key)) => {
+                Ok(Item::Bool(entries.iter().any(|(k, _)| k == key)))
+            }
+            (Item::Array(items), Item::Number(n)) => {
+                Ok(Item::Bool(*n >= 0.0 && *n < items.len() as f64))
+            }
+            (target, key) => Err(format!(
+                "Cannot check whether {} has a {} key",
+                type_name(target),
+                type_name(key)
+            )),
+        }
+    }
+
+    fn string_test(&self, builtin: Builtin, input: &Item, arg: &Item) -> Result<Item, String> {
+        let input = self.resolve(input);
+        let arg = self.resolve(arg);
+
+        let (Item::String(s), Item::String(arg)) = (input.as_ref(), arg.as_ref()) else {
+            let name = match builtin {
+                Builtin::Test => "test",
+                Builtin::StartsWith => "startswith",
+                _ => "endswith",
+            };
+            return Err(format!("{name}() requires string inputs"));
+        };
+
+        let result = match builtin {
+            Builtin::Test => match Regex::new(arg) {
+                Ok(regex) => regex.is_match(s),
+                Err(err) => return Err(format!("Invalid regex: {err}")),
+            },
+            Builtin::StartsWith => s.starts_with(arg.as_str()),
+            _ => s.ends_with(arg.as_str()),
+        };
+
+        Ok(Item::Bool(result))
+    }
+
+    fn binary(&self, op: BinaryOp, lhs: &Item, rhs: &Item) -> Result<Item, String> {
+        let comparison = |f: fn(Ordering) -> bool| Ok(Item::Bool(f(self.compare(lhs, rhs))));
+
+        match op {
+            BinaryOp::Eq => return comparison(|o| o == Ordering::Equal),
+            BinaryOp::Ne => return comparison(|o| o != Ordering::Equal),
+            BinaryOp::Lt => return comparison(|o| o == Ordering::Less),
+            BinaryOp::Le => return comparison(|o| o != Ordering::Greater),
+            BinaryOp::Gt => return comparison(|o| o == Ordering::Greater),
+            BinaryOp::Ge => return comparison(|o| o != Ordering::Less),
+            _ => {}
+        }
+
+        let lhs = self.resolve(lhs).into_owned();
+        let rhs = self.resolve(rhs).into_owned();
+
+        let result = match (op, lhs, rhs) {
+            (BinaryOp::Add, Item::Null, item) | (BinaryOp::Add, item, Item::Null) => item,
+            (BinaryOp::Add, Item::Number(a), Item::Number(b)) => Item::Number(a + b),
+            (BinaryOp::Add, Item::String(a), Item::String(b)) => Item::String(a + &b),
+            (BinaryOp::Add, Item::Array(mut a), Item::Array(b)) => {
+                a.extend(b);
+                Item::Array(a)
+            }
+            (BinaryOp::Add, Item::Object(mut a), Item::Object(b)) => {
+                for (key, value) in b.into_iter() {
+                    insert_entry(&mut a, key, value);
+                }
+                Item::Object(a)
+            }
+            (BinaryOp::Sub, Item::Number(a), Item::Number(b)) => Item::Number(a - b),
+            (BinaryOp::Sub, Item::Array(a), Item::Array(b)) => Item::Array(
+                a.into_iter()
+                    .filter(|a| b.iter().all(|b| self.compare(a, b) != Ordering::Equal))
+                    .collect(),
+            ),
+            (BinaryOp::Mul, Item::Number(a), Item::Number(b)) => Item::Number(a * b),
+            (BinaryOp::Div, Item::Number(a), Item::Number(b)) => {
+                if b == 0.0 {
+                    return Err("Cannot divide by zero".to_string());
+                }
+                Item::Number(a / b)
+            }
+            (BinaryOp::Mod, Item::Number(a), Item::Number(b)) => {
+                let b = b as i64;
+                if b == 0 {
+                    return Err("Cannot divide by zero".to_string());
+                }
+                Item::Number((a as i64 % b) as f64)
+            }
+            (op, lhs, rhs) => {
+                let verb = match op {
+                    BinaryOp::Add => "added",
+                    BinaryOp::Sub => "subtracted",
+                    BinaryOp::Mul => "multiplied",
+                    _ => "divided",
+                };
+                return Err(format!(
+                    "{} and {} cannot be {verb}",
+                    type_name(&lhs),
+                    type_name(&rhs)
+                ));
+            }
+        };
+
+        Ok(result)
+    }
+
+    // Values are ordered like they are in jq: null, false, true, numbers,
+    // strings, arrays, then objects.
+    fn compare(&self, a: &Item, b: &Item) -> Ordering {
+        let a = self.resolve(a);
+        let b = self.resolve(b);
+
+        let rank = |item: &Item| match item {
+            Item::Null => 0,
+            Item::Bool(false) => 1,
+            Item::Bool(true) => 2,
+            Item::Number(_) => 3,
+            Item::String(_) => 4,
+            Item::Array(_) => 5,
+            Item::Object(_) => 6,
+            Item::Row(_) => unreachable!(),
+        };
+
+        match (a.as_ref(), b.as_ref()) {
+            (Item::Number(a), Item::Number(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (Item::String(a), Item::String(b)) => a.cmp(b),
+            (Item::Array(a), Item::Array(b)) => {
+                for (a, b) in a.iter().zip(b.iter()) {
+                    let ordering = self.compare(a, b);
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+                a.len().cmp(&b.len())
+            }
+            // Objects are compared by their (sorted) keys first, then by
+            // their values, in key order.
+            (Item::Object(a), Item::Object(b)) => {
+                let mut a: Vec<&(String, Item)> = a.iter().collect();
+                let mut b: Vec<&(String, Item)> = b.iter().collect();
+                a.sort_by(|x, y| x.0.cmp(&y.0));
+                b.sort_by(|x, y| x.0.cmp(&y.0));
+
+                let a_keys = a.iter().map(|(k, _)| k);
+                let b_keys = b.iter().map(|(k, _)| k);
+                let ordering = a_keys.cmp(b_keys);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+
+                for ((_, a), (_, b)) in a.iter().zip(b.iter()) {
+                    let ordering = self.compare(a, b);
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+                Ordering::Equal
+            }
+            (a, b) => rank(a).cmp(&rank(b)),
+        }
+    }
+
+    fn write_item(&self, item: &Item, buf: &mut String) {
+        match item {
+            Item::Row(index) => self.write_row(*index, buf),
+            Item::Null => buf.push_str("null"),
+            Item::Bool(b) => buf.push_str(if *b { "true" } else { "false" }),
+            Item::Number(n) => buf.push_str(&format_number(*n)),
+            Item::String(s) => write_string(s, buf),
+            Item::Array(items) => {
+                buf.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        buf.push(',');
+                    }
+                    self.write_item(item, buf);
+                }
+                buf.push(']');
+            }
+            Item::Object(entries) => {
+                buf.push('{');
+                for (i, (key, item)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        buf.push(',');
+                    }
+                    write_string(key, buf);
+                    buf.push(':');
+                    self.write_item(item, buf);
+                }
+                buf.push('}');
+            }
+        }
+    }
+
+    // Write a value from the input document. Other data formats may not
+    // display values as valid JSON, so we only copy numbers as is, to
+    // preserve their precision, and convert everything else.
+    fn write_row(&self, index: Index, buf: &mut String) {
+        let row = &self.flatjson[index];
+
+        match &row.value {
+            Value::Number => {
+                let text = self.flatjson.1[row.range.clone()].trim();
+                if JSON_NUMBER.is_match(text) {
+                    buf.push_str(text);
+                } else {
+                    buf.push_str(&format_number(text.parse().unwrap_or(f64::NAN)));
+                }
+            }
+            Value::OpenContainer { container_type, .. } => {
+                buf.push_str(container_type.open_str());
+                for (i, child) in self.children(index).into_iter().enumerate() {
+                    if i > 0 {
+                        buf.push(',');
+                    }
+                    if let ContainerType::Object = container_type {
+                        write_string(&self.key(child), buf);
+                        buf.push(':');
+                    }
+                    self.write_row(child, buf);
+                }
+                buf.push_str(container_type.close_str());
+            }
+            _ => {
+                let item = self.resolve(&Item::Row(index)).into_owned();
+                self.write_item(&item, buf);
+            }
+        }
+    }
+}
+
+fn type_name(item: &Item) -> &'static str {
+    match item {
+        Item::Null => "null",
+        Item::Bool(_) => "boolean",
+        Item::Number(_) => "number",
+        Item::String(_) => "string",
+        Item::Array(_) => "array",
+        Item::Object(_) => "object",
+        Item::Row(_) => unreachable!("rows should be resolved first"),
+    }
+}
+
+fn insert_entry(entries: &mut Vec<(String, Item)>, key: String, value: Item) {
+    match entries.iter_mut().find(|(k, _)| *k == key) {
+        Some(entry) => entry.1 = value,
+        None => entries.push((key, value)),
+    }
+}
+
+fn unescape(s: &str) -> String {
+    if has_valid_escapes(s) {
+        unsafe_unescape_json_string(s).unwrap_or_else(|_| s.to_string())
+    } else {
+        s.to_string()
+    }
+}
+
+fn write_string(s: &str, buf: &mut String) {
+    buf.push('"');
+    buf.push_str(&escape_json_string(s));
+    buf.push('"');
+}
+
+fn format_number(n: f64) -> String {
+    if !n.is_finite() {
+        "null".to_string()
+    } else if n.fract() == 0.0 && n.abs() < 1e17 {
+        format!("{}", n as i64)
+    } else if n.abs() >= 1e17 || n.abs() < 1e-5 {
+        format!("{n:e}")
+    } else {
+        format!("{n}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USERS: &str = r#"{
+        "users": [
+            {"name": "alice", "age": 31, "tags": ["admin"], "status": "ok"},
+            {"name": "bob", "age": 25, "tags": [], "status": "failed"},
+            {"name": "carol", "age": 42, "tags": ["ops", "dev"], "status": null}
+        ],
+        "count": 3
+    }"#;
+
+    fn filter(json: &str, filter: &str) -> Result<String, String> {
+        let flatjson = flatjson::parse_top_level_json(json.to_string()).unwrap();
+        evaluate_filter(&flatjson, filter).map(|output| output.trim_end().to_string())
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(filter(USERS, ".count").unwrap(), "3");
+        assert_eq!(filter(USERS, ".users[1].name").unwrap(), r#""bob""#);
+        assert_eq!(
+            filter(USERS, r#".["users"][-1]."name""#).unwrap(),
+            r#""carol""#
+        );
+        assert_eq!(
+            filter(USERS, ".users[].name").unwrap(),
+            "\"alice\"\n\"bob\"\n\"carol\""
+        );
+        assert_eq!(filter(USERS, ".users[2].tags[0:1]").unwrap(), r#"["ops"]"#);
+        assert_eq!(filter(USERS, ".missing.deeper").unwrap(), "null");
+        assert_eq!(filter("[1, [2]]", "[..]").unwrap(), "[[1,[2]],1,[2],2]");
+        assert_eq!(filter(r#"{"a": 1}"#, ".").unwrap(), r#"{"a":1}"#);
+    }
+
+    #[test]
+    fn test_select_and_comparisons() {
+        assert_eq!(
+            filter(USERS, r#".users[] | select(.status != "ok") | .name"#).unwrap(),
+            "\"bob\"\n\"carol\""
+        );
+        assert_eq!(
+            filter(
+                USERS,
+                ".users[] | select(.age >= 30 and .tags != []) | .age"
+            )
+            .unwrap(),
+            "31\n42"
+        );
+        assert_eq!(
+            filter(USERS, ".users | map(.age > 30 or .name == \"bob\")").unwrap(),
+            "[true,true,true]"
+        );
+        assert_eq!(
+            filter("[1, 1.0, \"1\"]", ".[0] == .[1], .[0] < .[2]").unwrap(),
+            "true\ntrue"
+        );
+        assert_eq!(
+            filter(USERS, ".users[2].status // \"none\"").unwrap(),
+            r#""none""#
+        );
+        assert_eq!(
+            filter(USERS, r#".users[] | select(.name | test("^[ab]")) | .name"#).unwrap(),
+            "\"alice\"\n\"bob\""
+        );
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_eq!(filter(USERS, ".users | length").unwrap(), "3");
+        assert_eq!(filter(USERS, "keys").unwrap(), r#"["count","users"]"#);
+        assert_eq!(
+            filter(USERS, "keys_unsorted").unwrap(),
+            r#"["users","count"]"#
+        );
+        assert_eq!(filter(USERS, ".users | map(.age) | add").unwrap(), "98");
+        assert_eq!(
+            filter(USERS, ".users | map(.age) | sort | first, last").unwrap(),
+            "25\n42"
+        );
+        assert_eq!(filter(USERS, ".users[0] | has(\"tags\")").unwrap(), "true");
+        assert_eq!(
+            filter(r#"{"a": 1}"#, "to_entries").unwrap(),
+            r#"[{"key":"a","value":1}]"#
+        );
+        assert_eq!(
+            filter("[1, \"a\", null]", "map(type)").unwrap(),
+            r#"["number","string","null"]"#
+        );
+    }
+
+    #[test]
+    fn test_construction() {
+        assert_eq!(
+            filter(USERS, ".users[0] | {name, years: .age, (.status): true}").unwrap(),
+            r#"{"name":"alice","years":31,"ok":true}"#
+        );
+        assert_eq!(
+            filter(USERS, "[.users[] | .tags[]]").unwrap(),
+            r#"["admin","ops","dev"]"#
+        );
+        assert_eq!(
+            filter("null", "{a: (1, 2)}").unwrap(),
+            "{\"a\":1}\n{\"a\":2}"
+        );
+        assert_eq!(
+            filter("null", "[1 + 2 * 3, -(4 - 1), \"a\" + \"b\"]").unwrap(),
+            "[7,-3,\"ab\"]"
+        );
+    }
+
+    #[test]
+    fn test_multiple_top_level_values() {
+        assert_eq!(filter("{\"a\": 1} {\"a\": 2}", ".a").unwrap(), "1\n2");
+        assert_eq!(filter("1 2 3", "select(. > 1)").unwrap(), "2\n3");
+        assert_eq!(filter("1 2 3", "empty").unwrap(), "");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            filter(USERS, ".count.foo").unwrap_err(),
+            "Cannot index number with \"foo\""
+        );
+        assert_eq!(
+            filter(USERS, ".count[]").unwrap_err(),
+            "Cannot iterate over number"
+        );
+        assert_eq!(filter(USERS, ".count[]?").unwrap(), "");
+        assert_eq!(
+            filter(USERS, ".users | foo").unwrap_err(),
+            "Invalid filter: foo/0 is not defined"
+        );
+        assert_eq!(
+            filter(USERS, ".users[").unwrap_err(),
+            "Invalid filter: unexpected end of filter"
+        );
+        assert_eq!(
+            filter(USERS, ".users )").unwrap_err(),
+            "Invalid filter: unexpected )"
+        );
+        assert!(filter(USERS, r#""\q""#).is_err());
+    }
+
+    #[test]
+    fn test_apply_filter() {
+        let flatjson = flatjson::parse_top_level_json(USERS.to_string()).unwrap();
+
+        let filtered = apply_filter(&flatjson, ".users[0].tags").unwrap();
+        assert_eq!(filtered.pretty_printed(), "[\n  \"admin\"\n]\n");
+
+        let filtered = apply_filter(&flatjson, "empty").unwrap();
+        assert!(filtered.is_empty());
+    }
+}
@@ -1,12 +1,21 @@
 use std::fmt::{Debug, Write};
 use std::ops::Range;
 
+use crate::binaryparser;
+use crate::csvparser;
+use crate::csvparser::CsvOptions;
 use crate::jsonparser;
+use crate::jsonparser::RecoveredError;
+use crate::jsonstringescaper::escape_json_string;
 use crate::lineprinter;
+use crate::options::OutputFormat;
+use crate::parseerror::ParseError;
+use crate::tomlparser;
 use crate::yamlparser;
 
+use crate::jsonstringunescaper::unsafe_unescape_json_string;
 #[cfg(feature = "sexp")]
-use crate::jsonstringunescaper::{unsafe_unescape_json_string, UnescapeError};
+use crate::jsonstringunescaper::UnescapeError;
 
 pub type Index = usize;
 
@@ -31,6 +40,13 @@ impl OptionIndex {
             OptionIndex::Index(i) => *i,
         }
     }
+
+    pub fn unwrap_or(&self, default: Index) -> Index {
+        match self {
+            OptionIndex::Nil => default,
+            OptionIndex::Index(i) => *i,
+        }
+    }
 }
 
 pub const NIL: usize = usize::MAX;
@@ -54,6 +70,18 @@ pub enum PathType {
     DotWithTopLevelIndex,
 }
 
+// A single step of a path to a node, as parsed by parse_path.
+#[derive(Debug, PartialEq)]
+pub enum PathSegment {
+    // An object key, as it appears between the quotes in the input, or,
+    // for non-string YAML keys, the whole key.
+    Key(String),
+    Index(usize),
+    // A JSON Pointer reference token, which is either an object key or
+    // an array index, depending on what it's applied to.
+    Token(String),
+}
+
 #[derive(Debug)]
 pub struct FlatJson(
     pub Vec<Row>,
@@ -65,62 +93,277 @@ pub struct FlatJson(
 );
 
 impl FlatJson {
-    pub fn last_visible_index(&self) -> Index {
-        let last_index = self.0.len() - 1;
+    pub fn empty() -> FlatJson {
+        FlatJson(vec![], String::new(), 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 
-        let row = &self.0[last_index];
+    // Append the top-level values of another FlatJson after our own
+    // top-level values. The other rows' indexes and ranges are shifted
+    // to point into our rows and pretty printed string, and the two
+    // sets of top-level values are linked together as siblings.
+    pub fn append(&mut self, other: FlatJson) {
+        let FlatJson(rows, pretty_printed, max_depth) = other;
 
-        if row.is_container() && row.is_collapsed() {
-            row.pair_index().unwrap()
-        } else {
-            last_index
+        if rows.is_empty() {
+            return;
         }
-    }
 
-    pub fn last_visible_item(&self) -> Index {
-        let mut last_index = self.0.len() - 1;
+        let row_offset = self.0.len();
 
-        loop {
-            let row = &self.0[last_index];
+        // The last row is always the end of the last top-level value.
+        let (last_top_level, range_offset) = if self.0.is_empty() {
+            (OptionIndex::Nil, 0)
+        } else {
+            let last_row = &self.0[row_offset - 1];
+            let last_top_level = if last_row.is_closing_of_container() {
+                last_row.pair_index().unwrap()
+            } else {
+                row_offset - 1
+            };
 
-            if row.is_primitive() {
-                return last_index;
+            // Top-level values are separated by newlines.
+            self.1.push('\n');
+            (OptionIndex::Index(last_top_level), self.1.len())
+        };
+
+        let index_offset = match last_top_level {
+            OptionIndex::Nil => 0,
+            OptionIndex::Index(i) => self.0[i].index_in_parent + 1,
+        };
+
+        let shift = |index: OptionIndex| match index {
+            OptionIndex::Nil => OptionIndex::Nil,
+            OptionIndex::Index(i) => OptionIndex::Index(i + row_offset),
+        };
+
+        for mut row in rows.into_iter() {
+            row.next_sibling = shift(row.next_sibling);
+
+            // The parser doesn't set the sibling fields or index of the
+            // closing rows of top-level containers.
+            if row.parent.is_nil() && !row.is_closing_of_container() {
+                row.index_in_parent += index_offset;
+                if row.prev_sibling.is_nil() {
+                    row.prev_sibling = last_top_level;
+                } else {
+                    row.prev_sibling = shift(row.prev_sibling);
+                }
+            } else {
+                row.parent = shift(row.parent);
+                row.prev_sibling = shift(row.prev_sibling);
             }
 
-            if row.is_closing_of_container() && row.is_collapsed() {
-                return row.pair_index().unwrap();
+            row.range = (row.range.start + range_offset)..(row.range.end + range_offset);
+            if let Some(key_range) = &mut row.key_range {
+                *key_range = (key_range.start + range_offset)..(key_range.end + range_offset);
             }
 
-            last_index -= 1;
+            match &mut row.value {
+                Value::OpenContainer {
+                    first_child,
+                    close_index,
+                    ..
+                } => {
+                    *first_child += row_offset;
+                    *close_index += row_offset;
+                }
+                Value::CloseContainer {
+                    last_child,
+                    open_index,
+                    ..
+                } => {
+                    *last_child += row_offset;
+                    *open_index += row_offset;
+                }
+                _ => {}
+            }
+
+            self.0.push(row);
+        }
+
+        if let OptionIndex::Index(last_top_level) = last_top_level {
+            self.0[last_top_level].next_sibling = OptionIndex::Index(row_offset);
         }
+
+        self.1.push_str(&pretty_printed);
+        self.2 = self.2.max(max_depth);
     }
 
-    pub fn prev_visible_row(&self, index: Index) -> OptionIndex {
-        if index == 0 {
-            return OptionIndex::Nil;
+    // Replace unloaded containers (see Row::is_unloaded) with the rows for
+    // the containers and their children, which are loaded lazily in
+    // large-file mode. Each load is the index of a container's open row
+    // and the rows and pretty printed text that replace it; loads must be
+    // sorted by index. The rows must start with the open row of the
+    // container and end with its close row; indexes in them should assume
+    // that the container stays at the same index, and ranges should be
+    // relative to the start of the text. The placeholder for each
+    // container in our pretty printed string is replaced with its text.
+    //
+    // All the rows after each container are shifted to make space for the
+    // new rows, which takes time proportional to the size of the whole
+    // document, so multiple containers are loaded in a single pass. Each
+    // container's own position in the tree (its parent, siblings, key, and
+    // whether it is collapsed) is preserved.
+    pub fn load_containers(&mut self, loads: Vec<(Index, Vec<Row>, String)>) {
+        debug_assert!(loads.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        debug_assert!(loads.iter().all(|(index, ..)| self.0[*index].is_unloaded()));
+
+        let load_indexes: Vec<Index> = loads.iter().map(|(index, ..)| *index).collect();
+        let placeholder_ends: Vec<usize> =
+            load_indexes.iter().map(|i| self.0[*i].range.end).collect();
+
+        // The number of rows, and bytes of text, added by the first n loads.
+        let mut rows_added = vec![0];
+        let mut text_added: Vec<isize> = vec![0];
+        for (index, rows, pretty_printed) in loads.iter() {
+            let placeholder_len = self.0[*index].range.len() as isize;
+            rows_added.push(rows_added.last().unwrap() + rows.len() - 2);
+            text_added
+                .push(text_added.last().unwrap() + pretty_printed.len() as isize - placeholder_len);
         }
 
-        let row = &self.0[index - 1];
+        // Rows after a container's open row (including its close row) are
+        // shifted by the rows added for it, and positions after its
+        // placeholder by the difference in length between the placeholder
+        // and the new text.
+        let shift_index =
+            |i: Index| i + rows_added[load_indexes.partition_point(|index| *index < i)];
+        let shift_position = |p: usize| {
+            let num_before = placeholder_ends.partition_point(|end| *end <= p);
+            (p as isize + text_added[num_before]) as usize
+        };
+        let shift_row = |row: &mut Row| {
+            row.shift_indexes(shift_index);
+            row.range = shift_position(row.range.start)..shift_position(row.range.end);
+            if let Some(key_range) = &mut row.key_range {
+                *key_range = shift_position(key_range.start)..shift_position(key_range.end);
+            }
+        };
 
-        if row.is_closing_of_container() && row.is_collapsed() {
-            row.pair_index()
-        } else {
-            OptionIndex::Index(index - 1)
+        let mut new_rows = Vec::with_capacity(self.0.len() + rows_added.last().unwrap());
+        let mut new_text =
+            String::with_capacity((self.1.len() as isize + text_added.last().unwrap()) as usize);
+        let mut next_old_text = 0;
+
+        let mut loads = loads.into_iter().peekable();
+        let mut old_rows = std::mem::take(&mut self.0).into_iter().enumerate();
+
+        while let Some((index, mut old_open)) = old_rows.next() {
+            let Some((_, mut rows, pretty_printed)) = loads.next_if(|(i, ..)| *i == index) else {
+                shift_row(&mut old_open);
+                new_rows.push(old_open);
+                continue;
+            };
+            let (_, mut old_close) = old_rows.next().unwrap();
+
+            new_text.push_str(&self.1[next_old_text..old_open.range.start]);
+            let text_offset = new_text.len();
+            new_text.push_str(&pretty_printed);
+            next_old_text = old_open.range.end;
+
+            let index_offset = new_rows.len() - index;
+            for row in rows.iter_mut() {
+                row.shift_indexes(|i| i + index_offset);
+                row.range = (row.range.start + text_offset)..(row.range.end + text_offset);
+                if let Some(key_range) = &mut row.key_range {
+                    *key_range = (key_range.start + text_offset)..(key_range.end + text_offset);
+                }
+                self.2 = self.2.max(row.depth);
+            }
+
+            shift_row(&mut old_open);
+            shift_row(&mut old_close);
+            let collapsed = old_open.is_collapsed();
+
+            let new_open = rows.first_mut().unwrap();
+            new_open.parent = old_open.parent;
+            new_open.prev_sibling = old_open.prev_sibling;
+            new_open.next_sibling = old_open.next_sibling;
+            new_open.depth = old_open.depth;
+            new_open.index_in_parent = old_open.index_in_parent;
+            new_open.key_range = old_open.key_range;
+            new_open.value.set_collapsed(collapsed);
+
+            let new_close = rows.last_mut().unwrap();
+            new_close.parent = old_close.parent;
+            new_close.prev_sibling = old_close.prev_sibling;
+            new_close.next_sibling = old_close.next_sibling;
+            new_close.depth = old_close.depth;
+            new_close.index_in_parent = old_close.index_in_parent;
+            new_close.value.set_collapsed(collapsed);
+
+            new_rows.extend(rows);
         }
+
+        new_text.push_str(&self.1[next_old_text..]);
+
+        self.0 = new_rows;
+        self.1 = new_text;
     }
 
-    pub fn next_visible_row(&self, mut index: Index) -> OptionIndex {
-        // If row is collapsed container, jump to closing char and move past there.
-        if self.0[index].is_opening_of_container() && self.0[index].is_collapsed() {
-            index = self.0[index].pair_index().unwrap();
+    pub fn last_visible_index(&self) -> Index {
+        self.prev_visible_row(self.0.len()).unwrap()
+    }
+
+    pub fn last_visible_item(&self) -> Index {
+        let last_index = self.last_visible_index();
+
+        if self.0[last_index].is_closing_of_container() {
+            self.prev_item(last_index).unwrap()
+        } else {
+            last_index
         }
+    }
+
+    pub fn prev_visible_row(&self, mut index: Index) -> OptionIndex {
+        while index > 0 {
+            let row = &self.0[index - 1];
 
-        // We can always go to the next row, unless we're at the end of the file.
-        if index == self.0.len() - 1 {
-            return OptionIndex::Nil;
+            // Skip past hidden rows. All the descendants of a hidden container
+            // are hidden too, so we can skip its contents entirely.
+            if row.hidden {
+                index = if row.is_closing_of_container() {
+                    row.pair_index().unwrap()
+                } else {
+                    index - 1
+                };
+                continue;
+            }
+
+            if row.is_closing_of_container() && row.is_collapsed() {
+                return row.pair_index();
+            } else {
+                return OptionIndex::Index(index - 1);
+            }
         }
 
-        OptionIndex::Index(index + 1)
+        OptionIndex::Nil
+    }
+
+    pub fn next_visible_row(&self, mut index: Index) -> OptionIndex {
+        loop {
+            // If row is collapsed (or hidden) container, jump to closing char
+            // and move past there.
+            let row = &self.0[index];
+            if row.is_opening_of_container() && (row.is_collapsed() || row.hidden) {
+                index = row.pair_index().unwrap();
+            }
+
+            // We can always go to the next row, unless we're at the end of the file.
+            if index == self.0.len() - 1 {
+                return OptionIndex::Nil;
+            }
+
+            index += 1;
+
+            if !self.0[index].hidden {
+                return OptionIndex::Index(index);
+            }
+        }
     }
 
     pub fn prev_item(&self, mut index: Index) -> OptionIndex {
@@ -161,11 +404,45 @@ impl FlatJson {
         self.0[index].collapse();
     }
 
-    pub fn toggle_collapsed(&mut self, index: Index) {
-        if let OptionIndex::Index(pair) = self.0[index].pair_index() {
-            self.0[pair].toggle_collapsed();
+    // The opening rows of all the collapsed containers.
+    pub fn collapsed_containers(&self) -> Vec<Index> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.is_opening_of_container() && row.is_collapsed())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    // Hide every row except for the given rows and their ancestors.
+    pub fn hide_rows_except(&mut self, rows: &[Index]) {
+        for row in self.0.iter_mut() {
+            row.hidden = true;
+        }
+
+        for &index in rows {
+            let mut curr = OptionIndex::Index(index);
+
+            // If a row isn't hidden, none of its ancestors are either.
+            while let OptionIndex::Index(i) = curr {
+                if !self.0[i].hidden {
+                    break;
+                }
+
+                self.0[i].hidden = false;
+                if let OptionIndex::Index(pair) = self.0[i].pair_index() {
+                    self.0[pair].hidden = false;
+                }
+
+                curr = self.0[i].parent;
+            }
+        }
+    }
+
+    pub fn unhide_all_rows(&mut self) {
+        for row in self.0.iter_mut() {
+            row.hidden = false;
         }
-        self.0[index].toggle_collapsed();
     }
 
     pub fn first_visible_ancestor(&self, mut index: Index) -> Index {
@@ -270,6 +547,77 @@ impl FlatJson {
         res.map_err(|e| e.to_string())
     }
 
+    // Find the child of a container that a path segment refers to.
+    pub fn child_at_path_segment(
+        &self,
+        index: Index,
+        segment: &PathSegment,
+    ) -> Result<Index, String> {
+        let row = &self[index];
+
+        let container_type = match &row.value {
+            Value::OpenContainer { container_type, .. } => Some(*container_type),
+            Value::EmptyObject => Some(ContainerType::Object),
+            Value::EmptyArray => Some(ContainerType::Array),
+            _ => None,
+        };
+
+        let mut child = row.first_child();
+
+        match (container_type, segment) {
+            (Some(ContainerType::Array), PathSegment::Index(_) | PathSegment::Token(_)) => {
+                let target_index = match segment {
+                    PathSegment::Index(i) => Some(*i),
+                    // Array indexes in JSON Pointers can't have leading zeros.
+                    PathSegment::Token(token) if token == "0" || !token.starts_with('0') => {
+                        token.parse::<usize>().ok()
+                    }
+                    _ => None,
+                };
+                let Some(target_index) = target_index else {
+                    return Err(format!("{segment} is not a valid array index"));
+                };
+                while let OptionIndex::Index(i) = child {
+                    if self[i].index_in_parent == target_index {
+                        return Ok(i);
+                    }
+                    child = self[i].next_sibling;
+                }
+                Err(format!("No element at index {target_index}"))
+            }
+            (Some(ContainerType::Array), PathSegment::Key(_)) => {
+                Err(format!("Cannot look up key {segment} in an array"))
+            }
+            (Some(ContainerType::Object), PathSegment::Index(_)) => {
+                Err(format!("Cannot look up index {segment} in an object"))
+            }
+            (Some(ContainerType::Object), PathSegment::Key(key) | PathSegment::Token(key)) => {
+                while let OptionIndex::Index(i) = child {
+                    if self.key_matches(i, key) {
+                        return Ok(i);
+                    }
+                    child = self[i].next_sibling;
+                }
+                Err(format!("No key {segment}"))
+            }
+            (None, _) => Err(format!("Cannot look up {segment} in a primitive value")),
+        }
+    }
+
+    fn key_matches(&self, index: Index, key: &str) -> bool {
+        let Some(key_range) = &self[index].key_range else {
+            return false;
+        };
+
+        let full_key = &self.1[key_range.clone()];
+        match full_key.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+            // The key may be specified either as it appears in the input
+            // (i.e., with escapes), or unescaped.
+            Some(raw_key) => raw_key == key || raw_key == escape_json_string(key),
+            None => full_key == key,
+        }
+    }
+
     pub fn pretty_printed(&self) -> String {
         let mut buf = String::new();
 
@@ -278,7 +626,7 @@ impl FlatJson {
                 buf.push_str("  ");
             }
             if let Some(ref key_range) = row.key_range {
-                buf.push_str(&self.1[key_range.clone()]);
+                self.write_json_key(&mut buf, key_range.clone());
                 buf.push_str(": ");
             }
             let mut trailing_comma = row.parent.is_some() && row.next_sibling.is_some();
@@ -294,7 +642,7 @@ impl FlatJson {
                         && self[row.pair_index().unwrap()].next_sibling.is_some();
                 }
             } else {
-                buf.push_str(&self.1[row.range.clone()]);
+                self.write_json_primitive(&mut buf, row);
             }
             if trailing_comma {
                 buf.push(',');
@@ -305,6 +653,263 @@ impl FlatJson {
         buf
     }
 
+    pub fn top_level_values(&self) -> Vec<Index> {
+        let mut values = vec![];
+        let mut top_level_value = if self.is_empty() {
+            OptionIndex::Nil
+        } else {
+            OptionIndex::Index(0)
+        };
+
+        while let OptionIndex::Index(index) = top_level_value {
+            values.push(index);
+            top_level_value = self[index].next_sibling;
+        }
+
+        values
+    }
+
+    // Format the whole document.
+    pub fn formatted(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Json => self.pretty_printed(),
+            OutputFormat::Yaml => self.yaml_string(),
+            _ => self.format_values(&self.top_level_values(), format),
+        }
+    }
+
+    // Format some values of the document (which shouldn't be nested in
+    // each other) as if they were the top-level values of a document.
+    pub fn format_values(&self, values: &[Index], format: OutputFormat) -> String {
+        let mut buf = String::new();
+
+        for (i, &index) in values.iter().enumerate() {
+            // Closing rows represent their whole container too.
+            let index = self[index].pair_index().unwrap_or(index).min(index);
+
+            match format {
+                OutputFormat::Json => {
+                    buf.push_str(&self.pretty_printed_value(index).unwrap());
+                    if self[index].is_primitive() {
+                        buf.push('\n');
+                    }
+                }
+                OutputFormat::Compact => {
+                    self.write_compact_value(&mut buf, index);
+                    buf.push('\n');
+                }
+                OutputFormat::Ndjson if self[index].is_array() => {
+                    let mut child = self[index].first_child();
+                    while let OptionIndex::Index(child_index) = child {
+                        self.write_compact_value(&mut buf, child_index);
+                        buf.push('\n');
+                        child = self[child_index].next_sibling;
+                    }
+                }
+                // Empty arrays have no elements to write.
+                OutputFormat::Ndjson if matches!(self[index].value, Value::EmptyArray) => {}
+                OutputFormat::Ndjson => {
+                    self.write_compact_value(&mut buf, index);
+                    buf.push('\n');
+                }
+                OutputFormat::Yaml => {
+                    if i != 0 {
+                        buf.push_str("---\n");
+                    }
+                    if self[index].is_primitive() {
+                        self.write_yaml_scalar(&mut buf, index);
+                        buf.push('\n');
+                    } else {
+                        self.write_yaml_children(&mut buf, index, 0, false);
+                    }
+                }
+            }
+        }
+
+        buf
+    }
+
+    // Write a value (without its key) on a single line, without any
+    // whitespace.
+    fn write_compact_value(&self, buf: &mut String, index: Index) {
+        let end_index = self[index].pair_index().unwrap_or(index);
+
+        for i in index..=end_index {
+            let row = &self[i];
+            if i != index {
+                if row.prev_sibling.is_some() && !row.is_closing_of_container() {
+                    buf.push(',');
+                }
+                if let Some(key_range) = &row.key_range {
+                    self.write_json_key(buf, key_range.clone());
+                    buf.push(':');
+                }
+            }
+
+            match row.value.container_type() {
+                Some(container_type) if row.is_opening_of_container() => {
+                    buf.push_str(container_type.open_str())
+                }
+                Some(container_type) => buf.push_str(container_type.close_str()),
+                None => self.write_json_primitive(buf, row),
+            }
+        }
+    }
+
+    // JSON5 allows Infinity and NaN, which have no JSON equivalent, so
+    // they're written as strings.
+    pub fn write_json_primitive(&self, buf: &mut String, row: &Row) {
+        let text = &self.1[row.range.clone()];
+        let unsigned = text.trim_start_matches(['-', '+']);
+
+        if matches!(row.value, Value::Number) && (unsigned == "Infinity" || unsigned == "NaN") {
+            buf.push('"');
+            buf.push_str(text);
+            buf.push('"');
+        } else {
+            buf.push_str(text);
+        }
+    }
+
+    pub fn write_json_key(&self, buf: &mut String, key_range: Range<usize>) {
+        let key = &self.1[key_range];
+
+        // Non-string keys (from YAML or MessagePack) are displayed in square
+        // brackets; JSON only allows string keys.
+        match key.strip_prefix('[').and_then(|k| k.strip_suffix(']')) {
+            Some(key) => {
+                buf.push('"');
+                buf.push_str(&escape_json_string(key));
+                buf.push('"');
+            }
+            None => buf.push_str(key),
+        }
+    }
+
+    // Format the document as YAML, using block style for non-empty
+    // containers. Multiple top-level values are written as separate
+    // YAML documents.
+    pub fn yaml_string(&self) -> String {
+        self.format_values(&self.top_level_values(), OutputFormat::Yaml)
+    }
+
+    // Write the entries of a non-empty container, one per line. If
+    // continue_line is set, the first entry is written on the current
+    // line (after a "- ").
+    fn write_yaml_children(
+        &self,
+        buf: &mut String,
+        index: Index,
+        indent: usize,
+        continue_line: bool,
+    ) {
+        let mut child = self[index].first_child();
+        let mut first = true;
+
+        while let OptionIndex::Index(child_index) = child {
+            if !first || !continue_line {
+                for _ in 0..indent {
+                    buf.push(' ');
+                }
+            }
+            first = false;
+
+            let row = &self[child_index];
+            match &row.key_range {
+                Some(key_range) => {
+                    self.write_yaml_key(buf, key_range.clone());
+                    buf.push(':');
+                }
+                None => buf.push('-'),
+            }
+
+            if row.is_primitive() {
+                buf.push(' ');
+                self.write_yaml_scalar(buf, child_index);
+                buf.push('\n');
+            } else if row.key_range.is_some() {
+                buf.push('\n');
+                self.write_yaml_children(buf, child_index, indent + 2, false);
+            } else {
+                buf.push(' ');
+                self.write_yaml_children(buf, child_index, indent + 2, true);
+            }
+
+            child = row.next_sibling;
+        }
+    }
+
+    fn write_yaml_key(&self, buf: &mut String, key_range: Range<usize>) {
+        let key = &self.1[key_range];
+
+        // Non-string keys (from YAML or MessagePack) are displayed in square
+        // brackets; they're already valid YAML without them.
+        if let Some(key) = key.strip_prefix('[').and_then(|k| k.strip_suffix(']')) {
+            buf.push_str(key);
+        } else {
+            Self::write_yaml_string(buf, key);
+        }
+    }
+
+    fn write_yaml_scalar(&self, buf: &mut String, index: Index) {
+        let row = &self[index];
+        let text = &self.1[row.range.clone()];
+
+        match row.value {
+            Value::String => Self::write_yaml_string(buf, text),
+            // JSON5 allows these, but YAML spells them differently.
+            Value::Number => match text {
+                "Infinity" | "+Infinity" => buf.push_str(".inf"),
+                "-Infinity" => buf.push_str("-.inf"),
+                "NaN" => buf.push_str(".nan"),
+                _ => buf.push_str(text),
+            },
+            _ => buf.push_str(text),
+        }
+    }
+
+    // Write a (quoted) JSON string as a YAML string, only quoting it if
+    // it would otherwise be interpreted as something else.
+    fn write_yaml_string(buf: &mut String, quoted: &str) {
+        let escaped = &quoted[1..quoted.len() - 1];
+        let unescaped =
+            unsafe_unescape_json_string(escaped).unwrap_or_else(|_| escaped.to_string());
+
+        if Self::yaml_string_needs_quotes(&unescaped) {
+            buf.push('"');
+            buf.push_str(&escape_json_string(&unescaped));
+            buf.push('"');
+        } else {
+            buf.push_str(&unescaped);
+        }
+    }
+
+    fn yaml_string_needs_quotes(s: &str) -> bool {
+        let Some(first_ch) = s.chars().next() else {
+            return true;
+        };
+
+        if s.trim() != s || s.ends_with(':') || s.contains(": ") || s.contains(" #") {
+            return true;
+        }
+
+        // Indicator characters, and characters that numbers start with.
+        if "-?:,[]{}#&*!|>'\"%@`.+0123456789".contains(first_ch) {
+            return true;
+        }
+
+        if s.chars().any(|ch| ch.is_control()) {
+            return true;
+        }
+
+        // Strings that would be parsed as other scalars (including YAML 1.1
+        // booleans), or as numbers, like "inf" and "nan".
+        matches!(
+            s.to_lowercase().as_str(),
+            "null" | "~" | "true" | "false" | "yes" | "no" | "on" | "off" | "y" | "n" | "<<"
+        ) || s.parse::<f64>().is_ok()
+    }
+
     #[cfg(feature = "sexp")]
     fn sexp_atom_needs_escaping(s: &str) -> bool {
         // See: https://github.com/janestreet/sexplib0/blob/master/src/sexp.ml#L58
@@ -434,12 +1039,13 @@ impl FlatJson {
     // complicated, that I don't think it's worth it to try to have them
     // share an implementation.
     pub fn pretty_printed_value(&self, value_index: Index) -> Result<String, std::fmt::Error> {
+        let mut buf = String::new();
+
         if self[value_index].is_primitive() {
-            return Ok(self.1[self[value_index].range.clone()].to_string());
+            self.write_json_primitive(&mut buf, &self[value_index]);
+            return Ok(buf);
         }
 
-        let mut buf = String::new();
-
         let container_type = self[value_index].value.container_type().unwrap();
         let depth_offset = self[value_index].depth;
         let pair_index = self[value_index].pair_index().unwrap();
@@ -455,7 +1061,8 @@ impl FlatJson {
                 write!(buf, "  ")?;
             }
             if let Some(ref key_range) = row.key_range {
-                write!(buf, "{}: ", &self.1[key_range.clone()])?;
+                self.write_json_key(&mut buf, key_range.clone());
+                write!(buf, ": ")?;
             }
             let mut trailing_comma = row.parent.is_some() && row.next_sibling.is_some();
             if let Some(container_type) = row.value.container_type() {
@@ -470,7 +1077,7 @@ impl FlatJson {
                         && self[row.pair_index().unwrap()].next_sibling.is_some();
                 }
             } else {
-                write!(buf, "{}", &self.1[row.range.clone()])?;
+                self.write_json_primitive(&mut buf, row);
             }
             if trailing_comma {
                 write!(buf, ",")?;
@@ -510,6 +1117,9 @@ pub struct Row {
     pub range: Range<usize>,
     pub key_range: Option<Range<usize>>,
     pub value: Value,
+    // Set on rows that are hidden because we're only showing the
+    // matches of a search (and their ancestors).
+    pub hidden: bool,
 }
 
 impl Row {
@@ -544,9 +1154,6 @@ impl Row {
     fn collapse(&mut self) {
         self.value.collapse()
     }
-    fn toggle_collapsed(&mut self) {
-        self.value.toggle_collapsed()
-    }
 
     pub fn first_child(&self) -> OptionIndex {
         self.value.first_child()
@@ -558,6 +1165,56 @@ impl Row {
         self.value.pair_index()
     }
 
+    // Update all the indexes of other rows that this row refers to.
+    fn shift_indexes(&mut self, shift: impl Fn(Index) -> Index) {
+        let shift_option_index = |index: OptionIndex| match index {
+            OptionIndex::Nil => OptionIndex::Nil,
+            OptionIndex::Index(i) => OptionIndex::Index(shift(i)),
+        };
+
+        self.parent = shift_option_index(self.parent);
+        self.prev_sibling = shift_option_index(self.prev_sibling);
+        self.next_sibling = shift_option_index(self.next_sibling);
+
+        match &mut self.value {
+            Value::OpenContainer {
+                first_child,
+                close_index,
+                ..
+            } => {
+                *first_child = shift(*first_child);
+                *close_index = shift(*close_index);
+            }
+            Value::CloseContainer {
+                last_child,
+                open_index,
+                ..
+            } => {
+                *last_child = shift(*last_child);
+                *open_index = shift(*open_index);
+            }
+            _ => {}
+        }
+    }
+
+    // In large-file mode, containers whose contents haven't been loaded
+    // yet are represented by an open and close row with no children.
+    pub fn is_unloaded(&self) -> bool {
+        match self.value {
+            Value::OpenContainer {
+                first_child,
+                close_index,
+                ..
+            } => first_child == close_index,
+            Value::CloseContainer {
+                last_child,
+                open_index,
+                ..
+            } => last_child == open_index,
+            _ => false,
+        }
+    }
+
     // The range of what the row represents on the screen. If the row is
     // a container, and it is collapsed, this includes the entire range
     // of the container, but if it is expanded, it just represents the
@@ -688,10 +1345,6 @@ impl Value {
         )
     }
 
-    fn toggle_collapsed(&mut self) {
-        self.set_collapsed(!self.is_collapsed())
-    }
-
     fn expand(&mut self) {
         self.set_collapsed(false)
     }
@@ -735,16 +1388,163 @@ impl Value {
     }
 }
 
-pub fn parse_top_level_json(json: String) -> Result<FlatJson, String> {
+impl std::fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathSegment::Key(key) | PathSegment::Token(key) => write!(f, "\"{key}\""),
+            PathSegment::Index(i) => write!(f, "[{i}]"),
+        }
+    }
+}
+
+// Parse a path to a node, either a path like the ones jless produces,
+// e.g., `.items[3].name` or `["items"][3]["name"]`, optionally preceded
+// by `input`, like in the status bar, or a JSON Pointer, e.g.,
+// `/items/3/name`.
+pub fn parse_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let path = path.trim();
+
+    if let Some(pointer) = path.strip_prefix('/') {
+        // JSON Pointer: "~1" is an escaped '/', and "~0" an escaped '~'.
+        return Ok(pointer
+            .split('/')
+            .map(|token| PathSegment::Token(token.replace("~1", "/").replace("~0", "~")))
+            .collect());
+    }
+
+    let mut rest = path;
+    if let Some(after_input) = rest.strip_prefix("input") {
+        if after_input.is_empty() || after_input.starts_with(['.', '[']) {
+            rest = after_input;
+        }
+    }
+
+    // A single "." refers to the root.
+    if rest == "." {
+        return Ok(vec![]);
+    }
+
+    let mut segments = vec![];
+
+    while !rest.is_empty() {
+        if let Some(after_dot) = rest.strip_prefix('.') {
+            // A leading ".[" is how jq paths start.
+            if after_dot.starts_with('[') && segments.is_empty() {
+                rest = after_dot;
+                continue;
+            }
+
+            let len = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+            if len == 0 {
+                return Err(format!("Invalid path: missing key after '.' in {path}"));
+            }
+            segments.push(PathSegment::Key(after_dot[..len].to_string()));
+            rest = &after_dot[len..];
+        } else if rest.starts_with("[]") {
+            return Err("Invalid path: paths must use array indexes, not []".to_string());
+        } else if rest.starts_with('[') {
+            let (segment, len) = parse_bracketed_path_segment(rest)
+                .ok_or_else(|| format!("Invalid path: unterminated [ in {path}"))?;
+            segments.push(segment);
+            rest = &rest[len..];
+        } else {
+            return Err(format!("Invalid path: expected '.' or '[' in {path}"));
+        }
+    }
+
+    Ok(segments)
+}
+
+// Parse a ["key"], [index], or, for non-string YAML keys, [key] path
+// segment, returning the segment and its length.
+fn parse_bracketed_path_segment(s: &str) -> Option<(PathSegment, usize)> {
+    let contents = &s[1..];
+
+    if contents.starts_with('"') {
+        let mut escaped = false;
+        for (i, ch) in contents.char_indices().skip(1) {
+            if escaped {
+                escaped = false;
+            } else if ch == '\\' {
+                escaped = true;
+            } else if ch == '"' {
+                if !contents[i + 1..].starts_with(']') {
+                    return None;
+                }
+                let key = contents[1..i].to_string();
+                return Some((PathSegment::Key(key), i + 3));
+            }
+        }
+        return None;
+    }
+
+    // Find the matching close bracket, since non-string YAML keys may
+    // contain brackets themselves.
+    let mut depth = 1;
+    for (i, ch) in contents.char_indices() {
+        match ch {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            let contents = &contents[..i];
+            let segment = match contents.parse::<usize>() {
+                Ok(i) => PathSegment::Index(i),
+                Err(_) => PathSegment::Key(contents.to_string()),
+            };
+            return Some((segment, i + 2));
+        }
+    }
+
+    None
+}
+
+pub fn parse_top_level_json(json: String) -> Result<FlatJson, ParseError> {
     let (rows, pretty, depth) = jsonparser::parse(json)?;
     Ok(FlatJson(rows, pretty, depth))
 }
 
-pub fn parse_top_level_yaml(yaml: String) -> Result<FlatJson, String> {
+pub fn parse_top_level_json5(json5: String) -> Result<FlatJson, ParseError> {
+    let (rows, pretty, depth) = jsonparser::parse_json5(json5)?;
+    Ok(FlatJson(rows, pretty, depth))
+}
+
+// Parse JSON (or JSON5), recovering from any errors in it, which are
+// also returned.
+pub fn parse_top_level_json_leniently(
+    json: String,
+    json5: bool,
+) -> Result<(FlatJson, Vec<RecoveredError>), ParseError> {
+    let (rows, pretty, depth, recovered_errors) = jsonparser::parse_leniently(json, json5)?;
+    Ok((FlatJson(rows, pretty, depth), recovered_errors))
+}
+
+pub fn parse_top_level_yaml(yaml: String) -> Result<FlatJson, ParseError> {
     let (rows, pretty, depth) = yamlparser::parse(yaml)?;
     Ok(FlatJson(rows, pretty, depth))
 }
 
+pub fn parse_top_level_toml(toml: String) -> Result<FlatJson, ParseError> {
+    let (rows, pretty, depth) = tomlparser::parse(toml)?;
+    Ok(FlatJson(rows, pretty, depth))
+}
+
+pub fn parse_top_level_csv(csv: String, options: CsvOptions) -> Result<FlatJson, ParseError> {
+    let (rows, pretty, depth) = csvparser::parse(csv, options)?;
+    Ok(FlatJson(rows, pretty, depth))
+}
+
+pub fn parse_top_level_msgpack(msgpack: &[u8]) -> Result<FlatJson, ParseError> {
+    let (rows, pretty, depth) = binaryparser::parse_msgpack(msgpack)?;
+    Ok(FlatJson(rows, pretty, depth))
+}
+
+pub fn parse_top_level_cbor(cbor: &[u8]) -> Result<FlatJson, ParseError> {
+    let (rows, pretty, depth) = binaryparser::parse_cbor(cbor)?;
+    Ok(FlatJson(rows, pretty, depth))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -849,6 +1649,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_append() {
+        let first = r#"{"a": [1, 2]} 3"#;
+        let second = r#"[{"b": null}, "c"] {}"#;
+
+        let mut fj = FlatJson::empty();
+        fj.append(parse_top_level_json(first.to_owned()).unwrap());
+        fj.append(parse_top_level_json(second.to_owned()).unwrap());
+
+        let expected = parse_top_level_json(format!("{first}\n{second}")).unwrap();
+
+        assert_eq!(fj.1, expected.1);
+        assert_eq!(fj.2, expected.2);
+        assert_eq!(fj.0.len(), expected.0.len());
+
+        for (i, (row, expected_row)) in fj.0.iter().zip(expected.0.iter()).enumerate() {
+            assert_eq!(row.parent, expected_row.parent, "parent at index {i}");
+            assert_eq!(
+                row.prev_sibling, expected_row.prev_sibling,
+                "prev_sibling at {i}"
+            );
+            assert_eq!(
+                row.next_sibling, expected_row.next_sibling,
+                "next_sibling at {i}"
+            );
+            assert_eq!(
+                row.index_in_parent, expected_row.index_in_parent,
+                "index at {i}"
+            );
+            assert_eq!(row.range, expected_row.range, "range at index {i}");
+            assert_eq!(
+                row.key_range, expected_row.key_range,
+                "key_range at index {i}"
+            );
+            assert_eq!(
+                row.first_child(),
+                expected_row.first_child(),
+                "first_child at {i}"
+            );
+            assert_eq!(
+                row.last_child(),
+                expected_row.last_child(),
+                "last_child at {i}"
+            );
+            assert_eq!(
+                row.pair_index(),
+                expected_row.pair_index(),
+                "pair_index at {i}"
+            );
+        }
+    }
+
     #[test]
     fn test_first_visible_ancestor() {
         let mut fj = parse_top_level_json(NESTED_OBJECT.to_owned()).unwrap();
@@ -918,11 +1770,32 @@ mod tests {
         assert_visited_items(&fj, vec![NIL]);
     }
 
+    #[test]
+    fn test_move_by_visible_rows_hidden() {
+        let mut fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+
+        fj.hide_rows_except(&[4, 8]);
+        assert_visited_rows(&fj, vec![2, 4, 5, 6, 8, 10, 12, NIL]);
+        assert_visited_items(&fj, vec![2, 4, 6, 8, NIL]);
+        assert_eq!(fj.last_visible_index(), 12);
+        assert_eq!(fj.last_visible_item(), 8);
+
+        fj.collapse(6);
+        assert_visited_rows(&fj, vec![2, 4, 5, 6, 12, NIL]);
+
+        // Showing a closing row shows the whole container.
+        fj.hide_rows_except(&[5]);
+        assert_visited_rows(&fj, vec![2, 5, 12, NIL]);
+
+        fj.unhide_all_rows();
+        assert_visited_rows(&fj, vec![1, 2, 3, 4, 5, 6, 11, 12, NIL]);
+    }
+
     fn assert_row_iter(
         movement_name: &'static str,
         fj: &FlatJson,
         start_index: Index,
-        expected_visited_rows: &Vec<usize>,
+        expected_visited_rows: &[usize],
         movement_fn: fn(&FlatJson, Index) -> OptionIndex,
     ) {
         let mut curr_index = start_index;
@@ -958,7 +1831,7 @@ mod tests {
         assert_prev_visited_rows(fj, start, &expected);
     }
 
-    fn assert_next_visited_rows(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_next_visited_rows(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter(
             "next_visible_row",
             fj,
@@ -968,7 +1841,7 @@ mod tests {
         );
     }
 
-    fn assert_prev_visited_rows(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_prev_visited_rows(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter(
             "prev_visible_row",
             fj,
@@ -994,11 +1867,11 @@ mod tests {
         assert_prev_visited_items(fj, start, &expected);
     }
 
-    fn assert_next_visited_items(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_next_visited_items(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter("next_item", fj, start_index, expected, FlatJson::next_item);
     }
 
-    fn assert_prev_visited_items(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_prev_visited_items(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter("prev_item", fj, start_index, expected, FlatJson::prev_item);
     }
 
@@ -1135,6 +2008,79 @@ mod tests {
         assert!(fj.build_path_to_node(Query, 1).is_err());
     }
 
+    #[test]
+    fn test_parse_path() {
+        use PathSegment::*;
+
+        let key = |k: &str| Key(k.to_string());
+        let token = |t: &str| Token(t.to_string());
+
+        let expected = vec![key("plain_key"), Index(1), key("nested")];
+        assert_eq!(expected, parse_path(".plain_key[1].nested").unwrap());
+        assert_eq!(
+            expected,
+            parse_path(r#"["plain_key"][1]["nested"]"#).unwrap()
+        );
+        assert_eq!(expected, parse_path("input.plain_key[1].nested").unwrap());
+        assert_eq!(expected, parse_path(r#".["plain_key"][1].nested"#).unwrap());
+
+        assert_eq!(
+            vec![Index(0), key("a b")],
+            parse_path(r#"[0]["a b"]"#).unwrap()
+        );
+        assert_eq!(vec![key("[1, [2]]")], parse_path("[[1, [2]]]").unwrap());
+        assert_eq!(Vec::<PathSegment>::new(), parse_path(".").unwrap());
+        assert_eq!(Vec::<PathSegment>::new(), parse_path("input").unwrap());
+
+        assert_eq!(
+            vec![token("items"), token("3"), token("a/b~c")],
+            parse_path("/items/3/a~1b~0c").unwrap()
+        );
+        assert_eq!(vec![token("")], parse_path("/").unwrap());
+
+        assert!(parse_path(".plain_key[].nested").is_err());
+        assert!(parse_path(".plain_key[1").is_err());
+        assert!(parse_path(r#"["unterminated]"#).is_err());
+        assert!(parse_path("plain_key").is_err());
+        assert!(parse_path(".a..b").is_err());
+    }
+
+    #[test]
+    fn test_child_at_path_segment() {
+        use PathSegment::*;
+
+        const JSON: &str = r#"{
+            "a": [1, 2, {"b": null}],
+            "quote\"d": true,
+            "": {}
+        }"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        let key = |k: &str| Key(k.to_string());
+        let token = |t: &str| Token(t.to_string());
+
+        assert_eq!(1, fj.child_at_path_segment(0, &key("a")).unwrap());
+        assert_eq!(3, fj.child_at_path_segment(1, &Index(1)).unwrap());
+        assert_eq!(4, fj.child_at_path_segment(1, &token("2")).unwrap());
+        assert_eq!(8, fj.child_at_path_segment(0, &key(r#"quote\"d"#)).unwrap());
+        assert_eq!(8, fj.child_at_path_segment(0, &token("quote\"d")).unwrap());
+        assert_eq!(9, fj.child_at_path_segment(0, &token("")).unwrap());
+
+        assert_eq!(
+            "No key \"c\"",
+            fj.child_at_path_segment(0, &key("c")).unwrap_err()
+        );
+        assert_eq!(
+            "No element at index 3",
+            fj.child_at_path_segment(1, &Index(3)).unwrap_err()
+        );
+        assert!(fj.child_at_path_segment(1, &token("01")).is_err());
+        assert!(fj.child_at_path_segment(1, &key("a")).is_err());
+        assert!(fj.child_at_path_segment(0, &Index(0)).is_err());
+        assert!(fj.child_at_path_segment(2, &Index(0)).is_err());
+        assert!(fj.child_at_path_segment(9, &key("a")).is_err());
+    }
+
     #[track_caller]
     fn assert_paths_to_node(fj: &FlatJson, index: Index, paths: (&str, &str, &str, &str)) {
         use PathType::*;
@@ -1239,4 +2185,142 @@ mod tests {
         const PRETTY_NESTED_OBJ: &str = "{\n  \"8\": false\n}\n";
         assert_eq!(PRETTY_NESTED_OBJ, fj.pretty_printed_value(7).unwrap());
     }
+
+    #[test]
+    fn test_yaml_string() {
+        const JSON: &str = r#"{
+            "a": [1, [2, 3], {"b": null, "c": {}}],
+            "d": {"e": "plain string", "f": "true", "g": "", "h": "multi\nline"},
+            "i": ["- dash", "x: y", "12", "1e3", " space"]
+        }
+        "second document"
+        []"#;
+        const YAML: &str = r#"a:
+  - 1
+  - - 2
+    - 3
+  - b: null
+    c: {}
+d:
+  e: plain string
+  f: "true"
+  g: ""
+  h: "multi\nline"
+i:
+  - "- dash"
+  - "x: y"
+  - "12"
+  - "1e3"
+  - " space"
+---
+second document
+---
+[]
+"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        assert_eq!(YAML, fj.yaml_string());
+
+        // Parsing the YAML should give us the same document.
+        let (_, pretty_printed, _) = yamlparser::parse(fj.yaml_string()).unwrap();
+        assert_eq!(fj.1, pretty_printed);
+    }
+
+    #[test]
+    fn test_yaml_string_of_non_string_keys() {
+        let (rows, pretty_printed, depth) =
+            yamlparser::parse("1: a\n[2, 3]: b\nnull: c\n".to_owned()).unwrap();
+        let fj = FlatJson(rows, pretty_printed, depth);
+        assert_eq!("1: a\n[2, 3]: b\nnull: c\n", fj.yaml_string());
+    }
+
+    #[test]
+    fn test_yaml_string_of_multiple_documents() {
+        const YAML: &str = "a: [1, {}]\n---\n- null\n- []\n---\nplain\n---\n\"1\"\n";
+        let (rows, pretty_printed, depth) = yamlparser::parse(YAML.to_owned()).unwrap();
+        let fj = FlatJson(rows, pretty_printed, depth);
+
+        assert_eq!(
+            fj.yaml_string(),
+            "a:\n  - 1\n  - {}\n---\n- null\n- []\n---\nplain\n---\n\"1\"\n"
+        );
+
+        // Appended files are written as separate documents too.
+        let mut appended = FlatJson::empty();
+        appended.append(parse_top_level_json("{\"a\": 1}".to_owned()).unwrap());
+        appended.append(parse_top_level_json("[2]".to_owned()).unwrap());
+        assert_eq!(appended.yaml_string(), "a: 1\n---\n- 2\n");
+    }
+
+    #[test]
+    fn test_ndjson() {
+        const JSON: &str = r#"[{"a": [1, {}]}, [], "x"] 2 {"b": null} [[3]]"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        // The elements of top-level arrays are written one per line, but
+        // nested arrays are kept whole.
+        assert_eq!(
+            fj.formatted(OutputFormat::Ndjson),
+            "{\"a\":[1,{}]}\n[]\n\"x\"\n2\n{\"b\":null}\n[3]\n"
+        );
+        assert_eq!(fj.format_values(&[2], OutputFormat::Ndjson), "1\n{}\n");
+    }
+
+    #[test]
+    fn test_format_values() {
+        const JSON: &str = r#"{"a": [1, 2], "b": {"c": "x"}} [] [3]"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        assert_eq!(
+            fj.formatted(OutputFormat::Compact),
+            "{\"a\":[1,2],\"b\":{\"c\":\"x\"}}\n[]\n[3]\n",
+        );
+        // Arrays are split up, so the empty array isn't written at all.
+        assert_eq!(
+            fj.formatted(OutputFormat::Ndjson),
+            "{\"a\":[1,2],\"b\":{\"c\":\"x\"}}\n3\n",
+        );
+        assert_eq!(fj.formatted(OutputFormat::Json), fj.pretty_printed());
+
+        // Keys of the values themselves aren't written, and closing rows
+        // represent their containers.
+        assert_eq!(
+            fj.format_values(&[1, 7], OutputFormat::Compact),
+            "[1,2]\n{\"c\":\"x\"}\n"
+        );
+        assert_eq!(fj.format_values(&[1], OutputFormat::Ndjson), "1\n2\n");
+        assert_eq!(fj.format_values(&[6, 2], OutputFormat::Yaml), "x\n---\n1\n");
+        assert_eq!(fj.format_values(&[6], OutputFormat::Json), "\"x\"\n");
+
+        // Strings that YAML would interpret as something else are quoted.
+        let fj = parse_top_level_json(r##"["<<", "n", "#", "a#", "\\"]"##.to_owned()).unwrap();
+        assert_eq!(
+            fj.formatted(OutputFormat::Yaml),
+            "- \"<<\"\n- \"n\"\n- \"#\"\n- a#\n- \\\n"
+        );
+
+        // Numbers that JSON doesn't support are written as strings.
+        let fj = parse_top_level_json5("[Infinity, -Infinity, NaN, 1]".to_owned()).unwrap();
+        assert_eq!(
+            fj.formatted(OutputFormat::Compact),
+            "[\"Infinity\",\"-Infinity\",\"NaN\",1]\n"
+        );
+        assert_eq!(
+            fj.formatted(OutputFormat::Json),
+            "[\n  \"Infinity\",\n  \"-Infinity\",\n  \"NaN\",\n  1\n]\n"
+        );
+        assert_eq!(fj.format_values(&[3], OutputFormat::Json), "\"NaN\"\n");
+        assert_eq!(
+            fj.formatted(OutputFormat::Yaml),
+            "- .inf\n- -.inf\n- .nan\n- 1\n"
+        );
+
+        // Non-string keys become strings in JSON.
+        let (rows, pretty_printed, depth) =
+            yamlparser::parse("1: a\n[2, 3]: b\n".to_owned()).unwrap();
+        let fj = FlatJson(rows, pretty_printed, depth);
+        assert_eq!(
+            fj.formatted(OutputFormat::Compact),
+            "{\"1\":\"a\",\"[2, 3]\":\"b\"}\n"
+        );
+    }
 }
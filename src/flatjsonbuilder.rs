@@ -0,0 +1,269 @@
+use std::ops::Range;
+
+use crate::flatjson::{ContainerType, FlatJson, Index, OptionIndex, Row, Value};
+
+// Builds a new document, either from values decoded by the parsers for
+// non-JSON formats, or by copying rows from existing documents, along
+// with their text in the pretty printed version of the documents. Copying
+// keeps the text of values as is (even if it isn't valid JSON, e.g.,
+// non-string YAML keys), and keeps collapsed containers collapsed.
+//
+// Primitive values are added by calling push_primitive, and containers by
+// calling open_container, adding each of their children, and then calling
+// finish_container. Each child is added by calling start_child, to write its key, then
+// adding it, and then calling finish_child to link it to its siblings.
+// Siblings are tracked by the caller as the indexes of the first and last
+// sibling added so far.
+#[derive(Default)]
+pub struct FlatJsonBuilder {
+    pub rows: Vec<Row>,
+    pub pretty_printed: String,
+    parents: Vec<Index>,
+    max_depth: usize,
+}
+
+impl FlatJsonBuilder {
+    pub fn build(self) -> FlatJson {
+        FlatJson(self.rows, self.pretty_printed, self.max_depth)
+    }
+
+    // The index that the next row that's created will have.
+    pub fn next_index(&self) -> Index {
+        self.rows.len()
+    }
+
+    // The opening row of the container we're currently copying children
+    // into, if any.
+    pub fn current_parent(&self) -> Option<Index> {
+        self.parents.last().copied()
+    }
+
+    // Write the separator and key before a child, returning the range
+    // of the key, if present.
+    pub fn start_child(
+        &mut self,
+        key: Option<&str>,
+        container_type: Option<ContainerType>,
+        siblings: &Option<(Index, Index)>,
+    ) -> Option<Range<usize>> {
+        match (container_type, siblings) {
+            // Top-level values are separated by newlines.
+            (None, Some(_)) => self.pretty_printed.push('\n'),
+            (None, None) => {}
+            (Some(_), Some(_)) => self.pretty_printed.push_str(", "),
+            // Add space inside objects.
+            (Some(ContainerType::Object), None) => self.pretty_printed.push(' '),
+            (Some(ContainerType::Array), None) => {}
+        }
+
+        key.map(|key| {
+            let key_start = self.pretty_printed.len();
+            self.pretty_printed.push_str(key);
+            let key_end = self.pretty_printed.len();
+            self.pretty_printed.push_str(": ");
+            key_start..key_end
+        })
+    }
+
+    // Set the key range of a child that was just copied, and link it to
+    // its previous sibling.
+    pub fn finish_child(
+        &mut self,
+        index: Index,
+        key_range: Option<Range<usize>>,
+        siblings: &mut Option<(Index, Index)>,
+    ) {
+        self.rows[index].key_range = key_range;
+
+        match siblings {
+            None => {
+                *siblings = Some((index, index));
+            }
+            Some((_, last)) => {
+                self.rows[index].prev_sibling = OptionIndex::Index(*last);
+                self.rows[index].index_in_parent = self.rows[*last].index_in_parent + 1;
+                self.rows[*last].next_sibling = OptionIndex::Index(index);
+                *last = index;
+            }
+        }
+    }
+
+    // Copy a node, along with all of its descendants.
+    pub fn copy_node(&mut self, source: &FlatJson, index: Index) {
+        let Some(open_index) = self.copy_row(source, index) else {
+            return;
+        };
+
+        let (container_type, collapsed) = container_info(&source[index].value);
+        let mut siblings = None;
+
+        let mut child = source[index].first_child();
+        while let OptionIndex::Index(child_index) = child {
+            let key = source[child_index]
+                .key_range
+                .clone()
+                .map(|key_range| &source.1[key_range]);
+            let key_range = self.start_child(key, Some(container_type), &siblings);
+            let new_index = self.next_index();
+            self.copy_node(source, child_index);
+            self.finish_child(new_index, key_range, &mut siblings);
+            child = source[child_index].next_sibling;
+        }
+
+        self.finish_container(open_index, container_type, collapsed, siblings);
+    }
+
+    // Copy a single row. Primitive values are copied entirely, but for
+    // containers, this just creates the opening row, whose index is
+    // returned, and the caller must copy the children and then call
+    // finish_container.
+    pub fn copy_row(&mut self, source: &FlatJson, index: Index) -> Option<Index> {
+        let row = &source[index];
+
+        let value = match &row.value {
+            Value::Null => Value::Null,
+            Value::Boolean => Value::Boolean,
+            Value::Number => Value::Number,
+            Value::String => Value::String,
+            Value::EmptyObject => Value::EmptyObject,
+            Value::EmptyArray => Value::EmptyArray,
+            Value::OpenContainer {
+                container_type,
+                collapsed,
+                ..
+            } => {
+                let open_index = self.open_container(*container_type);
+                if let Value::OpenContainer {
+                    collapsed: ref mut new_collapsed,
+                    ..
+                } = self.rows[open_index].value
+                {
+                    *new_collapsed = *collapsed;
+                }
+                return Some(open_index);
+            }
+            Value::CloseContainer { .. } => panic!("Can't copy the closing of a container"),
+        };
+
+        self.push_primitive(value, &source.1[row.range.clone()]);
+        None
+    }
+
+    // Create the row for a primitive value, with the given text.
+    pub fn push_primitive(&mut self, value: Value, text: &str) -> Index {
+        let index = self.create_row(value);
+        self.pretty_printed.push_str(text);
+        self.rows[index].range.end = self.pretty_printed.len();
+        index
+    }
+
+    // Create the opening row of a container; the caller must add its
+    // children and then call finish_container.
+    pub fn open_container(&mut self, container_type: ContainerType) -> Index {
+        let open_index = self.create_row(Value::OpenContainer {
+            container_type,
+            collapsed: false,
+            // To be set by finish_container.
+            first_child: 0,
+            close_index: 0,
+        });
+        self.pretty_printed.push_str(container_type.open_str());
+        self.parents.push(open_index);
+        open_index
+    }
+
+    // Create the closing row of a container, once all of its children
+    // have been copied. If it doesn't have any children, it becomes an
+    // empty object or array instead.
+    pub fn finish_container(
+        &mut self,
+        open_index: Index,
+        container_type: ContainerType,
+        collapsed: bool,
+        children: Option<(Index, Index)>,
+    ) {
+        self.parents.pop();
+
+        let Some((first, last)) = children else {
+            let (value, empty_str) = match container_type {
+                ContainerType::Object => (Value::EmptyObject, "{}"),
+                ContainerType::Array => (Value::EmptyArray, "[]"),
+            };
+            let range_start = self.rows[open_index].range.start;
+            self.pretty_printed.truncate(range_start);
+            self.pretty_printed.push_str(empty_str);
+            self.rows[open_index].value = value;
+            self.rows[open_index].range.end = self.pretty_printed.len();
+            return;
+        };
+
+        if let ContainerType::Object = container_type {
+            self.pretty_printed.push(' ');
+        }
+
+        let close_index = self.create_row(Value::CloseContainer {
+            container_type,
+            collapsed,
+            last_child: last,
+            open_index,
+        });
+        self.pretty_printed.push_str(container_type.close_str());
+        self.rows[open_index].range.end = self.pretty_printed.len();
+
+        match self.rows[open_index].value {
+            Value::OpenContainer {
+                ref mut first_child,
+                close_index: ref mut open_close_index,
+                ..
+            } => {
+                *first_child = first;
+                *open_close_index = close_index;
+            }
+            _ => panic!("Must be a container!"),
+        }
+    }
+
+    fn create_row(&mut self, value: Value) -> Index {
+        let index = self.rows.len();
+
+        let parent = match self.parents.last() {
+            None => OptionIndex::Nil,
+            Some(row_index) => OptionIndex::Index(*row_index),
+        };
+
+        let range_start = self.pretty_printed.len();
+        self.max_depth = self.max_depth.max(self.parents.len());
+
+        self.rows.push(Row {
+            parent,
+            prev_sibling: OptionIndex::Nil,
+            next_sibling: OptionIndex::Nil,
+            depth: self.parents.len(),
+            index_in_parent: 0,
+            range: range_start..range_start + 1,
+            key_range: None,
+            value,
+            hidden: false,
+        });
+
+        index
+    }
+}
+
+// The type of a container, and whether it's collapsed, given either its
+// opening or closing row.
+pub fn container_info(value: &Value) -> (ContainerType, bool) {
+    match value {
+        Value::OpenContainer {
+            container_type,
+            collapsed,
+            ..
+        }
+        | Value::CloseContainer {
+            container_type,
+            collapsed,
+            ..
+        } => (*container_type, *collapsed),
+        _ => panic!("Must be a container!"),
+    }
+}
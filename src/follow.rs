@@ -0,0 +1,126 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+
+// How a followed file has changed since we last checked it.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FileChange {
+    // Data was appended to the end of the file.
+    Appended(Vec<u8>),
+    // The file was truncated; contains the entire new contents of the file.
+    Truncated(Vec<u8>),
+    // The file was replaced by a new file at the same path, e.g., because
+    // of log rotation; contains the entire contents of the new file.
+    Replaced(Vec<u8>),
+}
+
+// A file that we watch for new data, like `tail -F`. We don't rely on
+// any platform specific file system notifications; the file is just
+// checked periodically.
+pub struct FollowedFile {
+    path: PathBuf,
+    file: File,
+    // Used to identify the file we have open, so we can tell when the
+    // path starts referring to a different file.
+    dev: u64,
+    ino: u64,
+    // How much of the file we've read so far.
+    position: u64,
+}
+
+impl FollowedFile {
+    // Start following a file, assuming that its first `position` bytes
+    // have already been read.
+    pub fn open(path: PathBuf, position: u64) -> io::Result<FollowedFile> {
+        let file = File::open(&path)?;
+        let metadata = file.metadata()?;
+
+        Ok(FollowedFile {
+            path,
+            file,
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+            position,
+        })
+    }
+
+    pub fn check_for_changes(&mut self) -> io::Result<Option<FileChange>> {
+        match std::fs::metadata(&self.path) {
+            Ok(metadata) => {
+                if metadata.dev() != self.dev || metadata.ino() != self.ino {
+                    *self = FollowedFile::open(self.path.clone(), 0)?;
+                    return Ok(Some(FileChange::Replaced(self.read_to_end()?)));
+                }
+            }
+            // The file may be temporarily missing while it is being rotated;
+            // we'll keep checking for it to show up again.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        }
+
+        let len = self.file.metadata()?.len();
+
+        if len < self.position {
+            self.position = 0;
+            Ok(Some(FileChange::Truncated(self.read_to_end()?)))
+        } else if len > self.position {
+            Ok(Some(FileChange::Appended(self.read_to_end()?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn read_to_end(&mut self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.file.seek(SeekFrom::Start(self.position))?;
+        self.position += self.file.read_to_end(&mut buf)? as u64;
+        Ok(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_check_for_changes() {
+        let dir = std::env::temp_dir().join(format!("jless-follow-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log.json");
+
+        std::fs::write(&path, "{}\n").unwrap();
+        let mut followed = FollowedFile::open(path.clone(), 3).unwrap();
+        assert_eq!(followed.check_for_changes().unwrap(), None);
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"[1]\n").unwrap();
+        assert_eq!(
+            followed.check_for_changes().unwrap(),
+            Some(FileChange::Appended(b"[1]\n".to_vec()))
+        );
+        assert_eq!(followed.check_for_changes().unwrap(), None);
+
+        file.set_len(0).unwrap();
+        file.write_all(b"2\n").unwrap();
+        assert_eq!(
+            followed.check_for_changes().unwrap(),
+            Some(FileChange::Truncated(b"2\n".to_vec()))
+        );
+
+        std::fs::rename(&path, dir.join("log.json.1")).unwrap();
+        assert_eq!(followed.check_for_changes().unwrap(), None);
+
+        std::fs::write(&path, "3\n").unwrap();
+        assert_eq!(
+            followed.check_for_changes().unwrap(),
+            Some(FileChange::Replaced(b"3\n".to_vec()))
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,10 +1,11 @@
 use std::fmt;
 use std::iter::Peekable;
 use std::ops::Range;
+use std::sync::RwLock;
 
 use crate::search::MatchRangeIter;
 use crate::terminal;
-use crate::terminal::{Style, Terminal};
+use crate::terminal::{Color, Style, Terminal};
 use crate::truncatedstrview::TruncatedStrView;
 
 // This module is responsible for highlighting text in the
@@ -99,6 +100,27 @@ pub const CURRENT_LINE_NUMBER: Style = Style {
 
 pub const PREVIEW_STYLES: (&Style, &Style) = (&DIMMED_STYLE, &GRAY_INVERTED_STYLE);
 
+pub const ADDED_STYLE: Style = Style {
+    fg: terminal::GREEN,
+    ..Style::default()
+};
+
+pub const REMOVED_STYLE: Style = Style {
+    fg: terminal::RED,
+    ..Style::default()
+};
+
+pub const CHANGED_STYLE: Style = Style {
+    fg: terminal::YELLOW,
+    ..Style::default()
+};
+
+pub const RECOVERED_ERROR_STYLE: Style = Style {
+    fg: terminal::RED,
+    bold: true,
+    ..Style::default()
+};
+
 pub const BLUE_STYLE: Style = Style {
     fg: terminal::LIGHT_BLUE,
     ..Style::default()
@@ -111,6 +133,56 @@ pub const INVERTED_BOLD_BLUE_STYLE: Style = Style {
     ..Style::default()
 };
 
+// The colors of object keys and of the different types of values, which
+// can be changed in the config file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Colors {
+    pub key: Color,
+    // The background of the focused key.
+    pub focused_key: Color,
+    pub string: Color,
+    pub number: Color,
+    pub boolean: Color,
+    pub null: Color,
+}
+
+impl Colors {
+    pub const fn default() -> Colors {
+        Colors {
+            key: BLUE_STYLE.fg,
+            focused_key: INVERTED_BOLD_BLUE_STYLE.bg,
+            string: terminal::GREEN,
+            number: terminal::MAGENTA,
+            boolean: terminal::YELLOW,
+            null: terminal::LIGHT_BLACK,
+        }
+    }
+
+    pub fn key_style(&self) -> Style {
+        Style {
+            fg: self.key,
+            ..Style::default()
+        }
+    }
+
+    pub fn focused_key_style(&self) -> Style {
+        Style {
+            bg: self.focused_key,
+            ..INVERTED_BOLD_BLUE_STYLE
+        }
+    }
+}
+
+static COLORS: RwLock<Colors> = RwLock::new(Colors::default());
+
+pub fn colors() -> Colors {
+    *COLORS.read().unwrap()
+}
+
+pub fn set_colors(colors: Colors) {
+    *COLORS.write().unwrap() = colors;
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn highlight_truncated_str_view(
     out: &mut dyn Terminal,
@@ -2,14 +2,19 @@ use signal_hook::consts::SIGWINCH;
 use signal_hook::low_level::pipe;
 use termion::event::{parse_event, Event, Key, MouseEvent};
 
+use std::fs::File;
 use std::io;
 use std::io::{stdin, Read, Stdin};
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::os::unix::net::UnixStream;
+use std::time::Duration;
 
 const POLL_INFINITE_TIMEOUT: i32 = -1;
 const SIGWINCH_PIPE_INDEX: usize = 0;
+const TTY_INDEX: usize = 1;
+const DATA_INDEX: usize = 2;
 const BUFFER_SIZE: usize = 1024;
+const DATA_BUFFER_SIZE: usize = 64 * 1024;
 
 const ESCAPE: u8 = 0o33;
 
@@ -32,17 +37,30 @@ pub fn remap_dev_tty_to_stdin() {
     }
 }
 
-pub fn get_input() -> impl Iterator<Item = io::Result<TuiEvent>> {
+// Duplicate the file descriptor for STDIN, so that we can continue to
+// read input data from it after we remap /dev/tty to STDIN.
+pub fn take_stdin_as_data_source() -> io::Result<File> {
+    let fd = unsafe { libc::dup(libc::STDIN_FILENO) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+// Returns an iterator over input events. If a data source is provided,
+// it will be read from as data becomes available, and its contents will
+// be returned via DataEvents, followed by a single DataEof event.
+pub fn get_input(data_source: Option<File>) -> TuiInput {
     let (sigwinch_read, sigwinch_write) = UnixStream::pair().unwrap();
     // NOTE: This overrides the SIGWINCH handler registered by rustyline.
     // We should maybe get a reference to the existing signal handler
     // and call it when appropriate, but it seems to only be used to handle
     // line wrapping, and it seems to work fine without it.
     pipe::register(SIGWINCH, sigwinch_write).unwrap();
-    TuiInput::new(stdin(), sigwinch_read)
+    TuiInput::new(stdin(), sigwinch_read, data_source)
 }
 
-fn read_and_retry_on_interrupt(input: &mut Stdin, buf: &mut [u8]) -> io::Result<usize> {
+fn read_and_retry_on_interrupt<R: Read>(input: &mut R, buf: &mut [u8]) -> io::Result<usize> {
     loop {
         match input.read(buf) {
             res @ Ok(_) => {
@@ -150,18 +168,22 @@ impl<const N: usize> Iterator for BufferedInput<N> {
     }
 }
 
-struct TuiInput {
-    poll_fds: [libc::pollfd; 2],
+pub struct TuiInput {
+    poll_fds: [libc::pollfd; 3],
     sigwinch_pipe: UnixStream,
     buffered_input: BufferedInput<BUFFER_SIZE>,
+    data_source: Option<File>,
+    poll_timeout: i32,
 }
 
 impl TuiInput {
-    fn new(input: Stdin, sigwinch_pipe: UnixStream) -> TuiInput {
+    fn new(input: Stdin, sigwinch_pipe: UnixStream, data_source: Option<File>) -> TuiInput {
         let sigwinch_fd = sigwinch_pipe.as_raw_fd();
         let stdin_fd = input.as_raw_fd();
+        // poll(2) ignores entries with negative file descriptors.
+        let data_fd = data_source.as_ref().map_or(-1, |file| file.as_raw_fd());
 
-        let poll_fds: [libc::pollfd; 2] = [
+        let poll_fds: [libc::pollfd; 3] = [
             libc::pollfd {
                 fd: sigwinch_fd,
                 events: libc::POLLIN,
@@ -172,15 +194,50 @@ impl TuiInput {
                 events: libc::POLLIN,
                 revents: 0,
             },
+            libc::pollfd {
+                fd: data_fd,
+                events: libc::POLLIN,
+                revents: 0,
+            },
         ];
 
         TuiInput {
             poll_fds,
             sigwinch_pipe,
             buffered_input: BufferedInput::new(input),
+            data_source,
+            poll_timeout: POLL_INFINITE_TIMEOUT,
         }
     }
 
+    // When set, a TickEvent will be returned whenever no other events
+    // have happened for the given interval.
+    pub fn set_tick_interval(&mut self, interval: Option<Duration>) {
+        self.poll_timeout = match interval {
+            Some(interval) => interval.as_millis().min(i32::MAX as u128) as i32,
+            None => POLL_INFINITE_TIMEOUT,
+        };
+    }
+
+    fn read_data(&mut self) -> io::Result<TuiEvent> {
+        let Some(data_source) = &mut self.data_source else {
+            return Ok(TuiEvent::DataEof);
+        };
+
+        let mut buf = vec![0; DATA_BUFFER_SIZE];
+        let bytes_read = read_and_retry_on_interrupt(data_source, &mut buf)?;
+
+        if bytes_read == 0 {
+            // Stop polling the data source once we hit EOF.
+            self.poll_fds[DATA_INDEX].fd = -1;
+            self.data_source = None;
+            return Ok(TuiEvent::DataEof);
+        }
+
+        buf.truncate(bytes_read);
+        Ok(TuiEvent::DataEvent(buf))
+    }
+
     fn get_event_from_buffered_input(&mut self) -> Option<io::Result<TuiEvent>> {
         if !self.buffered_input.has_buffered_data() {
             if let Some(err) = self.buffered_input.read_more_if_needed() {
@@ -216,7 +273,8 @@ impl Iterator for TuiInput {
         let poll_res: Option<io::Error>;
 
         loop {
-            match unsafe { libc::poll(self.poll_fds.as_mut_ptr(), 2, POLL_INFINITE_TIMEOUT) } {
+            let num_fds = self.poll_fds.len() as libc::nfds_t;
+            match unsafe { libc::poll(self.poll_fds.as_mut_ptr(), num_fds, self.poll_timeout) } {
                 -1 => {
                     let err = io::Error::last_os_error();
                     if err.kind() != io::ErrorKind::Interrupted {
@@ -225,6 +283,7 @@ impl Iterator for TuiInput {
                     }
                     // Try poll again.
                 }
+                0 => return Some(Ok(TuiEvent::TickEvent)),
                 _ => {
                     poll_res = None;
                     break;
@@ -243,6 +302,14 @@ impl Iterator for TuiInput {
             return Some(Ok(TuiEvent::WinChEvent));
         }
 
+        // Prioritize user input over reading more data so that we stay
+        // responsive while loading a lot of input.
+        let tty_revents = self.poll_fds[TTY_INDEX].revents;
+        let data_revents = self.poll_fds[DATA_INDEX].revents;
+        if tty_revents == 0 && data_revents & (libc::POLLIN | libc::POLLHUP | libc::POLLERR) != 0 {
+            return Some(self.read_data());
+        }
+
         self.get_event_from_buffered_input()
     }
 }
@@ -252,5 +319,8 @@ pub enum TuiEvent {
     WinChEvent,
     KeyEvent(Key),
     MouseEvent(MouseEvent),
+    DataEvent(Vec<u8>),
+    DataEof,
+    TickEvent,
     Unknown(Vec<u8>),
 }
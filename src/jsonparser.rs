@@ -2,30 +2,97 @@ use logos::{Lexer, Logos};
 
 use crate::flatjson::{ContainerType, Index, OptionIndex, Row, Value};
 use crate::jsontokenizer::JsonToken;
+use crate::parseerror::{Locator, ParseError};
+
+const EXPECTED_VALUE: &str = "a value";
+const EXPECTED_KEY: &str = "a string key";
 
 struct JsonParser<'a> {
     tokenizer: Lexer<'a, JsonToken>,
+    // Finds the line and column of errors.
+    locator: Locator<'a>,
     parents: Vec<Index>,
     rows: Vec<Row>,
     pretty_printed: String,
     max_depth: usize,
 
     peeked_token: Option<Option<JsonToken>>,
+
+    // Whether to accept JSON5 extensions: comments, unquoted object keys,
+    // single quoted strings, and additional number formats. These are all
+    // converted to regular JSON in the pretty printed output (except for
+    // Infinity and NaN, which have no JSON equivalent, and are written as
+    // strings when the document is written as JSON).
+    json5: bool,
+
+    // Whether to recover from errors, rather than failing, by closing
+    // any containers that are still open and skipping ahead to the next
+    // top-level value.
+    lenient: bool,
+    recovered_errors: Vec<RecoveredError>,
+    // Set while we're closing the containers that are still open after
+    // an error, until we get back to the top level.
+    recovering: bool,
+}
+
+// An error in the input that was recovered from when parsing leniently.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RecoveredError {
+    // The container that was closed early because of the error, or, if
+    // the error was between top-level values, the following top-level
+    // value (or the preceding one, if there isn't one after it).
+    pub row: Index,
+    pub error: ParseError,
 }
 
-pub fn parse(json: String) -> Result<(Vec<Row>, String, usize), String> {
+pub fn parse(json: String) -> Result<(Vec<Row>, String, usize), ParseError> {
+    let (rows, pretty, depth, _) = parse_impl(json, false, false)?;
+    Ok((rows, pretty, depth))
+}
+
+pub fn parse_json5(json5: String) -> Result<(Vec<Row>, String, usize), ParseError> {
+    let (rows, pretty, depth, _) = parse_impl(json5, true, false)?;
+    Ok((rows, pretty, depth))
+}
+
+// Parse as much of the input as possible, returning the errors that were
+// recovered from, sorted by row. This only fails if there isn't a single
+// value that could be parsed.
+pub fn parse_leniently(
+    json: String,
+    json5: bool,
+) -> Result<(Vec<Row>, String, usize, Vec<RecoveredError>), ParseError> {
+    parse_impl(json, json5, true)
+}
+
+fn parse_impl(
+    json: String,
+    json5: bool,
+    lenient: bool,
+) -> Result<(Vec<Row>, String, usize, Vec<RecoveredError>), ParseError> {
     let mut parser = JsonParser {
         tokenizer: JsonToken::lexer(&json),
+        locator: Locator::new(&json),
         parents: vec![],
         rows: vec![],
         pretty_printed: String::new(),
         max_depth: 0,
         peeked_token: None,
+        json5,
+        lenient,
+        recovered_errors: vec![],
+        recovering: false,
     };
 
     parser.parse_top_level_json()?;
-
-    Ok((parser.rows, parser.pretty_printed, parser.max_depth))
+    parser.recovered_errors.sort_by_key(|error| error.row);
+
+    Ok((
+        parser.rows,
+        parser.pretty_printed,
+        parser.max_depth,
+        parser.recovered_errors,
+    ))
 }
 
 impl<'a> JsonParser<'a> {
@@ -54,94 +121,186 @@ impl<'a> JsonParser<'a> {
         self.peeked_token.unwrap()
     }
 
-    fn peek_token(&mut self) -> Result<JsonToken, String> {
-        self.peek_token_or_eof()
-            .ok_or_else(|| "Unexpected EOF".to_string())
+    // Peek at the next token, failing at the end of the input, in which
+    // case `expected` describes what we were looking for.
+    fn peek_token(&mut self, expected: &str) -> Result<JsonToken, ParseError> {
+        match self.peek_token_or_eof() {
+            Some(token) => Ok(token),
+            None => Err(self.unexpected_token_error(expected)),
+        }
     }
 
-    fn unexpected_token(&mut self) -> Result<usize, String> {
-        Err(format!("Unexpected token: {:?}", self.peek_token()))
+    fn unexpected_token(&mut self, expected: &str) -> Result<usize, ParseError> {
+        Err(self.unexpected_token_error(expected))
     }
 
-    fn consume_whitespace(&mut self) {
-        while let Some(JsonToken::Whitespace | JsonToken::Newline) = self.peek_token_or_eof() {
+    fn unexpected_token_error(&mut self, expected: &str) -> ParseError {
+        match self.peek_token_or_eof() {
+            Some(token) => {
+                let found = describe_token(token, self.tokenizer.slice());
+                let location = self.locator.locate(self.tokenizer.span().start);
+                ParseError::unexpected_at(location, &found, expected)
+            }
+            None => {
+                let location = self.locator.locate(self.tokenizer.source().len());
+                ParseError::unexpected_at(location, "EOF", expected)
+            }
+        }
+    }
+
+    // An error at the last token that was peeked at (or consumed).
+    fn error_at_token(&mut self, message: String) -> ParseError {
+        let location = self.locator.locate(self.tokenizer.span().start);
+        ParseError::at_location(location, message)
+    }
+
+    // Handle an error while parsing the children of a container. When
+    // parsing leniently, the error is recorded, and the container, and
+    // all of its ancestors, will be closed, as if the input ended here.
+    // Otherwise the error is returned.
+    fn recover(&mut self, error: ParseError, container: Index) -> Result<(), ParseError> {
+        if !self.lenient {
+            return Err(error);
+        }
+
+        self.recovered_errors.push(RecoveredError {
+            row: container,
+            error,
+        });
+        self.recovering = true;
+        Ok(())
+    }
+
+    // After an error, skip the rest of the line, along with any following
+    // lines that don't start with an object or an array, which are likely
+    // to be the rest of a value that we couldn't parse, rather than the
+    // next newline-delimited value.
+    fn skip_to_next_record(&mut self) {
+        let mut at_line_start = false;
+        loop {
+            match self.peek_token_or_eof() {
+                None => break,
+                Some(JsonToken::Newline) => at_line_start = true,
+                Some(JsonToken::OpenCurly | JsonToken::OpenSquare) if at_line_start => break,
+                Some(_) => at_line_start = false,
+            }
             self.advance();
         }
     }
 
-    fn parse_top_level_json(&mut self) -> Result<(), String> {
-        self.consume_whitespace();
-        let mut prev_top_level = self.parse_elem()?;
+    fn consume_whitespace(&mut self) {
+        loop {
+            match self.peek_token_or_eof() {
+                Some(JsonToken::Whitespace | JsonToken::Newline) => self.advance(),
+                Some(JsonToken::Comment) if self.json5 => self.advance(),
+                _ => break,
+            }
+        }
+    }
+
+    fn parse_top_level_json(&mut self) -> Result<(), ParseError> {
+        let mut prev_top_level = None;
         let mut num_child = 0;
+        // Errors between top-level values, which will be attached to the
+        // next value, when parsing leniently.
+        let mut pending_errors = vec![];
 
         loop {
             self.consume_whitespace();
 
-            if self.peek_token_or_eof().is_none() {
+            // There has to be at least one value.
+            if self.peek_token_or_eof().is_none()
+                && (prev_top_level.is_some() || !pending_errors.is_empty())
+            {
                 break;
             }
 
-            self.pretty_printed.push('\n');
-            let next_top_level = self.parse_elem()?;
-            num_child += 1;
+            let separator_start = self.pretty_printed.len();
+            if prev_top_level.is_some() {
+                self.pretty_printed.push('\n');
+            }
+
+            let next_top_level = match self.parse_elem() {
+                Ok(next_top_level) => next_top_level,
+                Err(err) if self.lenient => {
+                    self.pretty_printed.truncate(separator_start);
+                    pending_errors.push(err);
+                    self.skip_to_next_record();
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+
+            for error in pending_errors.drain(..) {
+                self.recovered_errors.push(RecoveredError {
+                    row: next_top_level,
+                    error,
+                });
+            }
 
-            self.rows[next_top_level].prev_sibling = OptionIndex::Index(prev_top_level);
-            self.rows[next_top_level].index_in_parent = num_child;
-            self.rows[prev_top_level].next_sibling = OptionIndex::Index(next_top_level);
+            if let Some(prev_top_level) = prev_top_level {
+                num_child += 1;
+                self.rows[next_top_level].prev_sibling = OptionIndex::Index(prev_top_level);
+                self.rows[next_top_level].index_in_parent = num_child;
+                self.rows[prev_top_level].next_sibling = OptionIndex::Index(next_top_level);
+            }
+            prev_top_level = Some(next_top_level);
 
-            prev_top_level = next_top_level;
+            if self.recovering {
+                self.recovering = false;
+                self.skip_to_next_record();
+            }
         }
 
-        Ok(())
+        match prev_top_level {
+            Some(prev_top_level) => {
+                for error in pending_errors {
+                    self.recovered_errors.push(RecoveredError {
+                        row: prev_top_level,
+                        error,
+                    });
+                }
+                Ok(())
+            }
+            // Nothing could be parsed, so report the first error.
+            None => Err(pending_errors.swap_remove(0)),
+        }
     }
 
-    fn parse_elem(&mut self) -> Result<usize, String> {
+    fn parse_elem(&mut self) -> Result<usize, ParseError> {
         self.consume_whitespace();
 
         self.max_depth = self.max_depth.max(self.parents.len());
 
-        loop {
-            match self.peek_token()? {
-                JsonToken::OpenCurly => {
-                    return self.parse_object();
-                }
-                JsonToken::OpenSquare => {
-                    return self.parse_array();
-                }
-                JsonToken::Null => {
-                    return self.parse_null();
-                }
-                JsonToken::True => {
-                    return self.parse_bool(true);
-                }
-                JsonToken::False => {
-                    return self.parse_bool(false);
-                }
-                JsonToken::Number => {
-                    return self.parse_number();
-                }
-                JsonToken::String => {
-                    return self.parse_string();
-                }
+        match self.peek_token(EXPECTED_VALUE)? {
+            JsonToken::OpenCurly => self.parse_object(),
+            JsonToken::OpenSquare => self.parse_array(),
+            JsonToken::Null => self.parse_null(),
+            JsonToken::True => self.parse_bool(true),
+            JsonToken::False => self.parse_bool(false),
+            JsonToken::Number => self.parse_number(),
+            JsonToken::String => self.parse_string(),
 
-                JsonToken::Whitespace | JsonToken::Newline => {
-                    panic!("Should have just consumed whitespace");
-                }
+            JsonToken::Json5Number if self.json5 => self.parse_json5_number(),
+            JsonToken::Json5String if self.json5 => self.parse_json5_string(),
 
-                JsonToken::Error => {
-                    return Err("Parse error".to_string());
-                }
-                JsonToken::CloseCurly
-                | JsonToken::CloseSquare
-                | JsonToken::Colon
-                | JsonToken::Comma => {
-                    return Err(format!("Unexpected character: {:?}", self.tokenizer.span()));
-                }
+            JsonToken::Whitespace | JsonToken::Newline => {
+                panic!("Should have just consumed whitespace");
             }
+
+            JsonToken::Error
+            | JsonToken::Comment
+            | JsonToken::Identifier
+            | JsonToken::Json5Number
+            | JsonToken::Json5String
+            | JsonToken::CloseCurly
+            | JsonToken::CloseSquare
+            | JsonToken::Colon
+            | JsonToken::Comma => self.unexpected_token(EXPECTED_VALUE),
         }
     }
 
-    fn parse_array(&mut self) -> Result<usize, String> {
+    fn parse_array(&mut self) -> Result<usize, ParseError> {
         let open_value = Value::OpenContainer {
             container_type: ContainerType::Array,
             collapsed: false,
@@ -160,30 +319,16 @@ impl<'a> JsonParser<'a> {
         let mut num_children = 0;
 
         loop {
-            if num_children != 0 {
-                match self.peek_token()? {
-                    // Great, we needed a comma; eat it up.
-                    JsonToken::Comma => self.advance_and_consume_whitespace(),
-                    // We're going to peek again below and check for ']', so we don't
-                    // need to do anything.
-                    JsonToken::CloseSquare => {}
-                    _ => return self.unexpected_token(),
+            let child_start = self.pretty_printed.len();
+            let child = match self.parse_array_child(num_children) {
+                Ok(Some(child)) => child,
+                Ok(None) => break,
+                Err(err) => {
+                    self.pretty_printed.truncate(child_start);
+                    self.recover(err, array_open_index)?;
+                    break;
                 }
-            }
-
-            if self.peek_token()? == JsonToken::CloseSquare {
-                self.advance();
-                break;
-            }
-
-            // Add comma to pretty printed version _after_ we know
-            // we didn't see a CloseSquare so we don't add a trailing comma.
-            if num_children != 0 {
-                self.pretty_printed.push_str(", ");
-            }
-
-            let child = self.parse_elem()?;
-            self.consume_whitespace();
+            };
 
             if num_children == 0 {
                 match self.rows[array_open_index].value {
@@ -205,6 +350,10 @@ impl<'a> JsonParser<'a> {
 
             num_children += 1;
             prev_sibling = OptionIndex::Index(child);
+
+            if self.recovering {
+                break;
+            }
         }
 
         self.parents.pop();
@@ -241,7 +390,37 @@ impl<'a> JsonParser<'a> {
         Ok(array_open_index)
     }
 
-    fn parse_object(&mut self) -> Result<usize, String> {
+    // Parse the next element of an array, returning None if we've reached
+    // the end of the array instead.
+    fn parse_array_child(&mut self, num_children: usize) -> Result<Option<usize>, ParseError> {
+        if num_children != 0 {
+            match self.peek_token("',' or ']'")? {
+                // Great, we needed a comma; eat it up.
+                JsonToken::Comma => self.advance_and_consume_whitespace(),
+                // We're going to peek again below and check for ']', so we don't
+                // need to do anything.
+                JsonToken::CloseSquare => {}
+                _ => return self.unexpected_token("',' or ']'").map(Some),
+            }
+        }
+
+        if self.peek_token("a value or ']'")? == JsonToken::CloseSquare {
+            self.advance();
+            return Ok(None);
+        }
+
+        // Add comma to pretty printed version _after_ we know
+        // we didn't see a CloseSquare so we don't add a trailing comma.
+        if num_children != 0 {
+            self.pretty_printed.push_str(", ");
+        }
+
+        let child = self.parse_elem()?;
+        self.consume_whitespace();
+        Ok(Some(child))
+    }
+
+    fn parse_object(&mut self) -> Result<usize, ParseError> {
         let open_value = Value::OpenContainer {
             container_type: ContainerType::Object,
             collapsed: false,
@@ -260,55 +439,17 @@ impl<'a> JsonParser<'a> {
         let mut num_children = 0;
 
         loop {
-            if num_children != 0 {
-                match self.peek_token()? {
-                    // Great, we needed a comma; eat it up.
-                    JsonToken::Comma => self.advance_and_consume_whitespace(),
-                    // We're going to peek again below and check for '}', so we don't
-                    // need to do anything.
-                    JsonToken::CloseCurly => {}
-                    _ => return self.unexpected_token(),
+            let child_start = self.pretty_printed.len();
+            let child = match self.parse_object_child(num_children) {
+                Ok(Some(child)) => child,
+                Ok(None) => break,
+                Err(err) => {
+                    self.pretty_printed.truncate(child_start);
+                    self.recover(err, object_open_index)?;
+                    break;
                 }
-            }
-
-            if self.peek_token()? == JsonToken::CloseCurly {
-                self.advance();
-                break;
-            }
-
-            // Add comma to pretty printed version _after_ we know
-            // we didn't see a CloseSquare so we don't add a trailing comma.
-            if num_children != 0 {
-                self.pretty_printed.push_str(", ");
-            } else {
-                // Add space inside objects.
-                self.pretty_printed.push(' ');
-            }
-
-            if self.peek_token()? != JsonToken::String {
-                return self.unexpected_token();
-            }
-
-            let key_range = {
-                let key_range_start = self.pretty_printed.len();
-                let key_span_len = self.tokenizer.span().len();
-                let key_range = key_range_start..key_range_start + key_span_len;
-
-                self.pretty_printed.push_str(self.tokenizer.slice());
-                self.advance_and_consume_whitespace();
-                key_range
             };
 
-            if self.peek_token()? != JsonToken::Colon {
-                return self.unexpected_token();
-            }
-            self.advance_and_consume_whitespace();
-            self.pretty_printed.push_str(": ");
-
-            let child = self.parse_elem()?;
-            self.rows[child].key_range = Some(key_range);
-            self.consume_whitespace();
-
             if num_children == 0 {
                 match self.rows[object_open_index].value {
                     Value::OpenContainer {
@@ -329,6 +470,10 @@ impl<'a> JsonParser<'a> {
 
             num_children += 1;
             prev_sibling = OptionIndex::Index(child);
+
+            if self.recovering {
+                break;
+            }
         }
 
         self.parents.pop();
@@ -368,7 +513,74 @@ impl<'a> JsonParser<'a> {
         Ok(object_open_index)
     }
 
-    fn parse_null(&mut self) -> Result<usize, String> {
+    // Parse the next entry of an object, returning None if we've reached
+    // the end of the object instead.
+    fn parse_object_child(&mut self, num_children: usize) -> Result<Option<usize>, ParseError> {
+        if num_children != 0 {
+            match self.peek_token("',' or '}'")? {
+                // Great, we needed a comma; eat it up.
+                JsonToken::Comma => self.advance_and_consume_whitespace(),
+                // We're going to peek again below and check for '}', so we don't
+                // need to do anything.
+                JsonToken::CloseCurly => {}
+                _ => return self.unexpected_token("',' or '}'").map(Some),
+            }
+        }
+
+        if self.peek_token("a key or '}'")? == JsonToken::CloseCurly {
+            self.advance();
+            return Ok(None);
+        }
+
+        // Add comma to pretty printed version _after_ we know
+        // we didn't see a CloseSquare so we don't add a trailing comma.
+        if num_children != 0 {
+            self.pretty_printed.push_str(", ");
+        } else {
+            // Add space inside objects.
+            self.pretty_printed.push(' ');
+        }
+
+        let key = match self.peek_token(EXPECTED_KEY)? {
+            JsonToken::String => self.tokenizer.slice().to_string(),
+            JsonToken::Json5String if self.json5 => json5_string_to_json(self.tokenizer.slice())
+                .map_err(|err| self.error_at_token(err))?,
+            // JSON5 allows any identifier as a key, including reserved words.
+            JsonToken::Identifier | JsonToken::Null | JsonToken::True | JsonToken::False
+                if self.json5 =>
+            {
+                format!("\"{}\"", self.tokenizer.slice())
+            }
+            JsonToken::Json5Number
+                if self.json5 && matches!(self.tokenizer.slice(), "Infinity" | "NaN") =>
+            {
+                format!("\"{}\"", self.tokenizer.slice())
+            }
+            _ => return self.unexpected_token(EXPECTED_KEY).map(Some),
+        };
+
+        let key_range = {
+            let key_range_start = self.pretty_printed.len();
+            let key_range = key_range_start..key_range_start + key.len();
+
+            self.pretty_printed.push_str(&key);
+            self.advance_and_consume_whitespace();
+            key_range
+        };
+
+        if self.peek_token("':'")? != JsonToken::Colon {
+            return self.unexpected_token("':'").map(Some);
+        }
+        self.advance_and_consume_whitespace();
+        self.pretty_printed.push_str(": ");
+
+        let child = self.parse_elem()?;
+        self.rows[child].key_range = Some(key_range);
+        self.consume_whitespace();
+        Ok(Some(child))
+    }
+
+    fn parse_null(&mut self) -> Result<usize, ParseError> {
         self.advance();
         let row_index = self.create_row(Value::Null);
         self.rows[row_index].range.end = self.rows[row_index].range.start + 4;
@@ -376,7 +588,7 @@ impl<'a> JsonParser<'a> {
         Ok(row_index)
     }
 
-    fn parse_bool(&mut self, b: bool) -> Result<usize, String> {
+    fn parse_bool(&mut self, b: bool) -> Result<usize, ParseError> {
         self.advance();
 
         let row_index = self.create_row(Value::Boolean);
@@ -388,7 +600,7 @@ impl<'a> JsonParser<'a> {
         Ok(row_index)
     }
 
-    fn parse_number(&mut self) -> Result<usize, String> {
+    fn parse_number(&mut self) -> Result<usize, ParseError> {
         let row_index = self.create_row(Value::Number);
         self.pretty_printed.push_str(self.tokenizer.slice());
 
@@ -399,7 +611,7 @@ impl<'a> JsonParser<'a> {
         Ok(row_index)
     }
 
-    fn parse_string(&mut self) -> Result<usize, String> {
+    fn parse_string(&mut self) -> Result<usize, ParseError> {
         let row_index = self.create_row(Value::String);
 
         // The token includes the quotation marks.
@@ -411,6 +623,29 @@ impl<'a> JsonParser<'a> {
         Ok(row_index)
     }
 
+    fn parse_json5_number(&mut self) -> Result<usize, ParseError> {
+        let number = json5_number_to_json(self.tokenizer.slice());
+
+        let row_index = self.create_row(Value::Number);
+        self.pretty_printed.push_str(&number);
+        self.rows[row_index].range.end = self.rows[row_index].range.start + number.len();
+
+        self.advance();
+        Ok(row_index)
+    }
+
+    fn parse_json5_string(&mut self) -> Result<usize, ParseError> {
+        let string =
+            json5_string_to_json(self.tokenizer.slice()).map_err(|err| self.error_at_token(err))?;
+
+        let row_index = self.create_row(Value::String);
+        self.pretty_printed.push_str(&string);
+        self.rows[row_index].range.end = self.rows[row_index].range.start + string.len();
+
+        self.advance();
+        Ok(row_index)
+    }
+
     // Add a new row to the FlatJson representation.
     //
     // self.pretty_printed should NOT include the added row yet;
@@ -431,6 +666,7 @@ impl<'a> JsonParser<'a> {
             parent,
             depth: self.parents.len(),
             value,
+            hidden: false,
 
             // The start of this range is set by us, but then we set
             // the end when we're done parsing the row. We'll set
@@ -448,6 +684,146 @@ impl<'a> JsonParser<'a> {
         index
     }
 }
+
+// How a token is described in error messages.
+fn describe_token(token: JsonToken, slice: &str) -> String {
+    match token {
+        JsonToken::OpenCurly
+        | JsonToken::CloseCurly
+        | JsonToken::OpenSquare
+        | JsonToken::CloseSquare
+        | JsonToken::Colon
+        | JsonToken::Comma
+        | JsonToken::Identifier
+        | JsonToken::Json5Number => format!("'{slice}'"),
+        JsonToken::Null | JsonToken::True | JsonToken::False => slice.to_string(),
+        JsonToken::Number => "number".to_string(),
+        JsonToken::String => "string".to_string(),
+        JsonToken::Json5String if slice.starts_with('\'') => "single quoted string".to_string(),
+        JsonToken::Json5String => "invalid string".to_string(),
+        JsonToken::Comment => "comment".to_string(),
+        JsonToken::Newline | JsonToken::Whitespace => "whitespace".to_string(),
+        JsonToken::Error if slice.starts_with('"') => "unterminated string".to_string(),
+        JsonToken::Error => format!("character '{slice}'"),
+    }
+}
+
+// Convert a JSON5 number to the equivalent JSON number: drop leading
+// plus signs, convert hexadecimal numbers to decimal, and add digits
+// around leading or trailing decimal points. Infinity and NaN have no
+// JSON equivalent, so they are left alone.
+fn json5_number_to_json(number: &str) -> String {
+    let (sign, unsigned) = match number.as_bytes()[0] {
+        b'-' => ("-", &number[1..]),
+        b'+' => ("", &number[1..]),
+        _ => ("", number),
+    };
+
+    if unsigned == "Infinity" || unsigned == "NaN" {
+        return format!("{sign}{unsigned}");
+    }
+
+    if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        return format!("{sign}{}", hex_to_decimal(hex));
+    }
+
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => unsigned.split_at(i),
+        None => (unsigned, ""),
+    };
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+        None => (mantissa, ""),
+    };
+
+    let integer = integer.trim_start_matches('0');
+    let integer = if integer.is_empty() { "0" } else { integer };
+
+    if fraction.is_empty() {
+        format!("{sign}{integer}{exponent}")
+    } else {
+        format!("{sign}{integer}.{fraction}{exponent}")
+    }
+}
+
+// Convert a hexadecimal number of any size to decimal, one digit at a
+// time, storing the decimal digits least significant first.
+fn hex_to_decimal(hex: &str) -> String {
+    let mut digits: Vec<u8> = vec![0];
+
+    for hex_digit in hex.chars() {
+        let mut carry = hex_digit.to_digit(16).unwrap();
+        for digit in digits.iter_mut() {
+            let n = *digit as u32 * 16 + carry;
+            *digit = (n % 10) as u8;
+            carry = n / 10;
+        }
+        while carry > 0 {
+            digits.push((carry % 10) as u8);
+            carry /= 10;
+        }
+    }
+
+    while digits.len() > 1 && digits.last() == Some(&0) {
+        digits.pop();
+    }
+
+    digits.iter().rev().map(|d| (b'0' + d) as char).collect()
+}
+
+// Convert a single or double quoted JSON5 string to a JSON string,
+// converting any escape sequences (and characters) that aren't valid
+// in JSON strings.
+fn json5_string_to_json(string: &str) -> Result<String, String> {
+    let mut json = String::with_capacity(string.len());
+    json.push('"');
+
+    // Strip the quotes.
+    let mut chars = string[1..string.len() - 1].chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => match chars.next() {
+                Some(ch @ ('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' | 'u')) => {
+                    json.push('\\');
+                    json.push(ch);
+                }
+                Some('\'') => json.push('\''),
+                Some('v') => json.push_str("\\u000b"),
+                Some('0') => json.push_str("\\u0000"),
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    if hex.len() != 2 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                        return Err(format!("Invalid escape sequence: \\x{hex}"));
+                    }
+                    json.push_str("\\u00");
+                    json.push_str(&hex);
+                }
+                // Escaped line terminators are line continuations.
+                Some('\n' | '\u{2028}' | '\u{2029}') => {}
+                Some('\r') => {
+                    if chars.peek() == Some(&'\n') {
+                        chars.next();
+                    }
+                }
+                // Any other escaped character is just that character.
+                Some(ch) => json.push(ch),
+                None => return Err("Unterminated escape sequence".to_string()),
+            },
+            '\t' => json.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => json.push(ch),
+        }
+    }
+
+    json.push('"');
+    Ok(json)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -496,4 +872,139 @@ mod tests {
         assert_eq!(rows[7].range, 46..51); // false
         assert_eq!(rows[8].range, 51..52); // ]
     }
+
+    #[test]
+    fn test_json5() {
+        let json5 = r#"
+            // A comment
+            {
+                unquoted: 'single \'quoted\' "string"',
+                'quoted key': +0x1F, /* Another comment */
+                numbers: [.5, 5., -Infinity, NaN, 1e+3,],
+                escapes: "\x41\v\
+continued",
+                null: null,
+            }
+        "#
+        .to_owned();
+        let (_, pretty, _) = parse_json5(json5).unwrap();
+
+        assert_eq!(
+            pretty,
+            concat!(
+                r#"{ "unquoted": "single 'quoted' \"string\"", "quoted key": 31, "#,
+                r#""numbers": [0.5, 5, -Infinity, NaN, 1e+3], "#,
+                r#""escapes": "\u0041\u000bcontinued", "null": null }"#,
+            )
+        );
+    }
+
+    #[test]
+    fn test_hex_to_decimal() {
+        assert_eq!(hex_to_decimal("0"), "0");
+        assert_eq!(hex_to_decimal("00ff"), "255");
+        assert_eq!(hex_to_decimal("DeadBeef"), "3735928559");
+        // Larger than u128::MAX.
+        assert_eq!(
+            hex_to_decimal("100000000000000000000000000000000"),
+            "340282366920938463463374607431768211456"
+        );
+        assert_eq!(
+            parse_json5("-0x100000000000000000000000000000000".to_owned())
+                .unwrap()
+                .1,
+            "-340282366920938463463374607431768211456"
+        );
+    }
+
+    #[test]
+    fn test_json5_extensions_are_not_valid_json() {
+        assert!(parse("// comment\n1".to_owned()).is_err());
+        assert!(parse("{ a: 1 }".to_owned()).is_err());
+        assert!(parse("'string'".to_owned()).is_err());
+        assert!(parse("0x1F".to_owned()).is_err());
+        assert!(parse("NaN".to_owned()).is_err());
+        assert!(parse_json5("{ a: b }".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_error_positions() {
+        assert_eq!(
+            parse("{\n  \"a\": 1,\n  \"b\" 2\n}".to_owned())
+                .unwrap_err()
+                .to_string(),
+            "Unexpected number at line 3, column 7; expected ':'",
+        );
+        assert_eq!(
+            parse("[1, 2".to_owned()).unwrap_err().to_string(),
+            "Unexpected EOF at line 1, column 6; expected ',' or ']'",
+        );
+        assert_eq!(
+            parse("[1, ]]".to_owned()).unwrap_err().to_string(),
+            "Unexpected ']' at line 1, column 6; expected a value",
+        );
+        assert_eq!(
+            parse("{\"a\": tru}".to_owned()).unwrap_err().to_string(),
+            "Unexpected 'tru' at line 1, column 7; expected a value",
+        );
+        assert_eq!(
+            parse("[\"abc".to_owned()).unwrap_err().to_string(),
+            "Unexpected unterminated string at line 1, column 2; expected a value",
+        );
+
+        let error = parse("[1,\n 2 3]".to_owned()).unwrap_err();
+        assert_eq!(error.location.as_ref().unwrap().offset, 7);
+        assert_eq!(error.excerpt().unwrap(), " 2 |  2 3]\n   |    ^");
+    }
+
+    fn describe_errors(errors: &[RecoveredError]) -> Vec<(Index, String)> {
+        errors
+            .iter()
+            .map(|error| (error.row, error.error.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_lenient_closes_unterminated_containers() {
+        let json = r#"{"a": [1, {"b": 2}, {"c": "#.to_owned();
+        let (rows, pretty, _, errors) = parse_leniently(json, false).unwrap();
+
+        assert_eq!(pretty, r#"{ "a": [1, { "b": 2 }, {}] }"#);
+        assert_eq!(
+            describe_errors(&errors),
+            vec![(
+                6,
+                "Unexpected EOF at line 1, column 27; expected a value".to_string()
+            )],
+        );
+        assert!(matches!(rows[6].value, Value::EmptyObject));
+        assert_eq!(rows.len(), 9);
+    }
+
+    #[test]
+    fn test_lenient_skips_invalid_records() {
+        let json = "{\"a\": 1}\n{\"a\": 2 oops}\ngarbage\n  more garbage\n[3]\n}".to_owned();
+        let (rows, pretty, _, errors) = parse_leniently(json, false).unwrap();
+
+        assert_eq!(pretty, "{ \"a\": 1 }\n{ \"a\": 2 }\n[3]");
+        assert_eq!(
+            describe_errors(&errors),
+            vec![
+                // The lines after this error are skipped as part of it.
+                (
+                    3,
+                    "Unexpected 'oops' at line 2, column 9; expected ',' or '}'".to_string()
+                ),
+                (
+                    6,
+                    "Unexpected '}' at line 6, column 1; expected a value".to_string()
+                ),
+            ]
+        );
+        assert_eq!(rows[6].index_in_parent, 2);
+
+        // Nothing could be parsed.
+        assert!(parse_leniently("oops".to_owned(), false).is_err());
+        assert!(parse_leniently("".to_owned(), false).is_err());
+    }
 }
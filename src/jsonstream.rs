@@ -0,0 +1,169 @@
+// This module is responsible for splitting a stream of bytes into
+// chunks that contain only complete top-level JSON values, so that
+// we can parse and display newline-delimited JSON (or any other
+// sequence of top-level values) as it arrives, rather than waiting
+// until we've read all of the input.
+//
+// We don't actually parse anything here; we just track enough state
+// (nesting depth and whether we're inside a string) to know where it
+// is safe to split the input. It is safe to split the input anywhere
+// we're at the top level and not in the middle of a value:
+//
+// - Right after the closing delimiter of a top-level container
+// - Right after the closing quote of a top-level string
+// - At any whitespace at the top level (this terminates top-level
+//   numbers, booleans and nulls)
+//
+// All of these are ASCII characters, so splitting at them will never
+// split a multi-byte UTF-8 character.
+//
+// Malformed input is passed along as is; the parser will report
+// errors once the chunk containing them is complete.
+#[derive(Default)]
+pub struct JsonStream {
+    buffer: Vec<u8>,
+    // How much of the buffer we've already scanned.
+    scanned: usize,
+    // The end of the last complete top-level value in the buffer.
+    boundary: usize,
+
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl JsonStream {
+    // Add more data to the stream. If this completes any top-level
+    // values, all the complete values will be returned.
+    pub fn push(&mut self, bytes: &[u8]) -> Option<Result<String, String>> {
+        self.buffer.extend_from_slice(bytes);
+        self.scan();
+
+        if self.boundary == 0 {
+            return None;
+        }
+
+        let rest = self.buffer.split_off(self.boundary);
+        let complete = std::mem::replace(&mut self.buffer, rest);
+
+        self.scanned -= self.boundary;
+        self.boundary = 0;
+
+        Self::into_non_empty_string(complete)
+    }
+
+    // Signal that there is no more data; returns whatever data is
+    // left over, whether or not it is a complete value.
+    pub fn finish(&mut self) -> Option<Result<String, String>> {
+        let remaining = std::mem::take(&mut self.buffer);
+        self.scanned = 0;
+        self.boundary = 0;
+        Self::into_non_empty_string(remaining)
+    }
+
+    fn scan(&mut self) {
+        for i in self.scanned..self.buffer.len() {
+            let byte = self.buffer[i];
+
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                    if self.depth == 0 {
+                        self.boundary = i + 1;
+                    }
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.depth == 0 {
+                        self.boundary = i + 1;
+                    }
+                }
+                b' ' | b'\t' | b'\r' | b'\n' if self.depth == 0 => self.boundary = i + 1,
+                _ => {}
+            }
+        }
+
+        self.scanned = self.buffer.len();
+    }
+
+    fn into_non_empty_string(bytes: Vec<u8>) -> Option<Result<String, String>> {
+        if bytes.iter().all(u8::is_ascii_whitespace) {
+            return None;
+        }
+
+        Some(String::from_utf8(bytes).map_err(|_| "Input is not valid UTF-8".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(stream: &mut JsonStream, s: &str) -> Option<String> {
+        stream.push(s.as_bytes()).map(Result::unwrap)
+    }
+
+    #[test]
+    fn test_splits_after_complete_values() {
+        let mut stream = JsonStream::default();
+
+        assert_eq!(push(&mut stream, r#"{"a": [1, "#), None);
+        assert_eq!(
+            push(&mut stream, r#"2]}{"b": "#),
+            Some(r#"{"a": [1, 2]}"#.to_string())
+        );
+        assert_eq!(
+            push(&mut stream, "3}\n[4"),
+            Some("{\"b\": 3}\n".to_string())
+        );
+        assert_eq!(push(&mut stream, "]"), Some("[4]".to_string()));
+        assert_eq!(stream.finish(), None);
+    }
+
+    #[test]
+    fn test_ignores_delimiters_in_strings() {
+        let mut stream = JsonStream::default();
+
+        assert_eq!(push(&mut stream, r#"{"}": "[\"]""#), None);
+        assert_eq!(push(&mut stream, r#", "x": "\\"#), None);
+        assert_eq!(
+            push(&mut stream, r#""} "str"#),
+            Some(r#"{"}": "[\"]", "x": "\\"} "#.to_string())
+        );
+        assert_eq!(
+            push(&mut stream, r#"ing""#),
+            Some(r#""string""#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_top_level_primitives() {
+        let mut stream = JsonStream::default();
+
+        assert_eq!(push(&mut stream, "12"), None);
+        assert_eq!(push(&mut stream, "3 tr"), Some("123 ".to_string()));
+        assert_eq!(push(&mut stream, "ue\n"), Some("true\n".to_string()));
+        assert_eq!(push(&mut stream, "\n  \n"), None);
+        assert_eq!(push(&mut stream, "nu"), None);
+        assert_eq!(stream.finish().unwrap(), Ok("nu".to_string()));
+    }
+
+    #[test]
+    fn test_multi_byte_characters_split_across_pushes() {
+        let mut stream = JsonStream::default();
+        let bytes = "[\"☃\"]".as_bytes();
+
+        assert!(stream.push(&bytes[..3]).is_none());
+        assert_eq!(stream.push(&bytes[3..]).unwrap(), Ok("[\"☃\"]".to_string()));
+    }
+}
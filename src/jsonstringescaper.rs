@@ -0,0 +1,42 @@
+// Escape a string so that it can be used as the contents of a JSON
+// string literal. This is used when converting values from other data
+// formats into the JSON we display.
+pub fn escape_json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for ch in s.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\x08' => escaped.push_str("\\b"),
+            '\x0c' => escaped.push_str("\\f"),
+            ch if (ch as u32) < 0x20 || ch == '\x7f' => {
+                escaped.push_str(&format!("\\u{:04x}", ch as u32));
+            }
+            ch => escaped.push(ch),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_json_string() {
+        assert_eq!(escape_json_string("plain ☃"), "plain ☃");
+        assert_eq!(
+            escape_json_string("\"quoted\" \\ back\\slash"),
+            r#"\"quoted\" \\ back\\slash"#
+        );
+        assert_eq!(
+            escape_json_string("\n\r\t\x08\x0c\x00\x1b"),
+            r#"\n\r\t\b\f\u0000\u001b"#
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use logos::Logos;
+use logos::{Lexer, Logos};
 
 // A basic JSON tokenizer
 
@@ -23,12 +23,29 @@ pub enum JsonToken {
     True,
     #[token("false")]
     False,
-    #[regex(r"-?(0|([1-9][0-9]*))(\.[0-9]+)?([eE][-+]?[0-9]+)?")]
+    #[regex(r"-?(0|([1-9][0-9]*))(\.[0-9]+)?([eE][-+]?[0-9]+)?", priority = 3)]
     Number,
     // I get an error when I do [0-9a-fA-F]{4}.
     #[regex("\"((\\\\([\"\\\\/bfnrt]|u[0-9a-fA-F][0-9a-fA-F][0-9a-fA-F][0-9a-fA-F]))|[^\"\\\\\x00-\x1F])*\"")]
     String,
 
+    // JSON5 extensions; these are only accepted by the parser when parsing
+    // JSON5. Where these overlap with the regular JSON tokens, the regular
+    // JSON tokens have higher priority.
+    #[regex(r"//[^\n]*")]
+    #[token("/*", block_comment)]
+    Comment,
+    #[regex(r"[a-zA-Z_$][a-zA-Z0-9_$]*", priority = 1)]
+    Identifier,
+    #[regex(
+        r"[-+]?(0[xX][0-9a-fA-F]+|Infinity|NaN|([0-9]+\.?[0-9]*|\.[0-9]+)([eE][-+]?[0-9]+)?)",
+        priority = 2
+    )]
+    Json5Number,
+    #[regex(r#""([^"\\\n\r]|\\([^\r]|\r\n?))*""#, priority = 1)]
+    #[regex(r#"'([^'\\\n\r]|\\([^\r]|\r\n?))*'"#)]
+    Json5String,
+
     // Whitespace; need separate newline token to handle newline-delimited JSON.
     #[token("\n")]
     Newline,
@@ -38,3 +55,14 @@ pub enum JsonToken {
     #[error]
     Error,
 }
+
+// Consume the rest of a block comment; fails if the comment is never closed.
+fn block_comment(lex: &mut Lexer<JsonToken>) -> bool {
+    match lex.remainder().find("*/") {
+        Some(end) => {
+            lex.bump(end + 2);
+            true
+        }
+        None => false,
+    }
+}
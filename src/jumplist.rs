@@ -0,0 +1,129 @@
+use crate::flatjson::Index;
+
+const MAX_JUMPS: usize = 100;
+
+// The rows we were focused on before making large jumps around the
+// document, which can be returned to with Ctrl-o and Ctrl-i, like vim's
+// jump list.
+//
+// `position` is the index of the jump we most recently returned to, or
+// the length of the list if we haven't gone back since the last jump.
+#[derive(Debug, Default)]
+pub struct JumpList {
+    jumps: Vec<Index>,
+    position: usize,
+}
+
+impl JumpList {
+    pub fn clear(&mut self) {
+        self.jumps.clear();
+        self.position = 0;
+    }
+
+    // Record that we've jumped away from a row. Any jumps that we had
+    // gone back past are forgotten.
+    pub fn record(&mut self, from: Index) {
+        self.jumps.truncate(self.position);
+        self.push(from);
+        self.position = self.jumps.len();
+    }
+
+    // Returns the row to go back to, given the row that's currently
+    // focused.
+    pub fn back(&mut self, current: Index) -> Option<Index> {
+        // Remember where we are before going back for the first time, so
+        // that we can go forward to it again.
+        if self.position == self.jumps.len() {
+            self.push(current);
+            self.position = self.jumps.len() - 1;
+        }
+
+        if self.position == 0 {
+            return None;
+        }
+
+        self.position -= 1;
+        Some(self.jumps[self.position])
+    }
+
+    // Returns the row to go forward to, after having gone back.
+    pub fn forward(&mut self) -> Option<Index> {
+        if self.position + 1 >= self.jumps.len() {
+            return None;
+        }
+
+        self.position += 1;
+        Some(self.jumps[self.position])
+    }
+
+    fn push(&mut self, index: Index) {
+        if self.jumps.last() == Some(&index) {
+            return;
+        }
+        if self.jumps.len() == MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.jumps.push(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_back_and_forward() {
+        let mut jump_list = JumpList::default();
+        assert_eq!(None, jump_list.back(5));
+        assert_eq!(None, jump_list.forward());
+
+        jump_list.clear();
+        jump_list.record(1);
+        jump_list.record(4);
+        jump_list.record(9);
+
+        assert_eq!(Some(9), jump_list.back(12));
+        assert_eq!(Some(4), jump_list.back(9));
+        assert_eq!(Some(1), jump_list.back(4));
+        assert_eq!(None, jump_list.back(1));
+
+        assert_eq!(Some(4), jump_list.forward());
+        assert_eq!(Some(9), jump_list.forward());
+        assert_eq!(Some(12), jump_list.forward());
+        assert_eq!(None, jump_list.forward());
+
+        // Going back from the newest jump doesn't add it again.
+        assert_eq!(Some(9), jump_list.back(12));
+    }
+
+    #[test]
+    fn test_record_after_going_back() {
+        let mut jump_list = JumpList::default();
+        jump_list.record(1);
+        jump_list.record(4);
+        jump_list.record(9);
+
+        assert_eq!(Some(9), jump_list.back(12));
+        assert_eq!(Some(4), jump_list.back(9));
+
+        // Jumping from 4 forgets the jumps after it.
+        jump_list.record(4);
+        assert_eq!(None, jump_list.forward());
+        assert_eq!(Some(4), jump_list.back(20));
+        assert_eq!(Some(1), jump_list.back(4));
+    }
+
+    #[test]
+    fn test_max_jumps() {
+        let mut jump_list = JumpList::default();
+        for i in 0..MAX_JUMPS + 10 {
+            jump_list.record(i);
+        }
+
+        // Going back adds the current row, which drops another jump.
+        for i in (11..MAX_JUMPS + 10).rev() {
+            assert_eq!(Some(i), jump_list.back(i + 1));
+        }
+        assert_eq!(None, jump_list.back(11));
+    }
+}
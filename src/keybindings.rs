@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+
+use termion::event::Key;
+
+use crate::app::ContentTarget;
+use crate::config::parse_key;
+
+// The named actions that key sequences can be bound to, either in the
+// config file or with :map.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum KeyAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    ScrollUp,
+    ScrollDown,
+    JumpUp,
+    JumpDown,
+    PageUp,
+    PageDown,
+    PrevSibling,
+    NextSibling,
+    FirstSibling,
+    LastSibling,
+    FocusParent,
+    FocusTop,
+    FocusBottom,
+    MatchingPair,
+    PrevDepthChange,
+    NextDepthChange,
+    ScrollLeft,
+    ScrollRight,
+    ScrollToEnd,
+    FocusedLineToTop,
+    FocusedLineToCenter,
+    FocusedLineToBottom,
+    ToggleCollapsed,
+    CollapseSiblings,
+    DeepCollapseSiblings,
+    ExpandSiblings,
+    DeepExpandSiblings,
+    ToggleMode,
+    DecreaseIndentation,
+    IncreaseIndentation,
+    SearchForward,
+    SearchBackward,
+    SearchKeyForward,
+    SearchKeyBackward,
+    NextMatch,
+    PrevMatch,
+    ToggleOnlyMatches,
+    NextChange,
+    PrevChange,
+    SetMark,
+    JumpToMark,
+    JumpBack,
+    JumpForward,
+    Print(ContentTarget),
+    Copy(ContentTarget),
+    Delete,
+    EditValue,
+    RenameKey,
+    InsertSiblingAfter,
+    InsertSiblingBefore,
+    Undo,
+    Redo,
+    // Return to the document from before the last filter was applied,
+    // or move up if no filter is applied.
+    Unfilter,
+    TableView,
+    // Actions that are only bound while viewing a table.
+    TableDrillIn,
+    TableSort,
+    Follow,
+    Reload,
+    Command,
+    Help,
+    Cancel,
+    Quit,
+}
+
+const ACTION_NAMES: &[(&str, KeyAction)] = &[
+    ("move-up", KeyAction::MoveUp),
+    ("move-down", KeyAction::MoveDown),
+    ("move-left", KeyAction::MoveLeft),
+    ("move-right", KeyAction::MoveRight),
+    ("scroll-up", KeyAction::ScrollUp),
+    ("scroll-down", KeyAction::ScrollDown),
+    ("jump-up", KeyAction::JumpUp),
+    ("jump-down", KeyAction::JumpDown),
+    ("page-up", KeyAction::PageUp),
+    ("page-down", KeyAction::PageDown),
+    ("prev-sibling", KeyAction::PrevSibling),
+    ("next-sibling", KeyAction::NextSibling),
+    ("first-sibling", KeyAction::FirstSibling),
+    ("last-sibling", KeyAction::LastSibling),
+    ("focus-parent", KeyAction::FocusParent),
+    ("focus-top", KeyAction::FocusTop),
+    ("focus-bottom", KeyAction::FocusBottom),
+    ("matching-pair", KeyAction::MatchingPair),
+    ("prev-depth-change", KeyAction::PrevDepthChange),
+    ("next-depth-change", KeyAction::NextDepthChange),
+    ("scroll-left", KeyAction::ScrollLeft),
+    ("scroll-right", KeyAction::ScrollRight),
+    ("scroll-to-end", KeyAction::ScrollToEnd),
+    ("focused-line-to-top", KeyAction::FocusedLineToTop),
+    ("focused-line-to-center", KeyAction::FocusedLineToCenter),
+    ("focused-line-to-bottom", KeyAction::FocusedLineToBottom),
+    ("toggle-collapsed", KeyAction::ToggleCollapsed),
+    ("collapse-siblings", KeyAction::CollapseSiblings),
+    ("deep-collapse-siblings", KeyAction::DeepCollapseSiblings),
+    ("expand-siblings", KeyAction::ExpandSiblings),
+    ("deep-expand-siblings", KeyAction::DeepExpandSiblings),
+    ("toggle-mode", KeyAction::ToggleMode),
+    ("decrease-indentation", KeyAction::DecreaseIndentation),
+    ("increase-indentation", KeyAction::IncreaseIndentation),
+    ("search-forward", KeyAction::SearchForward),
+    ("search-backward", KeyAction::SearchBackward),
+    ("search-key-forward", KeyAction::SearchKeyForward),
+    ("search-key-backward", KeyAction::SearchKeyBackward),
+    ("next-match", KeyAction::NextMatch),
+    ("prev-match", KeyAction::PrevMatch),
+    ("toggle-only-matches", KeyAction::ToggleOnlyMatches),
+    ("next-change", KeyAction::NextChange),
+    ("prev-change", KeyAction::PrevChange),
+    ("set-mark", KeyAction::SetMark),
+    ("jump-to-mark", KeyAction::JumpToMark),
+    ("jump-back", KeyAction::JumpBack),
+    ("jump-forward", KeyAction::JumpForward),
+    (
+        "print-value",
+        KeyAction::Print(ContentTarget::PrettyPrintedValue),
+    ),
+    (
+        "print-one-line-value",
+        KeyAction::Print(ContentTarget::OneLineValue),
+    ),
+    ("print-string", KeyAction::Print(ContentTarget::String)),
+    ("print-key", KeyAction::Print(ContentTarget::Key)),
+    ("print-dot-path", KeyAction::Print(ContentTarget::DotPath)),
+    (
+        "print-bracket-path",
+        KeyAction::Print(ContentTarget::BracketPath),
+    ),
+    (
+        "print-query-path",
+        KeyAction::Print(ContentTarget::QueryPath),
+    ),
+    (
+        "copy-value",
+        KeyAction::Copy(ContentTarget::PrettyPrintedValue),
+    ),
+    (
+        "copy-one-line-value",
+        KeyAction::Copy(ContentTarget::OneLineValue),
+    ),
+    ("copy-string", KeyAction::Copy(ContentTarget::String)),
+    ("copy-key", KeyAction::Copy(ContentTarget::Key)),
+    ("copy-dot-path", KeyAction::Copy(ContentTarget::DotPath)),
+    (
+        "copy-bracket-path",
+        KeyAction::Copy(ContentTarget::BracketPath),
+    ),
+    ("copy-query-path", KeyAction::Copy(ContentTarget::QueryPath)),
+    ("delete", KeyAction::Delete),
+    ("edit-value", KeyAction::EditValue),
+    ("rename-key", KeyAction::RenameKey),
+    ("insert-sibling-after", KeyAction::InsertSiblingAfter),
+    ("insert-sibling-before", KeyAction::InsertSiblingBefore),
+    ("undo", KeyAction::Undo),
+    ("redo", KeyAction::Redo),
+    ("unfilter", KeyAction::Unfilter),
+    ("table-view", KeyAction::TableView),
+    ("table-drill-in", KeyAction::TableDrillIn),
+    ("table-sort", KeyAction::TableSort),
+    ("follow", KeyAction::Follow),
+    ("reload", KeyAction::Reload),
+    ("command", KeyAction::Command),
+    ("help", KeyAction::Help),
+    ("cancel", KeyAction::Cancel),
+    ("quit", KeyAction::Quit),
+];
+
+// The key sequences that actions are bound to by default.
+const DEFAULT_BINDINGS: &[(&str, KeyAction)] = &[
+    ("up", KeyAction::MoveUp),
+    ("k", KeyAction::MoveUp),
+    ("ctrl-p", KeyAction::MoveUp),
+    ("down", KeyAction::MoveDown),
+    ("j", KeyAction::MoveDown),
+    ("ctrl-n", KeyAction::MoveDown),
+    ("enter", KeyAction::MoveDown),
+    ("left", KeyAction::MoveLeft),
+    ("h", KeyAction::MoveLeft),
+    ("right", KeyAction::MoveRight),
+    ("l", KeyAction::MoveRight),
+    ("ctrl-y", KeyAction::ScrollUp),
+    ("ctrl-e", KeyAction::ScrollDown),
+    ("ctrl-u", KeyAction::JumpUp),
+    ("ctrl-d", KeyAction::JumpDown),
+    ("ctrl-b", KeyAction::PageUp),
+    ("pageup", KeyAction::PageUp),
+    ("ctrl-f", KeyAction::PageDown),
+    ("pagedown", KeyAction::PageDown),
+    ("K", KeyAction::PrevSibling),
+    ("J", KeyAction::NextSibling),
+    ("0", KeyAction::FirstSibling),
+    ("^", KeyAction::FirstSibling),
+    ("$", KeyAction::LastSibling),
+    ("H", KeyAction::FocusParent),
+    ("g", KeyAction::FocusTop),
+    ("home", KeyAction::FocusTop),
+    ("G", KeyAction::FocusBottom),
+    ("end", KeyAction::FocusBottom),
+    ("%", KeyAction::MatchingPair),
+    ("b", KeyAction::PrevDepthChange),
+    ("w", KeyAction::NextDepthChange),
+    (",", KeyAction::ScrollLeft),
+    (".", KeyAction::ScrollRight),
+    (";", KeyAction::ScrollToEnd),
+    ("zt", KeyAction::FocusedLineToTop),
+    ("zz", KeyAction::FocusedLineToCenter),
+    ("zb", KeyAction::FocusedLineToBottom),
+    ("space", KeyAction::ToggleCollapsed),
+    ("c", KeyAction::CollapseSiblings),
+    ("C", KeyAction::DeepCollapseSiblings),
+    ("e", KeyAction::ExpandSiblings),
+    ("E", KeyAction::DeepExpandSiblings),
+    ("m", KeyAction::ToggleMode),
+    ("<", KeyAction::DecreaseIndentation),
+    (">", KeyAction::IncreaseIndentation),
+    ("/", KeyAction::SearchForward),
+    ("?", KeyAction::SearchBackward),
+    ("*", KeyAction::SearchKeyForward),
+    ("#", KeyAction::SearchKeyBackward),
+    ("n", KeyAction::NextMatch),
+    ("N", KeyAction::PrevMatch),
+    ("&", KeyAction::ToggleOnlyMatches),
+    ("]c", KeyAction::NextChange),
+    ("[c", KeyAction::PrevChange),
+    ("M", KeyAction::SetMark),
+    ("'", KeyAction::JumpToMark),
+    ("ctrl-o", KeyAction::JumpBack),
+    // Ctrl-i and Tab send the same byte.
+    ("tab", KeyAction::JumpForward),
+    ("pp", KeyAction::Print(ContentTarget::PrettyPrintedValue)),
+    ("pv", KeyAction::Print(ContentTarget::OneLineValue)),
+    ("ps", KeyAction::Print(ContentTarget::String)),
+    ("pk", KeyAction::Print(ContentTarget::Key)),
+    ("pP", KeyAction::Print(ContentTarget::DotPath)),
+    ("pb", KeyAction::Print(ContentTarget::BracketPath)),
+    ("pq", KeyAction::Print(ContentTarget::QueryPath)),
+    ("yy", KeyAction::Copy(ContentTarget::PrettyPrintedValue)),
+    ("yv", KeyAction::Copy(ContentTarget::OneLineValue)),
+    ("ys", KeyAction::Copy(ContentTarget::String)),
+    ("yk", KeyAction::Copy(ContentTarget::Key)),
+    ("yp", KeyAction::Copy(ContentTarget::DotPath)),
+    ("yb", KeyAction::Copy(ContentTarget::BracketPath)),
+    ("yq", KeyAction::Copy(ContentTarget::QueryPath)),
+    ("dd", KeyAction::Delete),
+    ("ie", KeyAction::EditValue),
+    ("ik", KeyAction::RenameKey),
+    ("o", KeyAction::InsertSiblingAfter),
+    ("O", KeyAction::InsertSiblingBefore),
+    ("u", KeyAction::Undo),
+    ("ctrl-r", KeyAction::Redo),
+    ("backspace", KeyAction::Unfilter),
+    ("T", KeyAction::TableView),
+    ("F", KeyAction::Follow),
+    ("R", KeyAction::Reload),
+    (":", KeyAction::Command),
+    ("f1", KeyAction::Help),
+    ("esc", KeyAction::Cancel),
+    ("q", KeyAction::Quit),
+];
+
+// The key sequences that table actions are bound to by default. These
+// take precedence over the other bindings while viewing a table.
+const DEFAULT_TABLE_BINDINGS: &[(&str, KeyAction)] = &[
+    ("enter", KeyAction::TableDrillIn),
+    ("s", KeyAction::TableSort),
+];
+
+impl KeyAction {
+    pub fn from_name(name: &str) -> Option<KeyAction> {
+        ACTION_NAMES
+            .iter()
+            .find(|(action_name, _)| *action_name == name)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn name(self) -> &'static str {
+        ACTION_NAMES
+            .iter()
+            .find(|(_, action)| *action == self)
+            .map(|(name, _)| *name)
+            .unwrap()
+    }
+
+    fn is_table_action(self) -> bool {
+        matches!(self, KeyAction::TableDrillIn | KeyAction::TableSort)
+    }
+}
+
+// The result of looking up the keys that have been pressed.
+#[derive(Debug, PartialEq)]
+pub enum Lookup {
+    Action(KeyAction),
+    // The keys are the start of a longer sequence.
+    Pending,
+    Unbound,
+}
+
+// A table of the actions that key sequences are bound to. A sequence may
+// be bound even if it's the start of a longer sequence, in which case the
+// longer sequence can't be entered. The table actions are kept separately,
+// so that keys can be bound to them as well as to other actions.
+#[derive(Debug, PartialEq)]
+pub struct KeyBindings {
+    bindings: HashMap<Vec<Key>, KeyAction>,
+    table_bindings: HashMap<Vec<Key>, KeyAction>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        let parse = |defaults: &[(&str, KeyAction)]| {
+            defaults
+                .iter()
+                .map(|(keys, action)| (parse_key_sequence(keys).unwrap(), *action))
+                .collect()
+        };
+        KeyBindings {
+            bindings: parse(DEFAULT_BINDINGS),
+            table_bindings: parse(DEFAULT_TABLE_BINDINGS),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn lookup(&self, keys: &[Key]) -> Lookup {
+        lookup_in(&[&self.bindings], keys)
+    }
+
+    // Look up keys pressed while viewing a table, where the table actions
+    // take precedence.
+    pub fn lookup_in_table(&self, keys: &[Key]) -> Lookup {
+        lookup_in(&[&self.table_bindings, &self.bindings], keys)
+    }
+
+    // Binding keys to an action other than a table action also replaces
+    // what they do in the table.
+    pub fn bind(&mut self, keys: Vec<Key>, action: KeyAction) {
+        if action.is_table_action() {
+            self.table_bindings.insert(keys, action);
+        } else {
+            self.table_bindings.remove(&keys);
+            self.bindings.insert(keys, action);
+        }
+    }
+
+    // Make keys do whatever other keys are bound to, including starting
+    // the same sequences. Every key is remapped based on the bindings from
+    // before any of them were remapped.
+    pub fn remap(&mut self, remaps: &HashMap<Key, Key>) {
+        remap_in(&mut self.bindings, remaps);
+        remap_in(&mut self.table_bindings, remaps);
+    }
+
+    // Returns whether the keys were bound, either to a table action or
+    // to another action.
+    pub fn unbind(&mut self, keys: &[Key]) -> bool {
+        let table_binding = self.table_bindings.remove(keys);
+        let binding = self.bindings.remove(keys);
+        table_binding.is_some() || binding.is_some()
+    }
+
+    // The names of the keys and actions of every binding, with related
+    // actions next to each other.
+    pub fn describe(&self) -> Vec<(String, &'static str)> {
+        let action_index = |action: &KeyAction| ACTION_NAMES.iter().position(|(_, a)| a == action);
+
+        let mut bindings: Vec<(&Vec<Key>, &KeyAction)> = self
+            .bindings
+            .iter()
+            .chain(self.table_bindings.iter())
+            .collect();
+        bindings.sort_by_key(|(keys, action)| (action_index(action), key_sequence_name(keys)));
+
+        bindings
+            .into_iter()
+            .map(|(keys, action)| (key_sequence_name(keys), action.name()))
+            .collect()
+    }
+}
+
+// Look up keys in tables of bindings, where earlier tables take
+// precedence over later ones.
+fn lookup_in(tables: &[&HashMap<Vec<Key>, KeyAction>], keys: &[Key]) -> Lookup {
+    if let Some(action) = tables.iter().find_map(|bindings| bindings.get(keys)) {
+        return Lookup::Action(*action);
+    }
+
+    let is_prefix = tables
+        .iter()
+        .flat_map(|bindings| bindings.keys())
+        .any(|sequence| sequence.len() > keys.len() && sequence.starts_with(keys));
+    if is_prefix {
+        Lookup::Pending
+    } else {
+        Lookup::Unbound
+    }
+}
+
+fn remap_in(bindings: &mut HashMap<Vec<Key>, KeyAction>, remaps: &HashMap<Key, Key>) {
+    let mut remapped = vec![];
+    for (&from, &to) in remaps.iter() {
+        for (keys, action) in bindings.iter() {
+            if keys.first() == Some(&to) {
+                let mut keys = keys.clone();
+                keys[0] = from;
+                remapped.push((keys, *action));
+            }
+        }
+    }
+
+    bindings.retain(|keys, _| !keys.first().map_or(false, |key| remaps.contains_key(key)));
+    bindings.extend(remapped);
+}
+
+// Parse a sequence of keys, which are separated by spaces, e.g.,
+// "ctrl-w j". A word that isn't the name of a single key is a sequence
+// of characters, e.g., "zt".
+pub fn parse_key_sequence(sequence: &str) -> Result<Vec<Key>, String> {
+    let mut keys = vec![];
+
+    for word in sequence.split_whitespace() {
+        match parse_key(word) {
+            Ok(key) => keys.push(key),
+            Err(err) if word.starts_with("ctrl-") || word.starts_with("alt-") => return Err(err),
+            Err(_) => keys.extend(word.chars().map(Key::Char)),
+        }
+    }
+
+    if keys.is_empty() {
+        return Err("No keys given".to_string());
+    }
+
+    // Digits (other than a leading 0) are entered as counts, so they can
+    // never start a sequence.
+    if let Key::Char(ch @ '1'..='9') = keys[0] {
+        return Err(format!("Keys can't start with {ch}, which starts a count"));
+    }
+
+    Ok(keys)
+}
+
+// The name of a key, which can be parsed by config::parse_key.
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Char(' ') => "space".to_string(),
+        Key::Char('\n') => "enter".to_string(),
+        Key::Char('\t') => "tab".to_string(),
+        Key::Char(ch) => ch.to_string(),
+        Key::Ctrl(ch) => format!("ctrl-{ch}"),
+        Key::Alt(ch) => format!("alt-{ch}"),
+        Key::F(n) => format!("f{n}"),
+        Key::Up => "up".to_string(),
+        Key::Down => "down".to_string(),
+        Key::Left => "left".to_string(),
+        Key::Right => "right".to_string(),
+        Key::Home => "home".to_string(),
+        Key::End => "end".to_string(),
+        Key::PageUp => "pageup".to_string(),
+        Key::PageDown => "pagedown".to_string(),
+        Key::Backspace => "backspace".to_string(),
+        Key::Delete => "delete".to_string(),
+        Key::Insert => "insert".to_string(),
+        Key::Esc => "esc".to_string(),
+        Key::BackTab => "backtab".to_string(),
+        key => format!("{key:?}").to_lowercase(),
+    }
+}
+
+// The name of a sequence of keys, which can be parsed by
+// parse_key_sequence. Sequences of characters are written without spaces
+// between them, unless that would be the name of a different key.
+pub fn key_sequence_name(keys: &[Key]) -> String {
+    let names: Vec<String> = keys.iter().map(|key| key_name(*key)).collect();
+    let joined = names.concat();
+
+    let all_chars = names.iter().all(|name| name.chars().count() == 1);
+    if names.len() > 1 && all_chars && parse_key(&joined).is_err() {
+        return joined;
+    }
+
+    names.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_sequence() {
+        assert_eq!(parse_key_sequence("J"), Ok(vec![Key::Char('J')]));
+        assert_eq!(
+            parse_key_sequence("zt"),
+            Ok(vec![Key::Char('z'), Key::Char('t')])
+        );
+        assert_eq!(
+            parse_key_sequence("ctrl-w j"),
+            Ok(vec![Key::Ctrl('w'), Key::Char('j')])
+        );
+        assert_eq!(parse_key_sequence("f1"), Ok(vec![Key::F(1)]));
+        assert_eq!(
+            parse_key_sequence("f 1"),
+            Ok(vec![Key::Char('f'), Key::Char('1')])
+        );
+        assert_eq!(
+            parse_key_sequence("ctrl-ww"),
+            Err("Unknown key: ctrl-ww".to_string())
+        );
+        assert_eq!(parse_key_sequence(" "), Err("No keys given".to_string()));
+        assert_eq!(
+            parse_key_sequence("1j"),
+            Err("Keys can't start with 1, which starts a count".to_string())
+        );
+        assert_eq!(
+            parse_key_sequence("0j"),
+            Ok(vec![Key::Char('0'), Key::Char('j')])
+        );
+
+        for keys in [
+            vec![Key::Char('z'), Key::Char('t')],
+            vec![Key::Char('f'), Key::Char('1')],
+            vec![Key::Ctrl('w'), Key::Char(' ')],
+            vec![Key::Char('\''), Key::PageDown],
+        ] {
+            assert_eq!(parse_key_sequence(&key_sequence_name(&keys)), Ok(keys));
+        }
+    }
+
+    #[test]
+    fn test_default_bindings() {
+        let bindings = KeyBindings::default();
+        assert_eq!(bindings.bindings.len(), DEFAULT_BINDINGS.len());
+        assert_eq!(bindings.table_bindings.len(), DEFAULT_TABLE_BINDINGS.len());
+
+        for (name, action) in ACTION_NAMES.iter() {
+            assert_eq!(KeyAction::from_name(name), Some(*action));
+            assert_eq!(action.name(), *name);
+        }
+        for (_, action) in DEFAULT_BINDINGS.iter() {
+            assert!(ACTION_NAMES.iter().any(|(_, a)| a == action));
+            assert!(!action.is_table_action());
+        }
+        for (_, action) in DEFAULT_TABLE_BINDINGS.iter() {
+            assert!(action.is_table_action());
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut bindings = KeyBindings::default();
+
+        assert_eq!(
+            bindings.lookup(&[Key::Char('j')]),
+            Lookup::Action(KeyAction::MoveDown)
+        );
+        assert_eq!(bindings.lookup(&[Key::Char('z')]), Lookup::Pending);
+        assert_eq!(
+            bindings.lookup(&[Key::Char('z'), Key::Char('t')]),
+            Lookup::Action(KeyAction::FocusedLineToTop)
+        );
+        assert_eq!(
+            bindings.lookup(&[Key::Char('z'), Key::Char('x')]),
+            Lookup::Unbound
+        );
+        assert_eq!(bindings.lookup(&[Key::Char('x')]), Lookup::Unbound);
+
+        // Swap J and Ctrl-d.
+        bindings.bind(vec![Key::Char('J')], KeyAction::JumpDown);
+        bindings.bind(vec![Key::Ctrl('d')], KeyAction::NextSibling);
+        assert_eq!(
+            bindings.lookup(&[Key::Char('J')]),
+            Lookup::Action(KeyAction::JumpDown)
+        );
+        assert_eq!(
+            bindings.lookup(&[Key::Ctrl('d')]),
+            Lookup::Action(KeyAction::NextSibling)
+        );
+
+        bindings.bind(vec![Key::Ctrl('x'), Key::Ctrl('c')], KeyAction::Quit);
+        assert_eq!(bindings.lookup(&[Key::Ctrl('x')]), Lookup::Pending);
+
+        assert!(bindings.unbind(&[Key::Char('z'), Key::Char('t')]));
+        assert!(!bindings.unbind(&[Key::Char('z'), Key::Char('t')]));
+        assert_eq!(bindings.lookup(&[Key::Char('z')]), Lookup::Pending);
+        assert!(bindings.unbind(&[Key::Char('z'), Key::Char('z')]));
+        assert!(bindings.unbind(&[Key::Char('z'), Key::Char('b')]));
+        assert_eq!(bindings.lookup(&[Key::Char('z')]), Lookup::Unbound);
+    }
+
+    #[test]
+    fn test_lookup_in_table() {
+        let mut bindings = KeyBindings::default();
+        let enter = [Key::Char('\n')];
+
+        assert_eq!(bindings.lookup(&enter), Lookup::Action(KeyAction::MoveDown));
+        assert_eq!(
+            bindings.lookup_in_table(&enter),
+            Lookup::Action(KeyAction::TableDrillIn)
+        );
+        assert_eq!(bindings.lookup(&[Key::Char('s')]), Lookup::Unbound);
+        assert_eq!(
+            bindings.lookup_in_table(&[Key::Char('j')]),
+            Lookup::Action(KeyAction::MoveDown)
+        );
+        assert_eq!(bindings.lookup_in_table(&[Key::Char('z')]), Lookup::Pending);
+
+        // Binding a key to a table action leaves what it does elsewhere.
+        bindings.bind(vec![Key::Char('S')], KeyAction::TableSort);
+        bindings.bind(vec![Key::Char('J')], KeyAction::TableSort);
+        assert_eq!(
+            bindings.lookup_in_table(&[Key::Char('J')]),
+            Lookup::Action(KeyAction::TableSort)
+        );
+        assert_eq!(
+            bindings.lookup(&[Key::Char('J')]),
+            Lookup::Action(KeyAction::NextSibling)
+        );
+
+        // Binding it to another action replaces the table action.
+        bindings.bind(enter.to_vec(), KeyAction::MoveUp);
+        assert_eq!(
+            bindings.lookup_in_table(&enter),
+            Lookup::Action(KeyAction::MoveUp)
+        );
+
+        assert!(bindings.unbind(&[Key::Char('s')]));
+        assert_eq!(bindings.lookup_in_table(&[Key::Char('s')]), Lookup::Unbound);
+        assert_eq!(
+            bindings.lookup_in_table(&[Key::Char('S')]),
+            Lookup::Action(KeyAction::TableSort)
+        );
+    }
+
+    #[test]
+    fn test_remap() {
+        let mut bindings = KeyBindings::default();
+        bindings.remap(&HashMap::from([
+            (Key::Char('J'), Key::Ctrl('d')),
+            (Key::Ctrl('d'), Key::Char('J')),
+            (Key::Char('Z'), Key::Char('z')),
+            (Key::Char('g'), Key::Char('x')),
+        ]));
+
+        assert_eq!(
+            bindings.lookup(&[Key::Char('J')]),
+            Lookup::Action(KeyAction::JumpDown)
+        );
+        assert_eq!(
+            bindings.lookup(&[Key::Ctrl('d')]),
+            Lookup::Action(KeyAction::NextSibling)
+        );
+        // Sequences starting with the other key are remapped too.
+        assert_eq!(bindings.lookup(&[Key::Char('Z')]), Lookup::Pending);
+        assert_eq!(
+            bindings.lookup(&[Key::Char('Z'), Key::Char('t')]),
+            Lookup::Action(KeyAction::FocusedLineToTop)
+        );
+        assert_eq!(bindings.lookup(&[Key::Char('z')]), Lookup::Pending);
+        // Keys remapped to unbound keys are unbound.
+        assert_eq!(bindings.lookup(&[Key::Char('g')]), Lookup::Unbound);
+    }
+}
@@ -0,0 +1,760 @@
+// Large-file mode: viewing JSON files that are too large to parse up
+// front.
+//
+// Normally we parse the entire input into rows before displaying it, which
+// requires several times the size of the input in memory. In large-file
+// mode we instead memory map the input file, and only create rows for
+// the parts of it that are actually displayed:
+//
+// - We make a single pass over the input to find the boundaries of
+//   large containers, so that we can skip over them quickly later.
+// - Initially we only create rows for the top-level values. Containers
+//   are represented by an open and close row with no children (see
+//   Row::is_unloaded), and "{…}" or "[…]" in the pretty printed string.
+// - The first time a container needs to be displayed, we parse just its
+//   direct children, and splice the rows for them into the FlatJson (see
+//   FlatJson::load_containers). Containers nested inside it remain
+//   unloaded until they are displayed.
+// - Splicing in rows means shifting all the rows after them, so every
+//   time we do that we also load other containers, until the document has
+//   grown by a fraction of its size. This keeps the cost of loading a
+//   container proportional to its own size, averaged over many loads, in
+//   exchange for loading some containers before they're displayed.
+//
+// We also keep track of what part of the input each row corresponds to,
+// so that values inside containers that haven't been loaded can be copied
+// directly from the input.
+
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+
+use logos::{Lexer, Logos};
+use memmap2::Mmap;
+
+use crate::decompress;
+use crate::flatjson::{ContainerType, FlatJson, Index, OptionIndex, Row, Value};
+use crate::jsontokenizer::JsonToken;
+
+// Containers that are at least this many bytes long are indexed on the
+// first pass over the input; smaller ones are just scanned when we need
+// to skip over them.
+const LARGE_CONTAINER_SIZE: usize = 4096;
+
+// When loading containers, we keep loading more until the number of rows
+// has grown by at least 1 / LOAD_GROWTH_FACTOR.
+const LOAD_GROWTH_FACTOR: usize = 4;
+
+pub struct LazyInput {
+    mmap: Mmap,
+    // Byte ranges of large containers in the input, sorted by start.
+    large_containers: Vec<Range<usize>>,
+    // Byte range of the input that each row corresponds to; this is kept
+    // parallel to the rows of the FlatJson.
+    input_ranges: Vec<Range<usize>>,
+}
+
+// Describes how the rows and pretty printed string of a FlatJson changed
+// when a container was loaded, so that anything that refers to them can
+// be updated.
+pub struct LoadedContainer {
+    // The index of the container's open row, which doesn't change.
+    pub index: Index,
+    pub rows_added: usize,
+    // The range of the placeholder for the container in the pretty printed
+    // string, and the length of the text that replaced it.
+    pub placeholder_range: Range<usize>,
+    pub new_text_len: usize,
+}
+
+// The new index of a row after containers were loaded (see
+// LazyInput::load_containers).
+pub fn shift_index(loaded: &[LoadedContainer], index: Index) -> Index {
+    let rows_added: usize = loaded
+        .iter()
+        .take_while(|container| container.index < index)
+        .map(|container| container.rows_added)
+        .sum();
+    index + rows_added
+}
+
+impl LazyInput {
+    pub fn open(path: &Path) -> Result<(LazyInput, FlatJson), String> {
+        let file = File::open(path).map_err(|err| format!("Unable to open input: {err}"))?;
+
+        // SAFETY: Modifying the file while it is mapped is undefined
+        // behavior, which we can't prevent. We only ever read the input,
+        // and validate it as UTF-8 (once, here) before using it as a string.
+        let mmap = unsafe { Mmap::map(&file) }
+            .map_err(|err| format!("Unable to memory map input: {err}"))?;
+
+        if decompress::is_compressed(&mmap) {
+            return Err("Compressed input is not supported in large-file mode".to_string());
+        }
+
+        if let Err(err) = std::str::from_utf8(&mmap) {
+            return Err(format!("Input is not valid UTF-8: {err}"));
+        }
+
+        let large_containers = index_large_containers(&mmap);
+
+        let mut input = LazyInput {
+            mmap,
+            large_containers,
+            input_ranges: vec![],
+        };
+
+        let mut parser = LazyParser::new(&input, 0..input.mmap.len(), 0, 0, OptionIndex::Nil);
+        parser
+            .parse_values(None)
+            .map_err(|err| format!("Unable to parse input: {err}"))?;
+
+        let LazyParser {
+            rows,
+            pretty_printed,
+            input_ranges,
+            ..
+        } = parser;
+
+        if rows.is_empty() {
+            return Err("Unable to parse input: Unexpected EOF".to_string());
+        }
+
+        input.input_ranges = input_ranges;
+        Ok((input, FlatJson(rows, pretty_printed, 0)))
+    }
+
+    // Load the direct children of containers that haven't been loaded yet,
+    // given their open rows, along with enough other unloaded containers
+    // to make it worth shifting the rows after them (see the comment at
+    // the top of this file). Returns how each container was loaded, in
+    // order; if any of the requested containers can't be loaded, nothing
+    // is loaded.
+    pub fn load_containers(
+        &mut self,
+        flatjson: &mut FlatJson,
+        indexes: &[Index],
+    ) -> Result<Vec<LoadedContainer>, String> {
+        let mut loads = vec![];
+        let mut rows_added = 0;
+
+        for &index in indexes.iter() {
+            let load = self.parse_container(flatjson, index)?;
+            rows_added += load.1.len() - 2;
+            loads.push(load);
+        }
+
+        // Load other containers, starting with the ones after the
+        // requested ones, since they're most likely to be displayed next.
+        // Containers that can't be loaded are skipped, and will report an
+        // error when they're displayed.
+        let min_rows_added = flatjson.0.len() / LOAD_GROWTH_FACTOR;
+        let start = indexes.iter().max().map_or(0, |index| index + 1);
+        let mut others = (start..flatjson.0.len()).chain(0..start);
+
+        while rows_added < min_rows_added {
+            let Some(index) = others.next() else {
+                break;
+            };
+            let row = &flatjson[index];
+            if !row.is_opening_of_container() || !row.is_unloaded() || indexes.contains(&index) {
+                continue;
+            }
+            if let Ok(load) = self.parse_container(flatjson, index) {
+                rows_added += load.1.len() - 2;
+                loads.push(load);
+            }
+        }
+
+        loads.sort_unstable_by_key(|(index, ..)| *index);
+
+        let loaded: Vec<LoadedContainer> = loads
+            .iter()
+            .map(|(index, rows, pretty_printed, _)| LoadedContainer {
+                index: *index,
+                rows_added: rows.len() - 2,
+                placeholder_range: flatjson[*index].range.clone(),
+                new_text_len: pretty_printed.len(),
+            })
+            .collect();
+
+        // Update the input ranges in the same way as the rows; the open and
+        // close rows correspond to the same input as before.
+        let mut new_input_ranges = Vec::with_capacity(self.input_ranges.len() + rows_added);
+        let mut next_old_range = 0;
+        let mut flatjson_loads = Vec::with_capacity(loads.len());
+
+        for (index, rows, pretty_printed, mut input_ranges) in loads.into_iter() {
+            new_input_ranges.extend_from_slice(&self.input_ranges[next_old_range..index]);
+            input_ranges[0] = self.input_ranges[index].clone();
+            *input_ranges.last_mut().unwrap() = self.input_ranges[index + 1].clone();
+            new_input_ranges.extend(input_ranges);
+            next_old_range = index + 2;
+
+            flatjson_loads.push((index, rows, pretty_printed));
+        }
+
+        new_input_ranges.extend_from_slice(&self.input_ranges[next_old_range..]);
+        self.input_ranges = new_input_ranges;
+
+        flatjson.load_containers(flatjson_loads);
+
+        Ok(loaded)
+    }
+
+    // Parse the direct children of a container that hasn't been loaded yet.
+    #[allow(clippy::type_complexity)]
+    fn parse_container(
+        &self,
+        flatjson: &FlatJson,
+        index: Index,
+    ) -> Result<(Index, Vec<Row>, String, Vec<Range<usize>>), String> {
+        let row = &flatjson[index];
+        debug_assert!(row.is_opening_of_container() && row.is_unloaded());
+
+        let container_type = row.value.container_type().unwrap();
+        let input_range = self.input_ranges[index].clone();
+
+        let mut parser = LazyParser::new(
+            self,
+            (input_range.start + 1)..(input_range.end - 1),
+            index,
+            row.depth + 1,
+            OptionIndex::Index(index),
+        );
+        let (rows, pretty_printed, input_ranges) = parser.parse_container(container_type)?;
+
+        Ok((index, rows, pretty_printed, input_ranges))
+    }
+
+    // The text of the input that a row corresponds to; for containers, this
+    // is the entire container, whether or not it has been loaded.
+    pub fn input_text(&self, index: Index) -> String {
+        String::from_utf8_lossy(&self.mmap[self.input_ranges[index].clone()]).into_owned()
+    }
+
+    pub fn input(&self) -> &[u8] {
+        &self.mmap
+    }
+}
+
+struct LazyParser<'a> {
+    input: &'a LazyInput,
+    tokenizer: Lexer<'a, JsonToken>,
+    // Where the text being tokenized starts in the input.
+    offset: usize,
+
+    // Rows are created with their final indexes, starting at first_index.
+    first_index: Index,
+    depth: usize,
+    parent: OptionIndex,
+
+    rows: Vec<Row>,
+    pretty_printed: String,
+    input_ranges: Vec<Range<usize>>,
+}
+
+impl<'a> LazyParser<'a> {
+    fn new(
+        input: &'a LazyInput,
+        range: Range<usize>,
+        first_index: Index,
+        depth: usize,
+        parent: OptionIndex,
+    ) -> LazyParser<'a> {
+        let offset = range.start;
+        // SAFETY: The input was validated as UTF-8 when it was opened, and
+        // ranges always start and end at ASCII delimiters (or the start
+        // and end of the input), so they're valid UTF-8 too.
+        let text = unsafe { std::str::from_utf8_unchecked(&input.mmap[range]) };
+
+        LazyParser {
+            input,
+            tokenizer: JsonToken::lexer(text),
+            offset,
+            first_index,
+            depth,
+            parent,
+            rows: vec![],
+            pretty_printed: String::new(),
+            input_ranges: vec![],
+        }
+    }
+
+    // Parse the contents of a container, returning the rows for the
+    // container, including its open and close rows.
+    #[allow(clippy::type_complexity)]
+    fn parse_container(
+        &mut self,
+        container_type: ContainerType,
+    ) -> Result<(Vec<Row>, String, Vec<Range<usize>>), String> {
+        let open_value = Value::OpenContainer {
+            container_type,
+            collapsed: false,
+            // To be set when parsing is complete.
+            first_child: 0,
+            close_index: 0,
+        };
+
+        // The open and close rows are created with the same parent and
+        // depth as the children; FlatJson::load_container fixes them.
+        let open_index = self.create_row(open_value, 0..0);
+        self.pretty_printed.push_str(container_type.open_str());
+
+        let last_child = self.parse_values(Some(container_type))?;
+        let OptionIndex::Index(last_child) = last_child else {
+            return Err("Unexpected empty container".to_string());
+        };
+
+        let close_value = Value::CloseContainer {
+            container_type,
+            collapsed: false,
+            last_child,
+            open_index,
+        };
+
+        let close_index = self.create_row(close_value, 0..0);
+        let close_start = self.pretty_printed.len();
+        self.pretty_printed.push_str(container_type.close_str());
+        self.rows.last_mut().unwrap().range = close_start..self.pretty_printed.len();
+
+        let open_row = &mut self.rows[0];
+        open_row.range.end = self.pretty_printed.len();
+        if let Value::OpenContainer {
+            first_child: ref mut first,
+            close_index: ref mut close,
+            ..
+        } = open_row.value
+        {
+            *first = open_index + 1;
+            *close = close_index;
+        }
+
+        let rows = std::mem::take(&mut self.rows);
+        let pretty_printed = std::mem::take(&mut self.pretty_printed);
+        let input_ranges = std::mem::take(&mut self.input_ranges);
+
+        Ok((rows, pretty_printed, input_ranges))
+    }
+
+    // Parse a sequence of values, either the children of a container, or
+    // top-level values if container_type is None. Returns the index of the
+    // last value.
+    fn parse_values(
+        &mut self,
+        container_type: Option<ContainerType>,
+    ) -> Result<OptionIndex, String> {
+        let mut prev_sibling = OptionIndex::Nil;
+        let mut num_values = 0;
+
+        while let Some(mut token) = self.next_token() {
+            if num_values > 0 {
+                match container_type {
+                    Some(_) => {
+                        if token != JsonToken::Comma {
+                            return Err(self.unexpected_token(token));
+                        }
+                        token = self.expect_token()?;
+                        self.pretty_printed.push_str(", ");
+                    }
+                    // Top-level values are separated by newlines.
+                    None => self.pretty_printed.push('\n'),
+                }
+            } else if let Some(ContainerType::Object) = container_type {
+                // Add space inside objects.
+                self.pretty_printed.push(' ');
+            }
+
+            let key_range = if let Some(ContainerType::Object) = container_type {
+                if token != JsonToken::String {
+                    return Err(self.unexpected_token(token));
+                }
+
+                let key_range_start = self.pretty_printed.len();
+                self.pretty_printed.push_str(self.tokenizer.slice());
+                let key_range = key_range_start..self.pretty_printed.len();
+
+                let colon = self.expect_token()?;
+                if colon != JsonToken::Colon {
+                    return Err(self.unexpected_token(colon));
+                }
+                self.pretty_printed.push_str(": ");
+
+                token = self.expect_token()?;
+                Some(key_range)
+            } else {
+                None
+            };
+
+            let value_index = self.parse_value(token)?;
+            let row = &mut self.rows[value_index - self.first_index];
+            row.key_range = key_range;
+            row.index_in_parent = num_values;
+            row.prev_sibling = prev_sibling;
+
+            if let OptionIndex::Index(prev) = prev_sibling {
+                self.rows[prev - self.first_index].next_sibling = OptionIndex::Index(value_index);
+            }
+
+            prev_sibling = OptionIndex::Index(value_index);
+            num_values += 1;
+        }
+
+        if num_values > 0 {
+            if let Some(ContainerType::Object) = container_type {
+                // Print space inside closing brace.
+                self.pretty_printed.push(' ');
+            }
+        }
+
+        Ok(prev_sibling)
+    }
+
+    fn parse_value(&mut self, token: JsonToken) -> Result<Index, String> {
+        let span = self.tokenizer.span();
+        let input_start = self.offset + span.start;
+
+        let value = match token {
+            JsonToken::Null => Value::Null,
+            JsonToken::True | JsonToken::False => Value::Boolean,
+            JsonToken::Number => Value::Number,
+            JsonToken::String => Value::String,
+            JsonToken::OpenCurly => return self.parse_unloaded_container(ContainerType::Object),
+            JsonToken::OpenSquare => return self.parse_unloaded_container(ContainerType::Array),
+            _ => return Err(self.unexpected_token(token)),
+        };
+
+        let index = self.create_row(value, input_start..(self.offset + span.end));
+        let range_start = self.pretty_printed.len();
+        self.pretty_printed.push_str(self.tokenizer.slice());
+        self.rows[index - self.first_index].range = range_start..self.pretty_printed.len();
+
+        Ok(index)
+    }
+
+    // Create the rows for a container without parsing its contents, and
+    // skip over it.
+    fn parse_unloaded_container(&mut self, container_type: ContainerType) -> Result<Index, String> {
+        let input = self.input.input();
+        let start = self.offset + self.tokenizer.span().start;
+
+        let end = match self
+            .input
+            .large_containers
+            .binary_search_by_key(&start, |range| range.start)
+        {
+            Ok(i) => self.input.large_containers[i].end,
+            Err(_) => match find_container_end(input, start) {
+                Some(end) => end,
+                None => return Err("Unexpected EOF".to_string()),
+            },
+        };
+
+        // The container must end before the text we're parsing does.
+        if end > self.offset + self.tokenizer.source().len() {
+            return Err("Unexpected EOF".to_string());
+        }
+
+        // Skip over the rest of the container; the tokenizer is currently
+        // just past the opening delimiter.
+        self.tokenizer.bump(end - (start + 1));
+
+        let is_empty = input[(start + 1)..(end - 1)]
+            .iter()
+            .all(|b| b.is_ascii_whitespace());
+
+        let range_start = self.pretty_printed.len();
+
+        if is_empty {
+            let value = match container_type {
+                ContainerType::Object => Value::EmptyObject,
+                ContainerType::Array => Value::EmptyArray,
+            };
+            let index = self.create_row(value, start..end);
+            self.pretty_printed.push_str(container_type.open_str());
+            self.pretty_printed.push_str(container_type.close_str());
+            self.rows[index - self.first_index].range = range_start..self.pretty_printed.len();
+            return Ok(index);
+        }
+
+        let open_index = self.first_index + self.rows.len();
+        let close_index = open_index + 1;
+
+        // Unloaded containers are always collapsed; see Row::is_unloaded.
+        self.create_row(
+            Value::OpenContainer {
+                container_type,
+                collapsed: true,
+                first_child: close_index,
+                close_index,
+            },
+            start..end,
+        );
+        self.create_row(
+            Value::CloseContainer {
+                container_type,
+                collapsed: true,
+                last_child: open_index,
+                open_index,
+            },
+            (end - 1)..end,
+        );
+
+        self.pretty_printed
+            .push_str(container_type.collapsed_preview());
+        let range_end = self.pretty_printed.len();
+
+        self.rows[open_index - self.first_index].range = range_start..range_end;
+        let close_start = range_end - container_type.close_str().len();
+        self.rows[close_index - self.first_index].range = close_start..range_end;
+
+        Ok(open_index)
+    }
+
+    fn next_token(&mut self) -> Option<JsonToken> {
+        loop {
+            match self.tokenizer.next() {
+                Some(JsonToken::Newline) => continue,
+                token => return token,
+            }
+        }
+    }
+
+    fn expect_token(&mut self) -> Result<JsonToken, String> {
+        self.next_token()
+            .ok_or_else(|| "Unexpected EOF".to_string())
+    }
+
+    fn unexpected_token(&self, token: JsonToken) -> String {
+        let position = self.offset + self.tokenizer.span().start;
+        format!("Unexpected token {token:?} at byte {position}")
+    }
+
+    // Add a new row; the caller is responsible for setting its range
+    // and siblings.
+    fn create_row(&mut self, value: Value, input_range: Range<usize>) -> Index {
+        let index = self.first_index + self.rows.len();
+
+        self.rows.push(Row {
+            parent: self.parent,
+            prev_sibling: OptionIndex::Nil,
+            next_sibling: OptionIndex::Nil,
+            depth: self.depth,
+            index_in_parent: 0,
+            range: 0..0,
+            key_range: None,
+            value,
+            hidden: false,
+        });
+        self.input_ranges.push(input_range);
+
+        index
+    }
+}
+
+// Find the end of the container that starts at the given position,
+// without validating its contents.
+fn find_container_end(input: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &byte) in input.iter().enumerate().skip(start) {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Find the byte ranges of all the large containers in the input. Malformed
+// input is ignored here; errors are reported when containers are loaded.
+fn index_large_containers(input: &[u8]) -> Vec<Range<usize>> {
+    let mut large_containers = vec![];
+    let mut open_containers = vec![];
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &byte) in input.iter().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => open_containers.push(i),
+            b'}' | b']' => {
+                if let Some(start) = open_containers.pop() {
+                    if i + 1 - start >= LARGE_CONTAINER_SIZE {
+                        large_containers.push(start..(i + 1));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Containers are added when they're closed, so nested containers come
+    // before their parents.
+    large_containers.sort_unstable_by_key(|range| range.start);
+    large_containers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson;
+
+    fn open_lazy_input(name: &str, json: &str) -> (LazyInput, FlatJson) {
+        let dir = std::env::temp_dir().join(format!("jless-lazy-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, json).unwrap();
+
+        let result = LazyInput::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn test_fully_loaded_matches_parsed() {
+        // Include a container large enough to be indexed.
+        let numbers = (0..1000).map(|i| i.to_string()).collect::<Vec<_>>();
+        let json = format!(
+            r#"{{"a": [1, {{"b": null}}, []], "c": {{}}, "large": [{}]}} "str" [[true]]"#,
+            numbers.join(", ")
+        );
+
+        let (mut lazy_input, mut lazy) = open_lazy_input("full.json", &json);
+        assert_eq!(lazy.0.len(), 5);
+        assert_eq!(lazy.1, "{…}\n\"str\"\n[…]");
+        assert!(lazy[0].is_unloaded());
+
+        while let Some(index) = lazy.0.iter().position(|row| row.is_unloaded()) {
+            lazy_input.load_containers(&mut lazy, &[index]).unwrap();
+        }
+
+        let mut parsed = flatjson::parse_top_level_json(json.clone()).unwrap();
+        for i in 0..lazy.0.len() {
+            lazy.expand(i);
+            parsed.expand(i);
+        }
+
+        assert_eq!(lazy.1, parsed.1);
+        assert_eq!(lazy.2, parsed.2);
+        assert_eq!(format!("{:?}", lazy.0), format!("{:?}", parsed.0));
+
+        // Rows still know where they came from in the input.
+        assert_eq!(lazy_input.input_text(1), "[1, {\"b\": null}, []]");
+        assert_eq!(lazy_input.input_text(4), "null");
+    }
+
+    #[test]
+    fn test_other_containers_are_loaded_at_the_same_time() {
+        let objects = (0..100)
+            .map(|i| format!(r#"{{"id": {i}, "tags": ["a", "b"]}}"#))
+            .collect::<Vec<_>>();
+        let json = format!("[{}]", objects.join(", "));
+
+        let (mut lazy_input, mut lazy) = open_lazy_input("batch.json", &json);
+        lazy_input.load_containers(&mut lazy, &[0]).unwrap();
+        assert_eq!(lazy.0.len(), 202);
+
+        // Loading the 50th object also loads enough objects after it to
+        // grow the document by a quarter.
+        let fiftieth = 1 + 49 * 2;
+        let loaded = lazy_input.load_containers(&mut lazy, &[fiftieth]).unwrap();
+        assert_eq!(loaded[0].index, fiftieth);
+        assert_eq!(loaded.len(), 17);
+        assert!(loaded.windows(2).all(|pair| pair[0].index < pair[1].index));
+        assert_eq!(lazy.0.len(), 202 + 17 * 3);
+        assert!(!lazy[fiftieth].is_unloaded());
+        assert!(lazy[fiftieth].is_collapsed());
+        assert!(lazy[fiftieth - 2].is_unloaded());
+        assert_eq!(shift_index(&loaded, fiftieth), fiftieth);
+        assert_eq!(shift_index(&loaded, fiftieth + 1), fiftieth + 4);
+
+        // The rows, their text, and the input they came from are all
+        // updated consistently.
+        let tags = lazy[fiftieth + 1].next_sibling.unwrap();
+        assert_eq!(&lazy.1[lazy[tags].key_range.clone().unwrap()], "\"tags\"");
+        assert_eq!(lazy_input.input_text(tags), r#"["a", "b"]"#);
+        assert_eq!(lazy_input.input_text(fiftieth + 5), objects[50]);
+        assert_eq!(lazy[fiftieth + 5].parent, OptionIndex::Index(0));
+        assert_eq!(
+            lazy[fiftieth + 5].prev_sibling,
+            OptionIndex::Index(fiftieth)
+        );
+
+        // Everything is eventually loaded, in any order.
+        while let Some(index) = lazy
+            .0
+            .iter()
+            .rposition(|row| row.is_opening_of_container() && row.is_unloaded())
+        {
+            lazy_input.load_containers(&mut lazy, &[index]).unwrap();
+        }
+
+        let mut parsed = flatjson::parse_top_level_json(json).unwrap();
+        for i in 0..lazy.0.len() {
+            lazy.expand(i);
+            parsed.expand(i);
+        }
+
+        assert_eq!(lazy.1, parsed.1);
+        assert_eq!(lazy.2, parsed.2);
+        assert_eq!(format!("{:?}", lazy.0), format!("{:?}", parsed.0));
+    }
+
+    #[test]
+    fn test_errors_are_reported_when_loading() {
+        let (mut lazy_input, mut lazy) =
+            open_lazy_input("error.json", "[1, {\"a\" 2}, {\"b\": 3}]");
+        assert!(lazy_input.load_containers(&mut lazy, &[0]).is_ok());
+        assert!(lazy[2].is_unloaded());
+
+        // The container that can't be loaded isn't loaded with other ones.
+        assert!(lazy_input.load_containers(&mut lazy, &[4]).is_ok());
+        assert!(lazy[2].is_unloaded());
+        assert!(lazy_input.load_containers(&mut lazy, &[2]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let dir = std::env::temp_dir().join(format!("jless-lazy-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("invalid.json");
+        std::fs::write(&path, b"[\"\xff\"]").unwrap();
+
+        let result = LazyInput::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -6,6 +6,7 @@ use std::ops::Range;
 
 use regex::Regex;
 
+use crate::diff::DiffStatus;
 use crate::flatjson::{FlatJson, OptionIndex, Row, Value};
 use crate::highlighting;
 use crate::search::MatchRangeIter;
@@ -85,6 +86,7 @@ const FOCUSED_EXPANDED_CONTAINER: &str = "▼ ";
 const COLLAPSED_CONTAINER: &str = "▷ ";
 const EXPANDED_CONTAINER: &str = "▽ ";
 const INDICATOR_WIDTH: isize = 2;
+const MARKER_WIDTH: isize = 2;
 const NO_FOCUSED_MATCH: Range<usize> = 0..0;
 
 lazy_static::lazy_static! {
@@ -154,6 +156,11 @@ pub struct LinePrinter<'a, 'b> {
     pub focused: bool,
     pub focused_because_matching_container_pair: bool,
     pub trailing_comma: bool,
+    // Present when viewing a diff.
+    pub diff_status: Option<DiffStatus>,
+    // Present when the document was recovered from errors in the input;
+    // whether this line is where an error was recovered from.
+    pub recovered_error: Option<bool>,
 
     // For highlighting
     pub search_matches: Option<Peekable<MatchRangeIter<'b>>>,
@@ -174,6 +181,9 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         let mut available_space = self.width;
 
+        let space_used_for_marker = self.print_marker(available_space)?;
+        available_space -= space_used_for_marker;
+
         let space_used_for_line_number = self.print_line_number(available_space)?;
         available_space -= space_used_for_line_number;
 
@@ -203,6 +213,33 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         Ok(())
     }
 
+    // When viewing a diff, we show whether each line was added ('+') or
+    // removed ('-'), or is a container with changes inside of it ('~').
+    // When the input had errors, we show where they were recovered from
+    // ('!').
+    fn print_marker(&mut self, available_space: isize) -> Result<isize, fmt::Error> {
+        let (marker, style) = match (self.diff_status, self.recovered_error) {
+            (Some(DiffStatus::Unchanged), _) => (' ', &highlighting::DEFAULT_STYLE),
+            (Some(DiffStatus::Added), _) => ('+', &highlighting::ADDED_STYLE),
+            (Some(DiffStatus::Removed), _) => ('-', &highlighting::REMOVED_STYLE),
+            (Some(DiffStatus::Changed), _) => ('~', &highlighting::CHANGED_STYLE),
+            (None, Some(true)) => ('!', &highlighting::RECOVERED_ERROR_STYLE),
+            (None, Some(false)) => (' ', &highlighting::DEFAULT_STYLE),
+            (None, None) => return Ok(0),
+        };
+
+        if MARKER_WIDTH >= available_space {
+            return Ok(0);
+        }
+
+        self.terminal.set_style(style)?;
+        write!(self.terminal, "{marker}")?;
+        self.terminal.reset_style()?;
+        write!(self.terminal, " ")?;
+
+        Ok(MARKER_WIDTH)
+    }
+
     // Absolute | Relative | Focused | Format
     // ---------+----------+---------+--------
     //     N    |     N    |    -    | Nothing
@@ -375,7 +412,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.terminal,
             delimiter.left(),
             label_open_delimiter_range_start,
-            style,
+            &style,
             highlighted_style,
             &mut matches,
             self.focused_search_match,
@@ -387,7 +424,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             label_ref,
             &truncated_view,
             label_range_start,
-            style,
+            &style,
             highlighted_style,
             &mut matches,
             self.focused_search_match,
@@ -398,7 +435,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.terminal,
             delimiter.right(),
             label_close_delimiter_range_start,
-            style,
+            &style,
             highlighted_style,
             &mut matches,
             self.focused_search_match,
@@ -421,6 +458,13 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         Ok(used_space)
     }
 
+    // Print just the value of the row, without a line number, indicators
+    // or label, e.g., in a cell of a table. Returns how much space was used.
+    pub fn print_value(&mut self, available_space: isize) -> Result<isize, fmt::Error> {
+        self.terminal.reset_style()?;
+        self.fill_in_value(available_space)
+    }
+
     // Check if a line has a label. A line has a label if it has
     // a key, or if we are in data mode and we have a parent.
     fn has_label(&self) -> bool {
@@ -468,26 +512,34 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         }
     }
 
-    fn get_label_styles(&self) -> (&'static Style, &'static Style) {
+    fn get_label_styles(&self) -> (Style, &'static Style) {
+        // Labels of added and removed lines are colored like their markers.
+        let diff_style = match self.diff_status {
+            Some(DiffStatus::Added) => Some(highlighting::ADDED_STYLE),
+            Some(DiffStatus::Removed) => Some(highlighting::REMOVED_STYLE),
+            _ => None,
+        };
+        let colors = highlighting::colors();
+
         match self.label_type() {
             LabelType::Key => {
                 if self.focused {
                     (
-                        &highlighting::INVERTED_BOLD_BLUE_STYLE,
+                        colors.focused_key_style(),
                         &highlighting::BOLD_INVERTED_STYLE,
                     )
                 } else {
                     (
-                        &highlighting::BLUE_STYLE,
+                        diff_style.unwrap_or_else(|| colors.key_style()),
                         &highlighting::SEARCH_MATCH_HIGHLIGHTED,
                     )
                 }
             }
             LabelType::Index => {
                 let style = if self.focused {
-                    &highlighting::BOLD_INVERTED_STYLE
+                    highlighting::BOLD_INVERTED_STYLE
                 } else {
-                    &highlighting::DIMMED_STYLE
+                    diff_style.unwrap_or(highlighting::DIMMED_STYLE)
                 };
 
                 // No match highlighting for index labels.
@@ -652,14 +704,15 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             .unwrap_or_else(|| TruncatedStrView::init_start(value_ref, available_space))
     }
 
-    fn color_for_value_type(value: &Value) -> Color {
+    pub fn color_for_value_type(value: &Value) -> Color {
         debug_assert!(value.is_primitive());
 
+        let colors = highlighting::colors();
         match value {
-            Value::Null => terminal::LIGHT_BLACK,
-            Value::Boolean => terminal::YELLOW,
-            Value::Number => terminal::MAGENTA,
-            Value::String => terminal::GREEN,
+            Value::Null => colors.null,
+            Value::Boolean => colors.boolean,
+            Value::Number => colors.number,
+            Value::String => colors.string,
             Value::EmptyObject => terminal::WHITE,
             Value::EmptyArray => terminal::WHITE,
             _ => unreachable!(),
@@ -796,6 +849,12 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             available_space -= 1;
         }
 
+        // In large-file mode, if a container couldn't be loaded, we can't
+        // show a preview of its contents.
+        if row.is_unloaded() {
+            return self.fill_in_value_preview(row, available_space);
+        }
+
         let always_quote_string_object_keys = self.mode == Mode::Line;
         let is_nested = false;
         let mut used_space = self.generate_container_preview(
@@ -1000,17 +1059,18 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.highlight_str(": ", Some(key_range.end), highlighting::PREVIEW_STYLES)?;
         }
 
-        let space_used_for_value = if is_only_child && row.value.is_container() {
-            let is_nested = true;
-            self.generate_container_preview(
-                row,
-                available_space,
-                is_nested,
-                always_quote_string_object_keys,
-            )?
-        } else {
-            self.fill_in_value_preview(row, available_space)?
-        };
+        let space_used_for_value =
+            if is_only_child && row.value.is_container() && !row.is_unloaded() {
+                let is_nested = true;
+                self.generate_container_preview(
+                    row,
+                    available_space,
+                    is_nested,
+                    always_quote_string_object_keys,
+                )?
+            } else {
+                self.fill_in_value_preview(row, available_space)?
+            };
         used_space += space_used_for_value;
 
         // Make sure to print out ellipsis for the value if we printed out an
@@ -1227,6 +1287,8 @@ mod tests {
             focused: false,
             focused_because_matching_container_pair: false,
             trailing_comma: false,
+            diff_status: None,
+            recovered_error: None,
             search_matches: None,
             focused_search_match: &DUMMY_RANGE,
             emphasize_focused_search_match: true,
@@ -1307,6 +1369,55 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_diff_markers() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"{"a": 1}"#.to_owned()).unwrap();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+        line.line_number.absolute = Some(2);
+
+        for (diff_status, expected) in vec![
+            (None, "   2   a: 1"),
+            (Some(DiffStatus::Unchanged), "     2   a: 1"),
+            (Some(DiffStatus::Added), "+    2   a: 1"),
+            (Some(DiffStatus::Removed), "-    2   a: 1"),
+            (Some(DiffStatus::Changed), "~    2   a: 1"),
+        ]
+        .into_iter()
+        {
+            line.terminal.clear_output();
+            line.diff_status = diff_status;
+            line.print_line()?;
+            assert_eq!(expected, line.terminal.output());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recovered_error_markers() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"{"a": 1}"#.to_owned()).unwrap();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+        line.line_number.absolute = Some(2);
+
+        for (recovered_error, expected) in vec![
+            (Some(false), "     2   a: 1"),
+            (Some(true), "!    2   a: 1"),
+        ]
+        .into_iter()
+        {
+            line.terminal.clear_output();
+            line.recovered_error = recovered_error;
+            line.print_line()?;
+            assert_eq!(expected, line.terminal.output());
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_print_line_tracks_available_space() -> std::fmt::Result {
         const JSON: &str = r#"{
@@ -9,65 +9,230 @@ extern crate libc_stdhandle;
 
 use std::fs::File;
 use std::io;
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
 use termion::cursor::HideCursor;
 use termion::input::MouseTerminal;
-use termion::raw::IntoRawMode;
+use termion::raw::{IntoRawMode, RawTerminal};
 use termion::screen::AlternateScreen;
 
+use crate::flatjson::FlatJson;
+use crate::viewer::Mode;
+
 mod app;
+mod binaryparser;
+mod config;
+mod csvparser;
+mod decompress;
+mod diff;
+mod documentprinter;
+mod edit;
+mod filter;
 mod flatjson;
+mod flatjsonbuilder;
+mod follow;
 mod highlighting;
 mod input;
 mod jsonparser;
+mod jsonstream;
+mod jsonstringescaper;
 mod jsonstringunescaper;
 mod jsontokenizer;
+mod jumplist;
+mod keybindings;
+mod lazyjson;
 mod lineprinter;
 mod options;
+mod parseerror;
 mod screenwriter;
 mod search;
+mod tableview;
 mod terminal;
+mod tomlparser;
 mod truncatedstrview;
 mod types;
 mod viewer;
 mod yamlparser;
 
 use app::App;
-use options::{DataFormat, Opt};
+use options::{ColorWhen, DataFormat, Opt, OutputFormat};
+
+const STDIN_FILENAME: &str = "STDIN";
 
 fn main() {
-    let opt = Opt::parse();
+    let opt = config::parse_options();
 
-    let (input_string, input_filename) = match get_input_and_filename(&opt) {
-        Ok(input_and_filename) => input_and_filename,
-        Err(err) => {
-            eprintln!("Unable to get input: {err}");
+    if let Some(paths) = &opt.diff {
+        view_diff(&opt, &paths[0], &paths[1]);
+        return;
+    }
+
+    if opt.input.is_empty() && isatty::stdin_isatty() {
+        println!("Missing filename (\"jless --help\" for help)");
+        std::process::exit(1);
+    }
+
+    if opt.color == ColorWhen::Always
+        && matches!(
+            opt.output,
+            Some(OutputFormat::Compact | OutputFormat::Yaml | OutputFormat::Ndjson)
+        )
+    {
+        eprintln!("--color=always can only be used with JSON output");
+        std::process::exit(1);
+    }
+
+    if opt.watch && reads_from_stdin(&opt) {
+        eprintln!("--watch requires an input file");
+        std::process::exit(1);
+    }
+
+    // When viewing JSON from STDIN, we start the viewer immediately and
+    // display top-level values as they arrive, rather than waiting for
+    // all of the input to be read. (Unless we're filtering it, or going
+    // to a path in it, or parsing it leniently, in which case we need all
+    // of it.)
+    let stream_stdin = isatty::stdout_isatty()
+        && opt.input.len() <= 1
+        && reads_from_stdin(&opt)
+        && opt.filter.is_none()
+        && opt.path.is_none()
+        && !opt.lenient
+        && determine_data_format(&opt, STDIN_FILENAME) == DataFormat::Json;
+
+    if stream_stdin {
+        let data_source = match input::take_stdin_as_data_source() {
+            Ok(data_source) => data_source,
+            Err(err) => {
+                eprintln!("Unable to get input: {err}");
+                std::process::exit(1);
+            }
+        };
+
+        input::remap_dev_tty_to_stdin();
+
+        let mut app = App::new_streaming(&opt, STDIN_FILENAME.to_string(), raw_stdout());
+        app.run(input::get_input(Some(data_source)));
+        return;
+    }
+
+    // When following a file, we load it (and anything appended to it
+    // later) incrementally, just like input streamed via STDIN.
+    if opt.follow && isatty::stdout_isatty() && !reads_from_stdin(&opt) {
+        if opt.input.len() > 1 {
+            eprintln!("--follow only supports a single input file");
             std::process::exit(1);
         }
-    };
 
-    let data_format = determine_data_format(opt.data_format(), &input_filename);
+        let path = opt.input[0].clone();
+        let filename = filename_of_path(&path);
+
+        if determine_data_format(&opt, &filename) != DataFormat::Json {
+            eprintln!("--follow is only supported for JSON input");
+            std::process::exit(1);
+        }
+
+        if decompress::strip_compression_extension(&filename) != filename {
+            eprintln!("--follow is not supported for compressed input");
+            std::process::exit(1);
+        }
+
+        input::remap_dev_tty_to_stdin();
+
+        let mut app = match App::new_following(&opt, path, filename, raw_stdout()) {
+            Ok(jl) => jl,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        };
+
+        app.run(input::get_input(None));
+        return;
+    }
+
+    // In large-file mode, we memory map the input file rather than reading
+    // it, and only parse the parts of it that are displayed.
+    if opt.large_file {
+        let Some(path) = opt.input.first().filter(|path| path.as_os_str() != "-") else {
+            eprintln!("--large-file requires an input file");
+            std::process::exit(1);
+        };
+        if opt.input.len() > 1 {
+            eprintln!("--large-file only supports a single input file");
+            std::process::exit(1);
+        }
+        let path = path.clone();
+        let filename = filename_of_path(&path);
+
+        if determine_data_format(&opt, &filename) != DataFormat::Json {
+            eprintln!("--large-file is only supported for JSON input");
+            std::process::exit(1);
+        }
+
+        // Don't parse the input at all if we're not displaying it.
+        if !isatty::stdout_isatty() {
+            let result =
+                File::open(&path).and_then(|mut file| io::copy(&mut file, &mut io::stdout()));
+            if let Err(err) = result {
+                eprintln!("Unable to get input: {err}");
+                std::process::exit(1);
+            }
+            return;
+        }
+
+        input::remap_dev_tty_to_stdin();
+
+        let mut app = match App::new_large_file(&opt, &path, filename, raw_stdout()) {
+            Ok(jl) => jl,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        };
+
+        app.run(input::get_input(None));
+        return;
+    }
+
+    // Every input file is opened in its own buffer.
+    let mut inputs = vec![];
+    let paths = match opt.input.len() {
+        0 => vec![None],
+        _ => opt.input.iter().map(Some).collect(),
+    };
+    for path in paths {
+        match get_input_and_filename(path) {
+            Ok(input_and_filename) => inputs.push(input_and_filename),
+            Err(err) => {
+                eprintln!("Unable to get input: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
 
     if !isatty::stdout_isatty() {
-        print_pretty_printed_input(input_string, data_format);
+        let outputs = inputs.into_iter().map(|(input, input_filename)| {
+            let data_format = determine_data_format(&opt, &input_filename);
+            let is_yaml = prints_yaml(data_format, &opt);
+            (pretty_printed_input(input, data_format, &opt), is_yaml)
+        });
+        let _ = io::stdout().write_all(&join_outputs(outputs));
         std::process::exit(0);
     }
 
+    let mut inputs = inputs.into_iter();
+    let (input, input_filename) = inputs.next().unwrap();
+    let data_format = determine_data_format(&opt, &input_filename);
+
     // We use freopen to remap /dev/tty to STDIN so that rustyline works when
     // JSON input is provided via STDIN. rustyline gets initialized when we
     // create the App, so by putting this before creating the app, we make
     // sure rustyline gets the /dev/tty input.
     input::remap_dev_tty_to_stdin();
 
-    let stdout = Box::new(MouseTerminal::from(HideCursor::from(
-        AlternateScreen::from(io::stdout()),
-    ))) as Box<dyn std::io::Write>;
-    let raw_stdout = stdout.into_raw_mode().unwrap();
-
-    let mut app = match App::new(&opt, input_string, data_format, input_filename, raw_stdout) {
+    let mut app = match App::new(&opt, input, data_format, input_filename, raw_stdout()) {
         Ok(jl) => jl,
         Err(err) => {
             eprintln!("{err}");
@@ -75,62 +240,262 @@ fn main() {
         }
     };
 
-    app.run(Box::new(input::get_input()));
+    for ((input, input_filename), path) in inputs.zip(opt.input.iter().skip(1)) {
+        let data_format = determine_data_format(&opt, &input_filename);
+        if let Err(err) = app.open_buffer(&opt, path, input, data_format, input_filename) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+
+    app.run(input::get_input(None));
 }
 
-fn print_pretty_printed_input(input: String, data_format: DataFormat) {
-    // Don't try to pretty print YAML input; just pass it through.
-    if data_format == DataFormat::Yaml {
-        print!("{input}");
+// Compare two files, displaying the merged document, or printing a list
+// of the changes if we're not outputting to a TTY.
+fn view_diff(opt: &Opt, left_path: &Path, right_path: &Path) {
+    let (left, left_filename) = read_and_parse_file(opt, left_path);
+    let (right, right_filename) = read_and_parse_file(opt, right_path);
+
+    let (flatjson, statuses) = diff::diff(&left, &right, opt.diff_id_key.as_deref());
+
+    if !isatty::stdout_isatty() {
+        print!("{}", diff::format_changes(&flatjson, &statuses));
         return;
     }
 
-    let flatjson = match flatjson::parse_top_level_json(input) {
-        Ok(flatjson) => flatjson,
+    input::remap_dev_tty_to_stdin();
+
+    let input_filename = format!("{left_filename} → {right_filename}");
+    let mut app = App::new_diff(opt, flatjson, statuses, input_filename, raw_stdout());
+    app.run(input::get_input(None));
+}
+
+// Read and parse one of the files being compared, exiting if we're unable to.
+fn read_and_parse_file(opt: &Opt, path: &Path) -> (FlatJson, String) {
+    let mut input = vec![];
+    let filename;
+
+    let result = if path.as_os_str() == "-" {
+        filename = STDIN_FILENAME.to_string();
+        io::stdin().read_to_end(&mut input)
+    } else {
+        filename = filename_of_path(path);
+        File::open(path).and_then(|mut file| file.read_to_end(&mut input))
+    };
+
+    if let Err(err) = result {
+        eprintln!("Unable to get input: {err}");
+        std::process::exit(1);
+    }
+
+    let data_format = determine_data_format(opt, &filename);
+    match App::parse_input(input, data_format, opt.parse_options()) {
+        // --lenient can't be used with --diff, so there are never any
+        // recovered errors.
+        Ok((flatjson, _)) => (flatjson, filename),
+        Err(err) => {
+            eprintln!("Unable to parse {filename}: {}", err.report());
+            std::process::exit(1);
+        }
+    }
+}
+
+fn raw_stdout() -> RawTerminal<Box<dyn io::Write>> {
+    let stdout = Box::new(MouseTerminal::from(HideCursor::from(
+        AlternateScreen::from(io::stdout()),
+    ))) as Box<dyn io::Write>;
+    stdout.into_raw_mode().unwrap()
+}
+
+// Unless we're asked to convert or highlight it, we don't try to pretty
+// print YAML input; we just pass it through.
+fn passes_yaml_through(data_format: DataFormat, opt: &Opt) -> bool {
+    data_format == DataFormat::Yaml
+        && opt.filter.is_none()
+        && opt.output.is_none()
+        && !highlights_output(opt)
+        && opt.mode != Some(Mode::Data)
+}
+
+// Only JSON output is highlighted.
+fn highlights_output(opt: &Opt) -> bool {
+    matches!(opt.output, None | Some(OutputFormat::Json)) && opt.color.highlights_output()
+}
+
+fn prints_yaml(data_format: DataFormat, opt: &Opt) -> bool {
+    opt.output == Some(OutputFormat::Yaml) || passes_yaml_through(data_format, opt)
+}
+
+// Join the outputs for each input file, along with whether they're YAML,
+// in which case each file's values are written as separate documents.
+fn join_outputs(outputs: impl IntoIterator<Item = (Vec<u8>, bool)>) -> Vec<u8> {
+    let mut joined = vec![];
+    let mut prev_is_yaml = false;
+
+    for (output, is_yaml) in outputs {
+        if is_yaml && prev_is_yaml {
+            if !joined.is_empty() && !joined.ends_with(b"\n") {
+                joined.push(b'\n');
+            }
+            joined.extend_from_slice(b"---\n");
+        }
+        joined.extend(output);
+        prev_is_yaml = is_yaml;
+    }
+
+    joined
+}
+
+fn pretty_printed_input(input: Vec<u8>, data_format: DataFormat, opt: &Opt) -> Vec<u8> {
+    if passes_yaml_through(data_format, opt) {
+        return match decompress::decompress(input) {
+            Ok(input) => input,
+            Err(err) => {
+                eprintln!("Unable to decompress input: {err}");
+                std::process::exit(1);
+            }
+        };
+    }
+
+    let mut flatjson = match App::parse_input(input, data_format, opt.parse_options()) {
+        Ok((flatjson, recovered_errors)) => {
+            for error in recovered_errors {
+                eprintln!("Recovered from error: {}", error.error.report());
+            }
+            flatjson
+        }
         Err(err) => {
-            eprintln!("Unable to parse input: {err:?}");
+            eprintln!("Unable to parse input: {}", err.report());
             std::process::exit(1);
         }
     };
 
-    print!("{}", flatjson.pretty_printed());
+    if let Some(filter) = &opt.filter {
+        flatjson = match filter::apply_filter(&flatjson, filter) {
+            Ok(flatjson) => flatjson,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        };
+    }
+
+    // Unlike in the viewer, line mode is the default here, so that the
+    // output is valid JSON.
+    let colored = highlights_output(opt);
+    let output = match (opt.output, opt.mode) {
+        (None | Some(OutputFormat::Json), mode) if colored || mode == Some(Mode::Data) => {
+            documentprinter::print_document(&flatjson, mode.unwrap_or(Mode::Line), colored)
+        }
+        (output, _) => flatjson.formatted(output.unwrap_or(OutputFormat::Json)),
+    };
+    output.into_bytes()
 }
 
-fn get_input_and_filename(opt: &Opt) -> io::Result<(String, String)> {
-    let mut input_string = String::new();
+// The input is returned as bytes, rather than as a String, because binary
+// data formats, like MessagePack, aren't valid UTF-8.
+fn get_input_and_filename(path: Option<&PathBuf>) -> io::Result<(Vec<u8>, String)> {
+    let mut input = vec![];
     let filename;
 
-    match &opt.input {
+    match path {
         None => {
-            if isatty::stdin_isatty() {
-                println!("Missing filename (\"jless --help\" for help)");
-                std::process::exit(1);
-            }
-            filename = "STDIN".to_string();
-            io::stdin().read_to_string(&mut input_string)?;
+            filename = STDIN_FILENAME.to_string();
+            io::stdin().read_to_end(&mut input)?;
         }
         Some(path) => {
-            if *path == PathBuf::from("-") {
-                filename = "STDIN".to_string();
-                io::stdin().read_to_string(&mut input_string)?;
+            if path.as_os_str() == "-" {
+                filename = STDIN_FILENAME.to_string();
+                io::stdin().read_to_end(&mut input)?;
             } else {
-                File::open(path)?.read_to_string(&mut input_string)?;
-                filename = String::from(path.file_name().unwrap().to_string_lossy());
+                File::open(path)?.read_to_end(&mut input)?;
+                filename = filename_of_path(path);
             }
         }
     }
 
-    Ok((input_string, filename))
+    Ok((input, filename))
 }
 
-fn determine_data_format(format: Option<DataFormat>, filename: &str) -> DataFormat {
-    format.unwrap_or_else(|| {
-        match std::path::Path::new(filename)
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-        {
-            Some("yml") | Some("yaml") => DataFormat::Yaml,
-            _ => DataFormat::Json,
-        }
-    })
+fn filename_of_path(path: &Path) -> String {
+    String::from(path.file_name().unwrap().to_string_lossy())
+}
+
+fn reads_from_stdin(opt: &Opt) -> bool {
+    match opt.input.first() {
+        None => true,
+        Some(path) => path.as_os_str() == "-",
+    }
+}
+
+fn determine_data_format(opt: &Opt, filename: &str) -> DataFormat {
+    // Compressed files are decompressed before they're parsed, so we use
+    // the extension before the compression extension, e.g., "yaml" for
+    // "data.yaml.gz".
+    let filename = decompress::strip_compression_extension(filename);
+    let extension = std::path::Path::new(filename)
+        .extension()
+        .and_then(std::ffi::OsStr::to_str);
+
+    // Formats of extensions in the config file take precedence.
+    let configured_format =
+        extension.and_then(|extension| opt.extension_formats.get(extension).copied());
+
+    let default_format = match extension {
+        Some("yml") | Some("yaml") => DataFormat::Yaml,
+        Some("json5") | Some("jsonc") => DataFormat::Json5,
+        Some("toml") => DataFormat::Toml,
+        Some("csv") => DataFormat::Csv,
+        Some("tsv") => DataFormat::Tsv,
+        Some("msgpack") | Some("mpk") => DataFormat::MsgPack,
+        Some("cbor") => DataFormat::Cbor,
+        _ => DataFormat::Json,
+    };
+
+    opt.data_format()
+        .or(configured_format)
+        .unwrap_or(default_format)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn test_prints_yaml() {
+        let opt = |args: &[&str]| Opt::try_parse_from([&["jless"], args].concat()).unwrap();
+
+        assert!(prints_yaml(DataFormat::Yaml, &opt(&["--color", "never"])));
+        assert!(prints_yaml(DataFormat::Json, &opt(&["--output", "yaml"])));
+        assert!(!prints_yaml(DataFormat::Yaml, &opt(&["--output", "json"])));
+        assert!(!prints_yaml(DataFormat::Yaml, &opt(&["--filter", ".a"])));
+        assert!(!prints_yaml(DataFormat::Json, &opt(&[])));
+    }
+
+    #[test]
+    fn test_join_outputs() {
+        let output = |s: &str, is_yaml| (s.as_bytes().to_vec(), is_yaml);
+
+        // Each file's values are separate YAML documents, even if a file
+        // doesn't end with a newline.
+        assert_eq!(
+            join_outputs([
+                output("a: 1\n", true),
+                output("b: 2", true),
+                output("- 3\n", true)
+            ]),
+            b"a: 1\n---\nb: 2\n---\n- 3\n",
+        );
+        assert_eq!(
+            join_outputs([output("{}\n", false), output("[]\n", false)]),
+            b"{}\n[]\n",
+        );
+        assert_eq!(
+            join_outputs([output("{}\n", false), output("a: 1\n", true)]),
+            b"{}\na: 1\n",
+        );
+    }
 }
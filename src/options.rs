@@ -1,33 +1,107 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 use clap::{ArgAction, Parser, ValueEnum};
+use termion::event::Key;
 
+use crate::csvparser::CsvOptions;
+use crate::keybindings::KeyAction;
 use crate::viewer::Mode;
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug, ValueEnum)]
 pub enum DataFormat {
     Json,
+    Json5,
+    Toml,
     Yaml,
+    Csv,
+    Tsv,
+    #[value(name = "msgpack")]
+    MsgPack,
+    Cbor,
+}
+
+// Formats that documents (or parts of them) can be written in.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ValueEnum)]
+pub enum OutputFormat {
+    // Pretty printed JSON.
+    Json,
+    // JSON without any whitespace, with each value on its own line.
+    Compact,
+    Yaml,
+    // Like compact, except that the elements of arrays are written on
+    // separate lines.
+    Ndjson,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ValueEnum)]
+pub enum ColorWhen {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorWhen {
+    // Whether to highlight output that isn't going to a TTY.
+    pub fn highlights_output(self) -> bool {
+        self.highlights_output_with_env(|name| std::env::var_os(name))
+    }
+
+    // With auto, the NO_COLOR and CLICOLOR_FORCE environment variables
+    // are honored, with NO_COLOR taking precedence.
+    fn highlights_output_with_env(self, env_var: impl Fn(&str) -> Option<OsString>) -> bool {
+        match self {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            ColorWhen::Auto => {
+                let no_color = env_var("NO_COLOR").map_or(false, |value| !value.is_empty());
+                let force = env_var("CLICOLOR_FORCE")
+                    .map_or(false, |value| !value.is_empty() && value != "0");
+                !no_color && force
+            }
+        }
+    }
+}
+
+impl OutputFormat {
+    // Determine the format to write a file in from its extension.
+    pub fn from_filename(filename: &str) -> Option<OutputFormat> {
+        match std::path::Path::new(filename)
+            .extension()
+            .and_then(std::ffi::OsStr::to_str)
+        {
+            Some("json") => Some(OutputFormat::Json),
+            Some("yml") | Some("yaml") => Some(OutputFormat::Yaml),
+            Some("ndjson") | Some("jsonl") => Some(OutputFormat::Ndjson),
+            _ => None,
+        }
+    }
 }
 
 /// A pager for JSON (or YAML) data
-#[derive(Debug, Parser)]
-#[command(name = "jless", version)]
+#[derive(Debug, Clone, Parser)]
+// Options may be given more than once, e.g., once in the config file and
+// again on the command line, in which case the last one wins.
+#[command(name = "jless", version, args_override_self = true)]
 pub struct Opt {
-    /// Input file. jless will read from stdin if no input file is
+    /// Input files. jless will read from stdin if no input file is
     /// provided, or '-' is specified. If a filename is provided, jless
     /// will check the extension to determine what the input format is,
     /// and by default will assume JSON. Can specify input format
-    /// explicitly using --json or --yaml.
-    pub input: Option<PathBuf>,
+    /// explicitly using --json, --json5, --yaml, --toml, --csv, --tsv,
+    /// --msgpack or --cbor. Multiple files are opened in separate buffers;
+    /// switch between them with :bn and :bp.
+    pub input: Vec<PathBuf>,
 
     /// Initial viewing mode. In line mode (--mode line), opening
     /// and closing curly and square brackets are shown and all
     /// Object keys are quoted. In data mode (--mode data; the default),
     /// closing braces, commas, and quotes around Object keys are elided.
-    /// The active mode can be toggled by pressing 'm'.
-    #[arg(short, long, value_enum, hide_possible_values = true, default_value_t = Mode::Data)]
-    pub mode: Mode,
+    /// The active mode can be toggled by pressing 'm'. When STDOUT isn't
+    /// a TTY, input is printed in line mode unless --mode data is given.
+    #[arg(short, long, value_enum, hide_possible_values = true)]
+    pub mode: Option<Mode>,
 
     // This godforsaken configuration to get both --line-numbers and --no-line-numbers to
     // work (with --line-numbers as the default) and --relative-line-numbers and
@@ -71,23 +145,256 @@ pub struct Opt {
     #[arg(long = "scrolloff", default_value_t = 3)]
     pub scrolloff: u16,
 
+    /// Follow the input as it grows, like 'tail -f'. New top-level values
+    /// are displayed as they're written to the input file, and the last
+    /// line is kept focused. Press any key to stop following; press 'F'
+    /// to start following again.
+    #[arg(long = "follow")]
+    pub follow: bool,
+
+    /// Reload the input file whenever it changes, keeping the same
+    /// containers collapsed and the same node focused. Press 'R' (or use
+    /// :e) to reload the input file manually. When viewing multiple files,
+    /// only the file in the current buffer is watched.
+    #[arg(long = "watch", conflicts_with_all = ["follow", "large_file"])]
+    pub watch: bool,
+
+    /// The format to print the input in when STDOUT isn't a TTY: pretty
+    /// printed JSON (json), JSON without whitespace (compact), YAML
+    /// (yaml), or with each element of top-level arrays on its own line
+    /// (ndjson). By default, JSON is pretty printed and YAML is printed
+    /// as is.
+    #[arg(long = "output", value_enum, value_name = "FORMAT")]
+    pub output: Option<OutputFormat>,
+
+    /// Whether to highlight the syntax of the pretty printed input when
+    /// STDOUT isn't a TTY, e.g., when piping it to 'less -R'. With auto
+    /// (the default), the output is only highlighted when STDOUT is a TTY,
+    /// or when the CLICOLOR_FORCE environment variable is set (to anything
+    /// other than 0), unless NO_COLOR is set. Only JSON output can be
+    /// highlighted.
+    #[arg(
+        long = "color",
+        value_enum,
+        value_name = "WHEN",
+        default_value_t = ColorWhen::Auto
+    )]
+    pub color: ColorWhen,
+
+    /// Display as much of invalid JSON (or JSON5) input as possible, rather
+    /// than failing. Containers that aren't closed, e.g., because the input
+    /// was truncated, are closed where the error occurred, and any lines
+    /// after an error that don't start with an object or an array are
+    /// skipped. Lines where errors were recovered from are marked with '!'.
+    #[arg(long = "lenient", conflicts_with_all = ["follow", "large_file"])]
+    pub lenient: bool,
+
+    /// View a large JSON file without loading all of it into memory. The
+    /// file is memory mapped, and the contents of each container are only
+    /// parsed the first time the container is displayed. Searches only find
+    /// matches in containers that have been displayed, and copying a
+    /// container copies it from the input as is.
+    #[arg(long = "large-file", conflicts_with = "follow")]
+    pub large_file: bool,
+
+    /// Apply a filter to the input before displaying it. Filters use a
+    /// subset of jq's syntax, e.g., '.items[] | select(.status != "ok")'.
+    /// When viewing the result, press Backspace (or use :unfilter) to
+    /// return to the original input. When STDOUT isn't a TTY, the result
+    /// is printed instead.
+    #[arg(long = "filter", value_name = "FILTER", conflicts_with_all = ["follow", "large_file"])]
+    pub filter: Option<String>,
+
+    /// Focus the node at the given path when starting, e.g., '.items[3].name',
+    /// '["items"][3]["name"]', or a JSON Pointer, like '/items/3/name'.
+    #[arg(long = "path", value_name = "PATH", conflicts_with = "follow")]
+    pub path: Option<String>,
+
+    /// Compare two files, displaying a merged tree in which added, removed
+    /// and changed values are marked with '+', '-' and '~'. Object entries
+    /// are matched up by key, and array elements by index (or see
+    /// --diff-id-key). Press ']c' and '[c' to jump between changes, and use
+    /// :changes to only show the parts that changed. When STDOUT isn't a
+    /// TTY, a list of the changes is printed instead.
+    #[arg(
+        long = "diff",
+        num_args = 2,
+        value_names = ["LEFT", "RIGHT"],
+        conflicts_with_all = ["input", "follow", "watch", "lenient", "large_file", "filter", "output"]
+    )]
+    pub diff: Option<Vec<PathBuf>>,
+
+    /// When comparing files with --diff, match up array elements that are
+    /// objects by the value of this key (e.g., 'id'), rather than by index.
+    #[arg(long = "diff-id-key", value_name = "KEY", requires = "diff")]
+    pub diff_id_key: Option<String>,
+
     /// Parse input as JSON, regardless of file extension.
     #[arg(long = "json", group = "data-format", display_order = 1000)]
     pub json: bool,
 
+    /// Parse input as JSON5, regardless of file extension. JSON5 is a
+    /// superset of JSON that allows comments, trailing commas, unquoted
+    /// object keys, single quoted strings, and hexadecimal numbers,
+    /// among other things. This also works for JSONC (JSON with comments).
+    #[arg(long = "json5", group = "data-format", display_order = 1000)]
+    pub json5: bool,
+
     /// Parse input as YAML, regardless of file extension.
     #[arg(long = "yaml", group = "data-format", display_order = 1000)]
     pub yaml: bool,
+
+    /// Parse input as TOML, regardless of file extension.
+    #[arg(long = "toml", group = "data-format", display_order = 1000)]
+    pub toml: bool,
+
+    /// Parse input as CSV, regardless of file extension. The input is
+    /// displayed as an array of records. By default, the first record is
+    /// treated as a header, and each record is displayed as an object using
+    /// the header fields as keys.
+    #[arg(long = "csv", group = "data-format", display_order = 1000)]
+    pub csv: bool,
+
+    /// Parse input as TSV (tab-separated values), regardless of file
+    /// extension. TSV input is handled the same as CSV input.
+    #[arg(long = "tsv", group = "data-format", display_order = 1000)]
+    pub tsv: bool,
+
+    /// Parse input as MessagePack, regardless of file extension. Binary
+    /// data is displayed as base64 encoded strings, and extension types
+    /// are displayed as objects with "ext" and "data" keys.
+    #[arg(long = "msgpack", group = "data-format", display_order = 1000)]
+    pub msgpack: bool,
+
+    /// Parse input as CBOR, regardless of file extension. Binary data is
+    /// displayed as base64 encoded strings, and tagged values are displayed
+    /// as the value they tag.
+    #[arg(long = "cbor", group = "data-format", display_order = 1000)]
+    pub cbor: bool,
+
+    /// Don't treat the first record of CSV or TSV input as a header;
+    /// display each record as an array of fields instead.
+    #[arg(long = "no-header", display_order = 1001)]
+    pub no_header: bool,
+
+    /// Display fields of CSV or TSV input that look like numbers or
+    /// booleans as numbers or booleans, instead of as strings.
+    #[arg(long = "infer-types", display_order = 1001)]
+    pub infer_types: bool,
+
+    /// Read defaults from this config file, instead of
+    /// $XDG_CONFIG_HOME/jless/config.toml (~/.config/jless/config.toml by
+    /// default). Options can also be given in the JLESS_OPTIONS environment
+    /// variable, e.g., JLESS_OPTIONS='--mode line'. Options on the command
+    /// line take precedence over JLESS_OPTIONS, which take precedence over
+    /// the config file.
+    #[arg(long = "config", value_name = "FILE", display_order = 1002)]
+    pub config: Option<PathBuf>,
+
+    /// Don't read the config file.
+    #[arg(long = "no-config", conflicts_with = "config", display_order = 1002)]
+    pub no_config: bool,
+
+    // The mode to view input in if --mode isn't given, from the config
+    // file. Unlike --mode, it doesn't affect printing when STDOUT isn't a
+    // TTY.
+    #[arg(skip)]
+    pub default_mode: Option<Mode>,
+
+    // Data formats of file extensions, from the config file.
+    #[arg(skip)]
+    pub extension_formats: HashMap<String, DataFormat>,
+
+    // Keys that should do whatever other keys are bound to, from the
+    // config file.
+    #[arg(skip)]
+    pub key_remaps: HashMap<Key, Key>,
+
+    // Keys bound to actions (or unbound, if the action is None), from
+    // the config file.
+    #[arg(skip)]
+    pub key_bindings: Vec<(Vec<Key>, Option<KeyAction>)>,
 }
 
 impl Opt {
+    pub fn mode(&self) -> Mode {
+        self.mode.or(self.default_mode).unwrap_or(Mode::Data)
+    }
+
     pub fn data_format(&self) -> Option<DataFormat> {
         if self.json {
             Some(DataFormat::Json)
+        } else if self.json5 {
+            Some(DataFormat::Json5)
         } else if self.yaml {
             Some(DataFormat::Yaml)
+        } else if self.toml {
+            Some(DataFormat::Toml)
+        } else if self.csv {
+            Some(DataFormat::Csv)
+        } else if self.tsv {
+            Some(DataFormat::Tsv)
+        } else if self.msgpack {
+            Some(DataFormat::MsgPack)
+        } else if self.cbor {
+            Some(DataFormat::Cbor)
         } else {
             None
         }
     }
+
+    pub fn parse_options(&self) -> ParseOptions {
+        ParseOptions {
+            lenient: self.lenient,
+            csv_has_header: !self.no_header,
+            csv_infer_types: self.infer_types,
+        }
+    }
+}
+
+// The options that determine how input is parsed, which are kept so that
+// input files can be parsed again when they're reloaded.
+#[derive(Copy, Clone, Debug)]
+pub struct ParseOptions {
+    pub lenient: bool,
+    pub csv_has_header: bool,
+    pub csv_infer_types: bool,
+}
+
+impl ParseOptions {
+    pub fn csv_options(&self, delimiter: u8) -> CsvOptions {
+        CsvOptions {
+            delimiter,
+            has_header: self.csv_has_header,
+            infer_types: self.csv_infer_types,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlights_output() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+
+        assert!(!ColorWhen::Auto.highlights_output_with_env(env(&[])));
+        assert!(ColorWhen::Auto.highlights_output_with_env(env(&[("CLICOLOR_FORCE", "1")])));
+        assert!(!ColorWhen::Auto.highlights_output_with_env(env(&[("CLICOLOR_FORCE", "0")])));
+        assert!(!ColorWhen::Auto
+            .highlights_output_with_env(env(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")])));
+        // An empty NO_COLOR is ignored.
+        assert!(ColorWhen::Auto
+            .highlights_output_with_env(env(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "")])));
+
+        assert!(ColorWhen::Always.highlights_output_with_env(env(&[("NO_COLOR", "1")])));
+        assert!(!ColorWhen::Never.highlights_output_with_env(env(&[("CLICOLOR_FORCE", "1")])));
+    }
 }
@@ -0,0 +1,283 @@
+use std::fmt;
+
+// How many characters of the line containing an error to show on either
+// side of the error, so that errors in minified input don't print the
+// entire input.
+const EXCERPT_CONTEXT: usize = 40;
+
+// An error parsing the input, along with where in the input it occurred,
+// when the parser can tell us.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub location: Option<Location>,
+    // A description of what the parser expected to find instead.
+    pub expected: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    // A byte offset into the input.
+    pub offset: usize,
+    // Lines and columns start at 1; columns are counted in characters.
+    pub line: usize,
+    pub column: usize,
+    // The part of the line containing the error that excerpts show: at
+    // most EXCERPT_CONTEXT characters on either side of the error, with
+    // '…' where the rest of the line was cut off.
+    pub source_excerpt: String,
+    // The position of the error in source_excerpt, in characters.
+    pub excerpt_column: usize,
+}
+
+// Finds the locations of byte offsets in the input. Each offset is found
+// by scanning on from the previous one, rather than from the start of
+// the input, so that finding the locations of many errors in order (when
+// parsing leniently) takes time proportional to the size of the input.
+pub struct Locator<'a> {
+    source: &'a str,
+    // The last offset that was found, the line and column it was at, and
+    // where that line starts.
+    offset: usize,
+    line: usize,
+    column: usize,
+    line_start: usize,
+}
+
+impl ParseError {
+    // An error at a byte offset in the input.
+    pub fn at(source: &str, offset: usize, message: String) -> ParseError {
+        ParseError::at_location(Locator::new(source).locate(offset), message)
+    }
+
+    pub fn at_location(location: Location, message: String) -> ParseError {
+        ParseError {
+            message,
+            location: Some(location),
+            expected: None,
+        }
+    }
+
+    // An error at a line and column of the input (both starting at 1),
+    // for parsers that don't report byte offsets.
+    pub fn at_line_and_column(
+        source: &str,
+        line: usize,
+        column: usize,
+        message: String,
+    ) -> ParseError {
+        let line_start = match line {
+            0 | 1 => 0,
+            _ => source
+                .match_indices('\n')
+                .nth(line - 2)
+                .map_or(source.len(), |(i, _)| i + 1),
+        };
+        let offset = source[line_start..]
+            .char_indices()
+            .nth(column.saturating_sub(1))
+            .map_or(source.len(), |(i, _)| line_start + i);
+
+        ParseError::at(source, offset, message)
+    }
+
+    // An error about finding something other than what was expected.
+    pub fn unexpected_at(location: Location, found: &str, expected: &str) -> ParseError {
+        ParseError {
+            expected: Some(expected.to_string()),
+            ..ParseError::at_location(location, format!("Unexpected {found}"))
+        }
+    }
+
+    // The line containing the error, with a caret underneath the column
+    // of the error, e.g.:
+    //
+    //    3 |   "b" 2
+    //      |       ^
+    pub fn excerpt(&self) -> Option<String> {
+        let location = self.location.as_ref()?;
+
+        let line = &location.source_excerpt;
+        // Keep tabs so that the caret lines up.
+        let padding: String = line
+            .chars()
+            .take(location.excerpt_column)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let line_number = location.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        Some(format!(" {line_number} | {line}\n {gutter} | {padding}^"))
+    }
+
+    // The error, followed by the excerpt of the input, for printing to
+    // stderr.
+    pub fn report(&self) -> String {
+        match self.excerpt() {
+            Some(excerpt) => format!("{self}\n{excerpt}"),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl<'a> Locator<'a> {
+    pub fn new(source: &'a str) -> Locator<'a> {
+        Locator {
+            source,
+            offset: 0,
+            line: 1,
+            column: 1,
+            line_start: 0,
+        }
+    }
+
+    pub fn locate(&mut self, offset: usize) -> Location {
+        if offset < self.offset {
+            *self = Locator::new(self.source);
+        }
+
+        let skipped = &self.source[self.offset..offset];
+        match skipped.rfind('\n') {
+            Some(i) => {
+                self.line += skipped.matches('\n').count();
+                self.line_start = self.offset + i + 1;
+                self.column = self.source[self.line_start..offset].chars().count() + 1;
+            }
+            None => self.column += skipped.chars().count(),
+        }
+        self.offset = offset;
+
+        // Only look at the characters around the error, rather than the
+        // whole line, which may be the whole input if it's minified.
+        let mut before: Vec<char> = self.source[self.line_start..offset]
+            .chars()
+            .rev()
+            .take(EXCERPT_CONTEXT + 1)
+            .collect();
+        if before.len() > EXCERPT_CONTEXT {
+            before[EXCERPT_CONTEXT] = '…';
+        }
+        before.reverse();
+
+        let mut after: Vec<char> = self.source[offset..]
+            .chars()
+            .take_while(|&ch| ch != '\n')
+            .take(EXCERPT_CONTEXT + 1)
+            .collect();
+        if after.len() > EXCERPT_CONTEXT {
+            after[EXCERPT_CONTEXT] = '…';
+        } else if after.last() == Some(&'\r') {
+            after.pop();
+        }
+
+        Location {
+            offset,
+            line: self.line,
+            column: self.column,
+            source_excerpt: before.iter().chain(after.iter()).collect(),
+            excerpt_column: before.len(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(location) = &self.location {
+            write!(f, " at line {}, column {}", location.line, location.column)?;
+        }
+        if let Some(expected) = &self.expected {
+            write!(f, "; expected {expected}")?;
+        }
+        Ok(())
+    }
+}
+
+// Errors that we don't know the location of.
+impl From<String> for ParseError {
+    fn from(message: String) -> ParseError {
+        ParseError {
+            message,
+            location: None,
+            expected: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let source = "{\n  \"a\": 1,\n  \"b\" 2\n}";
+        let error = ParseError::unexpected_at(Locator::new(source).locate(18), "number", "':'");
+
+        assert_eq!(
+            error.location,
+            Some(Location {
+                offset: 18,
+                line: 3,
+                column: 7,
+                source_excerpt: "  \"b\" 2".to_string(),
+                excerpt_column: 6,
+            })
+        );
+        assert_eq!(
+            error.to_string(),
+            "Unexpected number at line 3, column 7; expected ':'"
+        );
+        assert_eq!(
+            error.report(),
+            "Unexpected number at line 3, column 7; expected ':'\n 3 |   \"b\" 2\n   |       ^"
+        );
+
+        assert_eq!(
+            ParseError::at_line_and_column(source, 3, 7, "Oops".to_string()).location,
+            error.location,
+        );
+
+        let error = ParseError::from("Oops".to_string());
+        assert_eq!(error.excerpt(), None);
+        assert_eq!(error.report(), "Oops");
+    }
+
+    #[test]
+    fn test_excerpt_of_long_line() {
+        let source = format!("[{}x]", "1, ".repeat(50));
+        let error = ParseError::at(&source, 151, "Parse error".to_string());
+
+        assert_eq!(
+            error.excerpt().unwrap(),
+            format!(
+                " 1 | …{}x]\n   | {}^",
+                " 1, 1".to_owned() + &", 1".repeat(11) + ", ",
+                " ".repeat(41),
+            ),
+        );
+    }
+
+    #[test]
+    fn test_locator() {
+        let source = "[1,\r\n\t2 x,\n\n  \"é\" y]";
+        let mut locator = Locator::new(source);
+
+        // Finding offsets in order, or going back, should give the same
+        // locations as finding each from the start of the input.
+        for offset in [0, 3, 8, 8, 17, 19, 5, source.len(), 1] {
+            let location = locator.locate(offset);
+            assert_eq!(location, Locator::new(source).locate(offset));
+            assert_eq!(location.offset, offset);
+        }
+
+        let location = locator.locate(8);
+        assert_eq!((location.line, location.column), (2, 4));
+        assert_eq!(location.source_excerpt, "\t2 x,");
+        assert_eq!(location.excerpt_column, 3);
+
+        let location = locator.locate(19);
+        assert_eq!((location.line, location.column), (4, 7));
+        assert_eq!(location.source_excerpt, "  \"é\" y]");
+        assert_eq!(location.excerpt_column, 6);
+    }
+}
@@ -10,10 +10,12 @@ use unicode_width::UnicodeWidthStr;
 
 use crate::app::MAX_BUFFER_SIZE;
 use crate::flatjson::{Index, OptionIndex, PathType, Row, Value};
+use crate::highlighting;
 use crate::lineprinter as lp;
 use crate::lineprinter::LineNumber;
 use crate::options::Opt;
 use crate::search::{MatchRangeIter, SearchState};
+use crate::tableview::{TableView, COLUMN_SEPARATOR};
 use crate::terminal;
 use crate::terminal::{AnsiTerminal, Terminal};
 use crate::truncatedstrview::{TruncatedStrSlice, TruncatedStrView};
@@ -130,10 +132,18 @@ impl ScreenWriter {
         viewer: &JsonViewer,
         search_state: &SearchState,
     ) -> std::fmt::Result {
-        let mut line = OptionIndex::Index(viewer.top_row);
-        let mut search_matches = search_state
-            .matches_iter(viewer.flatjson[line.unwrap()].range.start)
-            .peekable();
+        if let Some(table_view) = &viewer.table_view {
+            return self.print_table(viewer, table_view, search_state);
+        }
+
+        // The document may be empty if we're still loading the input.
+        let (mut line, range_start) = if viewer.flatjson.is_empty() {
+            (OptionIndex::Nil, 0)
+        } else {
+            let top_row = &viewer.flatjson[viewer.top_row];
+            (OptionIndex::Index(viewer.top_row), top_row.range.start)
+        };
+        let mut search_matches = search_state.matches_iter(range_start).peekable();
         let current_match = search_state.current_match_range();
 
         let mut delta_to_focused_row = viewer.index_of_focused_row_on_screen() as isize;
@@ -168,12 +178,14 @@ impl ScreenWriter {
         Ok(())
     }
 
-    pub fn get_command(&mut self, prompt: &str) -> rustyline::Result<String> {
+    pub fn get_command(&mut self, prompt: &str, initial: &str) -> rustyline::Result<String> {
         write!(self.stdout, "{}", termion::cursor::Show)?;
         let _ = self.terminal.position_cursor(1, self.dimensions.height);
         self.terminal.flush_contents(&mut self.stdout)?;
 
-        let result = self.command_editor.readline(prompt);
+        let result = self
+            .command_editor
+            .readline_with_initial(prompt, (initial, ""));
         write!(self.stdout, "{}", termion::cursor::Hide)?;
 
         let _ = self.terminal.position_cursor(1, self.dimensions.height);
@@ -271,6 +283,12 @@ impl ScreenWriter {
             focused,
             focused_because_matching_container_pair,
             trailing_comma,
+            diff_status: viewer
+                .diff_statuses
+                .as_ref()
+                .map(|statuses| statuses[index]),
+            recovered_error: (!viewer.recovered_errors.is_empty())
+                .then(|| viewer.recovered_error(index).is_some()),
 
             search_matches: Some(search_matches_copy),
             focused_search_match,
@@ -289,6 +307,169 @@ impl ScreenWriter {
         Ok(())
     }
 
+    fn print_table(
+        &mut self,
+        viewer: &JsonViewer,
+        table_view: &TableView,
+        search_state: &SearchState,
+    ) -> std::fmt::Result {
+        let current_match = search_state.current_match_range();
+        let index_column_width = table_view.index_column_width();
+
+        for screen_index in 0..viewer.dimensions.height {
+            self.terminal.position_cursor(1, screen_index + 1)?;
+            self.terminal.clear_line()?;
+            self.terminal.reset_style()?;
+
+            // The first line is the header.
+            if screen_index == 0 {
+                self.print_n_spaces(index_column_width)?;
+                self.print_table_columns(table_view, |screen_writer, column, width| {
+                    screen_writer.print_table_header(table_view, column, width)
+                })?;
+                continue;
+            }
+
+            let row_index = table_view.top_row + screen_index as usize - 1;
+            let Some(row) = table_view.rows.get(row_index) else {
+                self.terminal.set_fg(terminal::LIGHT_BLACK)?;
+                self.terminal.write_char('~')?;
+                continue;
+            };
+
+            let focused = row_index == table_view.focused_row;
+            let label = format!("[{}]", viewer.flatjson[row.element].index_in_parent);
+            self.print_n_spaces(index_column_width - label.len() as isize)?;
+            self.terminal.set_style(if focused {
+                &highlighting::BOLD_INVERTED_STYLE
+            } else {
+                &highlighting::DIMMED_STYLE
+            })?;
+            self.terminal.write_str(&label)?;
+
+            self.print_table_columns(table_view, |screen_writer, column, width| {
+                let Some(cell) = row.cells[column] else {
+                    return Ok(0);
+                };
+
+                let mut line = lp::LinePrinter {
+                    mode: Mode::Data,
+                    terminal: &mut screen_writer.terminal,
+
+                    flatjson: &viewer.flatjson,
+                    row: &viewer.flatjson[cell],
+                    line_number: LineNumber {
+                        absolute: None,
+                        relative: None,
+                        max_width: 0,
+                    },
+
+                    width,
+                    indentation: 0,
+
+                    focused: focused && column == table_view.focused_column,
+                    focused_because_matching_container_pair: false,
+                    trailing_comma: false,
+                    diff_status: None,
+                    recovered_error: None,
+
+                    search_matches: Some(
+                        search_state
+                            .matches_iter(viewer.flatjson[cell].range.start)
+                            .peekable(),
+                    ),
+                    focused_search_match: &current_match,
+                    emphasize_focused_search_match: true,
+
+                    cached_truncated_value: None,
+                };
+
+                line.print_value(width)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // Print the columns of a table that fit on the screen, starting with the
+    // first visible column, separating them and padding each one to its
+    // width. The contents of each column are printed by print_column, which
+    // returns how much space it used.
+    fn print_table_columns<F>(
+        &mut self,
+        table_view: &TableView,
+        mut print_column: F,
+    ) -> std::fmt::Result
+    where
+        F: FnMut(&mut Self, usize, isize) -> Result<isize, std::fmt::Error>,
+    {
+        let mut available_space = self.dimensions.width as isize - table_view.index_column_width();
+
+        for (index, column) in table_view
+            .columns
+            .iter()
+            .enumerate()
+            .skip(table_view.first_column)
+        {
+            if available_space <= COLUMN_SEPARATOR.len() as isize {
+                break;
+            }
+
+            self.terminal.reset_style()?;
+            self.terminal.write_str(COLUMN_SEPARATOR)?;
+            available_space -= COLUMN_SEPARATOR.len() as isize;
+
+            let width = column.width.min(available_space);
+            let used_space = print_column(self, index, width)?;
+
+            self.terminal.reset_style()?;
+            self.print_n_spaces(width - used_space)?;
+            available_space -= width;
+        }
+
+        Ok(())
+    }
+
+    fn print_table_header(
+        &mut self,
+        table_view: &TableView,
+        column: usize,
+        width: isize,
+    ) -> Result<isize, std::fmt::Error> {
+        let key = &table_view.columns[column].key;
+        let header = match table_view.sorted_by {
+            Some((sorted_column, descending)) if sorted_column == column => {
+                format!("{key} {}", if descending { '▼' } else { '▲' })
+            }
+            _ => key.clone(),
+        };
+
+        let colors = highlighting::colors();
+        self.terminal
+            .set_style(&if column == table_view.focused_column {
+                colors.focused_key_style()
+            } else {
+                colors.key_style()
+            })?;
+
+        let truncated_view = TruncatedStrView::init_start(&header, width);
+        let header_slice = TruncatedStrSlice {
+            s: &header,
+            truncated_view: &truncated_view,
+        };
+        write!(self.terminal, "{header_slice}")?;
+
+        Ok(truncated_view.used_space().unwrap_or(0))
+    }
+
+    fn print_n_spaces(&mut self, n: isize) -> std::fmt::Result {
+        for _ in 0..n {
+            self.terminal.write_char(' ')?;
+        }
+
+        Ok(())
+    }
+
     fn line_primitive_value_ref<'a, 'b>(
         &'a self,
         row: &'a Row,
@@ -329,10 +510,19 @@ impl ScreenWriter {
         }
         self.terminal.write_char('\r')?;
 
-        let path_to_node = viewer
-            .flatjson
-            .build_path_to_node(PathType::DotWithTopLevelIndex, viewer.focused_row)
-            .unwrap();
+        let path_to_node = if viewer.flatjson.is_empty() {
+            String::new()
+        } else {
+            // When viewing a table, show the path to the focused cell.
+            let focused_row = match &viewer.table_view {
+                Some(table_view) => table_view.focused_node(),
+                None => viewer.focused_row,
+            };
+            viewer
+                .flatjson
+                .build_path_to_node(PathType::DotWithTopLevelIndex, focused_row)
+                .unwrap()
+        };
         self.print_path_to_node_and_file_name(
             &path_to_node,
             input_filename,
@@ -466,6 +656,12 @@ impl ScreenWriter {
         Ok(())
     }
 
+    // The scroll positions of truncated values are tied to specific rows,
+    // so they need to be forgotten when the rows are replaced.
+    pub fn forget_truncated_value_views(&mut self) {
+        self.truncated_row_value_views.clear();
+    }
+
     pub fn decrease_indentation_level(&mut self, max_depth: u16) {
         self.indentation_reduction = self.indentation_reduction.saturating_add(1).min(max_depth);
     }
@@ -32,6 +32,8 @@ pub struct SearchState {
     pub search_term: String,
 
     matches: Vec<Range<usize>>,
+    // Kept so that text appended to the haystack can be searched too.
+    regex: Option<Regex>,
 
     immediate_state: ImmediateSearchState,
     pub ever_searched: bool,
@@ -64,11 +66,53 @@ impl SearchState {
             direction: SearchDirection::Forward,
             search_term: "".to_owned(),
             matches: vec![],
+            regex: None,
             immediate_state: ImmediateSearchState::NotSearching,
             ever_searched: false,
         }
     }
 
+    // Update the positions of matches after parts of the haystack have
+    // been replaced, e.g., when containers are loaded in large-file mode.
+    // Each replacement is the range of the replaced text, and the length
+    // of the text that replaced it; they must be sorted and not overlap.
+    // Matches in the replaced text are dropped.
+    pub fn adjust_for_replaced_text(&mut self, replacements: &[(Range<usize>, usize)]) {
+        self.matches.retain(|m| {
+            let i = replacements.partition_point(|(replaced, _)| replaced.end <= m.start);
+            match replacements.get(i) {
+                Some((replaced, _)) => m.end <= replaced.start,
+                None => true,
+            }
+        });
+
+        // Matches are sorted, so we just need to keep track of how much
+        // text has been added or removed before the current match.
+        let mut replacements = replacements.iter().peekable();
+        let mut shift: isize = 0;
+
+        for m in self.matches.iter_mut() {
+            while let Some((replaced, new_len)) = replacements.next_if(|(r, _)| r.end <= m.start) {
+                shift += *new_len as isize - replaced.len() as isize;
+            }
+            *m = ((m.start as isize + shift) as usize)..((m.end as isize + shift) as usize);
+        }
+    }
+
+    // Find matches in text that was appended to the haystack, e.g., when
+    // more input is streamed in, which starts at the previous length of
+    // the haystack.
+    pub fn add_matches_in_appended_text(&mut self, haystack: &str, appended_start: usize) {
+        let Some(regex) = &self.regex else {
+            return;
+        };
+
+        let appended_matches = regex
+            .find_iter(&haystack[appended_start..])
+            .map(|m| (m.start() + appended_start)..(m.end() + appended_start));
+        self.matches.extend(appended_matches);
+    }
+
     fn extract_search_term_and_case_sensitivity(search_input: &str) -> (&str, bool) {
         let regex_input;
         let mut case_sensitive_specified = false;
@@ -91,7 +135,7 @@ impl SearchState {
         (regex_input, case_sensitive)
     }
 
-    fn invert_square_and_curly_bracket_escaping(regex: &str) -> Cow<str> {
+    fn invert_square_and_curly_bracket_escaping(regex: &str) -> Cow<'_, str> {
         SQUARE_AND_CURLY_BRACKETS.replace_all(regex, |caps: &Captures| match &caps[0] {
             "\\[" => "[".to_owned(),
             "[" => "\\[".to_owned(),
@@ -132,6 +176,7 @@ impl SearchState {
             direction,
             search_term: regex_input.to_owned(),
             matches,
+            regex: Some(regex),
             immediate_state: ImmediateSearchState::NotSearching,
             ever_searched: true,
         })
@@ -233,7 +278,7 @@ impl SearchState {
     /// Return an iterator over all the stored matches. We pass in a
     /// start index that will be used to efficiently skip any matches
     /// before that index.
-    pub fn matches_iter(&self, range_start: usize) -> MatchRangeIter {
+    pub fn matches_iter(&self, range_start: usize) -> MatchRangeIter<'_> {
         match self.immediate_state {
             ImmediateSearchState::NotSearching => STATIC_EMPTY_SLICE.iter(),
             ImmediateSearchState::MatchesVisible
@@ -366,6 +411,13 @@ impl SearchState {
         }
     }
 
+    // The rows containing the start of each match.
+    pub fn rows_containing_matches(&self, flatjson: &FlatJson) -> Vec<Index> {
+        (0..self.matches.len())
+            .map(|match_index| self.compute_destination_row(flatjson, match_index))
+            .collect()
+    }
+
     // Helper for modifying a match_index that handles wrapping around the start or end of the
     // matches.
     fn cycle_match(&self, match_index: usize, delta: isize) -> usize {
@@ -472,6 +524,39 @@ mod tests {
         assert_eq!(search.jump_to_match(4, &fj, Prev, 4_000_000_001), 1);
     }
 
+    #[test]
+    fn test_search_appended_text() {
+        let mut fj = parse_top_level_json(r#"{"a": "bbb"}"#.to_owned()).unwrap();
+        let mut search = SearchState::initialize_search("b+".to_owned(), &fj.1, Forward).unwrap();
+        assert_eq!(search.num_matches(), 1);
+
+        let appended_start = fj.1.len();
+        fj.append(parse_top_level_json(r#"["b", "ab"]"#.to_owned()).unwrap());
+        search.add_matches_in_appended_text(&fj.1, appended_start);
+        assert_eq!(search.num_matches(), 3);
+        assert_eq!(search.jump_to_match(1, &fj, Next, 1), 4);
+        assert_eq!(search.jump_to_match(4, &fj, Next, 1), 5);
+
+        // Nothing is searched if there's no search.
+        let mut search = SearchState::empty();
+        search.add_matches_in_appended_text(&fj.1, 0);
+        assert_eq!(search.num_matches(), 0);
+    }
+
+    #[test]
+    fn test_adjust_for_replaced_text() {
+        let haystack = "x {…} x [x] {…} x";
+        let mut search = SearchState::initialize_search("x".to_owned(), haystack, Forward).unwrap();
+        assert_eq!(search.num_matches(), 4);
+
+        // Replace both placeholders, and the array containing a match.
+        let replacements = [(2..7, 6), (10..13, 2), (14..19, 2)];
+        search.adjust_for_replaced_text(&replacements);
+
+        // The new haystack would be, e.g., "x { a } x [] {} x"
+        assert_eq!(search.matches, vec![0..1, 9..10, 17..18]);
+    }
+
     #[test]
     fn test_basic_search_backwards() {
         let fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
@@ -576,7 +661,7 @@ mod tests {
         if let Some((_, wrapped)) = search.active_search_state() {
             assert_eq!(wrapped, expected);
         } else {
-            assert!(false, "Not in an active search state");
+            panic!("Not in an active search state");
         }
     }
 }
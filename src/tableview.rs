@@ -0,0 +1,469 @@
+use std::cmp::Ordering;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::flatjson::{ContainerType, FlatJson, Index, OptionIndex, Value};
+use crate::types::TTYDimensions;
+
+// This module is responsible for displaying an array of objects as a
+// table, where each element of the array is displayed as a row, and
+// each key that appears in any of the elements is displayed as a column.
+//
+// The table only stores indexes of rows in the FlatJson, so it needs to
+// be discarded if the FlatJson changes.
+
+// Columns are only as wide as their widest value (or their key), but
+// they can't get wider than this; longer values are truncated.
+const MAX_COLUMN_WIDTH: isize = 30;
+pub const COLUMN_SEPARATOR: &str = "  ";
+const COLUMN_SEPARATOR_WIDTH: isize = 2;
+
+#[derive(Debug, Copy, Clone)]
+pub enum TableAction {
+    MoveUp(usize),
+    MoveDown(usize),
+    MoveLeft,
+    MoveRight,
+    FocusFirstRow,
+    FocusLastRow,
+    FocusFirstColumn,
+    FocusLastColumn,
+    PageUp(usize),
+    PageDown(usize),
+    SortByFocusedColumn,
+    ResizeDimensions(TTYDimensions),
+}
+
+pub struct Column {
+    // The key, as it appears between the quotes in the input.
+    pub key: String,
+    pub width: isize,
+}
+
+pub struct TableRow {
+    // The element of the array that this row displays.
+    pub element: Index,
+    // The value of each column for this element, if it has one.
+    pub cells: Vec<Option<Index>>,
+}
+
+pub struct TableView {
+    pub columns: Vec<Column>,
+    pub rows: Vec<TableRow>,
+
+    pub top_row: usize,
+    pub focused_row: usize,
+    pub first_column: usize,
+    pub focused_column: usize,
+
+    // The column the rows are sorted by, and whether they're sorted in
+    // descending order.
+    pub sorted_by: Option<(usize, bool)>,
+
+    // The dimensions of the table, including the header.
+    pub dimensions: TTYDimensions,
+}
+
+impl TableView {
+    // Create a table of the array containing the focused row (or of the
+    // focused row itself, if it's an array). If the focused row is inside
+    // of an element of the array, that element (and column) is focused.
+    pub fn new(
+        flatjson: &FlatJson,
+        focused_row: Index,
+        dimensions: TTYDimensions,
+    ) -> Result<TableView, String> {
+        let mut index = if flatjson[focused_row].is_closing_of_container() {
+            flatjson[focused_row].pair_index().unwrap()
+        } else {
+            focused_row
+        };
+
+        // The rows between the focused row and the array, innermost first.
+        let mut descendants = vec![];
+
+        while !(flatjson[index].is_opening_of_container() && flatjson[index].is_array()) {
+            descendants.push(index);
+            match flatjson[index].parent {
+                OptionIndex::Index(parent) => index = parent,
+                OptionIndex::Nil => return Err("Not focused on an array".to_string()),
+            }
+        }
+
+        let mut columns: Vec<Column> = vec![];
+        let mut rows = vec![];
+
+        let mut next_element = flatjson[index].first_child();
+        while let OptionIndex::Index(element) = next_element {
+            let mut cells = vec![None; columns.len()];
+
+            if is_object(&flatjson[element].value) {
+                let mut next_child = flatjson[element].first_child();
+                while let OptionIndex::Index(child) = next_child {
+                    let key = key_of_row(flatjson, child);
+                    let column = match columns.iter().position(|column| column.key == key) {
+                        Some(column) => column,
+                        None => {
+                            let width = UnicodeWidthStr::width(key) as isize;
+                            columns.push(Column {
+                                key: key.to_string(),
+                                width: width.min(MAX_COLUMN_WIDTH),
+                            });
+                            cells.push(None);
+                            columns.len() - 1
+                        }
+                    };
+
+                    cells[column] = Some(child);
+                    let width = value_width(flatjson, child);
+                    columns[column].width = columns[column].width.max(width).max(1);
+
+                    next_child = flatjson[child].next_sibling;
+                }
+            }
+
+            rows.push(TableRow { element, cells });
+            next_element = flatjson[element].next_sibling;
+        }
+
+        if columns.is_empty() {
+            return Err("Table view requires an array of objects".to_string());
+        }
+
+        // Rows created before a column was first seen don't have a cell for it.
+        for row in rows.iter_mut() {
+            row.cells.resize(columns.len(), None);
+        }
+
+        let mut table = TableView {
+            columns,
+            rows,
+            top_row: 0,
+            focused_row: 0,
+            first_column: 0,
+            focused_column: 0,
+            sorted_by: None,
+            dimensions,
+        };
+
+        // Focus the element (and column) containing the focused row.
+        if let Some(&element) = descendants.last() {
+            table.focused_row = flatjson[element].index_in_parent;
+
+            if descendants.len() >= 2 {
+                let cell = descendants[descendants.len() - 2];
+                if let Some(column) = table.rows[table.focused_row]
+                    .cells
+                    .iter()
+                    .position(|c| *c == Some(cell))
+                {
+                    table.focused_column = column;
+                }
+            }
+        }
+
+        table.ensure_focus_is_visible();
+
+        Ok(table)
+    }
+
+    // The row of the focused cell, or, if the focused element doesn't
+    // have a value for the focused column, the element itself.
+    pub fn focused_node(&self) -> Index {
+        let row = &self.rows[self.focused_row];
+        row.cells[self.focused_column].unwrap_or(row.element)
+    }
+
+    pub fn index_column_width(&self) -> isize {
+        // Elements are labeled by their index, e.g., "[12]".
+        (self.rows.len().max(1) - 1).to_string().len() as isize + 2
+    }
+
+    // The number of rows of elements that fit on the screen, below the header.
+    fn height(&self) -> usize {
+        (self.dimensions.height as usize).saturating_sub(1).max(1)
+    }
+
+    pub fn perform_action(&mut self, flatjson: &FlatJson, action: TableAction) {
+        let last_row = self.rows.len() - 1;
+
+        match action {
+            TableAction::MoveUp(n) => self.focused_row = self.focused_row.saturating_sub(n),
+            TableAction::MoveDown(n) => {
+                self.focused_row = self.focused_row.saturating_add(n).min(last_row)
+            }
+            TableAction::MoveLeft => self.focused_column = self.focused_column.saturating_sub(1),
+            TableAction::MoveRight => {
+                self.focused_column = (self.focused_column + 1).min(self.columns.len() - 1)
+            }
+            TableAction::FocusFirstRow => self.focused_row = 0,
+            TableAction::FocusLastRow => self.focused_row = last_row,
+            TableAction::FocusFirstColumn => self.focused_column = 0,
+            TableAction::FocusLastColumn => self.focused_column = self.columns.len() - 1,
+            TableAction::PageUp(n) => {
+                let distance = self.height().saturating_mul(n);
+                self.top_row = self.top_row.saturating_sub(distance);
+                self.focused_row = self.focused_row.saturating_sub(distance);
+            }
+            TableAction::PageDown(n) => {
+                let distance = self.height().saturating_mul(n);
+                self.top_row = self.top_row.saturating_add(distance).min(last_row);
+                self.focused_row = self.focused_row.saturating_add(distance).min(last_row);
+            }
+            TableAction::SortByFocusedColumn => self.sort_by_focused_column(flatjson),
+            TableAction::ResizeDimensions(dimensions) => self.dimensions = dimensions,
+        }
+
+        self.ensure_focus_is_visible();
+    }
+
+    // Sort the rows by the values in the focused column. Sorting by the
+    // same column again reverses the order.
+    fn sort_by_focused_column(&mut self, flatjson: &FlatJson) {
+        let column = self.focused_column;
+        let descending = self.sorted_by == Some((column, false));
+        let focused_element = self.rows[self.focused_row].element;
+
+        self.rows.sort_by(|a, b| {
+            let ordering = compare_cells(flatjson, a.cells[column], b.cells[column]);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+
+        self.sorted_by = Some((column, descending));
+
+        // Keep the same element focused.
+        self.focused_row = self
+            .rows
+            .iter()
+            .position(|row| row.element == focused_element)
+            .unwrap();
+    }
+
+    fn ensure_focus_is_visible(&mut self) {
+        let height = self.height();
+
+        if self.focused_row < self.top_row {
+            self.top_row = self.focused_row;
+        } else if self.focused_row >= self.top_row + height {
+            self.top_row = self.focused_row + 1 - height;
+        }
+
+        // Scroll horizontally until the whole focused column is visible, or
+        // it's the first visible column.
+        let width = self.dimensions.width as isize;
+
+        if self.focused_column < self.first_column {
+            self.first_column = self.focused_column;
+        }
+
+        while self.first_column < self.focused_column
+            && self.width_of_columns(self.first_column, self.focused_column) > width
+        {
+            self.first_column += 1;
+        }
+    }
+
+    // The width of the index column, and the columns from first to last,
+    // inclusive, including the separators between them.
+    fn width_of_columns(&self, first: usize, last: usize) -> isize {
+        self.columns[first..=last]
+            .iter()
+            .map(|column| column.width + COLUMN_SEPARATOR_WIDTH)
+            .sum::<isize>()
+            + self.index_column_width()
+    }
+}
+
+fn is_object(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::OpenContainer {
+            container_type: ContainerType::Object,
+            ..
+        }
+    )
+}
+
+// The key of an object entry, without quotes.
+fn key_of_row(flatjson: &FlatJson, index: Index) -> &str {
+    let key_range = flatjson[index].key_range.clone().unwrap();
+    let key = &flatjson.1[key_range];
+
+    key.strip_prefix('"')
+        .and_then(|key| key.strip_suffix('"'))
+        .unwrap_or(key)
+}
+
+// How much space it takes to display the value of a row in full,
+// capped at the max column width.
+fn value_width(flatjson: &FlatJson, index: Index) -> isize {
+    let row = &flatjson[index];
+    if row.is_container() {
+        return MAX_COLUMN_WIDTH;
+    }
+
+    // Strings are displayed with their quotes.
+    let value = &flatjson.1[row.range.clone()];
+    (UnicodeWidthStr::width(value) as isize).min(MAX_COLUMN_WIDTH)
+}
+
+// Order values the same way jq does: null < false < true < numbers <
+// strings < arrays < objects. Missing values come first.
+fn compare_cells(flatjson: &FlatJson, a: Option<Index>, b: Option<Index>) -> Ordering {
+    let (a, b) = match (a, b) {
+        (None, None) => return Ordering::Equal,
+        (None, Some(_)) => return Ordering::Less,
+        (Some(_), None) => return Ordering::Greater,
+        (Some(a), Some(b)) => (a, b),
+    };
+
+    let a_text = &flatjson.1[flatjson[a].range.clone()];
+    let b_text = &flatjson.1[flatjson[b].range.clone()];
+
+    let rank = |value: &Value, text: &str| match value {
+        Value::Null => 0,
+        Value::Boolean if text == "false" => 1,
+        Value::Boolean => 2,
+        Value::Number => 3,
+        Value::String => 4,
+        Value::EmptyArray => 5,
+        Value::EmptyObject => 6,
+        _ if value.is_array() => 5,
+        _ => 6,
+    };
+
+    let a_rank = rank(&flatjson[a].value, a_text);
+    let b_rank = rank(&flatjson[b].value, b_text);
+
+    if a_rank != b_rank {
+        return a_rank.cmp(&b_rank);
+    }
+
+    if let Value::Number = flatjson[a].value {
+        if let (Ok(a_num), Ok(b_num)) = (a_text.parse::<f64>(), b_text.parse::<f64>()) {
+            return a_num.partial_cmp(&b_num).unwrap_or(Ordering::Equal);
+        }
+    }
+
+    a_text.cmp(b_text)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flatjson::parse_top_level_json;
+
+    use super::*;
+
+    const PEOPLE: &str = r#"{
+        "people": [
+            {"name": "Bob", "age": 30},
+            {"name": "Alice", "email": "alice@example.com"},
+            {"name": "Carol", "age": 4, "tags": ["a", "b"]},
+            null
+        ]
+    }"#;
+
+    fn dimensions() -> TTYDimensions {
+        TTYDimensions {
+            width: 40,
+            height: 4,
+        }
+    }
+
+    fn column_values(fj: &FlatJson, table: &TableView, column: usize) -> Vec<String> {
+        table
+            .rows
+            .iter()
+            .map(|row| match row.cells[column] {
+                Some(cell) => fj.1[fj[cell].range.clone()].to_string(),
+                None => "-".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_columns_and_cells() {
+        let fj = parse_top_level_json(PEOPLE.to_owned()).unwrap();
+        let table = TableView::new(&fj, 2, dimensions()).unwrap();
+
+        assert_eq!(table.rows.len(), 4);
+        let keys: Vec<&str> = table.columns.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, vec!["name", "age", "email", "tags"]);
+
+        let widths: Vec<isize> = table.columns.iter().map(|c| c.width).collect();
+        assert_eq!(widths, vec![7, 3, 19, MAX_COLUMN_WIDTH]);
+
+        assert_eq!(column_values(&fj, &table, 1), vec!["30", "-", "4", "-"],);
+        assert_eq!(table.index_column_width(), 3);
+
+        // Focused on the email of the second element.
+        let mut table = TableView::new(&fj, 8, dimensions()).unwrap();
+        assert_eq!((table.focused_row, table.focused_column), (1, 2));
+        assert_eq!(table.focused_node(), 8);
+
+        // The focused element doesn't have an age.
+        table.perform_action(&fj, TableAction::MoveLeft);
+        assert_eq!(table.focused_node(), 6);
+
+        assert_eq!(
+            TableView::new(&fj, 0, dimensions()).err(),
+            Some("Not focused on an array".to_string()),
+        );
+        // The focused row is inside an array of strings.
+        assert_eq!(
+            TableView::new(&fj, 14, dimensions()).err(),
+            Some("Table view requires an array of objects".to_string()),
+        );
+    }
+
+    #[test]
+    fn test_sorting() {
+        let fj = parse_top_level_json(PEOPLE.to_owned()).unwrap();
+        let mut table = TableView::new(&fj, 1, dimensions()).unwrap();
+
+        table.perform_action(&fj, TableAction::SortByFocusedColumn);
+        assert_eq!(
+            column_values(&fj, &table, 0),
+            vec!["-", "\"Alice\"", "\"Bob\"", "\"Carol\""],
+        );
+        // The first element is still focused.
+        assert_eq!(table.focused_row, 2);
+
+        table.perform_action(&fj, TableAction::MoveRight);
+        table.perform_action(&fj, TableAction::SortByFocusedColumn);
+        assert_eq!(column_values(&fj, &table, 1), vec!["-", "-", "4", "30"]);
+
+        table.perform_action(&fj, TableAction::SortByFocusedColumn);
+        assert_eq!(table.sorted_by, Some((1, true)));
+        assert_eq!(column_values(&fj, &table, 1), vec!["30", "4", "-", "-"]);
+    }
+
+    #[test]
+    fn test_scrolling() {
+        let fj = parse_top_level_json(PEOPLE.to_owned()).unwrap();
+        let mut table = TableView::new(&fj, 1, dimensions()).unwrap();
+
+        // Three rows fit below the header.
+        table.perform_action(&fj, TableAction::FocusLastRow);
+        assert_eq!((table.top_row, table.focused_row), (1, 3));
+        table.perform_action(&fj, TableAction::MoveUp(2));
+        assert_eq!((table.top_row, table.focused_row), (1, 1));
+        table.perform_action(&fj, TableAction::MoveUp(2));
+        assert_eq!((table.top_row, table.focused_row), (0, 0));
+
+        // [i] + name + age + email = 3 + 9 + 5 + 21 = 38
+        table.perform_action(&fj, TableAction::FocusLastColumn);
+        assert_eq!(table.first_column, 3);
+        table.perform_action(&fj, TableAction::MoveLeft);
+        assert_eq!(table.first_column, 2);
+        table.perform_action(&fj, TableAction::FocusFirstColumn);
+        assert_eq!(table.first_column, 0);
+        table.perform_action(&fj, TableAction::MoveRight);
+        table.perform_action(&fj, TableAction::MoveRight);
+        assert_eq!((table.first_column, table.focused_column), (0, 2));
+    }
+}
@@ -0,0 +1,173 @@
+use toml::{Table, Value as Toml};
+
+use crate::flatjson::{ContainerType, FlatJson, Index, Row, Value};
+use crate::flatjsonbuilder::FlatJsonBuilder;
+use crate::jsonstringescaper::escape_json_string;
+use crate::parseerror::ParseError;
+
+#[derive(Default)]
+struct TomlParser {
+    doc: FlatJsonBuilder,
+}
+
+pub fn parse(toml: String) -> Result<(Vec<Row>, String, usize), ParseError> {
+    let mut parser = TomlParser::default();
+
+    let table = match toml.parse::<Table>() {
+        Ok(table) => table,
+        Err(err) => {
+            // Messages can span multiple lines, e.g., "invalid string\nexpected ...".
+            let mut message = err.message().trim_end().replace('\n', "; ");
+            // Some errors, e.g., a missing value, don't have a message.
+            if message.is_empty() {
+                message = "invalid TOML".to_owned();
+            }
+            return Err(match err.span() {
+                Some(span) => ParseError::at(&toml, span.start, message),
+                None => ParseError::from(message),
+            });
+        }
+    };
+
+    // A TOML document is always a single table.
+    parser.parse_table(table);
+
+    let FlatJson(rows, pretty_printed, max_depth) = parser.doc.build();
+    Ok((rows, pretty_printed, max_depth))
+}
+
+impl TomlParser {
+    fn parse_toml_item(&mut self, item: Toml) -> Index {
+        match item {
+            Toml::Boolean(b) => self.doc.push_primitive(Value::Boolean, &b.to_string()),
+            Toml::Integer(i) => self.doc.push_primitive(Value::Number, &i.to_string()),
+            // Infinity and NaN don't have a JSON equivalent, so we display
+            // them as strings, using their TOML spelling.
+            Toml::Float(f) if f.is_nan() => self.parse_string("nan"),
+            Toml::Float(f) if f.is_infinite() => {
+                self.parse_string(if f > 0.0 { "inf" } else { "-inf" })
+            }
+            // Use the Debug representation so that floats with integral
+            // values still include a decimal point.
+            Toml::Float(f) => self.doc.push_primitive(Value::Number, &format!("{f:?}")),
+            Toml::String(s) => self.parse_string(&s),
+            // Datetimes don't have a JSON equivalent, so we display them as
+            // strings, using the same format as in the TOML document.
+            Toml::Datetime(datetime) => self.parse_string(&datetime.to_string()),
+            Toml::Array(arr) => self.parse_array(arr),
+            Toml::Table(table) => self.parse_table(table),
+        }
+    }
+
+    fn parse_string(&mut self, s: &str) -> Index {
+        let quoted = format!("\"{}\"", escape_json_string(s));
+        self.doc.push_primitive(Value::String, &quoted)
+    }
+
+    fn parse_array(&mut self, arr: Vec<Toml>) -> Index {
+        let open_index = self.doc.open_container(ContainerType::Array);
+        let mut siblings = None;
+
+        for child in arr {
+            let key_range = self
+                .doc
+                .start_child(None, Some(ContainerType::Array), &siblings);
+            let child_index = self.parse_toml_item(child);
+            self.doc.finish_child(child_index, key_range, &mut siblings);
+        }
+
+        self.doc
+            .finish_container(open_index, ContainerType::Array, false, siblings);
+        open_index
+    }
+
+    fn parse_table(&mut self, table: Table) -> Index {
+        let open_index = self.doc.open_container(ContainerType::Object);
+        let mut siblings = None;
+
+        for (key, value) in table {
+            let key = format!("\"{}\"", escape_json_string(&key));
+            let key_range =
+                self.doc
+                    .start_child(Some(&key), Some(ContainerType::Object), &siblings);
+            let child_index = self.parse_toml_item(value);
+            self.doc.finish_child(child_index, key_range, &mut siblings);
+        }
+
+        self.doc
+            .finish_container(open_index, ContainerType::Object, false, siblings);
+        open_index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+    use crate::flatjson::OptionIndex;
+
+    #[test]
+    fn test_basic() {
+        let toml = indoc! {r#"
+            title = "a \"title\""
+            count = 3
+            ratio = 1.0
+            created = 1979-05-27T07:32:00Z
+
+            [package]
+            name = "jless"
+            keywords = ["cli", "json"]
+
+            [[bin]]
+            name = "one"
+
+            [[bin]]
+            name = "two"
+        "#}
+        .to_owned();
+        let (rows, pretty, _) = parse(toml).unwrap();
+
+        assert_eq!(
+            pretty,
+            concat!(
+                r#"{ "title": "a \"title\"", "count": 3, "ratio": 1.0, "#,
+                r#""created": "1979-05-27T07:32:00Z", "#,
+                r#""package": { "name": "jless", "keywords": ["cli", "json"] }, "#,
+                r#""bin": [{ "name": "one" }, { "name": "two" }] }"#,
+            )
+        );
+
+        assert_eq!(rows[0].range, 0..pretty.len()); // Table
+        assert_eq!(rows[1].key_range, Some(2..9)); // "title"
+        assert_eq!(rows[1].range, 11..24); // "a \"title\""
+        assert!(rows[4].is_string()); // created
+    }
+
+    #[test]
+    fn test_empty_containers() {
+        let (rows, pretty, depth) = parse("a = []\n[b]\n".to_owned()).unwrap();
+        assert_eq!(pretty, r#"{ "a": [], "b": {} }"#);
+        assert!(rows[1].is_primitive());
+        assert_eq!(rows[2].prev_sibling, OptionIndex::Index(1));
+        assert_eq!(rows[0].range, 0..pretty.len());
+        assert_eq!(depth, 1);
+    }
+
+    #[test]
+    fn test_non_finite_floats() {
+        let toml = "a = inf\nb = -inf\nc = nan\nd = 1e3\n".to_owned();
+        let (rows, pretty, _) = parse(toml).unwrap();
+        assert_eq!(
+            pretty,
+            r#"{ "a": "inf", "b": "-inf", "c": "nan", "d": 1000.0 }"#
+        );
+        assert!(rows[1].is_string());
+        assert!(!rows[4].is_string());
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(parse("key = ".to_owned()).is_err());
+    }
+}
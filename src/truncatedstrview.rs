@@ -5,9 +5,9 @@ use std::ops::Range;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
-/// This module provides functionality for truncating strings,
-/// displaying them, and manipulating which portion of the string
-/// is visible.
+// This module provides functionality for truncating strings,
+// displaying them, and manipulating which portion of the string
+// is visible.
 
 /// A TruncatedStrView represents an attempt to fit a string within
 /// a given amount of available space. When `range` is None, it
@@ -1,6 +1,13 @@
+use std::collections::{BTreeSet, HashMap};
+
 use clap::ValueEnum;
 
-use crate::flatjson::{FlatJson, Index, OptionIndex};
+use crate::diff::DiffStatus;
+use crate::flatjson;
+use crate::flatjson::{ContainerType, FlatJson, Index, OptionIndex, PathSegment, PathType};
+use crate::jsonparser::RecoveredError;
+use crate::lazyjson::{self, LazyInput, LoadedContainer};
+use crate::tableview::{TableAction, TableView};
 use crate::types::TTYDimensions;
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug, ValueEnum)]
@@ -30,6 +37,45 @@ pub struct JsonViewer {
     // Access the functional value via .scrolloff().
     pub scrolloff_setting: u16,
     pub mode: Mode,
+
+    // Present in large-file mode, in which containers are loaded lazily.
+    pub lazy_input: Option<LazyInput>,
+    // Containers loaded since the app last checked, so that it can update
+    // anything that refers to rows or the pretty printed string. Each set
+    // of containers was loaded at the same time, and is sorted by index.
+    pub loaded_containers: Vec<Vec<LoadedContainer>>,
+    pub load_error: Option<String>,
+
+    // Whether some rows are hidden because we're only showing the
+    // matches of a search and their ancestors.
+    pub sparse_view: bool,
+
+    // Present while displaying an array of objects as a table.
+    pub table_view: Option<TableView>,
+
+    // Present when viewing the diff of two documents; the status of
+    // each row of the merged document.
+    pub diff_statuses: Option<Vec<DiffStatus>>,
+
+    // When the document was parsed leniently, the errors in the input
+    // that were recovered from, sorted by row.
+    pub recovered_errors: Vec<RecoveredError>,
+
+    // While performing an undoable action, the containers that have been
+    // collapsed or expanded, and whether they were collapsed before.
+    collapsed_state_changes: Option<Vec<(Index, bool)>>,
+}
+
+// The parts of the viewer's state that are restored when undoing changes
+// to it.
+pub struct ViewState {
+    top_row: Index,
+    focused_row: Index,
+    desired_depth: usize,
+    mode: Mode,
+    // The containers whose collapsed state has changed since, and whether
+    // they were collapsed, in the order they were changed.
+    collapsed_state_changes: Vec<(Index, bool)>,
 }
 
 impl JsonViewer {
@@ -43,6 +89,421 @@ impl JsonViewer {
             dimensions: TTYDimensions::default(),
             scrolloff_setting: DEFAULT_SCROLLOFF,
             mode,
+            lazy_input: None,
+            loaded_containers: vec![],
+            load_error: None,
+            sparse_view: false,
+            table_view: None,
+            diff_statuses: None,
+            recovered_errors: vec![],
+            collapsed_state_changes: None,
+        }
+    }
+
+    // Add more top-level values to the end of the document. Since these
+    // are added after all the existing rows, the focused and top rows
+    // remain valid.
+    pub fn append_top_level_values(&mut self, flatjson: FlatJson) {
+        self.flatjson.append(flatjson);
+    }
+
+    // Replace the entire document, e.g., because the input file was
+    // truncated; focus moves back to the start of the document. The
+    // previous document is returned.
+    pub fn replace_flatjson(&mut self, flatjson: FlatJson) -> FlatJson {
+        let previous = std::mem::replace(&mut self.flatjson, flatjson);
+        self.top_row = 0;
+        self.focused_row = 0;
+        self.desired_depth = 0;
+        self.sparse_view = false;
+        self.table_view = None;
+        self.recovered_errors.clear();
+        previous
+    }
+
+    // The error that was recovered from at a row (or at the container
+    // that it closes), if any.
+    pub fn recovered_error(&self, index: Index) -> Option<&RecoveredError> {
+        let index = match self.flatjson[index].is_closing_of_container() {
+            true => self.flatjson[index].pair_index().unwrap(),
+            false => index,
+        };
+        let i = self
+            .recovered_errors
+            .binary_search_by_key(&index, |error| error.row)
+            .ok()?;
+        Some(&self.recovered_errors[i])
+    }
+
+    // Replace the document with an edited version of it, focusing the
+    // given row, and keeping the focused row in the same place on the
+    // screen. The unedited document is returned.
+    pub fn replace_edited_flatjson(&mut self, flatjson: FlatJson, focused_row: Index) -> FlatJson {
+        let screen_index = self.index_of_focused_row_on_screen();
+
+        let previous = self.replace_flatjson(flatjson);
+        self.focused_row = focused_row;
+        self.desired_depth = self.flatjson[focused_row].depth;
+        self.top_row = self.count_n_lines_before(focused_row, screen_index as usize, self.mode);
+        previous
+    }
+
+    // Replace the document with a new version of it, e.g., after reloading
+    // the input file. The rows of the two versions don't correspond, so the
+    // collapsed containers and the focused node are found in the new version
+    // by matching up object entries by key and array elements by index (see
+    // corresponding_rows). If the focused node no longer exists, its closest
+    // ancestor that still exists is focused instead. The focused row stays
+    // in the same place on the screen. The old document is returned.
+    pub fn replace_reloaded_flatjson(&mut self, flatjson: FlatJson) -> FlatJson {
+        let collapsed_containers = self.flatjson.collapsed_containers();
+
+        let focused_closing = self.flatjson[self.focused_row].is_closing_of_container();
+        let mut focused_node_and_ancestors = vec![];
+        let mut ancestor = OptionIndex::Index(self.focused_row);
+        if focused_closing {
+            ancestor = self.flatjson[self.focused_row].pair_index();
+        }
+        while let OptionIndex::Index(index) = ancestor {
+            focused_node_and_ancestors.push(index);
+            ancestor = self.flatjson[index].parent;
+        }
+
+        let screen_index = self.index_of_focused_row_on_screen();
+        let previous = self.replace_flatjson(flatjson);
+        if self.flatjson.is_empty() {
+            return previous;
+        }
+
+        let collapsed_containers =
+            corresponding_rows(&previous, &self.flatjson, &collapsed_containers);
+        for index in collapsed_containers.into_iter().flatten() {
+            if self.flatjson[index].is_opening_of_container() {
+                self.flatjson.collapse(index);
+            }
+        }
+
+        let focused_node_and_ancestors =
+            corresponding_rows(&previous, &self.flatjson, &focused_node_and_ancestors);
+        let mut focused_row = 0;
+        for (i, index) in focused_node_and_ancestors.into_iter().enumerate() {
+            if let Some(index) = index {
+                focused_row = index;
+                if i == 0 && focused_closing && self.flatjson[index].is_opening_of_container() {
+                    focused_row = self.flatjson[index].pair_index().unwrap();
+                }
+                break;
+            }
+        }
+        let focused_row = self.flatjson.first_visible_ancestor(focused_row);
+
+        self.focused_row = focused_row;
+        self.desired_depth = self.flatjson[focused_row].depth;
+        self.top_row = self.count_n_lines_before(focused_row, screen_index as usize, self.mode);
+        previous
+    }
+
+    pub fn view_state(&self) -> ViewState {
+        ViewState {
+            top_row: self.top_row,
+            focused_row: self.focused_row,
+            desired_depth: self.desired_depth,
+            mode: self.mode,
+            collapsed_state_changes: vec![],
+        }
+    }
+
+    // Perform an action, returning the view state from before it if it
+    // collapsed or expanded any containers, changed the mode, or jumped to
+    // another part of the document. Moving the focus a row at a time
+    // isn't worth undoing.
+    pub fn perform_undoable_action(&mut self, action: Action) -> Option<ViewState> {
+        let mut view_state = self.view_state();
+        let is_jump = JsonViewer::is_jump(&action);
+
+        self.collapsed_state_changes = Some(vec![]);
+        self.perform_action(action);
+        view_state.collapsed_state_changes = self.collapsed_state_changes.take().unwrap();
+
+        let jumped = is_jump && view_state.focused_row != self.focused_row;
+        if view_state.collapsed_state_changes.is_empty() && view_state.mode == self.mode && !jumped
+        {
+            return None;
+        }
+        Some(view_state)
+    }
+
+    fn is_jump(action: &Action) -> bool {
+        matches!(
+            action,
+            Action::FocusTop
+                | Action::FocusBottom
+                | Action::FocusMatchingPair
+                | Action::JumpTo { .. }
+        )
+    }
+
+    // Restore an earlier view state, returning the view state that reverts
+    // it.
+    pub fn restore_view_state(&mut self, view_state: ViewState) -> ViewState {
+        let mut current_view_state = self.view_state();
+
+        for &(index, collapsed) in view_state.collapsed_state_changes.iter().rev() {
+            let currently_collapsed = self.flatjson[index].is_collapsed();
+            current_view_state
+                .collapsed_state_changes
+                .push((index, currently_collapsed));
+            if collapsed {
+                self.flatjson.collapse(index);
+            } else {
+                self.flatjson.expand(index);
+            }
+        }
+        self.mode = view_state.mode;
+
+        // Rows that were visible before may be hidden now if we're only
+        // showing search matches.
+        if self.flatjson[view_state.focused_row].hidden || self.flatjson[view_state.top_row].hidden
+        {
+            self.flatjson.unhide_all_rows();
+            self.sparse_view = false;
+        }
+
+        self.top_row = view_state.top_row;
+        self.focused_row = view_state.focused_row;
+        self.desired_depth = view_state.desired_depth;
+
+        current_view_state
+    }
+
+    // Display the array containing the focused row as a table.
+    pub fn open_table_view(&mut self) -> Result<(), String> {
+        if self.lazy_input.is_some() {
+            return Err("Table view isn't supported in large-file mode".to_string());
+        }
+
+        let table_view = TableView::new(&self.flatjson, self.focused_row, self.dimensions)?;
+        self.table_view = Some(table_view);
+        Ok(())
+    }
+
+    pub fn perform_table_action(&mut self, action: TableAction) {
+        if let Some(table_view) = &mut self.table_view {
+            table_view.perform_action(&self.flatjson, action);
+        }
+    }
+
+    // Hide every row except for the given rows and their ancestors. If the
+    // focused row is hidden, we focus the next visible row instead, and keep
+    // it in the same place on the screen.
+    pub fn show_only_rows(&mut self, rows: &[Index]) {
+        let screen_index = self.index_of_focused_row_on_screen();
+
+        self.flatjson.hide_rows_except(rows);
+        self.sparse_view = true;
+
+        if self.flatjson[self.focused_row].hidden {
+            let next = match self.mode {
+                Mode::Line => self.flatjson.next_visible_row(self.focused_row),
+                Mode::Data => self.flatjson.next_item(self.focused_row),
+            };
+
+            self.focused_row = match next {
+                OptionIndex::Index(i) => i,
+                OptionIndex::Nil => match self.mode {
+                    Mode::Line => self.flatjson.prev_visible_row(self.focused_row).unwrap(),
+                    Mode::Data => self.flatjson.prev_item(self.focused_row).unwrap(),
+                },
+            };
+            self.desired_depth = self.flatjson[self.focused_row].depth;
+        }
+
+        self.top_row =
+            self.count_n_lines_before(self.focused_row, screen_index as usize, self.mode);
+    }
+
+    pub fn show_all_rows(&mut self) {
+        if !self.sparse_view {
+            return;
+        }
+
+        let screen_index = self.index_of_focused_row_on_screen();
+
+        self.flatjson.unhide_all_rows();
+        self.sparse_view = false;
+
+        self.top_row =
+            self.count_n_lines_before(self.focused_row, screen_index as usize, self.mode);
+    }
+
+    // In large-file mode, make sure all the containers that will be
+    // displayed have been loaded, since we need their children to show
+    // previews of them.
+    pub fn load_visible_containers(&mut self) {
+        if self.lazy_input.is_none() {
+            return;
+        }
+
+        let mut unloaded = vec![];
+        let mut row = OptionIndex::Index(self.top_row);
+
+        for _ in 0..self.dimensions.height {
+            let OptionIndex::Index(index) = row else {
+                break;
+            };
+
+            if self.flatjson[index].is_unloaded() {
+                unloaded.push(self.open_index(index));
+            }
+
+            row = match self.mode {
+                Mode::Line => self.flatjson.next_visible_row(index),
+                Mode::Data => self.flatjson.next_item(index),
+            };
+        }
+
+        if self.load_containers(&unloaded) {
+            return;
+        }
+
+        // One of them couldn't be loaded, so load the rest of them one at
+        // a time.
+        for i in 0..unloaded.len() {
+            let index = unloaded[i];
+            if self.load_containers(&[index]) {
+                let loaded = self.loaded_containers.last().unwrap();
+                for later in unloaded[i + 1..].iter_mut() {
+                    *later = lazyjson::shift_index(loaded, *later);
+                }
+            }
+        }
+    }
+
+    // In large-file mode, load the contents of a container (specified by
+    // either its open or close row) if they haven't been loaded yet.
+    // Returns false if the contents couldn't be loaded.
+    fn load_container(&mut self, index: Index) -> bool {
+        if self.lazy_input.is_none() || !self.flatjson[index].is_unloaded() {
+            return true;
+        }
+
+        let open_index = self.open_index(index);
+        self.load_containers(&[open_index])
+    }
+
+    // Load the contents of unloaded containers, given their open rows.
+    // Returns false (and loads nothing) if any of them couldn't be loaded.
+    fn load_containers(&mut self, indexes: &[Index]) -> bool {
+        let Some(lazy_input) = &mut self.lazy_input else {
+            return true;
+        };
+
+        if indexes.is_empty() {
+            return true;
+        }
+
+        match lazy_input.load_containers(&mut self.flatjson, indexes) {
+            Ok(loaded) => {
+                // Rows after the containers have moved.
+                self.top_row = lazyjson::shift_index(&loaded, self.top_row);
+                self.focused_row = lazyjson::shift_index(&loaded, self.focused_row);
+                self.loaded_containers.push(loaded);
+                true
+            }
+            Err(err) => {
+                self.load_error = Some(format!("Unable to load container: {err}"));
+                false
+            }
+        }
+    }
+
+    fn open_index(&self, index: Index) -> Index {
+        if self.flatjson[index].is_closing_of_container() {
+            self.flatjson[index].pair_index().unwrap()
+        } else {
+            index
+        }
+    }
+
+    // Find the node that a path (see flatjson::parse_path) refers to. When
+    // there are multiple top-level values, the path must start with the
+    // index of one of them, like the paths in the status bar.
+    pub fn find_node_by_path(&mut self, path: &str) -> Result<Index, String> {
+        let segments = flatjson::parse_path(path)?;
+        let mut segments = segments.iter();
+
+        if self.flatjson.is_empty() {
+            return Err("Nothing to go to".to_string());
+        }
+
+        let mut index = 0;
+        if self.flatjson[0].next_sibling.is_some() {
+            let n = match segments.next() {
+                Some(PathSegment::Index(n)) => Some(*n),
+                Some(PathSegment::Token(token)) => token.parse::<usize>().ok(),
+                _ => None,
+            };
+            let Some(n) = n else {
+                return Err(
+                    "Path must start with the index of a top-level value, e.g., [0]".to_string(),
+                );
+            };
+
+            let mut top_level_value = OptionIndex::Index(0);
+            for _ in 0..n {
+                top_level_value = self.flatjson[top_level_value.unwrap()].next_sibling;
+                if top_level_value.is_nil() {
+                    return Err(format!("No top-level value at index {n}"));
+                }
+            }
+            index = top_level_value.unwrap();
+        }
+
+        for segment in segments {
+            if !self.load_container(index) {
+                return Err(self.load_error.take().unwrap_or_default());
+            }
+            index = self
+                .flatjson
+                .child_at_path_segment(index, segment)
+                .map_err(|err| {
+                    let path = self
+                        .flatjson
+                        .build_path_to_node(PathType::DotWithTopLevelIndex, index)
+                        .unwrap_or_default();
+                    format!("{err} in input{path}")
+                })?;
+        }
+
+        Ok(index)
+    }
+
+    fn expand(&mut self, index: Index) {
+        if self.load_container(index) {
+            self.set_collapsed(index, false);
+        }
+    }
+
+    fn collapse(&mut self, index: Index) {
+        self.set_collapsed(index, true);
+    }
+
+    // Collapse or expand a container, remembering its previous state if
+    // we're performing an undoable action.
+    fn set_collapsed(&mut self, index: Index, collapsed: bool) {
+        let row = &self.flatjson[index];
+        if !row.is_container() || row.is_collapsed() == collapsed {
+            return;
+        }
+
+        if let Some(changes) = &mut self.collapsed_state_changes {
+            changes.push((index, !collapsed));
+        }
+
+        if collapsed {
+            self.flatjson.collapse(index);
+        } else {
+            self.flatjson.expand(index);
         }
     }
 }
@@ -183,7 +644,10 @@ impl JsonViewer {
             Action::ExpandNodeAndSiblings => self.expand_node_and_siblings(),
             Action::DeepExpandNodeAndSiblings => self.deep_expand_node_and_siblings(),
             Action::ToggleMode => self.toggle_mode(),
-            Action::ResizeViewerDimensions(dims) => self.dimensions = dims,
+            Action::ResizeViewerDimensions(dims) => {
+                self.dimensions = dims;
+                self.perform_table_action(TableAction::ResizeDimensions(dims));
+            }
         }
 
         if reset_desired_depth {
@@ -311,12 +775,17 @@ impl JsonViewer {
         }
 
         if focused_row.is_collapsed() {
-            self.flatjson.expand(self.focused_row);
+            self.expand(self.focused_row);
             return;
         }
 
         if focused_row.is_opening_of_container() {
-            self.focused_row = focused_row.first_child().unwrap();
+            // The first child may be hidden, so we focus the next visible
+            // row instead, unless all the children are hidden.
+            let next = self.flatjson.next_visible_row(self.focused_row).unwrap();
+            if next != focused_row.pair_index().unwrap() {
+                self.focused_row = next;
+            }
         } else {
             debug_assert!(
                 self.mode == Mode::Line,
@@ -330,7 +799,7 @@ impl JsonViewer {
         if self.flatjson[self.focused_row].is_container()
             && self.flatjson[self.focused_row].is_expanded()
         {
-            self.flatjson.collapse(self.focused_row);
+            self.collapse(self.focused_row);
             if self.flatjson[self.focused_row].is_closing_of_container() {
                 self.focused_row = self.flatjson[self.focused_row].pair_index().unwrap();
             }
@@ -480,7 +949,8 @@ impl JsonViewer {
     fn focus_first_sibling(&mut self) {
         match &self.flatjson[self.focused_row].parent {
             OptionIndex::Index(parent_index) => {
-                self.focused_row = self.flatjson[*parent_index].first_child().unwrap();
+                // Use the first visible child, since the first child may be hidden.
+                self.focused_row = self.flatjson.next_visible_row(*parent_index).unwrap();
             }
             // If node has no parent, then we're at the top level and want to focus
             // the first element, which is the top of the file.
@@ -491,8 +961,17 @@ impl JsonViewer {
     fn focus_last_sibling(&mut self) {
         match &self.flatjson[self.focused_row].parent {
             OptionIndex::Index(parent_index) => {
+                // Use the last visible child, since the last child may be hidden.
                 let closing_parent_index = self.flatjson[*parent_index].pair_index().unwrap();
-                self.focused_row = self.flatjson[closing_parent_index].last_child().unwrap();
+                let last_row = self
+                    .flatjson
+                    .prev_visible_row(closing_parent_index)
+                    .unwrap();
+                if self.flatjson[last_row].is_closing_of_container() {
+                    self.focused_row = self.flatjson[last_row].pair_index().unwrap();
+                } else {
+                    self.focused_row = last_row;
+                }
             }
             // If node has no parent, then we're at the top level and want to focus
             // the last element. If this last element is a container though, we want to
@@ -511,6 +990,12 @@ impl JsonViewer {
     fn focus_top(&mut self) {
         self.top_row = 0;
         self.focused_row = 0;
+
+        // The first row may be hidden if we're only showing some rows.
+        if self.flatjson[0].hidden {
+            self.focused_row = self.flatjson.next_visible_row(0).unwrap();
+            self.top_row = self.focused_row;
+        }
     }
 
     fn focus_bottom(&mut self) {
@@ -626,25 +1111,31 @@ impl JsonViewer {
     // If make_visible is true, this will ensure all of the parent containers are opened.
     // If make_visible is false, then we'll focus the highest level closed parent.
     fn jump_to(&mut self, line: Index, make_visible: bool) {
-        self.focused_row = line.min(self.flatjson.0.len() - 1);
+        let mut row = line.min(self.flatjson.0.len() - 1);
 
-        match self.mode {
-            Mode::Data => {
-                // Back up to a non-closing of a container.
-                while self.flatjson[self.focused_row].is_closing_of_container() {
-                    self.focused_row -= 1;
-                }
+        if self.mode == Mode::Data {
+            // Back up to a non-closing of a container.
+            while self.flatjson[row].is_closing_of_container() {
+                row -= 1;
             }
-            Mode::Line => {
-                // If the line is the closing of a container, and it's collapsed, either
-                // make it visible by expanding it, or go to the opening instead.
-                let row = &self.flatjson[self.focused_row];
-                if row.is_closing_of_container() && row.is_collapsed() {
-                    if make_visible {
-                        self.flatjson.expand(self.focused_row);
-                    } else {
-                        self.focused_row = row.pair_index().unwrap();
-                    }
+        }
+
+        // If we're jumping to a hidden row, show the whole document again.
+        if self.flatjson[row].hidden {
+            self.show_all_rows();
+        }
+
+        self.focused_row = row;
+
+        if self.mode == Mode::Line {
+            // If the line is the closing of a container, and it's collapsed, either
+            // make it visible by expanding it, or go to the opening instead.
+            let row = &self.flatjson[self.focused_row];
+            if row.is_closing_of_container() && row.is_collapsed() {
+                if make_visible {
+                    self.expand(self.focused_row);
+                } else {
+                    self.focused_row = row.pair_index().unwrap();
                 }
             }
         }
@@ -652,7 +1143,7 @@ impl JsonViewer {
         if make_visible {
             let mut curr = self.focused_row;
             while let OptionIndex::Index(parent) = self.flatjson[curr].parent {
-                self.flatjson.expand(parent);
+                self.expand(parent);
                 curr = parent;
             }
         } else {
@@ -708,7 +1199,12 @@ impl JsonViewer {
             self.focused_row = self.flatjson[self.focused_row].pair_index().unwrap();
         }
 
-        self.flatjson.toggle_collapsed(self.focused_row);
+        if !self.load_container(self.focused_row) {
+            return;
+        }
+
+        let collapsed = self.flatjson[self.focused_row].is_collapsed();
+        self.set_collapsed(self.focused_row, !collapsed);
     }
 
     fn collapse_node_and_siblings(&mut self) {
@@ -756,32 +1252,44 @@ impl JsonViewer {
 
         while let OptionIndex::Index(next) = next_sibling {
             if collapsed {
-                self.flatjson.collapse(next);
+                self.collapse(next);
             } else {
-                self.flatjson.expand(next);
+                self.expand(next);
             }
             next_sibling = self.flatjson[next].next_sibling;
         }
     }
 
     fn set_deep_collapse_state_on_node_and_siblings(&mut self, collapsed: bool) {
-        let (start, end) =
-            if let OptionIndex::Index(parent) = self.flatjson[self.focused_row].parent {
-                (parent + 1, self.flatjson[parent].pair_index().unwrap())
-            } else {
-                // If we don't have parent, that means we're at the top level, so the first
-                // sibling is the very first element.
-                (0, self.flatjson.0.len())
+        let parent = self.flatjson[self.focused_row].parent;
+        let mut i = match parent {
+            OptionIndex::Index(parent) => parent + 1,
+            // If we don't have parent, that means we're at the top level, so the first
+            // sibling is the very first element.
+            OptionIndex::Nil => 0,
+        };
+
+        loop {
+            // Expanding containers in large-file mode may load more rows, so we
+            // need to check where the parent ends each time.
+            let end = match parent {
+                OptionIndex::Index(parent) => self.flatjson[parent].pair_index().unwrap(),
+                OptionIndex::Nil => self.flatjson.0.len(),
             };
 
-        for i in start..end {
+            if i >= end {
+                break;
+            }
+
             if self.flatjson[i].is_opening_of_container() {
                 if collapsed {
-                    self.flatjson.collapse(i);
+                    self.collapse(i);
                 } else {
-                    self.flatjson.expand(i);
+                    self.expand(i);
                 }
             }
+
+            i += 1;
         }
     }
 
@@ -949,13 +1457,20 @@ impl JsonViewer {
     }
 
     fn count_n_lines_before(&self, mut start: Index, mut lines: usize, mode: Mode) -> Index {
-        while lines != 0 && start != 0 {
-            start = match mode {
-                Mode::Line => self.flatjson.prev_visible_row(start).unwrap(),
-                Mode::Data => self.flatjson.prev_item(start).unwrap(),
+        while lines != 0 {
+            let prev = match mode {
+                Mode::Line => self.flatjson.prev_visible_row(start),
+                Mode::Data => self.flatjson.prev_item(start),
             };
+
+            match prev {
+                OptionIndex::Nil => break,
+                OptionIndex::Index(p) => start = p,
+            };
+
             lines -= 1;
         }
+
         start
     }
 
@@ -1008,16 +1523,105 @@ impl JsonViewer {
     }
 }
 
+// The children of a container, or the top-level values of a document,
+// which are looked up by key in objects, and by index otherwise.
+enum Children<'a> {
+    Keys(HashMap<&'a str, Index>),
+    Indexes(Vec<Index>),
+}
+
+impl<'a> Children<'a> {
+    fn of(flatjson: &'a FlatJson, parent: Option<Index>) -> Children<'a> {
+        let mut children = vec![];
+        let mut child = match parent {
+            Some(parent) => flatjson[parent].first_child(),
+            None if flatjson.is_empty() => OptionIndex::Nil,
+            None => OptionIndex::Index(0),
+        };
+        while let OptionIndex::Index(index) = child {
+            children.push(index);
+            child = flatjson[index].next_sibling;
+        }
+
+        let is_object = parent.map_or(false, |parent| {
+            matches!(
+                flatjson[parent].value.container_type(),
+                Some(ContainerType::Object)
+            )
+        });
+        if !is_object {
+            return Children::Indexes(children);
+        }
+
+        let mut keys = HashMap::new();
+        for index in children {
+            let key_range = flatjson[index].key_range.clone().unwrap();
+            // Like paths, duplicate keys refer to the first entry.
+            keys.entry(&flatjson.1[key_range]).or_insert(index);
+        }
+        Children::Keys(keys)
+    }
+}
+
+// Find the rows of a new version of a document that correspond to rows of
+// an old version of it, matching up object entries by their keys, and array
+// elements and top-level values by their indexes. Only the children of the
+// ancestors of the rows are looked at, each of them once.
+fn corresponding_rows(old: &FlatJson, new: &FlatJson, rows: &[Index]) -> Vec<Option<Index>> {
+    // The rows and their ancestors, sorted so that parents come before
+    // their children.
+    let mut rows_and_ancestors = BTreeSet::new();
+    for &row in rows {
+        let mut ancestor = OptionIndex::Index(row);
+        while let OptionIndex::Index(index) = ancestor {
+            if !rows_and_ancestors.insert(index) {
+                break;
+            }
+            ancestor = old[index].parent;
+        }
+    }
+
+    let mut corresponding: HashMap<Index, Index> = HashMap::new();
+    let mut new_children: HashMap<Option<Index>, Children> = HashMap::new();
+
+    for index in rows_and_ancestors {
+        let row = &old[index];
+        let new_parent = match row.parent {
+            OptionIndex::Index(parent) => match corresponding.get(&parent) {
+                Some(&new_parent) => Some(new_parent),
+                None => continue,
+            },
+            OptionIndex::Nil => None,
+        };
+
+        let children = new_children
+            .entry(new_parent)
+            .or_insert_with(|| Children::of(new, new_parent));
+        let new_index = match (children, &row.key_range) {
+            (Children::Keys(keys), Some(key_range)) => keys.get(&old.1[key_range.clone()]),
+            (Children::Indexes(indexes), None) => indexes.get(row.index_in_parent),
+            _ => None,
+        };
+        if let Some(&new_index) = new_index {
+            corresponding.insert(index, new_index);
+        }
+    }
+
+    rows.iter()
+        .map(|row| corresponding.get(row).copied())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::flatjson::{parse_top_level_json, NIL};
 
     impl OptionIndex {
-        pub fn to_usize(&self) -> usize {
+        pub fn to_usize(self) -> usize {
             match self {
                 OptionIndex::Nil => NIL,
-                OptionIndex::Index(i) => *i,
+                OptionIndex::Index(i) => i,
             }
         }
     }
@@ -2268,6 +2872,228 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_show_only_rows() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 8;
+
+        // Focus moves to the next visible row if the focused row is hidden.
+        viewer.focused_row = 1;
+        viewer.show_only_rows(&[4, 8, 9]);
+        assert!(viewer.sparse_view);
+        assert_eq!(viewer.focused_row, 2);
+
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::MoveDown(1), 4),
+                (Action::MoveDown(1), 5),
+                (Action::FocusNextSibling(1), 6),
+                (Action::MoveRight, 8),
+                (Action::FocusFirstSibling, 8),
+                (Action::FocusLastSibling, 9),
+                (Action::FocusPrevSibling(1), 8),
+                (Action::FocusParent, 6),
+                (Action::FocusPrevSibling(1), 5),
+                (Action::MoveRight, 4),
+                (Action::FocusTop, 0),
+                (Action::FocusBottom, 12),
+            ],
+        );
+
+        viewer.mode = Mode::Data;
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::FocusBottom, 9),
+                (Action::MoveUp(2), 6),
+                (Action::MoveUp(1), 4),
+            ],
+        );
+
+        // Jumping to a hidden row shows all the rows again.
+        assert_movements(
+            &mut viewer,
+            vec![(
+                Action::JumpTo {
+                    line: 11,
+                    make_visible: true,
+                },
+                11,
+            )],
+        );
+        assert!(!viewer.sparse_view);
+        assert!(!viewer.flatjson[1].hidden);
+
+        // The first row may be hidden when there are multiple top-level values.
+        let fj = parse_top_level_json("1 [2, 3] 4".to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+        viewer.dimensions.height = 8;
+        viewer.show_only_rows(&[3]);
+        assert_eq!(viewer.focused_row, 1);
+        assert_eq!(viewer.top_row, 1);
+
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::MoveDown(1), 3),
+                (Action::MoveDown(1), 3),
+                (Action::FocusTop, 1),
+                (Action::FocusLastSibling, 1),
+            ],
+        );
+
+        viewer.show_all_rows();
+        assert_movements(&mut viewer, vec![(Action::FocusTop, 0)]);
+    }
+
+    #[test]
+    fn test_restore_view_state() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 8;
+
+        viewer.perform_action(Action::JumpTo {
+            line: 7,
+            make_visible: false,
+        });
+        viewer.flatjson.collapse(2);
+
+        // Moving the focus a row at a time isn't undoable, but jumping is.
+        assert!(viewer
+            .perform_undoable_action(Action::MoveDown(1))
+            .is_none());
+        let jump = viewer.perform_undoable_action(Action::FocusTop).unwrap();
+        assert_eq!(viewer.focused_row, 0);
+        let redo_jump = viewer.restore_view_state(jump);
+        assert_eq!(viewer.focused_row, 8);
+        viewer.restore_view_state(redo_jump);
+        assert_eq!(viewer.focused_row, 0);
+        // Jumping to where we already are isn't undoable.
+        assert!(viewer.perform_undoable_action(Action::FocusTop).is_none());
+
+        let collapse = viewer
+            .perform_undoable_action(Action::DeepCollapseNodeAndSiblings)
+            .unwrap();
+        let toggle_mode = viewer.perform_undoable_action(Action::ToggleMode).unwrap();
+        assert_eq!(viewer.flatjson.collapsed_containers(), vec![0, 2, 6]);
+        assert_eq!(viewer.mode, Mode::Data);
+
+        let redo_toggle_mode = viewer.restore_view_state(toggle_mode);
+        assert_eq!(viewer.mode, Mode::Line);
+        let redo_collapse = viewer.restore_view_state(collapse);
+        assert_eq!(viewer.focused_row, 0);
+        assert_eq!(viewer.flatjson.collapsed_containers(), vec![2]);
+        assert!(viewer.flatjson[5].is_collapsed());
+        assert!(!viewer.flatjson[10].is_collapsed());
+
+        viewer.restore_view_state(redo_collapse);
+        assert_eq!(viewer.flatjson.collapsed_containers(), vec![0, 2, 6]);
+        viewer.restore_view_state(redo_toggle_mode);
+        assert_eq!(viewer.mode, Mode::Data);
+    }
+
+    #[test]
+    fn test_restore_view_state_after_unrecorded_changes() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 8;
+        viewer.focused_row = 3;
+
+        // Moving from a primitive to its parent doesn't collapse anything,
+        // but moving left again does.
+        assert!(viewer.perform_undoable_action(Action::MoveLeft).is_none());
+        assert_eq!(viewer.focused_row, 2);
+        let collapse = viewer.perform_undoable_action(Action::MoveLeft).unwrap();
+        assert!(viewer.flatjson[2].is_collapsed());
+
+        // The container is expanded again without being recorded, e.g., to
+        // show a search match, so restoring the view state leaves it expanded.
+        viewer.flatjson.expand(2);
+        let redo_collapse = viewer.restore_view_state(collapse);
+        assert!(!viewer.flatjson[2].is_collapsed());
+        assert!(!viewer.flatjson[5].is_collapsed());
+
+        viewer.restore_view_state(redo_collapse);
+        assert!(!viewer.flatjson[2].is_collapsed());
+    }
+
+    #[test]
+    fn test_find_node_by_path() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        assert_eq!(viewer.find_node_by_path("."), Ok(0));
+        assert_eq!(viewer.find_node_by_path(r#"["2"][1]"#), Ok(4));
+        assert_eq!(viewer.find_node_by_path("/6/9"), Ok(9));
+        assert_eq!(
+            viewer.find_node_by_path(r#"["6"]["10"]"#),
+            Err(r#"No key "10" in input["6"]"#.to_string())
+        );
+
+        // With multiple top-level values, the path starts with an index.
+        let fj = parse_top_level_json(r#"{"a": 1} {"a": [2]}"#.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        assert_eq!(viewer.find_node_by_path("[1].a[0]"), Ok(5));
+        assert_eq!(viewer.find_node_by_path("/0/a"), Ok(1));
+        assert!(viewer.find_node_by_path(".a").is_err());
+        assert!(viewer.find_node_by_path("[2].a").is_err());
+    }
+
+    #[test]
+    fn test_replace_reloaded_flatjson() {
+        let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions = TTYDimensions {
+            width: 80,
+            height: 5,
+        };
+        viewer.flatjson.collapse(2);
+        viewer.focused_row = 8;
+        viewer.top_row = 6;
+
+        let reloaded = r#"{"0": 0, "2": [3, "4", 5], "6": {"8": false, "9": 9}}"#;
+        viewer.replace_reloaded_flatjson(parse_top_level_json(reloaded.to_owned()).unwrap());
+        assert_eq!(viewer.flatjson.collapsed_containers(), vec![2]);
+        assert_eq!(viewer.focused_row, 8);
+        // The focused row stays two lines below the top of the screen, and
+        // rows 3 through 6 are hidden in the collapsed container.
+        assert_eq!(viewer.top_row, 2);
+
+        // If the focused node is removed, its parent is focused.
+        let reloaded = r#"{"6": {"9": 9}}"#;
+        viewer.replace_reloaded_flatjson(parse_top_level_json(reloaded.to_owned()).unwrap());
+        assert_eq!(viewer.focused_row, 1);
+        assert_eq!(viewer.top_row, 0);
+
+        // When focused on the closing of a container, we stay focused on it.
+        viewer.focused_row = 3;
+        let reloaded = r#"{"2": [], "6": {"7": null, "9": 9}}"#;
+        viewer.replace_reloaded_flatjson(parse_top_level_json(reloaded.to_owned()).unwrap());
+        assert_eq!(viewer.focused_row, 5);
+
+        // If every ancestor of the focused node is removed, the first row
+        // is focused.
+        let reloaded = r#"[1, 2]"#;
+        viewer.replace_reloaded_flatjson(parse_top_level_json(reloaded.to_owned()).unwrap());
+        assert_eq!(viewer.focused_row, 0);
+    }
+
+    #[test]
+    fn test_corresponding_rows() {
+        let old = r#"{"a": [1, {"b": 2}], "c": {"d": 3}} [4]"#;
+        let new = r#"{"c": 5, "a": [0, {"b": 2}, 1]}"#;
+        let old = parse_top_level_json(old.to_owned()).unwrap();
+        let new = parse_top_level_json(new.to_owned()).unwrap();
+
+        assert_eq!(
+            corresponding_rows(&old, &new, &[4, 8, 7, 12, 3, 0]),
+            vec![Some(5), None, Some(1), None, Some(4), Some(0)],
+        );
+    }
+
     #[track_caller]
     fn assert_window_tracking(
         viewer: &mut JsonViewer,
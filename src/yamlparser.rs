@@ -2,6 +2,8 @@ use yaml_rust::yaml::{Array, Hash, Yaml};
 use yaml_rust::YamlLoader;
 
 use crate::flatjson::{ContainerType, Index, OptionIndex, Row, Value};
+use crate::jsonstringescaper::escape_json_string;
+use crate::parseerror::ParseError;
 
 struct YamlParser {
     parents: Vec<Index>,
@@ -10,7 +12,7 @@ struct YamlParser {
     max_depth: usize,
 }
 
-pub fn parse(yaml: String) -> Result<(Vec<Row>, String, usize), String> {
+pub fn parse(yaml: String) -> Result<(Vec<Row>, String, usize), ParseError> {
     let mut parser = YamlParser {
         parents: vec![],
         rows: vec![],
@@ -20,7 +22,23 @@ pub fn parse(yaml: String) -> Result<(Vec<Row>, String, usize), String> {
 
     let docs = match YamlLoader::load_from_str(&yaml) {
         Ok(yaml_docs) => yaml_docs,
-        Err(err) => return Err(format!("{err}")),
+        Err(err) => {
+            // Scan errors are displayed with their position, which we
+            // report separately, so strip it from the message.
+            let marker = err.marker();
+            let position = format!(" at line {} column {}", marker.line(), marker.col() + 1);
+            let message = err.to_string();
+            let message = message
+                .strip_suffix(&position)
+                .unwrap_or(&message)
+                .to_string();
+            return Err(ParseError::at_line_and_column(
+                &yaml,
+                marker.line(),
+                marker.col() + 1,
+                message,
+            ));
+        }
     };
 
     let mut prev_sibling = OptionIndex::Nil;
@@ -94,8 +112,7 @@ impl YamlParser {
     fn parse_string(&mut self, s: String) -> usize {
         let row_index = self.create_row(Value::String);
 
-        // Escape newlines.
-        let s = s.replace('\n', "\\n");
+        let s = escape_json_string(&s);
 
         self.pretty_printed.push('"');
         self.pretty_printed.push_str(&s);
@@ -289,8 +306,7 @@ impl YamlParser {
 
     fn pretty_print_key_item(&mut self, item: Yaml, is_key: bool) -> Result<(), String> {
         if let Yaml::String(s) = item {
-            // Replace newlines.
-            let s = s.replace('\n', "\\n");
+            let s = escape_json_string(&s);
             self.pretty_printed.push('"');
             self.pretty_printed.push_str(&s);
             self.pretty_printed.push('"');
@@ -306,7 +322,7 @@ impl YamlParser {
             Yaml::Null => self.pretty_printed.push_str("null"),
             Yaml::Boolean(b) => self
                 .pretty_printed
-                .push_str(if b { "true" } else { "false " }),
+                .push_str(if b { "true" } else { "false" }),
             Yaml::Integer(i) => self.pretty_printed.push_str(&i.to_string()),
             Yaml::Real(real_str) => self.pretty_printed.push_str(&real_str),
             Yaml::Array(arr) => {
@@ -373,6 +389,7 @@ impl YamlParser {
             parent,
             depth: self.parents.len(),
             value,
+            hidden: false,
 
             // The start of this range is set by us, but then we set
             // the end when we're done parsing the row. We'll set
@@ -513,4 +530,31 @@ mod tests {
 
         assert_eq!(pretty, parsed_pretty);
     }
+
+    #[test]
+    fn test_strings_are_escaped() {
+        let yaml = indoc! {r#"
+            ---
+            a"b: c\d
+            tab: "x\ty"
+            false: no
+        "#}
+        .to_owned();
+        let pretty = r#"{ "a\"b": "c\\d", "tab": "x\ty", [false]: "no" }"#;
+        let (_, parsed_pretty, _) = parse(yaml).unwrap();
+
+        assert_eq!(pretty, parsed_pretty);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = parse("a: [1, 2\nb: 3\n".to_owned()).unwrap_err();
+        let location = error.location.as_ref().unwrap();
+
+        assert_eq!(
+            error.message,
+            "while parsing a flow sequence, expected ',' or ']'"
+        );
+        assert_eq!(location.line, 2);
+    }
 }